@@ -60,14 +60,20 @@ use tracing_subscriber::{
 use hickory_client::rr::Name;
 #[cfg(feature = "dns-over-tls")]
 use hickory_server::config::dnssec::{self, TlsCertConfig};
-#[cfg(feature = "blocklist")]
-use hickory_server::store::blocklist::BlocklistAuthority;
 #[cfg(feature = "resolver")]
 use hickory_server::store::forwarder::ForwardAuthority;
 #[cfg(feature = "recursor")]
 use hickory_server::store::recursor::RecursiveAuthority;
 #[cfg(feature = "sqlite")]
 use hickory_server::store::sqlite::{SqliteAuthority, SqliteConfig};
+#[cfg(feature = "blocklist")]
+use hickory_server::{
+    authority::{Authority, LookupOptions},
+    proto::rr::{LowerName, RecordType},
+    store::blocklist::{
+        BlocklistAuthority, BlocklistConfig, ListEntry, LoadFailurePolicy, StartupPolicy,
+    },
+};
 use hickory_server::{
     authority::{AuthorityObject, Catalog, ZoneType},
     config::{Config, ZoneConfig},
@@ -146,10 +152,225 @@ async fn load_keys<T>(
     Ok(())
 }
 
+/// Write the compiled policy file of each blocklist store which has one configured.
+#[cfg(feature = "blocklist")]
+async fn compile_blocklists(
+    config: &Config,
+    zone_dir: &Path,
+    config_dir: Option<&Path>,
+) -> Result<(), String> {
+    for zone in config.get_zones() {
+        let zone_name = zone
+            .get_zone()
+            .map_err(|err| format!("failed to read zone name: {err}"))?;
+
+        for store in blocklist_stores(zone) {
+            if store.compiled_policy.is_none() {
+                info!("blocklist {zone_name} has no compiled_policy configured, skipping");
+                continue;
+            }
+
+            let path =
+                BlocklistAuthority::compile(zone_name.clone(), store, Some(zone_dir), config_dir)
+                    .await
+                    .map_err(|err| format!("could not compile blocklist {zone_name}: {err}"))?;
+            info!("compiled blocklist {zone_name} to {path:?}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Load the lists of every blocklist store, as the server would at startup, and report each one which fails to load.
+/// Nothing is written: the list cache and the query log are left alone, and a failed load is never replaced by cached
+/// or empty lists.
+#[cfg(feature = "blocklist")]
+async fn check_blocklists(
+    config: &Config,
+    zone_dir: &Path,
+    config_dir: Option<&Path>,
+) -> Result<(), String> {
+    let mut errors = vec![];
+    for zone in config.get_zones() {
+        let zone_name = zone
+            .get_zone()
+            .map_err(|err| format!("failed to read zone name: {err}"))?;
+
+        for store in blocklist_stores(zone) {
+            let config = BlocklistConfig {
+                startup_policy: StartupPolicy::Wait,
+                on_load_failure: LoadFailurePolicy::Fail,
+                cache_dir: None,
+                query_log: None,
+                ..store.clone()
+            };
+            let loaded = BlocklistAuthority::try_from_config(
+                zone_name.clone(),
+                zone.get_zone_type(),
+                &config,
+                Some(zone_dir),
+                config_dir,
+            )
+            .await;
+            match loaded {
+                Ok(_) => info!("blocklist {zone_name} loaded"),
+                Err(err) => {
+                    error!("blocklist {zone_name} failed to load: {err}");
+                    errors.push(format!("blocklist {zone_name}: {err}"));
+                }
+            }
+        }
+    }
+
+    match errors.len() {
+        0 => Ok(()),
+        1 => Err(errors.remove(0)),
+        n => Err(format!(
+            "{n} blocklists failed to load: {}",
+            errors.join("; ")
+        )),
+    }
+}
+
+/// Check each of `names` against a list read from standard input, with the default blocklist settings, and print whether
+/// it is blocked.  As with `check_blocklists`, nothing is written.
+#[cfg(feature = "blocklist")]
+async fn check_stdin_list(names: &[Name]) -> Result<(), String> {
+    let config = BlocklistConfig {
+        lists: vec![ListEntry::from_path("-")],
+        startup_policy: StartupPolicy::Wait,
+        on_load_failure: LoadFailurePolicy::Fail,
+        ..BlocklistConfig::default()
+    };
+    let authority =
+        BlocklistAuthority::try_from_config(Name::root(), ZoneType::Hint, &config, None, None)
+            .await
+            .map_err(|err| format!("could not load blocklist from standard input: {err}"))?;
+
+    for name in names {
+        let mut name = name.clone();
+        name.set_fqdn(true);
+        let lookup = authority
+            .lookup(
+                &LowerName::from(&name),
+                RecordType::A,
+                LookupOptions::default(),
+            )
+            .await;
+        let blocked = match lookup {
+            Ok(lookup) => lookup.is_some(),
+            Err(err) if err.is_name_exists() || err.is_nx_domain() => true,
+            Err(err) => return Err(format!("could not check {name}: {err}")),
+        };
+        match blocked {
+            true => println!("{name} blocked"),
+            false => println!("{name} not blocked"),
+        }
+    }
+
+    Ok(())
+}
+
+/// A configuration, the directory its zones are loaded from, and the directory containing the configuration file
+#[cfg(feature = "blocklist")]
+type ConfigSource<'a> = (&'a Config, &'a Path, Option<&'a Path>);
+
+/// Print how the names blocked by each blocklist change from the `old` configuration to the `new`.  Blocklists are
+/// matched up by zone and by their position in the zone's stores.  As with `check_blocklists`, nothing is written, and
+/// a list which fails to load fails the diff rather than being replaced by cached or empty lists.
+#[cfg(feature = "blocklist")]
+async fn diff_blocklists(old: ConfigSource<'_>, new: ConfigSource<'_>) -> Result<(), String> {
+    /// Number of names of each kind of change which are printed for each blocklist
+    const DIFF_NAMES: usize = 20;
+
+    async fn load(
+        zone: &ZoneConfig,
+        store: &BlocklistConfig,
+        (_, zone_dir, config_dir): ConfigSource<'_>,
+    ) -> Result<BlocklistAuthority, String> {
+        let zone_name = zone
+            .get_zone()
+            .map_err(|err| format!("failed to read zone name: {err}"))?;
+        let config = BlocklistConfig {
+            startup_policy: StartupPolicy::Wait,
+            on_load_failure: LoadFailurePolicy::Fail,
+            cache_dir: None,
+            query_log: None,
+            ..store.clone()
+        };
+        BlocklistAuthority::try_from_config(
+            zone_name.clone(),
+            zone.get_zone_type(),
+            &config,
+            Some(zone_dir),
+            config_dir,
+        )
+        .await
+        .map_err(|err| format!("could not load blocklist {zone_name}: {err}"))
+    }
+
+    let zone_names = |config: &Config| {
+        let mut names = vec![];
+        for zone in config.get_zones() {
+            for index in 0..blocklist_stores(zone).len() {
+                names.push((zone.zone.clone(), index));
+            }
+        }
+        names
+    };
+    let old_names = zone_names(old.0);
+    let new_names = zone_names(new.0);
+    for (zone, index) in old_names.iter().filter(|name| !new_names.contains(name)) {
+        println!("blocklist {index} of zone {zone} was removed");
+    }
+
+    for new_zone in new.0.get_zones() {
+        let old_zone = old
+            .0
+            .get_zones()
+            .iter()
+            .find(|zone| zone.zone == new_zone.zone);
+        for (index, new_store) in blocklist_stores(new_zone).into_iter().enumerate() {
+            let old_store =
+                old_zone.and_then(|zone| Some((zone, *blocklist_stores(zone).get(index)?)));
+            let Some((old_zone, old_store)) = old_store else {
+                println!("blocklist {index} of zone {} was added", new_zone.zone);
+                continue;
+            };
+
+            let before = load(old_zone, old_store, old).await?;
+            let after = load(new_zone, new_store, new).await?;
+            println!("blocklist {index} of zone {}:", new_zone.zone);
+            print!("{}", before.diff(&after, DIFF_NAMES));
+        }
+    }
+
+    Ok(())
+}
+
+/// The blocklist stores configured for `zone`
+#[cfg(feature = "blocklist")]
+fn blocklist_stores(zone: &ZoneConfig) -> Vec<&BlocklistConfig> {
+    let stores = match &zone.stores {
+        Some(StoreConfigContainer::Single(store)) => std::slice::from_ref(store),
+        Some(StoreConfigContainer::Chained(stores)) => stores.as_slice(),
+        _ => &[],
+    };
+
+    stores
+        .iter()
+        .filter_map(|store| match store {
+            StoreConfig::Blocklist(config) => Some(config),
+            _ => None,
+        })
+        .collect()
+}
+
 #[cfg_attr(not(feature = "dnssec"), allow(unused_mut, unused))]
 #[warn(clippy::wildcard_enum_match_arm)] // make sure all cases are handled despite of non_exhaustive
 async fn load_zone(
     zone_dir: &Path,
+    #[cfg_attr(not(feature = "blocklist"), allow(unused_variables))] config_dir: Option<&Path>,
     zone_config: &ZoneConfig,
 ) -> Result<Vec<Box<dyn AuthorityObject>>, String> {
     debug!("loading zone with config: {:#?}", zone_config);
@@ -254,9 +475,11 @@ async fn load_zone(
                     zone_type,
                     config,
                     Some(zone_dir),
+                    config_dir,
                 );
-                let authority = blocklist.await?;
-                Box::new(Arc::new(authority)) as Box<dyn AuthorityObject>
+                let authority = Arc::new(blocklist.await?);
+                authority.spawn_refresh();
+                Box::new(authority) as Box<dyn AuthorityObject>
             }
             #[cfg(feature = "sqlite")]
             _ if zone_config.is_update_allowed() => {
@@ -328,6 +551,35 @@ struct Cli {
     #[clap(long = "validate")]
     pub(crate) validate: bool,
 
+    /// Compile the lists of each blocklist with a `compiled_policy` file configured, write the files, and exit
+    #[cfg(feature = "blocklist")]
+    #[clap(long = "compile-blocklists")]
+    pub(crate) compile_blocklists: bool,
+
+    /// Compare the blocklists of this configuration file with those of `--config`, print how the names they block
+    /// change, and exit
+    #[cfg(feature = "blocklist")]
+    #[clap(long = "diff-blocklists", value_name = "OLD_CONFIG")]
+    pub(crate) diff_blocklists: Option<PathBuf>,
+
+    /// Load the lists of each blocklist without serving, report any which fail to load, and exit, with an error status if
+    /// any did
+    #[cfg(feature = "blocklist")]
+    #[clap(long = "check-blocklists")]
+    pub(crate) check_blocklists: bool,
+
+    /// With `--check-blocklists`, read a list from standard input instead of loading the configured lists, check each
+    /// of `NAMES` against it with the default blocklist settings, and print whether it is blocked, e.g.
+    /// `cat list.txt | hickory-dns --check-blocklists --stdin ads.example.com`
+    #[cfg(feature = "blocklist")]
+    #[clap(long = "stdin", requires = "check_blocklists")]
+    pub(crate) stdin: bool,
+
+    /// Names to check against the list read with `--stdin`
+    #[cfg(feature = "blocklist")]
+    #[clap(value_name = "NAMES", requires = "stdin")]
+    pub(crate) names: Vec<Name>,
+
     /// Number of runtime workers, defaults to the number of CPU cores
     #[clap(long = "workers")]
     pub(crate) workers: Option<usize>,
@@ -457,6 +709,36 @@ fn run() -> Result<(), String> {
         .build()
         .map_err(|err| format!("failed to initialize Tokio runtime: {err}"))?;
 
+    #[cfg(feature = "blocklist")]
+    if args.compile_blocklists {
+        return runtime.block_on(compile_blocklists(&config, &zone_dir, config_path.parent()));
+    }
+
+    #[cfg(feature = "blocklist")]
+    if args.stdin {
+        return runtime.block_on(check_stdin_list(&args.names));
+    }
+
+    #[cfg(feature = "blocklist")]
+    if args.check_blocklists {
+        runtime.block_on(check_blocklists(&config, &zone_dir, config_path.parent()))?;
+        info!("blocklists in {config_path:?} loaded");
+        return Ok(());
+    }
+
+    #[cfg(feature = "blocklist")]
+    if let Some(old_path) = &args.diff_blocklists {
+        let old = Config::read_config(old_path)
+            .map_err(|err| format!("failed to read config file from {old_path:?}: {err}"))?;
+        let old_zone_dir = zonedir
+            .as_ref()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| old.get_directory().to_path_buf());
+        let old = (&old, old_zone_dir.as_path(), old_path.parent());
+        let new = (&config, zone_dir.as_path(), config_path.parent());
+        return runtime.block_on(diff_blocklists(old, new));
+    }
+
     let mut catalog: Catalog = Catalog::new();
     // configure our server based on the config_path
     for zone in config.get_zones() {
@@ -464,8 +746,10 @@ fn run() -> Result<(), String> {
             .get_zone()
             .map_err(|err| format!("failed to read zone name from {config_path:?}: {err}"))?;
 
-        match runtime.block_on(load_zone(&zone_dir, zone)) {
-            Ok(authority) => catalog.upsert(zone_name.into(), authority),
+        match runtime.block_on(load_zone(&zone_dir, config_path.parent(), zone)) {
+            Ok(authority) => catalog
+                .try_upsert(zone_name.into(), authority)
+                .map_err(|err| format!("invalid zones in {config_path:?}: {err}"))?,
             Err(err) => return Err(format!("could not load zone {zone_name}: {err}")),
         }
     }
@@ -0,0 +1,91 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+#![cfg(feature = "blocklist")]
+
+use std::env;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+/// Run the server with `--check-blocklists` on the test configuration `toml`
+fn check_blocklists(toml: &str) -> Output {
+    let server_path = env::var("TDNS_WORKSPACE_ROOT").unwrap_or_else(|_| "..".to_owned());
+    Command::new(env!("CARGO_BIN_EXE_hickory-dns"))
+        .arg("--check-blocklists")
+        .arg(format!(
+            "--config={server_path}/tests/test-data/test_configs/{toml}"
+        ))
+        .arg(format!(
+            "--zonedir={server_path}/tests/test-data/test_configs"
+        ))
+        .output()
+        .expect("failed to run named")
+}
+
+#[test]
+fn test_check_blocklists() {
+    let output = check_blocklists("blocklist_check.toml");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn test_check_blocklists_broken() {
+    // The list is missing, and the check fails even though the configuration would start without it.
+    let output = check_blocklists("blocklist_check_broken.toml");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("missing_blocklist.txt"), "{stderr}");
+}
+
+#[test]
+fn test_check_blocklists_stdin() {
+    let server_path = env::var("TDNS_WORKSPACE_ROOT").unwrap_or_else(|_| "..".to_owned());
+    let mut child = Command::new(env!("CARGO_BIN_EXE_hickory-dns"))
+        .args(["--check-blocklists", "--stdin"])
+        .arg(format!(
+            "--config={server_path}/tests/test-data/test_configs/blocklist_check.toml"
+        ))
+        .args(["ads.example.com", "www.tracker.example.net.", "example.com"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to run named");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"# piped in\nads.example.com\n*.tracker.example.net\n")
+        .unwrap();
+    let output = child.wait_with_output().expect("failed to run named");
+
+    // The names are checked against the piped list, read with the same parser as list files.
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    // Logs are written to standard output as well.
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let checked = stdout
+        .lines()
+        .filter(|line| line.ends_with(" blocked"))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        checked,
+        [
+            "ads.example.com. blocked",
+            "www.tracker.example.net. blocked",
+            "example.com. not blocked"
+        ],
+        "{stdout}"
+    );
+}
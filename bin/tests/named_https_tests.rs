@@ -24,70 +24,16 @@ use rustls::{Certificate, ClientConfig, OwnedTrustAnchor, RootCertStore};
 use tokio::net::TcpStream as TokioTcpStream;
 use tokio::runtime::Runtime;
 
-use server_harness::{named_test_harness, query_a};
+use server_harness::{named_test_harness, query_a, SocketPorts};
 
 #[test]
 fn test_example_https_toml_startup() {
     // env_logger::try_init().ok();
 
-    const ALPN_H2: &[u8] = b"h2";
-
     named_test_harness("dns_over_https.toml", move |socket_ports| {
-        let mut cert_der = vec![];
-        let https_port = socket_ports.get_v4(Protocol::Https);
-        let server_path = env::var("TDNS_WORKSPACE_ROOT").unwrap_or_else(|_| "..".to_owned());
-        println!("using server src path: {server_path}");
-
-        File::open(format!(
-            "{server_path}/tests/test-data/test_configs/sec/example.cert"
-        ))
-        .expect("failed to open cert")
-        .read_to_end(&mut cert_der)
-        .expect("failed to read cert");
-
-        let mut io_loop = Runtime::new().unwrap();
-        let addr: SocketAddr = ("127.0.0.1", https_port.expect("no https_port"))
-            .to_socket_addrs()
-            .unwrap()
-            .next()
-            .unwrap();
-
-        std::thread::sleep(std::time::Duration::from_secs(1));
-
-        // using the mozilla default root store
-        let mut root_store = RootCertStore::empty();
-        root_store.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
-            OwnedTrustAnchor::from_subject_spki_name_constraints(
-                ta.subject,
-                ta.spki,
-                ta.name_constraints,
-            )
-        }));
-
-        let cert = to_trust_anchor(&cert_der);
-        root_store.add(&cert).unwrap();
-
-        let mut client_config = ClientConfig::builder()
-            .with_safe_default_cipher_suites()
-            .with_safe_default_kx_groups()
-            .with_safe_default_protocol_versions()
-            .unwrap()
-            .with_root_certificates(root_store)
-            .with_no_client_auth();
-        client_config.alpn_protocols.push(ALPN_H2.to_vec());
-
-        let client_config = Arc::new(client_config);
-
-        let https_builder = HttpsClientStreamBuilder::with_client_config(client_config);
-
-        let mp = https_builder
-            .build::<AsyncIoTokioAsStd<TokioTcpStream>>(addr, "ns.example.com".to_string());
-        let client = AsyncClient::connect(mp);
+        let (mut io_loop, mut client) = https_client(&socket_ports);
 
         // ipv4 should succeed
-        let (mut client, bg) = io_loop.block_on(client).expect("client failed to connect");
-        hickory_proto::spawn_bg(&io_loop, bg);
-
         query_a(&mut io_loop, &mut client);
 
         // a second request should work...
@@ -95,6 +41,93 @@ fn test_example_https_toml_startup() {
     })
 }
 
+#[test]
+#[cfg(feature = "blocklist")]
+fn test_blocklist_https() {
+    use std::str::FromStr;
+
+    use hickory_proto::op::ResponseCode;
+    use hickory_proto::rr::{rdata::A, Name, RData, RecordType};
+
+    use server_harness::query_message;
+
+    named_test_harness("dns_over_https_blocklist.toml", move |socket_ports| {
+        let (mut io_loop, mut client) = https_client(&socket_ports);
+
+        // Test: a blocked name is answered over HTTPS as it is over UDP and TCP: with an authoritative sinkhole address
+        let name = Name::from_str("foo.com.").unwrap();
+        let response = query_message(&mut io_loop, &mut client, name, RecordType::A).unwrap();
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert!(response.authoritative());
+        assert_eq!(response.answers().len(), 1);
+        assert_eq!(response.answers()[0].data(), &RData::A(A::new(0, 0, 0, 0)));
+
+        // Test: names which are not blocked are still answered by their zone.
+        query_a(&mut io_loop, &mut client);
+    })
+}
+
+/// Connect a client to the server's HTTPS port, trusting the test certificate
+fn https_client(socket_ports: &SocketPorts) -> (Runtime, AsyncClient) {
+    const ALPN_H2: &[u8] = b"h2";
+
+    let mut cert_der = vec![];
+    let https_port = socket_ports.get_v4(Protocol::Https);
+    let server_path = env::var("TDNS_WORKSPACE_ROOT").unwrap_or_else(|_| "..".to_owned());
+    println!("using server src path: {server_path}");
+
+    File::open(format!(
+        "{server_path}/tests/test-data/test_configs/sec/example.cert"
+    ))
+    .expect("failed to open cert")
+    .read_to_end(&mut cert_der)
+    .expect("failed to read cert");
+
+    let io_loop = Runtime::new().unwrap();
+    let addr: SocketAddr = ("127.0.0.1", https_port.expect("no https_port"))
+        .to_socket_addrs()
+        .unwrap()
+        .next()
+        .unwrap();
+
+    std::thread::sleep(std::time::Duration::from_secs(1));
+
+    // using the mozilla default root store
+    let mut root_store = RootCertStore::empty();
+    root_store.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+        OwnedTrustAnchor::from_subject_spki_name_constraints(
+            ta.subject,
+            ta.spki,
+            ta.name_constraints,
+        )
+    }));
+
+    let cert = to_trust_anchor(&cert_der);
+    root_store.add(&cert).unwrap();
+
+    let mut client_config = ClientConfig::builder()
+        .with_safe_default_cipher_suites()
+        .with_safe_default_kx_groups()
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_root_certificates(root_store)
+        .with_no_client_auth();
+    client_config.alpn_protocols.push(ALPN_H2.to_vec());
+
+    let client_config = Arc::new(client_config);
+
+    let https_builder = HttpsClientStreamBuilder::with_client_config(client_config);
+
+    let mp = https_builder
+        .build::<AsyncIoTokioAsStd<TokioTcpStream>>(addr, "ns.example.com".to_string());
+    let client = AsyncClient::connect(mp);
+
+    let (client, bg) = io_loop.block_on(client).expect("client failed to connect");
+    hickory_proto::spawn_bg(&io_loop, bg);
+
+    (io_loop, client)
+}
+
 fn to_trust_anchor(cert_der: &[u8]) -> Certificate {
     Certificate(cert_der.to_vec())
 }
@@ -66,3 +66,55 @@ fn nxdomain() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn allowlist_overrides_blocklist() -> Result<()> {
+    if !dns_test::SUBJECT.supports_blocklist() {
+        return Ok(());
+    }
+
+    let expected_ipv4_addr = Ipv4Addr::new(1, 2, 3, 4);
+    let blocked_fqdn = FQDN("example.nameservers.com.")?;
+    let ads_fqdn = FQDN("ads.example.nameservers.com.")?;
+    let safe_fqdn = FQDN("safe.example.nameservers.com.")?;
+
+    let network = Network::new()?;
+
+    let mut leaf_ns = NameServer::new(&dns_test::PEER, FQDN::NAMESERVERS, &network)?;
+    leaf_ns
+        .add(Record::a(ads_fqdn.clone(), expected_ipv4_addr))
+        .add(Record::a(safe_fqdn.clone(), expected_ipv4_addr));
+
+    let Graph {
+        nameservers: _nameservers,
+        root,
+        ..
+    } = Graph::build(leaf_ns, Sign::No)?;
+
+    let resolver = Resolver::new(&network, root)
+        .block(blocked_fqdn)
+        .allow(safe_fqdn.clone())
+        .start()?;
+    let resolver_ip_addr = resolver.ipv4_addr();
+
+    let client = Client::new(&network)?;
+    let settings = *DigSettings::default().recurse();
+
+    // names below the blocked name are answered by the blocklist with its sinkhole address
+    let output = client.dig(settings, resolver_ip_addr, RecordType::A, &ads_fqdn)?;
+    assert!(output.status.is_noerror());
+    let [answer] = output.answer.try_into().unwrap();
+    let a = answer.try_into_a().unwrap();
+    assert_eq!(ads_fqdn, a.fqdn);
+    assert_eq!(Ipv4Addr::UNSPECIFIED, a.ipv4_addr);
+
+    // the allowed name is passed on to the recursor, which resolves it
+    let output = client.dig(settings, resolver_ip_addr, RecordType::A, &safe_fqdn)?;
+    assert!(output.status.is_noerror());
+    let [answer] = output.answer.try_into().unwrap();
+    let a = answer.try_into_a().unwrap();
+    assert_eq!(safe_fqdn, a.fqdn);
+    assert_eq!(expected_ipv4_addr, a.ipv4_addr);
+
+    Ok(())
+}
@@ -16,6 +16,8 @@ pub enum Config<'a> {
         netmask: &'a str,
         /// Extended DNS error (RFC8914)
         ede: bool,
+        /// Answer queries through a blocklist, whose lists are at `/etc/blocklist.txt` and `/etc/allowlist.txt`
+        blocklist: bool,
     },
 }
 
@@ -50,6 +52,11 @@ impl Implementation {
         }
     }
 
+    /// Whether the resolver can block names through a blocklist
+    pub fn supports_blocklist(&self) -> bool {
+        self.is_hickory()
+    }
+
     /// Returns the latest hickory-dns local revision
     pub fn hickory() -> Self {
         Self::Hickory(Repository(crate::repo_root()))
@@ -81,9 +88,11 @@ impl Implementation {
                 use_dnssec,
                 netmask,
                 ede,
+                blocklist,
             } => match self {
                 Self::Bind => {
                     assert!(!ede, "the BIND resolver does not support EDE (RFC8914)");
+                    assert!(!blocklist, "the BIND resolver does not support a blocklist");
 
                     minijinja::render!(
                         include_str!("templates/named.resolver.conf.jinja"),
@@ -97,10 +106,13 @@ impl Implementation {
                     minijinja::render!(
                         include_str!("templates/hickory.resolver.toml.jinja"),
                         use_dnssec => use_dnssec,
+                        blocklist => blocklist,
                     )
                 }
 
                 Self::Unbound => {
+                    assert!(!blocklist, "the unbound resolver does not support a blocklist");
+
                     minijinja::render!(
                         include_str!("templates/unbound.conf.jinja"),
                         use_dnssec => use_dnssec,
@@ -8,7 +8,7 @@ use crate::record::DNSKEY;
 use crate::trust_anchor::TrustAnchor;
 use crate::tshark::Tshark;
 use crate::zone_file::Root;
-use crate::{Implementation, Result};
+use crate::{Implementation, Result, FQDN};
 
 pub struct Resolver {
     container: Container,
@@ -20,6 +20,8 @@ impl Resolver {
     #[allow(clippy::new_ret_no_self)]
     pub fn new(network: &Network, root: Root) -> ResolverSettings {
         ResolverSettings {
+            allowed: vec![],
+            blocked: vec![],
             ede: false,
             network: network.clone(),
             roots: vec![root],
@@ -74,6 +76,10 @@ kill -TERM $(cat {pidfile})"
 }
 
 pub struct ResolverSettings {
+    /// Names which are never blocked
+    allowed: Vec<FQDN>,
+    /// Names which are blocked, along with every name below them
+    blocked: Vec<FQDN>,
     /// Extended DNS Errors (RFC8914)
     ede: bool,
     network: Network,
@@ -103,11 +109,28 @@ impl ResolverSettings {
 
         container.cp("/etc/root.hints", &hints)?;
 
+        let blocklist = !self.blocked.is_empty() || !self.allowed.is_empty();
+        if blocklist {
+            // a leading dot blocks the name and every name below it
+            let mut blocked = String::new();
+            for fqdn in &self.blocked {
+                writeln!(blocked, ".{fqdn}").unwrap();
+            }
+            container.cp("/etc/blocklist.txt", &blocked)?;
+
+            let mut allowed = String::new();
+            for fqdn in &self.allowed {
+                writeln!(allowed, "^{}$", fqdn.as_str().replace('.', "\\.")).unwrap();
+            }
+            container.cp("/etc/allowlist.txt", &allowed)?;
+        }
+
         let use_dnssec = !self.trust_anchor.is_empty();
         let config = Config::Resolver {
             use_dnssec,
             netmask: self.network.netmask(),
             ede: self.ede,
+            blocklist,
         };
         container.cp(
             implementation.conf_file_path(config.role()),
@@ -153,6 +176,18 @@ impl ResolverSettings {
         })
     }
 
+    /// Blocks `fqdn`, and every name below it, answering queries for them without resolving them
+    pub fn block(&mut self, fqdn: FQDN) -> &mut Self {
+        self.blocked.push(fqdn);
+        self
+    }
+
+    /// Never blocks `fqdn`, even if it is below a blocked name; queries for it are resolved as usual
+    pub fn allow(&mut self, fqdn: FQDN) -> &mut Self {
+        self.allowed.push(fqdn);
+        self
+    }
+
     /// Enables the Extended DNS Errors (RFC8914) feature
     pub fn extended_dns_errors(&mut self) -> &mut Self {
         self.ede = true;
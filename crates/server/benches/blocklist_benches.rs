@@ -0,0 +1,323 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Blocked name lookups under a skewed workload, with and without the response cache, from one thread and from several
+//! at once, matching names against allow
+//! patterns, building the wildcards of deep names, and looking up a million entries in each block table backend.  Each
+//! lookup benchmark also prints the number of allocations per lookup, and each backend benchmark the time taken to load
+//! its entries and the memory they take.
+
+#![cfg(all(nightly, feature = "blocklist"))]
+#![feature(test)]
+
+extern crate test;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Instant;
+
+use futures_executor::block_on;
+use regex::{Regex, RegexSet};
+use test::Bencher;
+
+use hickory_server::authority::{Authority, LookupOptions};
+use hickory_server::proto::rr::{LowerName, Name, RecordType};
+use hickory_server::store::blocklist::{
+    BlockMatcher, BlocklistAuthority, BlocklistAuthorityBuilder, HashMatcher, PatriciaMatcher,
+    TreeMatcher,
+};
+
+/// Counts every allocation, and the bytes allocated, so that the benchmarks can report allocations per lookup and the
+/// memory a table takes
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const NAMES: usize = 10_000;
+const QUERIES: usize = 100_000;
+
+/// Blocked names, and queries for them drawn from a Zipf distribution with an exponent of 1, so that a few names take
+/// most of the queries, as they do in practice
+fn workload() -> (Vec<Name>, Vec<LowerName>) {
+    let names = (0..NAMES)
+        .map(|i| Name::from_str(&format!("ads{i}.tracker.example.com.")).unwrap())
+        .collect::<Vec<_>>();
+
+    let weights = (1..=NAMES).map(|rank| 1.0 / rank as f64);
+    let total = weights.clone().sum::<f64>();
+    let cdf = weights
+        .scan(0.0, |sum, weight| {
+            *sum += weight / total;
+            Some(*sum)
+        })
+        .collect::<Vec<_>>();
+
+    // A fixed xorshift sequence, so that every run queries the same names.
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let queries = (0..QUERIES)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let sample = (state >> 11) as f64 / (1_u64 << 53) as f64;
+            let rank = cdf.partition_point(|&p| p < sample).min(NAMES - 1);
+            LowerName::from(&names[rank])
+        })
+        .collect();
+
+    (names, queries)
+}
+
+/// An authority blocking the names of the workload, with a response cache of `response_cache_size` entries, and the
+/// queries to send it
+fn lookup_workload(response_cache_size: usize) -> (BlocklistAuthority, Vec<LowerName>) {
+    let (names, queries) = workload();
+    let authority = BlocklistAuthorityBuilder::new(Name::root())
+        .add_entries(names)
+        .response_cache_size(response_cache_size)
+        .build()
+        .unwrap();
+    (authority, queries)
+}
+
+fn lookup(authority: &BlocklistAuthority, name: &LowerName) {
+    block_on(authority.lookup(name, RecordType::A, LookupOptions::default()))
+        .unwrap()
+        .unwrap();
+}
+
+fn bench_lookups(b: &mut Bencher, response_cache_size: usize) {
+    let (authority, queries) = lookup_workload(response_cache_size);
+
+    let mut lookups = 0;
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    b.iter(|| {
+        lookup(&authority, &queries[lookups % QUERIES]);
+        lookups += 1;
+    });
+
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+    eprintln!(
+        "response_cache_size {response_cache_size}: {:.2} allocations per lookup",
+        allocations as f64 / lookups.max(1) as f64
+    );
+}
+
+const THREADS: usize = 8;
+const BATCH: usize = 1_000;
+
+/// Each iteration sends `BATCH` queries from each of `THREADS` threads at once, so the time per iteration covers
+/// `THREADS * BATCH` lookups, and shows how much the threads contend for the authority and its response cache
+fn bench_lookups_threaded(b: &mut Bencher, response_cache_size: usize) {
+    let (authority, queries) = lookup_workload(response_cache_size);
+
+    let mut batches = 0;
+    b.iter(|| {
+        thread::scope(|scope| {
+            for thread in 0..THREADS {
+                let (authority, queries) = (&authority, &queries);
+                let start = (batches * THREADS + thread) * BATCH;
+                scope.spawn(move || {
+                    for i in start..start + BATCH {
+                        lookup(authority, &queries[i % QUERIES]);
+                    }
+                });
+            }
+        });
+        batches += 1;
+    });
+}
+
+#[bench]
+fn blocked_zipf_uncached(b: &mut Bencher) {
+    bench_lookups(b, 0);
+}
+
+#[bench]
+fn blocked_zipf_cached(b: &mut Bencher) {
+    bench_lookups(b, 1_000);
+}
+
+#[bench]
+fn blocked_zipf_uncached_threaded(b: &mut Bencher) {
+    bench_lookups_threaded(b, 0);
+}
+
+#[bench]
+fn blocked_zipf_cached_threaded(b: &mut Bencher) {
+    bench_lookups_threaded(b, 1_000);
+}
+
+const PATTERNS: usize = 1_000;
+
+/// Allow patterns, one per internal domain, and names to match against them, most of which match none
+fn allow_patterns() -> (Vec<String>, Vec<String>) {
+    let patterns = (0..PATTERNS)
+        .map(|i| format!(r"^(.*\.)?internal{i}\.corp\.$"))
+        .collect();
+    let names = (0..100)
+        .map(|i| match i % 10 {
+            0 => format!("host.internal{}.corp.", i * 7),
+            _ => format!("ads{i}.tracker.example.com."),
+        })
+        .collect();
+    (patterns, names)
+}
+
+#[bench]
+fn allow_patterns_regex_set(b: &mut Bencher) {
+    let (patterns, names) = allow_patterns();
+    let set = RegexSet::new(patterns).unwrap();
+
+    b.iter(|| names.iter().filter(|name| set.is_match(name)).count());
+}
+
+#[bench]
+fn allow_patterns_regex_vec(b: &mut Bencher) {
+    let (patterns, names) = allow_patterns();
+    let regexes = patterns
+        .iter()
+        .map(|pattern| Regex::new(pattern).unwrap())
+        .collect::<Vec<_>>();
+
+    b.iter(|| {
+        names
+            .iter()
+            .filter(|name| regexes.iter().any(|regex| regex.is_match(name)))
+            .count()
+    });
+}
+
+/// Names ten labels deep, as seen from tracking subdomains and CDN edges
+fn deep_names() -> Vec<Name> {
+    (0..100)
+        .map(|i| Name::from_str(&format!("a{i}.b.c.d.e.f.g.h.example.com.")).unwrap())
+        .collect()
+}
+
+/// The wildcards of `name` with at least two labels, built by truncating the name to each length and replacing its first
+/// label, as the blocklist used to
+fn wildcards_trim_to(name: &Name) -> Vec<LowerName> {
+    name.iter()
+        .enumerate()
+        .filter(|(i, _)| *i >= 2)
+        .map(|(i, _)| name.trim_to(i + 1).into_wildcard().into())
+        .rev()
+        .collect()
+}
+
+/// The wildcards of `name` with at least two labels, built by walking up its parents, as the blocklist does
+fn wildcards_base_name(name: &LowerName) -> Vec<LowerName> {
+    let mut wildcards = Vec::new();
+    let mut current = name.clone();
+    while current.num_labels() > 2 {
+        let parent = current.base_name();
+        wildcards.push(current.into_wildcard());
+        current = parent;
+    }
+    wildcards
+}
+
+#[bench]
+fn deep_wildcards_trim_to(b: &mut Bencher) {
+    let names = deep_names();
+    b.iter(|| {
+        names
+            .iter()
+            .map(|name| wildcards_trim_to(name).len())
+            .sum::<usize>()
+    });
+}
+
+#[bench]
+fn deep_wildcards_base_name(b: &mut Bencher) {
+    let names = deep_names().iter().map(LowerName::from).collect::<Vec<_>>();
+    b.iter(|| {
+        names
+            .iter()
+            .map(|name| wildcards_base_name(name).len())
+            .sum::<usize>()
+    });
+}
+
+const TABLE_ENTRIES: usize = 1_000_000;
+
+/// A million blocked names, spread over a few thousand tracking domains, as in the large aggregated lists, and queries
+/// for them, a tenth of which are for names on none of them
+fn table_workload() -> (Vec<LowerName>, Vec<LowerName>) {
+    let names = (0..TABLE_ENTRIES)
+        .map(|i| {
+            let name = format!("host{i}.cdn{}.tracker{}.example.com.", i % 97, i % 5_000);
+            LowerName::from_str(&name).unwrap()
+        })
+        .collect::<Vec<_>>();
+    let queries = (0..QUERIES)
+        .map(|i| match i % 10 {
+            0 => LowerName::from_str(&format!("www{i}.unlisted.example.net.")).unwrap(),
+            _ => names[i * 7_919 % TABLE_ENTRIES].clone(),
+        })
+        .collect();
+    (names, queries)
+}
+
+fn bench_table<M: BlockMatcher + Default>(b: &mut Bencher, backend: &str) {
+    let (names, queries) = table_workload();
+
+    let allocated = ALLOCATED.load(Ordering::Relaxed);
+    let start = Instant::now();
+    let mut table = M::default();
+    for name in &names {
+        table.insert(name.clone(), 0);
+    }
+    table.shrink_to_fit();
+    eprintln!(
+        "{backend}: loaded {TABLE_ENTRIES} entries in {:?}, taking {:.1} MiB",
+        start.elapsed(),
+        ALLOCATED.load(Ordering::Relaxed).saturating_sub(allocated) as f64 / (1 << 20) as f64
+    );
+
+    let mut lookups = 0;
+    b.iter(|| {
+        let name = &queries[lookups % QUERIES];
+        lookups += 1;
+        table.matches(name)
+    });
+}
+
+#[bench]
+fn table_hash(b: &mut Bencher) {
+    bench_table::<HashMatcher>(b, "hash");
+}
+
+#[bench]
+fn table_tree(b: &mut Bencher) {
+    bench_table::<TreeMatcher>(b, "tree");
+}
+
+#[bench]
+fn table_patricia(b: &mut Bencher) {
+    bench_table::<PatriciaMatcher>(b, "patricia");
+}
@@ -16,7 +16,10 @@ use crate::proto::rr::{
 };
 use crate::{
     authority::{LookupError, MessageRequest, UpdateResult, ZoneType},
-    proto::rr::{LowerName, RecordSet, RecordType, RrsetRecords},
+    proto::{
+        op::ResponseCode,
+        rr::{LowerName, Record, RecordSet, RecordType, RrsetRecords},
+    },
     server::RequestInfo,
 };
 
@@ -109,6 +112,47 @@ pub trait Authority: Send + Sync {
         false
     }
 
+    /// Whether the authority's answers are authoritative even though its zone type is not, i.e. they are synthesized
+    /// locally rather than forwarded or resolved.  Responses from such authorities have the AA bit set.
+    fn answers_authoritatively(&self) -> bool {
+        false
+    }
+
+    /// Whether the authority screens every name below its origin before passing queries for them on, e.g. a blocklist.  A
+    /// zone with an overlapping origin which is screened as well would shadow it for the names below both, so
+    /// [`Catalog::try_upsert`](crate::authority::Catalog::try_upsert) rejects such zones.  Defaults to false.
+    fn screens_names(&self) -> bool {
+        false
+    }
+
+    /// Whether the EDNS client subnet (RFC 7871) is removed from requests this authority passes on, so that the
+    /// authorities after it in the chain, e.g. a forwarder, never see it.  Defaults to false.
+    fn strips_client_subnet(&self) -> bool {
+        false
+    }
+
+    /// Whether the answers to queries this authority passes on are filtered with [`Self::filter_answers`] once a later
+    /// authority in the chain, e.g. a forwarder, has resolved them.  Defaults to false.
+    fn filters_answers(&self) -> bool {
+        false
+    }
+
+    /// Filter the answers to a query for `name` and `rtype` which this authority passed on, as resolved by a later
+    /// authority in the chain.  An error replaces the answers with an empty response with its response code.  Only
+    /// called if [`Self::filters_answers`] is set; defaults to returning the answers unchanged.
+    fn filter_answers(
+        &self,
+        _name: &LowerName,
+        _rtype: RecordType,
+        answers: Vec<Record>,
+    ) -> Result<Vec<Record>, LookupError> {
+        Ok(answers)
+    }
+
+    /// Observe the response code of the response to a query for `name` which this authority passed on, as answered by a
+    /// later authority in the chain.  Defaults to doing nothing.
+    fn observe_response(&self, _name: &LowerName, _response_code: ResponseCode) {}
+
     /// Perform a dynamic update of a zone
     async fn update(&self, update: &MessageRequest) -> UpdateResult<bool>;
 
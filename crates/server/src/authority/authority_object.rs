@@ -13,7 +13,10 @@ use tracing::debug;
 
 use crate::{
     authority::{Authority, LookupError, LookupOptions, MessageRequest, UpdateResult, ZoneType},
-    proto::rr::{LowerName, Record, RecordType},
+    proto::{
+        op::ResponseCode,
+        rr::{LowerName, Record, RecordType},
+    },
     server::RequestInfo,
 };
 
@@ -32,6 +35,29 @@ pub trait AuthorityObject: Send + Sync {
     /// Whether the authority can perform DNSSEC validation
     fn can_validate_dnssec(&self) -> bool;
 
+    /// Whether the authority's answers are authoritative even though its zone type is not
+    fn answers_authoritatively(&self) -> bool;
+
+    /// Whether the authority screens every name below its origin before passing queries for them on
+    fn screens_names(&self) -> bool;
+
+    /// Whether the EDNS client subnet is removed from requests this authority passes on
+    fn strips_client_subnet(&self) -> bool;
+
+    /// Whether the answers to queries this authority passes on are filtered with `filter_answers`
+    fn filters_answers(&self) -> bool;
+
+    /// Filter the answers to a query this authority passed on, as resolved by a later authority in the chain
+    fn filter_answers(
+        &self,
+        name: &LowerName,
+        rtype: RecordType,
+        answers: Vec<Record>,
+    ) -> Result<Vec<Record>, LookupError>;
+
+    /// Observe the response code of the response to a query this authority passed on
+    fn observe_response(&self, name: &LowerName, response_code: ResponseCode);
+
     /// Perform a dynamic update of a zone
     async fn update(&self, update: &MessageRequest) -> UpdateResult<bool>;
 
@@ -142,6 +168,35 @@ where
         Authority::can_validate_dnssec(self.as_ref())
     }
 
+    fn answers_authoritatively(&self) -> bool {
+        Authority::answers_authoritatively(self.as_ref())
+    }
+
+    fn screens_names(&self) -> bool {
+        Authority::screens_names(self.as_ref())
+    }
+
+    fn strips_client_subnet(&self) -> bool {
+        Authority::strips_client_subnet(self.as_ref())
+    }
+
+    fn filters_answers(&self) -> bool {
+        Authority::filters_answers(self.as_ref())
+    }
+
+    fn filter_answers(
+        &self,
+        name: &LowerName,
+        rtype: RecordType,
+        answers: Vec<Record>,
+    ) -> Result<Vec<Record>, LookupError> {
+        Authority::filter_answers(self.as_ref(), name, rtype, answers)
+    }
+
+    fn observe_response(&self, name: &LowerName, response_code: ResponseCode) {
+        Authority::observe_response(self.as_ref(), name, response_code)
+    }
+
     /// Perform a dynamic update of a zone
     async fn update(&self, update: &MessageRequest) -> UpdateResult<bool> {
         Authority::update(self.as_ref(), update).await
@@ -247,6 +302,26 @@ pub trait LookupObject: Send {
     fn dnssec_validated(&self) -> bool {
         false
     }
+
+    /// The response code of the response, e.g. NXDOMAIN for a negative response synthesized by an authority which answers
+    /// authoritatively.  Only used for such authorities; defaults to NOERROR.
+    fn response_code(&self) -> ResponseCode {
+        ResponseCode::NoError
+    }
+
+    /// The SOA record returned in the authority section of a negative response synthesized by an authority which answers
+    /// authoritatively.  Its TTL and minimum set how long the response is cached (RFC 2308).
+    ///
+    /// it is acceptable for this to return None after the first call.
+    fn take_soa(&mut self) -> Option<Box<dyn LookupObject>> {
+        None
+    }
+
+    /// The extended DNS error (RFC 8914) attached to a response synthesized by an authority which answers
+    /// authoritatively, as its INFO-CODE and EXTRA-TEXT.  Only sent to clients which sent EDNS; defaults to none.
+    fn extended_error(&self) -> Option<(u16, &str)> {
+        None
+    }
 }
 
 /// A lookup that returns no records
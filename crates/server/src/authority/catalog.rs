@@ -14,20 +14,23 @@ use cfg_if::cfg_if;
 use tracing::{debug, error, info, trace, warn};
 
 #[cfg(feature = "dnssec")]
-use crate::proto::rr::{
-    dnssec::{Algorithm, SupportedAlgorithms},
-    rdata::opt::{EdnsCode, EdnsOption},
-};
+use crate::proto::rr::dnssec::{Algorithm, SupportedAlgorithms};
 use crate::{
     authority::{
         AuthLookup, AuthorityObject, EmptyLookup, LookupError, LookupObject, LookupOptions,
         MessageResponse, MessageResponseBuilder, ZoneType,
     },
     proto::op::{Edns, Header, LowerQuery, MessageType, OpCode, ResponseCode},
-    proto::rr::{LowerName, Record, RecordType},
+    proto::rr::{
+        rdata::opt::{EdnsCode, EdnsOption},
+        LowerName, Record, RecordType,
+    },
     server::{Request, RequestHandler, RequestInfo, ResponseHandler, ResponseInfo},
 };
 
+/// The EDNS option code of an extended DNS error (RFC 8914)
+const EXTENDED_DNS_ERROR: u16 = 15;
+
 /// Set of authorities, zones, available to this server.
 #[derive(Default)]
 pub struct Catalog {
@@ -192,6 +195,41 @@ impl Catalog {
         self.authorities.insert(name, authorities);
     }
 
+    /// Insert or update a zone authority, as with [`Self::upsert`], unless its authorities screen the names below its
+    /// origin, e.g. a blocklist, and so do those of a zone already in the catalog with an overlapping origin.
+    ///
+    /// Each query is routed to the zone with the most specific matching origin, so a screened zone whose origin is the
+    /// same as, or below, that of another would silently shadow the other for every name under it, and a duplicate
+    /// origin would replace the first zone outright.  Returns an error naming the overlapping zones.
+    pub fn try_upsert(
+        &mut self,
+        name: LowerName,
+        authorities: Vec<Box<dyn AuthorityObject>>,
+    ) -> Result<(), String> {
+        let screened = |authorities: &[Box<dyn AuthorityObject>]| {
+            authorities
+                .iter()
+                .any(|authority| authority.screens_names())
+        };
+        if screened(&authorities) {
+            let overlap = self.authorities.iter().find(|(origin, others)| {
+                screened(others) && (origin.zone_of(&name) || name.zone_of(origin))
+            });
+            if let Some((origin, _)) = overlap {
+                return Err(match *origin == name {
+                    true => format!("screened zone {name} is configured more than once"),
+                    false => format!(
+                        "screened zones {origin} and {name} overlap; queries for names in both would only be \
+                         screened by the more specific zone"
+                    ),
+                });
+            }
+        }
+
+        self.upsert(name, authorities);
+        Ok(())
+    }
+
     /// Remove a zone from the catalog
     pub fn remove(&mut self, name: &LowerName) -> Option<Vec<Box<dyn AuthorityObject>>> {
         self.authorities.remove(name)
@@ -347,14 +385,27 @@ impl Catalog {
         response_edns: Option<Edns>,
         response_handle: R,
     ) -> ResponseInfo {
-        let request_info = request.request_info();
+        let mut request_info = request.request_info();
         let authorities = self.find(request_info.query.name());
 
+        // The request as seen by the authorities after one which strips the client subnet from requests it passes on
+        let stripped_edns = request
+            .edns()
+            .filter(|edns| edns.option(EdnsCode::Subnet).is_some())
+            .map(|edns| {
+                let mut edns = edns.clone();
+                edns.options_mut().remove(EdnsCode::Subnet);
+                edns
+            });
+
         if let Some(authorities) = authorities {
+            // The authorities which passed the request on, which may filter the answers of the one which handles it
+            let mut passed = Vec::new();
             for authority in authorities {
                 let result = lookup(
                     request_info.clone(),
                     &**authority,
+                    &passed,
                     request,
                     response_edns
                         .as_ref()
@@ -367,6 +418,10 @@ impl Catalog {
                     // The current authority in the chain did not handle the request, so we need to try the next one, if any.
                     None => {
                         debug!("catalog::lookup::authority did not handle request.");
+                        if authority.strips_client_subnet() && stripped_edns.is_some() {
+                            request_info.edns = stripped_edns.as_ref();
+                        }
+                        passed.push(&**authority);
                     }
                     Some(Ok(r)) => {
                         debug!("Result: {r:?}");
@@ -418,11 +473,13 @@ impl Catalog {
 async fn lookup<'a, R: ResponseHandler + Unpin>(
     request_info: RequestInfo<'_>,
     authority: &dyn AuthorityObject,
+    passed: &[&dyn AuthorityObject],
     request: &Request,
-    response_edns: Option<Edns>,
+    mut response_edns: Option<Edns>,
     response_handle: R,
 ) -> Option<Result<ResponseInfo, LookupError>> {
     let query = request_info.query;
+    let edns = request_info.edns;
     debug!(
         "request: {} found authority: {}",
         request.id(),
@@ -431,11 +488,12 @@ async fn lookup<'a, R: ResponseHandler + Unpin>(
 
     let response = build_response(
         authority,
+        passed,
         request_info,
         request.id(),
         request.header(),
         query,
-        request.edns(),
+        edns,
     )
     .await;
 
@@ -445,7 +503,12 @@ async fn lookup<'a, R: ResponseHandler + Unpin>(
         debug!("build response returned error {e:?}");
         Some(Err(e))
     } else {
-        let (response_header, sections) = response.unwrap().unwrap();
+        let (response_header, sections, extended_error) = response.unwrap().unwrap();
+
+        // Clients which did not send EDNS get no extended error, as they have no way to receive it (RFC 8914).
+        if let (Some(edns), Some(extended_error)) = (&mut response_edns, extended_error) {
+            edns.options_mut().insert(extended_error);
+        }
 
         let response = MessageResponseBuilder::new(Some(request.raw_query())).build(
             response_header,
@@ -493,12 +556,13 @@ fn lookup_options_for_edns(edns: Option<&Edns>) -> LookupOptions {
 
 async fn build_response(
     authority: &dyn AuthorityObject,
+    passed: &[&dyn AuthorityObject],
     request_info: RequestInfo<'_>,
     request_id: u16,
     request_header: &Header,
     query: &LowerQuery,
     edns: Option<&Edns>,
-) -> Option<Result<(Header, LookupSections), LookupError>> {
+) -> Option<Result<(Header, LookupSections, Option<EdnsOption>), LookupError>> {
     let lookup_options = lookup_options_for_edns(edns);
 
     // log algorithms being requested
@@ -527,6 +591,7 @@ async fn build_response(
         Err(ref _e) => {}
     }
 
+    let mut extended_error = None;
     #[allow(deprecated)]
     let sections = match authority.zone_type() {
         ZoneType::Primary | ZoneType::Secondary | ZoneType::Master | ZoneType::Slave => {
@@ -541,17 +606,116 @@ async fn build_response(
             .await
         }
         ZoneType::Forward | ZoneType::Hint => {
-            send_forwarded_response(
+            // Answers synthesized by a non-authoritative zone, e.g. a blocklist, are its own rather than cached from
+            // upstream.  They have no signatures to validate, so they are neither failed for it when CD is clear, nor ever
+            // marked as authenticated data; CD is echoed from the query as for any other response.
+            let mut sections = send_forwarded_response(
                 future,
                 request_header,
                 &mut response_header,
-                authority.can_validate_dnssec(),
+                authority.can_validate_dnssec() && !authority.answers_authoritatively(),
             )
-            .await
+            .await;
+
+            if authority.answers_authoritatively() {
+                response_header.set_authoritative(true);
+                response_header.set_authentic_data(false);
+
+                // Negative responses synthesized by such a zone carry their response code, and its SOA, so that clients
+                // know how long to cache them.
+                let response_code = sections.answers.response_code();
+                if response_code != ResponseCode::NoError {
+                    response_header.set_response_code(response_code);
+                }
+                if let Some(soa) = sections.answers.take_soa() {
+                    sections.soa = soa;
+                }
+
+                extended_error = sections.answers.extended_error().map(|(code, text)| {
+                    let mut data = code.to_be_bytes().to_vec();
+                    data.extend_from_slice(text.as_bytes());
+                    EdnsOption::Unknown(EXTENDED_DNS_ERROR, data)
+                });
+            } else {
+                filter_answers(passed, query, &mut response_header, &mut sections.answers);
+            }
+
+            sections
         }
     };
 
-    Some(Ok((response_header, sections)))
+    for authority in passed {
+        authority.observe_response(query.name(), response_header.response_code());
+    }
+
+    Some(Ok((response_header, sections, extended_error)))
+}
+
+/// Filter the answers resolved by a forwarding authority through each authority which passed the query on to it, e.g. a
+/// blocklist removing addresses from them.  Answers which are changed are no longer those which were validated, so the
+/// response is no longer marked as authenticated data.
+fn filter_answers(
+    passed: &[&dyn AuthorityObject],
+    query: &LowerQuery,
+    response_header: &mut Header,
+    answers: &mut Box<dyn LookupObject>,
+) {
+    let mut filters = passed
+        .iter()
+        .filter(|authority| authority.filters_answers())
+        .peekable();
+    if filters.peek().is_none() {
+        return;
+    }
+
+    let mut records = answers.iter().cloned().collect::<Vec<_>>();
+    let mut changed = false;
+    for authority in filters {
+        match authority.filter_answers(query.name(), query.query_type(), records.clone()) {
+            Ok(filtered) => {
+                changed |= filtered != records;
+                records = filtered;
+            }
+            Err(e) => {
+                debug!(
+                    "answers for {} replaced by authority {}: {e}",
+                    query.name(),
+                    authority.origin()
+                );
+                let response_code = match e {
+                    LookupError::NameExists => ResponseCode::NoError,
+                    LookupError::ResponseCode(response_code) => response_code,
+                    _ => ResponseCode::ServFail,
+                };
+                response_header.set_response_code(response_code);
+                changed = true;
+                records.clear();
+                break;
+            }
+        }
+    }
+
+    if changed {
+        response_header.set_authentic_data(false);
+        *answers = Box::new(FilteredLookup(records));
+    }
+}
+
+/// Answers as filtered by the authorities which passed the query on
+struct FilteredLookup(Vec<Record>);
+
+impl LookupObject for FilteredLookup {
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a Record> + Send + 'a> {
+        Box::new(self.0.iter())
+    }
+
+    fn take_additionals(&mut self) -> Option<Box<dyn LookupObject>> {
+        None
+    }
 }
 
 async fn send_authoritative_response(
@@ -751,3 +915,60 @@ struct LookupSections {
     soa: Box<dyn LookupObject>,
     additionals: Box<dyn LookupObject>,
 }
+
+#[cfg(all(test, feature = "blocklist"))]
+mod tests {
+    use std::{str::FromStr, sync::Arc};
+
+    use super::Catalog;
+    use crate::{
+        authority::{AuthorityObject, ZoneType},
+        proto::rr::{LowerName, Name},
+        store::{blocklist::BlocklistAuthorityBuilder, in_memory::InMemoryAuthority},
+    };
+
+    fn blocklist(origin: &str) -> Vec<Box<dyn AuthorityObject>> {
+        let authority = BlocklistAuthorityBuilder::new(Name::from_str(origin).unwrap())
+            .build()
+            .expect("Unable to create blocklist authority");
+        vec![Box::new(Arc::new(authority))]
+    }
+
+    fn upsert(catalog: &mut Catalog, origin: &str) -> Result<(), String> {
+        catalog.try_upsert(LowerName::from_str(origin).unwrap(), blocklist(origin))
+    }
+
+    #[test]
+    fn test_try_upsert_screened_origins() {
+        // Test: disjoint screened zones are accepted, as is a zone below a screened zone which is not screened itself.
+        let mut catalog = Catalog::new();
+        for origin in ["example.com.", "example.net.", "ads.example.org."] {
+            upsert(&mut catalog, origin).unwrap();
+        }
+        let zone = InMemoryAuthority::empty(
+            Name::from_str("www.example.com.").unwrap(),
+            ZoneType::Primary,
+            false,
+        );
+        catalog
+            .try_upsert(
+                LowerName::from_str("www.example.com.").unwrap(),
+                vec![Box::new(Arc::new(zone))],
+            )
+            .unwrap();
+
+        // Test: duplicate origins are rejected, regardless of case.
+        let err = upsert(&mut catalog, "EXAMPLE.com.").unwrap_err();
+        assert!(err.contains("more than once"), "{err}");
+
+        // Test: nested origins are rejected in either order.
+        for names in [[".", "example.com."], ["example.com.", "."]] {
+            let mut catalog = Catalog::new();
+            upsert(&mut catalog, names[0]).unwrap();
+            let err = upsert(&mut catalog, names[1]).unwrap_err();
+            assert!(err.contains("overlap"), "{err}");
+        }
+        let err = upsert(&mut catalog, "ads.example.com.").unwrap_err();
+        assert!(err.contains("overlap"), "{err}");
+    }
+}
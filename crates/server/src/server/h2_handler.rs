@@ -30,6 +30,7 @@ pub(crate) async fn h2_handler<T, I>(
     handler: Arc<T>,
     io: I,
     src_addr: SocketAddr,
+    local_addr: Option<SocketAddr>,
     dns_hostname: Option<Arc<str>>,
     shutdown: CancellationToken,
 ) where
@@ -75,7 +76,9 @@ pub(crate) async fn h2_handler<T, I>(
 
         tokio::spawn(async move {
             match h2_server::message_from(dns_hostname, request).await {
-                Ok(bytes) => handle_request(bytes, src_addr, access, handler, responder).await,
+                Ok(bytes) => {
+                    handle_request(bytes, src_addr, local_addr, access, handler, responder).await
+                }
                 Err(err) => warn!("error while handling request from {}: {}", src_addr, err),
             };
         });
@@ -87,6 +90,7 @@ pub(crate) async fn h2_handler<T, I>(
 async fn handle_request<T>(
     bytes: BytesMut,
     src_addr: SocketAddr,
+    local_addr: Option<SocketAddr>,
     access: Arc<AccessControl>,
     handler: Arc<T>,
     responder: HttpsResponseHandle,
@@ -96,6 +100,7 @@ async fn handle_request<T>(
     server_future::handle_request(
         &bytes,
         src_addr,
+        local_addr,
         Protocol::Https,
         access,
         handler,
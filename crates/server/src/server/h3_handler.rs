@@ -31,6 +31,7 @@ pub(crate) async fn h3_handler<T>(
     handler: Arc<T>,
     mut connection: H3Connection,
     src_addr: SocketAddr,
+    local_addr: Option<SocketAddr>,
     _dns_hostname: Option<Arc<str>>,
     shutdown: CancellationToken,
 ) -> Result<(), ProtoError>
@@ -78,7 +79,7 @@ where
         let responder = H3ResponseHandle(stream.clone());
 
         tokio::spawn(handle_request(
-            request, src_addr, access, handler, responder,
+            request, src_addr, local_addr, access, handler, responder,
         ));
 
         max_requests -= 1;
@@ -96,13 +97,23 @@ where
 async fn handle_request<T>(
     bytes: Bytes,
     src_addr: SocketAddr,
+    local_addr: Option<SocketAddr>,
     access: Arc<AccessControl>,
     handler: Arc<T>,
     responder: H3ResponseHandle,
 ) where
     T: RequestHandler,
 {
-    server_future::handle_request(&bytes, src_addr, Protocol::H3, access, handler, responder).await
+    server_future::handle_request(
+        &bytes,
+        src_addr,
+        local_addr,
+        Protocol::H3,
+        access,
+        handler,
+        responder,
+    )
+    .await
 }
 
 #[derive(Clone)]
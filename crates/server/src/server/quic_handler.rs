@@ -32,6 +32,7 @@ pub(crate) async fn quic_handler<T>(
     handler: Arc<T>,
     mut quic_streams: QuicStreams,
     src_addr: SocketAddr,
+    local_addr: Option<SocketAddr>,
     _dns_hostname: Option<Arc<str>>,
     shutdown: CancellationToken,
 ) -> Result<(), ProtoError>
@@ -71,7 +72,7 @@ where
         let stream = Arc::new(Mutex::new(request_stream));
         let responder = QuicResponseHandle(stream.clone());
 
-        handle_request(request, src_addr, access, handler, responder).await;
+        handle_request(request, src_addr, local_addr, access, handler, responder).await;
 
         max_requests -= 1;
         if max_requests == 0 {
@@ -89,14 +90,23 @@ where
 async fn handle_request<T>(
     bytes: BytesMut,
     src_addr: SocketAddr,
+    local_addr: Option<SocketAddr>,
     access: Arc<AccessControl>,
     handler: Arc<T>,
     responder: QuicResponseHandle,
 ) where
     T: RequestHandler,
 {
-    server_future::handle_request(&bytes, src_addr, Protocol::Quic, access, handler, responder)
-        .await
+    server_future::handle_request(
+        &bytes,
+        src_addr,
+        local_addr,
+        Protocol::Quic,
+        access,
+        handler,
+        responder,
+    )
+    .await
 }
 
 #[derive(Clone)]
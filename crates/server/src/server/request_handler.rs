@@ -11,7 +11,7 @@ use std::net::SocketAddr;
 
 use crate::{
     authority::MessageRequest,
-    proto::op::{Header, LowerQuery, ResponseCode},
+    proto::op::{Edns, Header, LowerQuery, ResponseCode},
     server::{Protocol, ResponseHandler},
 };
 
@@ -24,6 +24,8 @@ pub struct Request {
     src: SocketAddr,
     /// Protocol of the request
     protocol: Protocol,
+    /// Local address the request was received on, if known
+    local: Option<SocketAddr>,
 }
 
 impl Request {
@@ -35,16 +37,25 @@ impl Request {
             message,
             src,
             protocol,
+            local: None,
         }
     }
 
+    /// Set the local address the request was received on, e.g. the address of the listening socket
+    pub fn with_local_addr(mut self, local: Option<SocketAddr>) -> Self {
+        self.local = local;
+        self
+    }
+
     /// Return just the header and request information from the Request Message
     pub fn request_info(&self) -> RequestInfo<'_> {
         RequestInfo {
             src: self.src,
+            local: self.local,
             protocol: self.protocol,
             header: self.message.header(),
             query: self.message.query(),
+            edns: self.message.edns(),
         }
     }
 
@@ -57,6 +68,11 @@ impl Request {
     pub fn protocol(&self) -> Protocol {
         self.protocol
     }
+
+    /// The local address the request was received on, if known
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local
+    }
 }
 
 impl std::ops::Deref for Request {
@@ -74,12 +90,18 @@ impl std::ops::Deref for Request {
 pub struct RequestInfo<'a> {
     /// The source address from which the request came
     pub src: SocketAddr,
+    /// The local address on which the request was received, if known.  For a UDP socket bound to an unspecified address,
+    /// such as `0.0.0.0`, this is the unspecified address.
+    pub(crate) local: Option<SocketAddr>,
     /// The protocol used for the request
     pub protocol: Protocol,
     /// The header from the original request
     pub header: &'a Header,
     /// The query from the request
     pub query: &'a LowerQuery,
+    /// The EDNS of the request, if it had any.  An authority which strips the client subnet from the requests it passes
+    /// on replaces it for the authorities after it.
+    pub(crate) edns: Option<&'a Edns>,
 }
 
 impl<'a> RequestInfo<'a> {
@@ -99,11 +121,29 @@ impl<'a> RequestInfo<'a> {
     ) -> Self {
         Self {
             src,
+            local: None,
             protocol,
             header,
             query,
+            edns: None,
         }
     }
+
+    /// Set the local address the request was received on
+    pub fn with_local_addr(mut self, local: Option<SocketAddr>) -> Self {
+        self.local = local;
+        self
+    }
+
+    /// The local address the request was received on, if known
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local
+    }
+
+    /// The EDNS of the request, if it had any, as passed on to this authority
+    pub fn edns(&self) -> Option<&'a Edns> {
+        self.edns
+    }
 }
 
 /// Information about the response sent for a request
@@ -70,6 +70,7 @@ impl<T: RequestHandler> ServerFuture<T> {
     /// Register a UDP socket. Should be bound before calling this function.
     pub fn register_socket(&mut self, socket: net::UdpSocket) {
         debug!("registering udp: {:?}", socket);
+        let local_addr = socket.local_addr().ok();
 
         // create the new UdpStream, the IP address isn't relevant, and ideally goes essentially no where.
         //   the address used is acquired from the inbound queries
@@ -121,8 +122,15 @@ impl<T: RequestHandler> ServerFuture<T> {
                     let stream_handle = stream_handle.with_remote_addr(src_addr);
 
                     inner_join_set.spawn(async move {
-                        handle_raw_request(message, Protocol::Udp, access, handler, stream_handle)
-                            .await;
+                        handle_raw_request(
+                            message,
+                            local_addr,
+                            Protocol::Udp,
+                            access,
+                            handler,
+                            stream_handle,
+                        )
+                        .await;
                     });
 
                     reap_tasks(&mut inner_join_set);
@@ -196,6 +204,7 @@ impl<T: RequestHandler> ServerFuture<T> {
 
                 let handler = handler.clone();
                 let access = access.clone();
+                let local_addr = tcp_stream.local_addr().ok();
 
                 // and spawn to the io_loop
                 inner_join_set.spawn(async move {
@@ -221,6 +230,7 @@ impl<T: RequestHandler> ServerFuture<T> {
                         // we don't spawn here to limit clients from getting too many resources
                         handle_raw_request(
                             message,
+                            local_addr,
                             Protocol::Tcp,
                             access.clone(),
                             handler.clone(),
@@ -332,6 +342,7 @@ impl<T: RequestHandler> ServerFuture<T> {
 
                 let handler = handler.clone();
                 let tls_acceptor = tls_acceptor.clone();
+                let local_addr = tcp_stream.local_addr().ok();
 
                 // kick out to a different task immediately, let them do the TLS handshake
                 inner_join_set.spawn(async move {
@@ -374,6 +385,7 @@ impl<T: RequestHandler> ServerFuture<T> {
 
                         self::handle_raw_request(
                             message,
+                            local_addr,
                             Protocol::Tls,
                             access.clone(),
                             handler.clone(),
@@ -493,6 +505,7 @@ impl<T: RequestHandler> ServerFuture<T> {
                 let handler = handler.clone();
                 let access = access.clone();
                 let tls_acceptor = tls_acceptor.clone();
+                let local_addr = tcp_stream.local_addr().ok();
 
                 // kick out to a different task immediately, let them do the TLS handshake
                 inner_join_set.spawn(async move {
@@ -527,6 +540,7 @@ impl<T: RequestHandler> ServerFuture<T> {
 
                         handle_raw_request(
                             message,
+                            local_addr,
                             Protocol::Tls,
                             access.clone(),
                             handler.clone(),
@@ -692,6 +706,7 @@ impl<T: RequestHandler> ServerFuture<T> {
                 let access = access.clone();
                 let tls_acceptor = tls_acceptor.clone();
                 let dns_hostname = dns_hostname.clone();
+                let local_addr = tcp_stream.local_addr().ok();
 
                 inner_join_set.spawn(async move {
                     debug!("starting HTTPS request from: {src_addr}");
@@ -714,6 +729,7 @@ impl<T: RequestHandler> ServerFuture<T> {
                         handler,
                         tls_stream,
                         src_addr,
+                        local_addr,
                         dns_hostname,
                         shutdown.clone(),
                     )
@@ -765,6 +781,7 @@ impl<T: RequestHandler> ServerFuture<T> {
         let access = self.access.clone();
 
         debug!("registered quic: {:?}", socket);
+        let local_addr = socket.local_addr().ok();
         let mut server =
             QuicServer::with_socket(socket, certificate_and_key.0, certificate_and_key.1)?;
 
@@ -813,6 +830,7 @@ impl<T: RequestHandler> ServerFuture<T> {
                         handler,
                         streams,
                         src_addr,
+                        local_addr,
                         dns_hostname,
                         shutdown.clone(),
                     )
@@ -864,6 +882,7 @@ impl<T: RequestHandler> ServerFuture<T> {
         let access = self.access.clone();
 
         debug!("registered h3: {:?}", socket);
+        let local_addr = socket.local_addr().ok();
         let mut server =
             H3Server::with_socket(socket, certificate_and_key.0, certificate_and_key.1)?;
 
@@ -912,6 +931,7 @@ impl<T: RequestHandler> ServerFuture<T> {
                         handler,
                         streams,
                         src_addr,
+                        local_addr,
                         dns_hostname,
                         shutdown.clone(),
                     )
@@ -984,6 +1004,7 @@ fn reap_tasks(join_set: &mut JoinSet<()>) {
 
 pub(crate) async fn handle_raw_request<T: RequestHandler>(
     message: SerialMessage,
+    local_addr: Option<SocketAddr>,
     protocol: Protocol,
     access: Arc<AccessControl>,
     request_handler: Arc<T>,
@@ -995,6 +1016,7 @@ pub(crate) async fn handle_raw_request<T: RequestHandler>(
     handle_request(
         message.bytes(),
         src_addr,
+        local_addr,
         protocol,
         access,
         request_handler,
@@ -1066,6 +1088,7 @@ pub(crate) async fn handle_request<R: ResponseHandler, T: RequestHandler>(
     // TODO: allow Message here...
     message_bytes: &[u8],
     src_addr: SocketAddr,
+    local_addr: Option<SocketAddr>,
     protocol: Protocol,
     access: Arc<AccessControl>,
     request_handler: Arc<T>,
@@ -1086,7 +1109,7 @@ pub(crate) async fn handle_request<R: ResponseHandler, T: RequestHandler>(
         let message_type = message.message_type();
         let is_dnssec = message.edns().map_or(false, Edns::dnssec_ok);
 
-        let request = Request::new(message, src_addr, protocol);
+        let request = Request::new(message, src_addr, protocol).with_local_addr(local_addr);
 
         let info = request.request_info();
         let query = info.query.clone();
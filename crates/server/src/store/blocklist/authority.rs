@@ -5,9 +5,26 @@
 // https://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use std::{io, path::Path};
+use std::{
+    borrow::Borrow,
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, RwLock, Weak,
+    },
+    time::{Duration, Instant, SystemTime},
+};
 
-use tracing::{debug, info, trace};
+use ipnet::IpNet;
+use rand::Rng;
+use time::OffsetDateTime;
+use tokio::{
+    sync::OnceCell,
+    task::{AbortHandle, JoinHandle},
+};
+use tracing::{debug, error, info, warn};
 
 use crate::{
     authority::{
@@ -15,18 +32,36 @@ use crate::{
     },
     proto::{
         op::{Query, ResponseCode},
-        rr::{rdata::A, LowerName, Name, RData, Record, RecordType},
+        rr::{
+            rdata::opt::{EdnsCode, EdnsOption},
+            DNSClass, LowerName, Name, RData, Record, RecordType,
+        },
     },
     server::RequestInfo,
-    store::blocklist::BlocklistConfig,
+    store::blocklist::{
+        learn::Learner,
+        load::{list_dir, resolve_path},
+        matcher::{new_matcher, BlockMatcher},
+        policy::{canonical_name, BlockList, EntryResponse, Fetch, FetchedList, Policy},
+        querylog::{MatchKind, QueryEvent, QueryLog},
+        ratelimit::ClientLimiter,
+        remote::Fetcher,
+        stats::{Counter, MatchCounters},
+        tombstone::Tombstones,
+        BlockResponse, BlocklistConfig, BlocklistStats, LearnedName, ListAction, ListEntry,
+        ListSourceInfo, ListStats, LoadFailurePolicy, MalformedQueryPolicy, PolicyDiff,
+        PolicyEvent, PublicSinkholePolicy, RefreshStatus, ReloadReport, StartupPolicy,
+        SubnetSinkhole,
+    },
 };
 
 use crate::resolver::lookup::Lookup;
 
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::prelude::*;
-use std::str::FromStr;
+use std::fs;
+
+/// A handler set with [`BlocklistAuthority::on_event`]
+type EventHandler = Arc<dyn Fn(&PolicyEvent) + Send + Sync>;
 
 /// A conditional authority that will resolve queries against one or more block lists.  The typical use case will be to use this in a
 /// chained configuration before a forwarding or recursive resolver:
@@ -38,376 +73,6193 @@ use std::str::FromStr;
 ///
 /// Note: the order of the stores is important: the first one specified in the store list will be the first consulted.  Subsequent stores
 /// will only be consulted if each prior store returns None in response to the query.
+///
+/// The blocklist is a `Hint` zone, so that it can be chained with a forwarder or recursor, but the responses it synthesizes
+/// for blocked names are its own: they are sent with the AA bit set, so that clients do not treat them as answers cached
+/// from upstream.  Queries it passes on are answered with the AA bit of the store which resolves them.
 pub struct BlocklistAuthority {
     origin: LowerName,
-    blocklist: HashMap<LowerName, bool>,
-    wildcard_match: bool,
-    min_wildcard_depth: u8,
+    /// The settings, entries and list configuration the policy is built from
+    source: BlocklistAuthorityBuilder,
+    /// Directory that relative list paths are resolved against
+    list_dir: Option<PathBuf>,
+    /// The policy currently used to answer queries, which may be shared with other authorities
+    shared: Arc<SharedPolicy>,
+    blocked: Counter,
+    passed: Counter,
+    monitored: Counter,
+    matches: MatchCounters,
+    /// The most recent background refresh of each remote list, by URL
+    refresh: Mutex<HashMap<String, RefreshStatus>>,
+    /// Names which failed to resolve after being passed on, in learn mode
+    learner: Option<Learner>,
+    /// Structured log of blocked and monitored queries, if enabled
+    query_log: Option<QueryLog>,
+    /// Names, and wildcards, whose queries are counted but not written to the query log
+    log_exclude: Box<dyn BlockMatcher>,
+    /// Query rates of recent clients, if `client_rate_limit` is set
+    limiter: Option<ClientLimiter>,
+    fetcher: Arc<Fetcher>,
+    /// Background tasks started for the authority, which are aborted when it is shut down or dropped
+    tasks: Mutex<Vec<AbortHandle>>,
+}
+
+/// The policy used to answer queries, and the state which changes along with it.  Authorities loaded with `share_lists`
+/// from the same lists and settings share a single instance.
+struct SharedPolicy {
+    /// The current policy; it is replaced as a whole when the lists are reloaded
+    policy: RwLock<Arc<Policy>>,
+    tombstones: Tombstones,
+    /// The authority which refreshes the remote lists, on behalf of every authority sharing the policy
+    refresher: Mutex<Weak<BlocklistAuthority>>,
+    /// Whether the lists have loaded.  This is only unset while they are loaded in the background at startup.
+    ready: AtomicBool,
+}
+
+/// Policies loaded with `share_lists`, keyed by the directory and settings they were loaded with
+static SHARED_POLICIES: Mutex<Vec<(String, Weak<SharedPolicy>)>> = Mutex::new(Vec::new());
+
+/// The client a query came from
+#[derive(Clone, Copy)]
+pub(super) struct Client {
+    pub(super) addr: IpAddr,
+    /// The network given by the EDNS client subnet of the query, if it had one
+    pub(super) subnet: Option<IpNet>,
+}
+
+/// Refresh state of a single remote list
+struct RemoteRefresh {
+    url: String,
+    /// Number of consecutive failed refreshes
+    failures: u32,
+    next: Instant,
+}
+
+/// Constructs a [`BlocklistAuthority`] without a configuration file.
+///
+/// ```
+/// use std::str::FromStr;
+///
+/// use hickory_server::proto::rr::Name;
+/// use hickory_server::store::blocklist::{BlockResponse, BlocklistAuthorityBuilder};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let authority = BlocklistAuthorityBuilder::new(Name::root())
+///     .block_response(BlockResponse::NxDomain)
+///     .add_entries([
+///         Name::from_str("ads.example.com.")?,
+///         Name::from_str("*.tracker.example.com.")?,
+///     ])
+///     .add_allow([Name::from_str("ok.tracker.example.com.")?])
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct BlocklistAuthorityBuilder {
+    pub(super) origin: Name,
+    pub(super) config: BlocklistConfig,
+    pub(super) entries: Vec<LowerName>,
+    pub(super) allow: Vec<LowerName>,
+    pub(super) matcher: Option<Arc<dyn Fn() -> Box<dyn BlockMatcher> + Send + Sync>>,
+    /// Lists added with [`BlocklistAuthority::add_runtime_list`], which are loaded after the configured lists
+    pub(super) runtime: Arc<Mutex<Vec<ListEntry>>>,
+    /// `wildcard_match` and `min_wildcard_depth` as set with [`BlocklistAuthority::set_wildcard_match`], which override
+    /// the configured settings
+    pub(super) wildcards: Arc<Mutex<Option<(bool, u8)>>>,
+    /// `sinkhole_ipv4` and `sinkhole_ipv6` as set with [`BlocklistAuthority::set_sinkhole`], which override the configured
+    /// addresses
+    pub(super) sinkholes: Arc<Mutex<Option<(Ipv4Addr, Ipv6Addr)>>>,
+    /// The list read from standard input, the first time a list with the path `-` is loaded, so that reloads see the same
+    /// list rather than the end of the input
+    pub(super) stdin: Arc<OnceCell<Arc<[u8]>>>,
+    /// The handler set with [`BlocklistAuthority::on_event`], if any
+    pub(super) events: Arc<Mutex<Option<EventHandler>>>,
+}
+
+impl BlocklistAuthorityBuilder {
+    /// A builder for an authority for `origin`, using the default blocklist settings
+    pub fn new(origin: Name) -> Self {
+        Self::with_config(origin, BlocklistConfig::default())
+    }
+
+    /// A builder for an authority for `origin`, using the settings from `config`.  The lists in `config` are not loaded.
+    pub(super) fn with_config(origin: Name, config: BlocklistConfig) -> Self {
+        Self {
+            origin,
+            config,
+            entries: Vec::new(),
+            allow: Vec::new(),
+            matcher: None,
+            runtime: Arc::default(),
+            wildcards: Arc::default(),
+            sinkholes: Arc::default(),
+            stdin: Arc::default(),
+            events: Arc::default(),
+        }
+    }
+
+    /// Block each of `entries`.  Entries may be wildcards, e.g. `*.example.com.`
+    pub fn add_entries(mut self, entries: impl IntoIterator<Item = Name>) -> Self {
+        self.entries.extend(entries.into_iter().map(canonical_name));
+        self
+    }
+
+    /// Never block any of `entries`, even if they are blocked by a list or a wildcard.  Entries may be wildcards.
+    pub fn add_allow(mut self, entries: impl IntoIterator<Item = Name>) -> Self {
+        self.allow.extend(entries.into_iter().map(canonical_name));
+        self
+    }
+
+    /// Whether block list entries containing asterisks are expanded to match queries
+    pub fn wildcard_match(mut self, wildcard_match: bool) -> Self {
+        self.config.wildcard_match = wildcard_match;
+        self
+    }
+
+    /// Minimum number of static labels a wildcard entry must have to be expanded
+    pub fn min_wildcard_depth(mut self, min_wildcard_depth: u8) -> Self {
+        self.config.min_wildcard_depth = min_wildcard_depth;
+        self
+    }
+
+    /// Whether a wildcard entry also blocks the name it is a wildcard of
+    pub fn wildcard_blocks_parent(mut self, wildcard_blocks_parent: bool) -> Self {
+        self.config.wildcard_blocks_parent = wildcard_blocks_parent;
+        self
+    }
+
+    /// Record types which are answered with NODATA when the queried name is blocked
+    pub fn nodata_types(mut self, nodata_types: Vec<RecordType>) -> Self {
+        self.config.nodata_types = nodata_types;
+        self
+    }
+
+    /// How queries for blocked names are answered
+    pub fn block_response(mut self, block_response: BlockResponse) -> Self {
+        self.config.block_response = block_response;
+        self
+    }
+
+    /// The name queries are redirected to when blocked names are answered with `dname`
+    pub fn dname_target(mut self, dname_target: Name) -> Self {
+        self.config.dname_target = Some(dname_target);
+        self
+    }
+
+    /// The address returned for A queries for blocked names
+    pub fn sinkhole_ipv4(mut self, sinkhole_ipv4: Ipv4Addr) -> Self {
+        self.config.sinkhole_ipv4 = sinkhole_ipv4;
+        self
+    }
+
+    /// The address returned for AAAA queries for blocked names
+    pub fn sinkhole_ipv6(mut self, sinkhole_ipv6: Ipv6Addr) -> Self {
+        self.config.sinkhole_ipv6 = sinkhole_ipv6;
+        self
+    }
+
+    /// Addresses to pick the answer to A and AAAA queries for blocked names from, in place of the sinkhole addresses
+    pub fn sinkhole_pool(mut self, sinkhole_pool: Vec<IpAddr>) -> Self {
+        self.config.sinkhole_pool = sinkhole_pool;
+        self
+    }
+
+    /// Only answer queries received on one of `local_addresses`, passing on those received on any other address
+    pub fn local_addresses(mut self, local_addresses: Vec<IpAddr>) -> Self {
+        self.config.local_addresses = local_addresses;
+        self
+    }
+
+    /// Sinkhole addresses for clients in particular networks, by their EDNS client subnet, in place of the sinkhole
+    /// addresses and the `sinkhole_pool`
+    pub fn subnet_sinkholes(mut self, subnet_sinkholes: Vec<SubnetSinkhole>) -> Self {
+        self.config.subnet_sinkholes = subnet_sinkholes;
+        self
+    }
+
+    /// TTL of the records synthesized for blocked names
+    pub fn ttl(mut self, ttl: u32) -> Self {
+        self.config.ttl = ttl;
+        self
+    }
+
+    /// Maximum number of seconds each synthesized TTL is randomly adjusted by, in either direction
+    pub fn ttl_jitter(mut self, ttl_jitter: u32) -> Self {
+        self.config.ttl_jitter = ttl_jitter;
+        self
+    }
+
+    /// Leave the SOA record out of NXDOMAIN and NODATA responses to blocked queries
+    pub fn minimal_responses(mut self, minimal_responses: bool) -> Self {
+        self.config.minimal_responses = minimal_responses;
+        self
+    }
+
+    /// Number of blocked names whose responses are kept ready built; 0 disables the cache
+    pub fn response_cache_size(mut self, response_cache_size: usize) -> Self {
+        self.config.response_cache_size = response_cache_size;
+        self
+    }
+
+    /// How queries whose name can not be matched against the lists are answered
+    pub fn malformed_query(mut self, malformed_query: MalformedQueryPolicy) -> Self {
+        self.config.malformed_query = malformed_query;
+        self
+    }
+
+    /// Also load the default list bundled with the server
+    pub fn use_default_lists(mut self, use_default_lists: bool) -> Self {
+        self.config.use_default_lists = use_default_lists;
+        self
+    }
+
+    /// Leave queries for `names`, which may be wildcards, out of the query log
+    pub fn log_exclude(mut self, names: Vec<Name>) -> Self {
+        self.config.log_exclude = names;
+        self
+    }
+
+    /// Block internationalized names which are homographs of `domains`, and the names below them
+    pub fn homograph_protected(mut self, domains: Vec<Name>) -> Self {
+        self.config.homograph_protected = domains;
+        self
+    }
+
+    /// Never block `domains`, nor any name below them
+    pub fn first_party_domains(mut self, domains: Vec<Name>) -> Self {
+        self.config.first_party_domains = domains;
+        self
+    }
+
+    /// What to do when a sinkhole address is publicly routable
+    pub fn public_sinkhole(mut self, public_sinkhole: PublicSinkholePolicy) -> Self {
+        self.config.public_sinkhole = public_sinkhole;
+        self
+    }
+
+    /// Store the block tables in the tables `matcher` creates, in place of the configured `matcher` backend.  It is called
+    /// for a new pair of empty tables each time the lists are loaded.
+    pub fn matcher(
+        mut self,
+        matcher: impl Fn() -> Box<dyn BlockMatcher> + Send + Sync + 'static,
+    ) -> Self {
+        self.matcher = Some(Arc::new(matcher));
+        self
+    }
+
+    /// Build the authority.  This fails if a sinkhole address is publicly routable and the policy is to refuse it.
+    pub fn build(self) -> Result<BlocklistAuthority, String> {
+        let policy = self.policy()?;
+        policy.warn_allow_overlaps(&self.origin);
+        let fetcher = Arc::new(Fetcher::new(&self.config)?);
+        let shared = SharedPolicy::new(policy, &self.config, true);
+        BlocklistAuthority::new(self, None, shared, fetcher)
+    }
 }
 
 impl BlocklistAuthority {
-    /// Read the Authority for the origin from the specified configuration
+    /// Read the Authority for the origin from the specified configuration.  `config_dir` is the directory containing the
+    /// server configuration file, which relative list paths are resolved against when `config_relative` is set.
     pub async fn try_from_config(
         origin: Name,
         _zone_type: ZoneType,
         config: &BlocklistConfig,
         root_dir: Option<&Path>,
+        config_dir: Option<&Path>,
     ) -> Result<Self, String> {
         info!("loading blocklist config: {}", origin);
 
-        let mut authority = Self {
-            origin: origin.into(),
-            blocklist: HashMap::new(),
-            wildcard_match: config.wildcard_match,
-            min_wildcard_depth: config.min_wildcard_depth,
+        let list_dir = list_dir(config, root_dir, config_dir)?;
+        let mut source = BlocklistAuthorityBuilder::with_config(origin.clone(), config.clone());
+        source.load_manifests(list_dir)?;
+        source.load_runtime_lists(list_dir)?;
+        let fetcher = Arc::new(Fetcher::new(config)?);
+        let key = config
+            .share_lists
+            .then(|| format!("{list_dir:?} {config:?}"));
+        if let Some(shared) = key.as_deref().and_then(SharedPolicy::find) {
+            info!("blocklist {origin} is sharing the lists loaded by another blocklist");
+            return Self::new(source, list_dir.map(Path::to_path_buf), shared, fetcher);
+        }
+
+        let background = config.startup_policy != StartupPolicy::Wait;
+        let policy = match background {
+            true => source.policy()?,
+            false => Self::load_initial(&source, list_dir, &fetcher).await?,
+        };
+        let created = SharedPolicy::new(policy, config, !background);
+        let shared = match key {
+            Some(key) => SharedPolicy::register(key, created.clone()),
+            None => created.clone(),
         };
 
-        // Load block lists into the block table cache for this authority.
-        for bl in &config.lists {
-            info!("Adding blocklist {bl:?}");
-            authority
-                .add(format!("{}/{bl}", root_dir.unwrap().display()))
-                .await;
+        // Lists shared with a blocklist created earlier are loaded by that blocklist.
+        let load = background && Arc::ptr_eq(&shared, &created);
+        drop(created);
+        let authority = Self::new(source, list_dir.map(Path::to_path_buf), shared, fetcher)?;
+        if load {
+            authority.spawn_initial_load();
         }
-
         Ok(authority)
     }
 
-    /// Add a configured block list to the in-memory cache.
-    pub async fn add(&mut self, file: String) -> bool {
-        let mut handle =
-            File::open(&file).expect(&format!("unable to open block list file '{}'", &file)[..]);
-        let mut contents = String::new();
-        let _ = handle.read_to_string(&mut contents);
+    /// Load the configured lists in the background, for the `open` and `closed` startup policies.  Queries are answered
+    /// according to the startup policy until the lists have loaded; if they fail to load, the `on_load_failure` policy is
+    /// applied, and if that fails too, queries continue to be answered according to the startup policy.
+    fn spawn_initial_load(&self) {
+        let source = self.source.clone();
+        let list_dir = self.list_dir.clone();
+        let shared = self.shared.clone();
+        let fetcher = self.fetcher.clone();
+        info!(
+            "loading blocklist {} in the background, startup policy: {:?}",
+            self.origin, source.config.startup_policy
+        );
 
-        for mut entry in contents.split('\n') {
-            // Strip comments and leading/trailing whitespace
-            if let Some(idx) = entry.chars().position(|c| c == '#') {
-                entry = &entry[0..idx].trim();
+        let task = tokio::spawn(async move {
+            let origin = &source.origin;
+            let start = Instant::now();
+            match Self::load_initial(&source, list_dir.as_deref(), &fetcher).await {
+                Ok(policy) => {
+                    source.emit(|| PolicyEvent::Loaded {
+                        lists: policy.lists.len(),
+                        names: policy.blocklist.len(),
+                        elapsed: start.elapsed(),
+                    });
+                    *shared
+                        .policy
+                        .write()
+                        .expect("blocklist policy lock poisoned") = Arc::new(policy);
+                    shared.ready.store(true, Ordering::Release);
+                    info!("blocklist {origin} finished loading");
+                }
+                Err(e) => {
+                    error!(
+                        "blocklist {origin} failed to load, and is still using its startup policy, {:?}: {e}",
+                        source.config.startup_policy
+                    );
+                    source.emit(|| PolicyEvent::LoadFailed { errors: vec![e] });
+                }
             }
+        });
+
+        self.tasks
+            .lock()
+            .expect("blocklist task lock poisoned")
+            .push(task.abort_handle());
+    }
+
+    /// Read the lists configured for `origin` and write them to the `compiled_policy` file in `config`, returning the path
+    /// written.  `root_dir` and `config_dir` are used as with [`Self::try_from_config`].
+    ///
+    /// Blocklists configured with the file load it in place of the lists, without parsing them, so a policy can be built
+    /// once, e.g. in CI, and the same policy shipped to every server.  Any existing file is replaced in a single step.
+    pub async fn compile(
+        origin: Name,
+        config: &BlocklistConfig,
+        root_dir: Option<&Path>,
+        config_dir: Option<&Path>,
+    ) -> Result<PathBuf, String> {
+        let list_dir = list_dir(config, root_dir, config_dir)?;
+        let Some(path) = &config.compiled_policy else {
+            return Err(format!(
+                "blocklist {origin} has no compiled_policy file configured"
+            ));
+        };
+        let path = resolve_path(list_dir, path);
+
+        let mut source = BlocklistAuthorityBuilder::with_config(
+            origin,
+            BlocklistConfig {
+                compiled_policy: None,
+                ..config.clone()
+            },
+        );
+        source.load_manifests(list_dir)?;
+        let fetcher = Fetcher::new(config)?;
+        let policy = source
+            .load(list_dir, Fetch::All, &fetcher)
+            .await
+            .map_err(|errors| errors.join("; "))?;
+
+        let partial = path.with_extension("partial");
+        fs::write(&partial, policy.compile(source.compile_key()).encode())
+            .and_then(|()| fs::rename(&partial, &path))
+            .map_err(|e| format!("unable to write compiled blocklist policy {path:?}: {e}"))?;
+
+        info!(
+            "compiled blocklist {} to {path:?}: {} entries",
+            source.origin,
+            policy.blocklist.len() + policy.monitorlist.len()
+        );
+        Ok(path)
+    }
 
-            if entry.is_empty() {
-                continue;
+    /// Build the policy an authority starts with, applying the `on_load_failure` policy if the lists can not be loaded.
+    async fn load_initial(
+        source: &BlocklistAuthorityBuilder,
+        list_dir: Option<&Path>,
+        fetcher: &Fetcher,
+    ) -> Result<Policy, String> {
+        let origin = &source.origin;
+        let config = &source.config;
+        let policy = match source.load(list_dir, Fetch::All, fetcher).await {
+            Ok(policy) => {
+                source.save_cache(list_dir, &policy);
+                policy
             }
+            Err(errors) => match (errors.join("; "), config.on_load_failure) {
+                (e, LoadFailurePolicy::Fail) => return Err(e),
+                (e, LoadFailurePolicy::Empty) => {
+                    error!("blocklist {origin} failed to load, and is NOT BLOCKING ANYTHING: {e}");
+                    source.policy()?
+                }
+                (e, LoadFailurePolicy::Cached) => {
+                    let Some(cache_dir) = source.cache_dir(list_dir) else {
+                        return Err(format!(
+                            "{e}; no cache_dir is configured to load cached lists from"
+                        ));
+                    };
+
+                    warn!("blocklist {origin} failed to load, using the cached lists in {cache_dir:?}: {e}");
+                    source
+                        .load_cached(list_dir, &cache_dir, fetcher)
+                        .await
+                        .map_err(|cached| {
+                            format!("{e}; the cached lists could not be loaded either: {cached}")
+                        })?
+                }
+            },
+        };
+
+        Ok(policy)
+    }
 
-            let mut str_entry = entry.to_string();
-            if !entry.ends_with('.') {
-                str_entry += ".";
+    fn new(
+        source: BlocklistAuthorityBuilder,
+        list_dir: Option<PathBuf>,
+        shared: Arc<SharedPolicy>,
+        fetcher: Arc<Fetcher>,
+    ) -> Result<Self, String> {
+        let query_log = match source.config.query_log.as_deref() {
+            Some(path) => {
+                let file = (path != "-").then(|| resolve_path(list_dir.as_deref(), path));
+                let log = QueryLog::open(file)
+                    .map_err(|e| format!("unable to open blocklist query log '{path}': {e}"))?;
+                Some(log)
             }
+            None => None,
+        };
 
-            trace!("Inserting blocklist entry {str_entry:?}");
-            self.blocklist
-                .insert(LowerName::from_str(&str_entry[..]).unwrap(), true);
+        let mut log_exclude = new_matcher(source.config.matcher);
+        for name in &source.config.log_exclude {
+            log_exclude.insert(canonical_name(name.clone()), 0);
         }
 
-        true
-    }
+        let config = &source.config;
+        let limiter = (config.client_rate_limit > 0).then(|| {
+            ClientLimiter::new(
+                config.client_rate_limit,
+                config.client_rate_burst,
+                Duration::from_secs(config.client_block_seconds.into()),
+                config.client_rate_capacity,
+            )
+        });
 
-    /// Build a wildcard match list for a given host
-    pub fn get_wildcards(&self, host: &Name) -> Vec<LowerName> {
-        host.iter()
-            .enumerate()
-            .filter(|(i, _x)| *i > (self.min_wildcard_depth - 1) as usize)
-            .map(|(i, _x)| host.trim_to(i + 1).into_wildcard().into())
-            .collect::<Vec<LowerName>>()
+        Ok(Self {
+            origin: LowerName::from(&source.origin),
+            learner: source
+                .config
+                .learn
+                .then(|| Learner::new(source.config.learn_capacity)),
+            limiter,
+            source,
+            list_dir,
+            shared,
+            blocked: Counter::default(),
+            passed: Counter::default(),
+            monitored: Counter::default(),
+            matches: MatchCounters::default(),
+            refresh: Mutex::new(HashMap::new()),
+            fetcher,
+            query_log,
+            log_exclude,
+            tasks: Mutex::new(Vec::new()),
+        })
     }
-}
 
-#[async_trait::async_trait]
-impl Authority for BlocklistAuthority {
-    type Lookup = BlocklistLookup;
+    /// Reload every configured list, returning a summary of the changes which is also logged.  The query log, if any, is
+    /// reopened first.
+    ///
+    /// The new lists are loaded in full before any of them are used: if any list fails to load, the report holds an error
+    /// for each list which failed, and queries continue to be answered from the current lists.  Names which are no longer
+    /// blocked after the reload are tombstoned, as with [`Self::remove`].  Lists added with [`Self::add_list`] and similar
+    /// methods are not part of the configuration, and are dropped by a successful reload; lists added with
+    /// [`Self::add_runtime_list`] are loaded again.
+    pub async fn reload(&self) -> ReloadReport {
+        info!("reloading blocklist {}", self.origin);
+        if let Some(query_log) = &self.query_log {
+            query_log.reopen();
+        }
 
-    /// Always Recursive
-    fn zone_type(&self) -> ZoneType {
-        ZoneType::Hint
+        let start = Instant::now();
+        let previous = self.policy();
+        match self
+            .source
+            .load(self.list_dir.as_deref(), Fetch::All, &self.fetcher)
+            .await
+        {
+            Ok(policy) => {
+                let report = policy.reload_report(&previous, start.elapsed());
+                self.replace_policy(policy);
+                info!("blocklist {} reloaded: {report}", self.origin);
+                self.source.emit(|| PolicyEvent::Reloaded(report.clone()));
+                report
+            }
+            Err(errors) => {
+                let report = ReloadReport {
+                    added: 0,
+                    removed: 0,
+                    lists: Vec::new(),
+                    errors,
+                    elapsed: start.elapsed(),
+                };
+                warn!(
+                    "blocklist {} reload failed, keeping the current lists: {report}",
+                    self.origin
+                );
+                self.source.emit(|| PolicyEvent::LoadFailed {
+                    errors: report.errors.clone(),
+                });
+                report
+            }
+        }
     }
 
-    /// Always false for Forward zones
-    fn is_axfr_allowed(&self) -> bool {
-        false
-    }
+    /// Add a list which is not part of the configuration, and start answering queries using it, returning a summary of the
+    /// changes.  `entry` sets the list's path or URL and its settings, as a list in the configuration would.
+    ///
+    /// The policy is rebuilt with the list loaded after the configured lists, reusing the contents the other remote lists
+    /// were last fetched with, and swapped in once it has loaded in full; if the list can not be loaded, the current
+    /// policy stays in use.  Unlike lists added with [`Self::add_list`], the list is kept by reloads and refreshes, and is
+    /// recorded in the `runtime_lists` file, if one is configured, so that it is loaded again when the server restarts.
+    pub async fn add_runtime_list(&self, entry: ListEntry) -> Result<ReloadReport, String> {
+        let config = &self.source.config;
+        if config.share_lists {
+            return Err(format!(
+                "blocklist {} lists are shared with other blocklists, and can not be modified",
+                self.origin
+            ));
+        }
+        if config.compiled_policy.is_some() {
+            return Err(format!(
+                "blocklist {} is loaded from a compiled policy, and can not have lists added",
+                self.origin
+            ));
+        }
 
-    async fn update(&self, _update: &MessageRequest) -> UpdateResult<bool> {
-        Err(ResponseCode::NotImp)
+        let source = match (&entry.path, &entry.url) {
+            (Some(path), None) => path.clone(),
+            (None, Some(url)) => url.clone(),
+            _ => {
+                return Err(format!(
+                    "blocklist entries require exactly one of path or url: {entry:?}"
+                ))
+            }
+        };
+        let same = |listed: &ListEntry| listed.path == entry.path && listed.url == entry.url;
+        if self.source.list_entries().any(|listed| same(&listed)) {
+            return Err(format!("blocklist {source} is already loaded"));
+        }
+
+        info!("adding blocklist {source} to {} at runtime", self.origin);
+        let start = Instant::now();
+        let previous = self.policy();
+        self.source
+            .runtime
+            .lock()
+            .expect("blocklist runtime lists lock poisoned")
+            .push(entry.clone());
+        let loaded = self
+            .source
+            .load(
+                self.list_dir.as_deref(),
+                Fetch::One {
+                    url: entry.url.as_deref().unwrap_or_default(),
+                    previous: &previous,
+                },
+                &self.fetcher,
+            )
+            .await
+            .map_err(|errors| errors.join("; "))
+            .and_then(|policy| {
+                self.source
+                    .save_runtime_lists(self.list_dir.as_deref())
+                    .map(|()| policy)
+            });
+
+        let policy = match loaded {
+            Ok(policy) => policy,
+            Err(e) => {
+                self.source
+                    .runtime
+                    .lock()
+                    .expect("blocklist runtime lists lock poisoned")
+                    .retain(|listed| !same(listed));
+                warn!("unable to add blocklist {source} to {}: {e}", self.origin);
+                return Err(e);
+            }
+        };
+
+        let report = policy.reload_report(&previous, start.elapsed());
+        self.replace_policy(policy);
+        info!("blocklist {} added {source}: {report}", self.origin);
+        Ok(report)
     }
 
-    /// Get the origin of this zone, i.e. example.com is the origin for www.example.com
+    /// Turn wildcard matching on or off and set the blocklist-wide minimum wildcard depth, overriding `wildcard_match` and
+    /// `min_wildcard_depth` from the configuration, e.g. to compare how many names are blocked either way without a reload.
     ///
-    /// In the context of a forwarder, this is either a zone which this forwarder is associated,
-    ///   or `.`, the root zone for all zones. If this is not the root zone, then it will only forward
-    ///   for lookups which match the given zone name.
-    fn origin(&self) -> &LowerName {
-        &self.origin
+    /// The lists are not read again: a copy of the current policy with the new settings is swapped in, so every query is
+    /// answered with either the old settings or the new ones.  The settings are kept by reloads and refreshes, but not
+    /// when the server restarts.
+    pub async fn set_wildcard_match(
+        &self,
+        wildcard_match: bool,
+        min_wildcard_depth: u8,
+    ) -> Result<(), String> {
+        if self.source.config.share_lists {
+            return Err(format!(
+                "blocklist {} lists are shared with other blocklists, and can not be modified",
+                self.origin
+            ));
+        }
+
+        info!(
+            "setting blocklist {} wildcard_match to {wildcard_match} and min_wildcard_depth to {min_wildcard_depth}",
+            self.origin
+        );
+        *self
+            .source
+            .wildcards
+            .lock()
+            .expect("blocklist wildcard settings lock poisoned") =
+            Some((wildcard_match, min_wildcard_depth));
+        self.change_policy(|policy| {
+            policy.wildcard_match = wildcard_match;
+            policy.min_wildcard_depth = min_wildcard_depth;
+            policy.shallowest_wildcard_depth = policy
+                .lists
+                .iter()
+                .filter_map(|list| list.min_wildcard_depth)
+                .fold(min_wildcard_depth, u8::min);
+        });
+        Ok(())
     }
 
-    /// Forwards a lookup given the resolver configuration for this Forwarded zone
-    async fn lookup(
+    /// Answer A and AAAA queries for blocked names with `sinkhole_ipv4` and `sinkhole_ipv6`, overriding the addresses from
+    /// the configuration, e.g. while moving block pages to a new server.  Lists and entries with sinkhole addresses of
+    /// their own, and the `sinkhole_pool`, keep answering with those.
+    ///
+    /// The addresses are checked as the configured ones are: publicly routable addresses are refused if `public_sinkhole`
+    /// is `refuse`, and leave the current addresses in place.  Otherwise a copy of the current policy with the new
+    /// addresses is swapped in as with [`Self::set_wildcard_match`], so every query is answered with either the old
+    /// addresses or the new ones.  The addresses are kept by reloads and refreshes, but not when the server restarts.
+    pub async fn set_sinkhole(
         &self,
-        name: &LowerName,
-        rtype: RecordType,
-        _lookup_options: LookupOptions,
-    ) -> Result<Option<Self::Lookup>, LookupError> {
-        debug!("blocklist lookup: {} {}", name, rtype);
+        sinkhole_ipv4: Ipv4Addr,
+        sinkhole_ipv6: Ipv6Addr,
+    ) -> Result<(), String> {
+        if self.source.config.share_lists {
+            return Err(format!(
+                "blocklist {} lists are shared with other blocklists, and can not be modified",
+                self.origin
+            ));
+        }
 
-        let mut match_list = vec![name.to_owned()];
-        if self.wildcard_match {
-            match_list.append(&mut self.get_wildcards(&Name::from(name)));
+        info!(
+            "setting blocklist {} sinkhole addresses to {sinkhole_ipv4} and {sinkhole_ipv6}",
+            self.origin
+        );
+        if let Err(e) = self.source.check_sinkholes(sinkhole_ipv4, sinkhole_ipv6) {
+            warn!(
+                "unable to change blocklist {} sinkhole addresses: {e}",
+                self.origin
+            );
+            return Err(e);
         }
-        debug!("Blocklist match list: {match_list:?}");
 
-        for host in match_list {
-            if self.blocklist.contains_key(&host) {
-                return Ok(Some(BlocklistLookup(Lookup::from_rdata(
-                    Query::query(name.into(), rtype),
-                    RData::A(A::new(0, 0, 0, 0)),
-                ))));
+        *self
+            .source
+            .sinkholes
+            .lock()
+            .expect("blocklist sinkhole settings lock poisoned") =
+            Some((sinkhole_ipv4, sinkhole_ipv6));
+        self.change_policy(|policy| {
+            policy.sinkhole_ipv4 = sinkhole_ipv4;
+            policy.sinkhole_ipv6 = sinkhole_ipv6;
+            for list in &mut policy.lists {
+                if list.pool_ipv4 {
+                    list.sinkhole_ipv4 = sinkhole_ipv4;
+                }
+                if list.pool_ipv6 {
+                    list.sinkhole_ipv6 = sinkhole_ipv6;
+                }
             }
+        });
+        Ok(())
+    }
+
+    /// Start refreshing remote lists every `refresh_interval` seconds, returning the handle of the refresh task.  Nothing
+    /// is started if refreshing is disabled, there are no remote lists, or the policy is loaded from a compiled policy
+    /// file.
+    ///
+    /// Each list is refreshed on its own schedule.  When a refresh fails, the last successfully fetched copy of the list
+    /// stays in use, and the refresh is retried with exponential backoff until it succeeds.  The task only holds a weak
+    /// reference to the authority, and is aborted when the authority is shut down or dropped.  When the lists are shared
+    /// with other authorities, only the first to start refreshing them does so.
+    pub fn spawn_refresh(self: &Arc<Self>) -> Option<JoinHandle<()>> {
+        let config = &self.source.config;
+        let interval = Duration::from_secs(config.refresh_interval);
+        let retry = Duration::from_secs(config.refresh_retry);
+        let max_backoff = Duration::from_secs(config.refresh_max_backoff);
+        if interval.is_zero() || config.compiled_policy.is_some() {
+            return None;
         }
-        debug!("Query '{name}' is not in blocklist; returning None...");
-        Ok(None)
+
+        let mut lists = self
+            .source
+            .list_entries()
+            .filter(|entry| entry.enabled)
+            .filter_map(|entry| entry.url)
+            .map(|url| RemoteRefresh {
+                url,
+                failures: 0,
+                next: Instant::now() + interval,
+            })
+            .collect::<Vec<_>>();
+        if lists.is_empty() {
+            return None;
+        }
+
+        let mut refresher = self
+            .shared
+            .refresher
+            .lock()
+            .expect("blocklist refresher lock poisoned");
+        if Weak::upgrade(&refresher).map_or(false, |refresher| !Arc::ptr_eq(&refresher, self)) {
+            debug!(
+                "remote blocklists for {} are refreshed by another blocklist sharing them",
+                self.origin
+            );
+            return None;
+        }
+        *refresher = Arc::downgrade(self);
+        drop(refresher);
+
+        info!(
+            "refreshing {} remote blocklists for {} every {interval:?}",
+            lists.len(),
+            self.origin
+        );
+        let authority = Arc::downgrade(self);
+        let task = tokio::spawn(async move {
+            loop {
+                let Some(next) = lists.iter().map(|list| list.next).min() else {
+                    return;
+                };
+                tokio::time::sleep_until(next.into()).await;
+
+                let Some(authority) = Weak::upgrade(&authority) else {
+                    return;
+                };
+
+                for list in lists.iter_mut().filter(|list| list.next <= Instant::now()) {
+                    let result = authority.refresh_remote(&list.url).await;
+                    match &result {
+                        Ok(()) => {
+                            if list.failures > 0 {
+                                info!(
+                                    "refresh of blocklist {} recovered after {} failures",
+                                    list.url, list.failures
+                                );
+                            }
+                            list.failures = 0;
+                            list.next = Instant::now() + interval;
+                        }
+                        Err(e) => {
+                            list.failures += 1;
+                            let delay = backoff(retry, max_backoff, list.failures);
+                            warn!(
+                                "refresh of blocklist {} failed {} times, keeping the last good copy; retrying in {delay:?}: {e}",
+                                list.url, list.failures
+                            );
+                            list.next = Instant::now() + delay;
+                            authority.source.emit(|| PolicyEvent::FetchFailed {
+                                url: list.url.clone(),
+                                error: e.clone(),
+                                failures: list.failures,
+                            });
+                        }
+                    }
+
+                    let status = RefreshStatus {
+                        at: SystemTime::now(),
+                        failures: list.failures,
+                        error: result.err(),
+                    };
+                    authority
+                        .refresh
+                        .lock()
+                        .expect("blocklist refresh lock poisoned")
+                        .insert(list.url.clone(), status);
+                }
+            }
+        });
+
+        self.tasks
+            .lock()
+            .expect("blocklist task lock poisoned")
+            .push(task.abort_handle());
+        Some(task)
     }
 
-    async fn search(
-        &self,
-        request_info: RequestInfo<'_>,
-        lookup_options: LookupOptions,
-    ) -> Result<Option<Self::Lookup>, LookupError> {
-        self.lookup(
-            request_info.query.name(),
-            request_info.query.query_type(),
-            lookup_options,
-        )
-        .await
+    /// Call `handler` with each [`PolicyEvent`] from now on, e.g. to alert when the lists fail to load, replacing any handler
+    /// set before.  Events are passed as they happen, on the task reloading or refreshing the lists, so the handler should
+    /// return quickly.  The lists loaded by [`Self::try_from_config`] with the `wait` startup policy are loaded before a
+    /// handler can be set, so only lists loaded in the background at startup produce a [`PolicyEvent::Loaded`].
+    pub fn on_event(&self, handler: impl Fn(&PolicyEvent) + Send + Sync + 'static) {
+        *self
+            .source
+            .events
+            .lock()
+            .expect("blocklist event handler lock poisoned") = Some(Arc::new(handler));
     }
 
-    async fn get_nsec_records(
-        &self,
-        _name: &LowerName,
-        _lookup_options: LookupOptions,
-    ) -> Result<Self::Lookup, LookupError> {
-        Err(LookupError::from(io::Error::new(
-            io::ErrorKind::Other,
-            "Getting NSEC records is unimplemented for the blocklist",
-        )))
+    /// Abort the background tasks started for the authority, such as remote list refreshes.  Queries are still answered
+    /// from the current lists, but they are no longer refreshed.  This is also done when the authority is dropped.
+    pub fn shutdown(&self) {
+        let tasks = std::mem::take(&mut *self.tasks.lock().expect("blocklist task lock poisoned"));
+        if !tasks.is_empty() {
+            debug!(
+                "stopping {} background tasks for blocklist {}",
+                tasks.len(),
+                self.origin
+            );
+        }
+
+        for task in tasks {
+            task.abort();
+        }
     }
-}
 
-pub struct BlocklistLookup(Lookup);
+    /// Fetch the remote list at `url` again, rebuilding the policy with the contents the other remote lists were last
+    /// fetched with.  On failure, the current policy stays in use.
+    async fn refresh_remote(&self, url: &str) -> Result<(), String> {
+        debug!("refreshing blocklist {url}");
+        let previous = self.policy();
+        let policy = self
+            .source
+            .load(
+                self.list_dir.as_deref(),
+                Fetch::One {
+                    url,
+                    previous: &previous,
+                },
+                &self.fetcher,
+            )
+            .await
+            .map_err(|errors| errors.join("; "))?;
 
-impl LookupObject for BlocklistLookup {
-    fn is_empty(&self) -> bool {
-        self.0.is_empty()
+        self.replace_policy(policy);
+        Ok(())
     }
 
-    fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a Record> + Send + 'a> {
-        Box::new(self.0.record_iter())
+    /// Start answering queries from `policy`, tombstoning the names which it no longer blocks.
+    fn replace_policy(&self, mut policy: Policy) {
+        self.source.save_cache(self.list_dir.as_deref(), &policy);
+        // The lists loaded in the background at startup are not a reload, so nothing on them is new.
+        if self.shared.ready.load(Ordering::Acquire) {
+            policy.track_first_seen(&self.policy());
+        }
+        // Several reloads may happen within a second, so the serial is increased even when the time has not.
+        policy.serial = policy.serial.max(self.policy().serial.wrapping_add(1));
+
+        let old = std::mem::replace(
+            &mut *self
+                .shared
+                .policy
+                .write()
+                .expect("blocklist policy lock poisoned"),
+            Arc::new(policy),
+        );
+        self.shared.ready.store(true, Ordering::Release);
+
+        let current = self.policy();
+        let removed = old
+            .blocklist
+            .entries()
+            .filter(|(name, _)| current.blocklist.matches(name).is_none())
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        self.shared.tombstones.insert_all(removed);
     }
 
-    fn take_additionals(&mut self) -> Option<Box<dyn LookupObject>> {
-        None
+    /// Swap in a copy of the current policy with the changes made by `change`.  Unlike [`Self::replace_policy`], the lists
+    /// are unchanged, so nothing is saved to the cache directory and the serial is kept.
+    fn change_policy(&self, change: impl FnOnce(&mut Policy)) {
+        // The copy is made under the lock, so that changes made at the same time are not lost.
+        let mut current = self
+            .shared
+            .policy
+            .write()
+            .expect("blocklist policy lock poisoned");
+        let mut policy = current.copy();
+        change(&mut policy);
+        *current = Arc::new(policy);
     }
-}
 
-#[cfg(test)]
-mod test {
-    use crate::{
-        authority::{AuthorityObject, LookupOptions, ZoneType},
-        proto::rr::domain::Name,
-        proto::rr::{rdata::A, LowerName, RData, RecordType},
-    };
-    use std::path::Path;
-    use std::str::FromStr;
-    use std::sync::Arc;
+    /// Compare the names this blocklist blocks with those `new` blocks, e.g. to check how a configuration change affects
+    /// the policy before applying it.  Up to `limit` names of each kind of change are kept, in sorted order; the counts
+    /// cover every change.
+    pub fn diff(&self, new: &Self, limit: usize) -> PolicyDiff {
+        self.policy().diff(&new.policy(), limit)
+    }
 
-    #[tokio::test]
-    async fn test_blocklist_basic() {
-        let config = super::BlocklistConfig {
-            wildcard_match: true,
-            min_wildcard_depth: 2,
-            lists: vec!["default/blocklist.txt".to_string()],
-        };
+    /// The policy currently used to answer queries
+    pub(super) fn policy(&self) -> Arc<Policy> {
+        self.shared
+            .policy
+            .read()
+            .expect("blocklist policy lock poisoned")
+            .clone()
+    }
 
-        let blocklist = super::BlocklistAuthority::try_from_config(
-            Name::from_str(".").unwrap(),
-            ZoneType::Hint,
-            &config,
-            Some(Path::new("../../tests/test-data/test_configs/")),
-        );
+    /// The policy, for modification.  This fails if it was loaded to be shared with other authorities.
+    fn policy_mut(&mut self) -> Result<&mut Policy, String> {
+        let origin = &self.origin;
+        let shared = Arc::get_mut(&mut self.shared).ok_or_else(|| {
+            format!("blocklist {origin} lists are shared with other blocklists, and can not be modified")
+        })?;
+        let policy = shared
+            .policy
+            .get_mut()
+            .expect("blocklist policy lock poisoned");
+        // Snapshots are only held for the duration of a lookup, which can not overlap with a mutable borrow.
+        let policy = Arc::get_mut(policy).expect("blocklist policy is shared");
+        // The caller may change the responses to any name.
+        if let Some(cache) = &policy.response_cache {
+            cache.clear();
+        }
+        Ok(policy)
+    }
 
-        let authority = blocklist.await;
+    /// Add a configured block list to the in-memory cache.  Names on the list are answered using the blocklist-wide settings.
+    pub async fn add(&mut self, file: String) -> bool {
+        let entry = ListEntry::from_path(file.clone());
+        self.add_list(file, &entry).await
+    }
 
-        // Test: verify the blocklist authority was successfully created.
-        match authority {
-            Ok(ref _authority) => {}
+    /// Add a block list to the in-memory cache, answering queries for names on it using the settings from `entry`.  Lists
+    /// with a `.gz` extension are decompressed as they are read.
+    pub async fn add_list(&mut self, file: String, entry: &ListEntry) -> bool {
+        let added = self.policy_mut().and_then(|policy| {
+            let added = policy.add_list(file, entry);
+            policy.apply_negations();
+            added
+        });
+        match added {
+            Ok(()) => true,
             Err(e) => {
-                panic!("Unable to create blocklist authority: {e}");
+                warn!("{e}");
+                false
             }
         }
+    }
 
-        let ao = Box::new(Arc::new(authority.unwrap())) as Box<dyn AuthorityObject>;
+    /// Add every list in a zip archive to the in-memory cache, returning the number of lists added.  Each member is tracked
+    /// as a separate list, using the settings from `entry`; members with a `.gz` extension are decompressed as they are read.
+    pub async fn add_archive(&mut self, file: String, entry: &ListEntry) -> Result<usize, String> {
+        let policy = self.policy_mut()?;
+        let added = policy.add_archive(file, entry);
+        policy.apply_negations();
+        added
+    }
 
-        // Test: lookup a record that is explicitly in the blocklist and that should match without a wildcard.
-        let res = ao
-            .lookup(
-                &LowerName::from_str("foo.com.").unwrap(),
-                RecordType::A,
-                LookupOptions::default(),
-            )
-            .await;
-        match res {
-            Ok(Some(l)) => {
-                if !l.iter().all(|x| {
-                    x.name() == &Name::from_str("foo.com.").unwrap()
-                        && x.data() == Some(&RData::A(A::new(0, 0, 0, 0)))
-                }) {
+    /// Fetch a remote block list, answering queries for names on it using the settings from `entry`.  The list is spooled
+    /// to disk as it arrives, and only added once it has been fetched in full and has matched its checksum, so that a
+    /// failed fetch leaves the policy as it was.
+    pub async fn add_remote(
+        &mut self,
+        url: String,
+        entry: &ListEntry,
+        timeout: Duration,
+    ) -> Result<(), String> {
+        let spool = self
+            .fetcher
+            .fetch(&url, entry.sha256.as_deref(), timeout, |_, _| ())
+            .await?
+            .ok_or_else(|| format!("unable to add block list {url}: it could not be spooled"))?;
+        let fetched = FetchedList {
+            spool,
+            fetched: SystemTime::now(),
+        };
+
+        let policy = self.policy_mut()?;
+        let added = policy.add_spooled(url, fetched, entry);
+        policy.apply_negations();
+        added
+    }
+
+    /// Remove an entry from the block table, returning whether it was present.  Removed entries are answered with NODATA
+    /// for the configured tombstone window.
+    pub fn remove(&mut self, entry: &LowerName) -> bool {
+        let entry = &canonical_name(entry.clone().into());
+        let removed = match self.policy_mut() {
+            Ok(policy) => policy.tables_mut().0.remove(entry).is_some(),
+            Err(e) => {
+                warn!("{e}");
+                false
+            }
+        };
+
+        if removed {
+            self.shared.tombstones.insert(entry.clone());
+        }
+        removed
+    }
+
+    /// Match a query against the block table, synthesizing the response if the name is blocked.  `client` is the querying
+    /// client, when known.
+    fn lookup_blocked(
+        &self,
+        name: &LowerName,
+        rtype: RecordType,
+        client: Option<Client>,
+    ) -> Result<Option<BlocklistLookup>, LookupError> {
+        debug!("blocklist lookup: {} {}", name, rtype);
+        let client_addr = client.map(|client| client.addr);
+
+        if is_malformed(name) {
+            if self.source.config.malformed_query == MalformedQueryPolicy::Refuse {
+                debug!("Query '{name}' is malformed; refusing...");
+                return Err(LookupError::from(ResponseCode::Refused));
+            }
+
+            debug!("Query '{name}' is malformed; returning None...");
+            self.passed.increment();
+            return Ok(None);
+        }
+
+        if !self.origin.zone_of(name) {
+            debug!(
+                "Query '{name}' is outside of {}; returning None...",
+                self.origin
+            );
+            return Ok(None);
+        }
+
+        if *name == self.origin && !self.source.config.block_origin {
+            debug!("Query '{name}' is for the origin; returning None...");
+            self.passed.increment();
+            return Ok(None);
+        }
+
+        if !self.shared.ready.load(Ordering::Acquire) {
+            if self.source.config.startup_policy == StartupPolicy::Closed {
+                debug!("Query '{name}' arrived before the lists loaded; refusing...");
+                return Err(LookupError::from(ResponseCode::Refused));
+            }
+
+            debug!("Query '{name}' arrived before the lists loaded; returning None...");
+            self.passed.increment();
+            return Ok(None);
+        }
+
+        let policy = self.policy();
+        if let Some((list, match_kind, lookup)) = policy.cached_response(name, rtype) {
+            debug!("Query '{name}' {rtype} answered from the response cache");
+            self.blocked.increment();
+            list.blocked.increment();
+            self.record_match(name, rtype, client_addr, list, list.action, match_kind);
+            return Ok(Some(lookup));
+        }
+
+        let match_list = policy.match_list(name);
+        // Responses to monitored names are not cached, so that every query for them is still logged and counted.
+        let monitored =
+            if let Some((host, list)) = policy.find_in(&*policy.monitorlist, name, &match_list) {
+                let from =
+                    client_addr.map_or_else(|| "unknown".to_string(), |client| client.to_string());
+                info!(
+                    "Query '{name}' {rtype} from {from} matched monitored list {}",
+                    list.source
+                );
+                self.monitored.increment();
+                list.blocked.increment();
+                let match_kind = MatchKind::of(name, host);
+                self.record_match(name, rtype, client_addr, list, list.action, match_kind);
+                true
+            } else if let Some(list) = policy.find_reverse(&policy.reverse_monitorlist, name) {
+                let from =
+                    client_addr.map_or_else(|| "unknown".to_string(), |client| client.to_string());
+                info!(
+                    "Query '{name}' {rtype} from {from} matched a range in monitored list {}",
+                    list.source
+                );
+                self.monitored.increment();
+                list.blocked.increment();
+                self.record_match(
+                    name,
+                    rtype,
+                    client_addr,
+                    list,
+                    list.action,
+                    MatchKind::Reverse,
+                );
+                true
+            } else {
+                false
+            };
+
+        let allowed = match policy.allowed_by_pattern(name) {
+            true => {
+                debug!("Query '{name}' matched an allowed pattern");
+                self.matches.increment_regex();
+                true
+            }
+            false => policy.allowed_by_name(name, &match_list),
+        };
+        let found = match allowed {
+            true => None,
+            false => policy.find_in(&*policy.blocklist, name, &match_list),
+        };
+        if let Some((host, list)) = found {
+            if policy.in_grace_period(host, list) {
+                let from =
+                    client_addr.map_or_else(|| "unknown".to_string(), |client| client.to_string());
+                info!(
+                    "Query '{name}' {rtype} from {from} matched {host} in {}, which is in its grace period",
+                    list.source
+                );
+                self.monitored.increment();
+                let match_kind = MatchKind::of(name, host);
+                self.record_match(
+                    name,
+                    rtype,
+                    client_addr,
+                    list,
+                    ListAction::Monitor,
+                    match_kind,
+                );
+            } else {
+                self.blocked.increment();
+                list.blocked.increment();
+                let match_kind = MatchKind::of(name, host);
+                self.record_match(name, rtype, client_addr, list, list.action, match_kind);
+                let entry = policy.entry_response(host, list);
+                let response =
+                    policy.blocked_response(name, Some(host), rtype, list, entry, client);
+                if !monitored {
+                    policy.cache_response(name, rtype, list, entry, match_kind, &response);
+                }
+                return response;
+            }
+        }
+
+        if let Some(list) = policy
+            .find_reverse(&policy.reverse_blocklist, name)
+            .filter(|_| !allowed)
+        {
+            debug!("Query '{name}' is in a range on {}", list.source);
+            self.blocked.increment();
+            list.blocked.increment();
+            self.record_match(
+                name,
+                rtype,
+                client_addr,
+                list,
+                list.action,
+                MatchKind::Reverse,
+            );
+            return policy.blocked_response(name, None, rtype, list, None, client);
+        }
+
+        if let Some((domain, list)) = policy.find_homograph(name).filter(|_| !allowed) {
+            info!("Query '{name}' is a homograph of the protected domain {domain}");
+            self.blocked.increment();
+            list.blocked.increment();
+            self.record_match(
+                name,
+                rtype,
+                client_addr,
+                list,
+                list.action,
+                MatchKind::Homograph,
+            );
+            return policy.blocked_response(name, None, rtype, list, None, client);
+        }
+
+        if match_list
+            .iter()
+            .any(|host| self.shared.tombstones.contains(host))
+        {
+            debug!("Query '{name}' was recently unblocked; answering with NODATA");
+            return Ok(Some(BlocklistLookup::tombstone(
+                Query::query(name.into(), rtype),
+                self.source.config.tombstone_ttl,
+            )));
+        }
+
+        debug!("Query '{name}' is not in blocklist; returning None...");
+        self.passed.increment();
+        Ok(None)
+    }
+
+    /// Replace the answers to a query this blocklist passed on with the block response of `list`, or of the matching
+    /// `entry` if its line set one
+    fn blocked_answers(
+        &self,
+        policy: &Policy,
+        name: &LowerName,
+        rtype: RecordType,
+        list: &BlockList,
+        entry: Option<EntryResponse>,
+        match_kind: MatchKind,
+    ) -> Result<Vec<Record>, LookupError> {
+        self.blocked.increment();
+        list.blocked.increment();
+        self.record_match(name, rtype, None, list, list.action, match_kind);
+        let lookup = policy.blocked_response(name, None, rtype, list, entry, None)?;
+        match lookup.map(BlocklistLookup::into_result).transpose()? {
+            Some(lookup) => Ok(lookup.lookup.record_iter().cloned().collect()),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Attach the SOA for the blocklist's origin to `lookup`, if it is a negative response other than a tombstone, unless
+    /// `minimal_responses` is set
+    fn with_negative_soa(&self, mut lookup: BlocklistLookup) -> BlocklistLookup {
+        if let Some(ttl) = lookup.negative_ttl.filter(|_| !lookup.tombstone) {
+            if !self.source.config.minimal_responses {
+                let soa = self.policy().negative_soa(&self.origin, ttl);
+                lookup.soa = Some(Box::new(soa));
+            }
+        }
+        lookup
+    }
+
+    /// Count a match of `match_kind` against `list`, and write an event for the query, which was handled according to
+    /// `action`, to the query log, if it is enabled
+    fn record_match(
+        &self,
+        name: &LowerName,
+        rtype: RecordType,
+        client: Option<IpAddr>,
+        list: &BlockList,
+        action: ListAction,
+        match_kind: MatchKind,
+    ) {
+        self.matches.increment(match_kind);
+
+        let Some(query_log) = &self.query_log else {
+            return;
+        };
+
+        if self.log_excluded(name) {
+            return;
+        }
+
+        query_log.write(&QueryEvent {
+            timestamp: OffsetDateTime::now_utc(),
+            client,
+            qname: name,
+            qtype: rtype,
+            action,
+            list: &list.source,
+            match_kind,
+        });
+    }
+
+    /// Whether `name` is on the `log_exclude` list, either exactly or below a wildcard entry
+    pub(super) fn log_excluded(&self, name: &LowerName) -> bool {
+        if self.log_exclude.is_empty() {
+            return false;
+        }
+
+        if self.log_exclude.matches(name).is_some() {
+            return true;
+        }
+
+        let mut current = name.clone();
+        while current.num_labels() > 1 {
+            if self
+                .log_exclude
+                .matches(&current.clone().into_wildcard())
+                .is_some()
+            {
+                return true;
+            }
+            current = current.base_name();
+        }
+        false
+    }
+
+    /// The candidate block list entries collected in learn mode, most frequently failed first.  Each formats as a block
+    /// list line.  This is empty unless learn mode is enabled.
+    pub fn learned(&self) -> Vec<LearnedName> {
+        self.learner
+            .as_ref()
+            .map_or_else(Vec::new, |learner| learner.report())
+    }
+
+    /// Every configured list, including those which are disabled, with the number of entries read from it, when it was
+    /// loaded, and how many queries it has answered.  Each member of an archive is listed separately.
+    pub fn list_sources(&self) -> Vec<ListSourceInfo> {
+        let policy = self.policy();
+        let refresh = self
+            .refresh
+            .lock()
+            .expect("blocklist refresh lock poisoned");
+        let now = OffsetDateTime::now_utc();
+
+        let loaded = policy.lists.iter().map(|list| ListSourceInfo {
+            source: list.source.clone(),
+            remote: list.remote,
+            action: list.action,
+            enabled: list
+                .schedule
+                .map_or(true, |schedule| schedule.contains(now.hour(), now.minute())),
+            entries: list.entries,
+            loaded: Some(list.loaded),
+            blocked: list.blocked.read(false),
+            refresh: refresh.get(&list.source).cloned(),
+        });
+        let disabled = self
+            .source
+            .list_entries()
+            .filter(|entry| !entry.enabled)
+            .map(|entry| ListSourceInfo {
+                remote: entry.url.is_some(),
+                source: entry.url.or(entry.path).unwrap_or_default(),
+                action: entry.action.unwrap_or_default(),
+                enabled: false,
+                entries: 0,
+                loaded: None,
+                blocked: 0,
+                refresh: None,
+            });
+
+        loaded.chain(disabled).collect()
+    }
+
+    /// Query counters since the authority was created, or since they were last reset.  Per list counters start again from
+    /// zero when the lists are reloaded.
+    pub fn stats(&self) -> BlocklistStats {
+        self.read_stats(false)
+    }
+
+    /// Query counters since the authority was created, or since they were last reset, resetting them to zero.  Queries
+    /// which are in flight during the reset are counted in exactly one interval.
+    pub fn reset_stats(&self) -> BlocklistStats {
+        self.read_stats(true)
+    }
+
+    fn read_stats(&self, reset: bool) -> BlocklistStats {
+        BlocklistStats {
+            blocked: self.blocked.read(reset),
+            passed: self.passed.read(reset),
+            monitored: self.monitored.read(reset),
+            matches: self.matches.read(reset),
+            lists: self
+                .policy()
+                .lists
+                .iter()
+                .map(|list| ListStats {
+                    source: list.source.clone(),
+                    action: list.action,
+                    blocked: list.blocked.read(reset),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl SharedPolicy {
+    fn new(policy: Policy, config: &BlocklistConfig, ready: bool) -> Arc<Self> {
+        Arc::new(Self {
+            policy: RwLock::new(Arc::new(policy)),
+            tombstones: Tombstones::new(
+                Duration::from_secs(config.tombstone_window.into()),
+                config.tombstone_capacity,
+            ),
+            refresher: Mutex::new(Weak::new()),
+            ready: AtomicBool::new(ready),
+        })
+    }
+
+    /// The shared policy loaded with `key`, if an authority using it still exists
+    fn find(key: &str) -> Option<Arc<Self>> {
+        let mut shared = SHARED_POLICIES
+            .lock()
+            .expect("shared blocklist lock poisoned");
+        shared.retain(|(_, policy)| policy.strong_count() > 0);
+        shared
+            .iter()
+            .find(|(shared_key, _)| shared_key == key)
+            .and_then(|(_, policy)| policy.upgrade())
+    }
+
+    /// Share `policy` with authorities loaded later with `key`.  If another authority registered a policy for `key` while
+    /// this one was loading, that policy is returned instead, so that there is only ever one in use.
+    fn register(key: String, policy: Arc<Self>) -> Arc<Self> {
+        let mut shared = SHARED_POLICIES
+            .lock()
+            .expect("shared blocklist lock poisoned");
+        let existing = shared
+            .iter()
+            .find(|(shared_key, _)| *shared_key == key)
+            .and_then(|(_, policy)| policy.upgrade());
+        if let Some(existing) = existing {
+            return existing;
+        }
+
+        shared.retain(|(_, policy)| policy.strong_count() > 0);
+        shared.push((key, Arc::downgrade(&policy)));
+        policy
+    }
+}
+
+impl Drop for BlocklistAuthority {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Whether `name` can not be matched against the lists: it is not fully qualified, which includes the empty name, or one
+/// of its labels contains a dot, so that it is not the name its text form gives
+fn is_malformed(name: &LowerName) -> bool {
+    let name: &Name = name.borrow();
+    !name.is_fqdn() || name.iter().any(|label| label.contains(&b'.'))
+}
+
+/// Delay before retrying a refresh which has failed `failures` times in a row: `retry` doubled for each failure after the
+/// first, capped at `max`, then randomly shortened by up to half.
+fn backoff(retry: Duration, max: Duration, failures: u32) -> Duration {
+    let delay = retry
+        .saturating_mul(2_u32.saturating_pow(failures.saturating_sub(1)))
+        .min(max);
+    delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+}
+
+/// The address of an A or AAAA record
+fn answer_addr(record: &Record) -> Option<IpAddr> {
+    match record.data() {
+        RData::A(a) => Some(IpAddr::V4(a.0)),
+        RData::AAAA(aaaa) => Some(IpAddr::V6(aaaa.0)),
+        _ => None,
+    }
+}
+
+#[async_trait::async_trait]
+impl Authority for BlocklistAuthority {
+    type Lookup = BlocklistLookup;
+
+    /// Always Recursive
+    fn zone_type(&self) -> ZoneType {
+        ZoneType::Hint
+    }
+
+    /// Always false for Forward zones
+    fn is_axfr_allowed(&self) -> bool {
+        false
+    }
+
+    /// Blocked responses are synthesized locally, rather than forwarded
+    fn answers_authoritatively(&self) -> bool {
+        true
+    }
+
+    /// Every name below the origin is checked against the lists
+    fn screens_names(&self) -> bool {
+        true
+    }
+
+    /// Set by `strip_client_subnet`
+    fn strips_client_subnet(&self) -> bool {
+        self.policy().strip_client_subnet
+    }
+
+    /// Set by `strip_answer_networks`, `block_cname_targets`, `block_ptr_targets`, `ip_blocklists` and
+    /// `upstream_sink_addresses`
+    fn filters_answers(&self) -> bool {
+        let policy = self.policy();
+        policy.block_cname_targets
+            || policy.block_ptr_targets
+            || !policy.ip_blocklist.is_empty()
+            || policy.upstream_sinks.is_some()
+            || !policy.strip_answer_networks.is_empty()
+    }
+
+    /// Filter the answers to a query this blocklist passed on, as resolved by a later store in the chain.
+    ///
+    /// If `block_cname_targets` is set and any of the first `max_cname_targets` CNAMEs in the answers points at a blocked
+    /// name, if `block_ptr_targets` is set and any PTR record in the answers points at a blocked name, or if any address in
+    /// the answers is on one of the `ip_blocklists`, the whole answer is replaced with the block response for the original
+    /// query.  An answer containing one of the `upstream_sink_addresses` is replaced with the `upstream_sink_response`.
+    /// Otherwise, address records within any of the `strip_answer_networks` are removed; all other records are returned
+    /// unchanged.
+    fn filter_answers(
+        &self,
+        name: &LowerName,
+        rtype: RecordType,
+        answers: Vec<Record>,
+    ) -> Result<Vec<Record>, LookupError> {
+        let policy = self.policy();
+        if policy.block_cname_targets || policy.block_ptr_targets {
+            let mut cnames = 0;
+            for record in &answers {
+                let (target, match_kind) = match record.data() {
+                    RData::CNAME(target) if policy.block_cname_targets => {
+                        cnames += 1;
+                        if cnames > policy.max_cname_targets {
+                            if cnames == policy.max_cname_targets + 1 {
+                                debug!(
+                                    "Query '{name}' has more than {} CNAME records; not checking the rest",
+                                    policy.max_cname_targets
+                                );
+                            }
+                            continue;
+                        }
+                        (&target.0, MatchKind::Cname)
+                    }
+                    RData::PTR(target) if policy.block_ptr_targets => (&target.0, MatchKind::Ptr),
+                    _ => continue,
+                };
+
+                let target = LowerName::from(target);
+                let match_list = policy.match_list(&target);
+                let Some((host, list)) = policy.find_list(&target, &match_list) else {
+                    continue;
+                };
+
+                if policy.in_grace_period(host, list) {
+                    debug!("Query '{name}' points at '{target}', which is in its grace period");
+                    continue;
+                }
+
+                let entry = policy.entry_response(host, list);
+                match match_kind {
+                    MatchKind::Ptr => debug!("Query '{name}' points at blocked name '{target}'"),
+                    _ => debug!("Query '{name}' is an alias of blocked name '{target}'"),
+                }
+
+                return self.blocked_answers(&policy, name, rtype, list, entry, match_kind);
+            }
+        }
+
+        if !policy.ip_blocklist.is_empty() {
+            for record in &answers {
+                let Some(addr) = answer_addr(record) else {
+                    continue;
+                };
+
+                if let Some(&list) = policy.ip_blocklist.get(addr) {
+                    let list = &policy.lists[list];
+                    debug!(
+                        "Query '{name}' resolved to {addr}, which is blocked by {}",
+                        list.source
+                    );
+                    return self.blocked_answers(
+                        &policy,
+                        name,
+                        rtype,
+                        list,
+                        None,
+                        MatchKind::Address,
+                    );
+                }
+            }
+        }
+
+        if let Some((sinks, list)) = &policy.upstream_sinks {
+            let sunk = answers
+                .iter()
+                .filter_map(answer_addr)
+                .find(|addr| sinks.iter().any(|net| net.contains(addr)));
+            if let Some(addr) = sunk {
+                debug!("Query '{name}' was blocked upstream, which answered {addr}");
+                let list = &policy.lists[*list];
+                return self.blocked_answers(
+                    &policy,
+                    name,
+                    rtype,
+                    list,
+                    None,
+                    MatchKind::UpstreamSink,
+                );
+            }
+        }
+
+        if policy.strip_answer_networks.is_empty() {
+            return Ok(answers);
+        }
+
+        Ok(answers
+            .into_iter()
+            .filter(|record| {
+                let Some(addr) = answer_addr(record) else {
+                    return true;
+                };
+
+                let strip = policy
+                    .strip_answer_networks
+                    .iter()
+                    .any(|net| net.contains(&addr));
+                if strip {
+                    debug!("Stripping {addr} from the answers for {}", record.name());
+                }
+                !strip
+            })
+            .collect())
+    }
+
+    /// Record the response code for a query this blocklist passed on, as resolved by a later store in the chain.  In learn
+    /// mode, names answered with NXDOMAIN or SERVFAIL are collected as candidate block list entries.
+    fn observe_response(&self, name: &LowerName, response_code: ResponseCode) {
+        if let Some(learner) = &self.learner {
+            learner.observe(name, response_code);
+        }
+    }
+
+    async fn update(&self, _update: &MessageRequest) -> UpdateResult<bool> {
+        Err(ResponseCode::NotImp)
+    }
+
+    /// Get the origin of this zone, i.e. example.com is the origin for www.example.com
+    ///
+    /// In the context of a forwarder, this is either a zone which this forwarder is associated,
+    ///   or `.`, the root zone for all zones. If this is not the root zone, then it will only forward
+    ///   for lookups which match the given zone name.
+    fn origin(&self) -> &LowerName {
+        &self.origin
+    }
+
+    /// Forwards a lookup given the resolver configuration for this Forwarded zone
+    async fn lookup(
+        &self,
+        name: &LowerName,
+        rtype: RecordType,
+        _lookup_options: LookupOptions,
+    ) -> Result<Option<Self::Lookup>, LookupError> {
+        self.lookup_blocked(name, rtype, None)?
+            .map(BlocklistLookup::into_result)
+            .transpose()
+    }
+
+    async fn search(
+        &self,
+        request_info: RequestInfo<'_>,
+        _lookup_options: LookupOptions,
+    ) -> Result<Option<Self::Lookup>, LookupError> {
+        // Block lists name internet hosts; queries in other classes, e.g. CHAOS queries for version.bind, are passed on
+        // rather than being answered with an IN class sinkhole record.
+        let class = request_info.query.query_class();
+        if class != DNSClass::IN {
+            debug!(
+                "Query '{}' is in class {class}; returning None...",
+                request_info.query.name()
+            );
+            return Ok(None);
+        }
+
+        let local_addresses = &self.source.config.local_addresses;
+        if let Some(local) = request_info.local_addr() {
+            if !local_addresses.is_empty() && !local_addresses.contains(&local.ip()) {
+                debug!(
+                    "Query '{}' was received on {local}, which the blocklist does not answer on; returning None...",
+                    request_info.query.name()
+                );
+                return Ok(None);
+            }
+        }
+
+        let client = Client {
+            addr: request_info.src.ip(),
+            subnet: request_info
+                .edns()
+                .and_then(|edns| match edns.option(EdnsCode::Subnet) {
+                    Some(EdnsOption::Subnet(subnet)) => {
+                        IpNet::new(subnet.addr(), subnet.source_prefix()).ok()
+                    }
+                    _ => None,
+                }),
+        };
+        if let Some(limiter) = &self.limiter {
+            if !limiter.allow(client.addr) {
+                debug!(
+                    "Query '{}' from {} exceeds the client rate limit; refusing...",
+                    request_info.query.name(),
+                    client.addr
+                );
+                return Err(LookupError::from(ResponseCode::Refused));
+            }
+        }
+
+        let lookup = self.lookup_blocked(
+            request_info.query.name(),
+            request_info.query.query_type(),
+            Some(client),
+        )?;
+        Ok(lookup.map(|lookup| self.with_negative_soa(lookup)))
+    }
+
+    async fn get_nsec_records(
+        &self,
+        _name: &LowerName,
+        _lookup_options: LookupOptions,
+    ) -> Result<Self::Lookup, LookupError> {
+        Err(LookupError::from(io::Error::new(
+            io::ErrorKind::Other,
+            "Getting NSEC records is unimplemented for the blocklist",
+        )))
+    }
+}
+
+/// The records synthesized in answer to a query, which may be several, e.g. a DNAME followed by the CNAME it synthesizes
+#[derive(Clone)]
+pub struct BlocklistLookup {
+    pub(super) lookup: Lookup,
+    pub(super) response_code: ResponseCode,
+    /// For NXDOMAIN and NODATA responses, the TTL and minimum of the SOA they carry
+    pub(super) negative_ttl: Option<u32>,
+    /// The SOA carried by a negative response, unless `minimal_responses` is set
+    pub(super) soa: Option<Box<Self>>,
+    /// Whether this is the NODATA answer for a recently unblocked name, which carries no SOA so that it is not negatively
+    /// cached downstream
+    tombstone: bool,
+    /// Set by the `ede_code` and `ede_text` of the list which blocked the name
+    pub(super) extended_error: Option<(u16, String)>,
+}
+
+impl BlocklistLookup {
+    /// A lookup answering `query` with each of `records`, in order
+    pub(super) fn new(query: Query, records: impl Into<Arc<[Record]>>) -> Self {
+        Self {
+            lookup: Lookup::new_with_max_ttl(query, records.into()),
+            response_code: ResponseCode::NoError,
+            negative_ttl: None,
+            soa: None,
+            tombstone: false,
+            extended_error: None,
+        }
+    }
+
+    /// A negative response to `query`: NXDOMAIN, or NODATA if `response_code` is NOERROR, which is cached for `ttl`
+    pub(super) fn negative(query: Query, response_code: ResponseCode, ttl: u32) -> Self {
+        Self {
+            response_code,
+            negative_ttl: Some(ttl),
+            ..Self::new(query, Vec::new())
+        }
+    }
+
+    /// The NODATA answer to `query` for a recently unblocked name, which lasts for `ttl` and carries no SOA
+    fn tombstone(query: Query, ttl: u32) -> Self {
+        Self {
+            tombstone: true,
+            ..Self::negative(query, ResponseCode::NoError, ttl)
+        }
+    }
+
+    /// The lookup as the result of `Authority::lookup`, which gives negative responses as the error for their response code
+    fn into_result(self) -> Result<Self, LookupError> {
+        match (self.negative_ttl, self.response_code) {
+            (None, _) => Ok(self),
+            (Some(_), ResponseCode::NoError) => Err(LookupError::for_name_exists()),
+            (Some(_), response_code) => Err(LookupError::from(response_code)),
+        }
+    }
+}
+
+impl LookupObject for BlocklistLookup {
+    fn is_empty(&self) -> bool {
+        self.lookup.is_empty()
+    }
+
+    fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a Record> + Send + 'a> {
+        Box::new(self.lookup.record_iter())
+    }
+
+    fn take_additionals(&mut self) -> Option<Box<dyn LookupObject>> {
+        None
+    }
+
+    fn response_code(&self) -> ResponseCode {
+        self.response_code
+    }
+
+    fn take_soa(&mut self) -> Option<Box<dyn LookupObject>> {
+        self.soa.take().map(|soa| soa as Box<dyn LookupObject>)
+    }
+
+    fn extended_error(&self) -> Option<(u16, &str)> {
+        let (code, text) = self.extended_error.as_ref()?;
+        Some((*code, text))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BlocklistAuthority, BlocklistAuthorityBuilder, BlocklistLookup};
+    use crate::proto::rr::Record;
+    use crate::server::{Protocol, Request, RequestInfo, ResponseHandler, ResponseInfo};
+
+    use crate::store::blocklist::remote::test::{serve_chunked, serve_sequence};
+    use crate::store::blocklist::{
+        BlockResponse, BlocklistConfig, ListAction, ListEntry, ListFormat, ListReload,
+        PublicSinkholePolicy,
+    };
+    use crate::{
+        authority::{
+            Authority, AuthorityObject, Catalog, LookupOptions, MessageRequest, MessageResponse,
+            ZoneType,
+        },
+        proto::op::{Header, Message, Query, ResponseCode},
+        proto::rr::domain::Name,
+        proto::rr::{
+            rdata::{A, AAAA, CNAME},
+            DNSClass, LowerName, RData, RecordType,
+        },
+        proto::serialize::binary::{BinDecodable, BinEncoder},
+    };
+    use std::collections::HashSet;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+    use std::path::Path;
+    use std::str::FromStr;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_blocklist_basic() {
+        let config = BlocklistConfig {
+            wildcard_match: true,
+            min_wildcard_depth: 2,
+            lists: vec![ListEntry::from_path("default/blocklist.txt")],
+            ..BlocklistConfig::default()
+        };
+
+        let blocklist = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            Some(Path::new("../../tests/test-data/test_configs/")),
+            None,
+        );
+
+        let authority = blocklist.await;
+
+        // Test: verify the blocklist authority was successfully created.
+        match authority {
+            Ok(ref _authority) => {}
+            Err(e) => {
+                panic!("Unable to create blocklist authority: {e}");
+            }
+        }
+
+        let ao = Box::new(Arc::new(authority.unwrap())) as Box<dyn AuthorityObject>;
+
+        // Test: lookup a record that is explicitly in the blocklist and that should match without a wildcard.
+        let res = ao
+            .lookup(
+                &LowerName::from_str("foo.com.").unwrap(),
+                RecordType::A,
+                LookupOptions::default(),
+            )
+            .await;
+        match res {
+            Ok(Some(l)) => {
+                if !l.iter().all(|x| {
+                    x.name() == &Name::from_str("foo.com.").unwrap()
+                        && x.data() == &RData::A(A::new(0, 0, 0, 0))
+                }) {
+                    panic!("foo.com lookup data is incorrect.");
+                }
+            }
+            Ok(None) => {
+                panic!("Lookup returned Ok(None); expected Ok(Some)");
+            }
+            Err(e) => {
+                panic!("Lookup error: {e}!");
+            }
+        }
+
+        // Test: lookup a record that is not in the blocklist. This test should fail.
+        let res = ao
+            .lookup(
+                &LowerName::from_str("test.com.").unwrap(),
+                RecordType::A,
+                LookupOptions::default(),
+            )
+            .await;
+        match res {
+            Ok(Some(_l)) => {
+                panic!("test.com lookup returned Ok; expected failure");
+            }
+            Ok(None) => {}
+            Err(_e) => {
+                panic!("test.com lookup returned Err; expected Ok(None)");
+            }
+        }
+
+        // Test: lookup a record that will match a wildcard that is in the blocklist.
+        let res = ao
+            .lookup(
+                &LowerName::from_str("www.foo.com.").unwrap(),
+                RecordType::A,
+                LookupOptions::default(),
+            )
+            .await;
+        match res {
+            Ok(Some(l)) => {
+                if !l.iter().all(|x| {
+                    x.name() == &Name::from_str("www.foo.com.").unwrap()
+                        && x.data() == &RData::A(A::new(0, 0, 0, 0))
+                }) {
+                    panic!("www.foo.com lookup data is incorrect.");
+                }
+            }
+            Ok(None) => {
+                panic!("Lookup returned Ok(None); expected Ok(Some)");
+            }
+            Err(e) => {
+                panic!("Lookup error: {e}!");
+            }
+        }
+
+        // Test: lookup a record that will match a wildcard that is in the blocklist.
+        let res = ao
+            .lookup(
+                &LowerName::from_str("www.com.foo.com.").unwrap(),
+                RecordType::A,
+                LookupOptions::default(),
+            )
+            .await;
+        match res {
+            Ok(Some(l)) => {
+                if !l.iter().all(|x| {
+                    x.name() == &Name::from_str("www.com.foo.com.").unwrap()
+                        && x.data() == &RData::A(A::new(0, 0, 0, 0))
+                }) {
+                    panic!("www.com.foo.com lookup data is incorrect.");
+                }
+            }
+            Ok(None) => {
+                panic!("lookup returned Ok(None); expected Ok(Some)");
+            }
+            Err(e) => {
+                panic!("Lookup error: {e}!");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_wildcard_disabled() {
+        let config = BlocklistConfig {
+            min_wildcard_depth: 2,
+            wildcard_match: false,
+            lists: vec![ListEntry::from_path("default/blocklist.txt")],
+            ..BlocklistConfig::default()
+        };
+
+        let blocklist = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            Some(Path::new("../../tests/test-data/test_configs/")),
+            None,
+        );
+
+        let authority = blocklist.await;
+
+        // Test: verify the blocklist authority was successfully created.
+        match authority {
+            Ok(ref _authority) => {}
+            Err(e) => {
+                panic!("Unable to create blocklist authority: {e}");
+            }
+        }
+
+        let ao = Box::new(Arc::new(authority.unwrap())) as Box<dyn AuthorityObject>;
+
+        // Test: lookup a record that is explicitly in the blocklist and that should match with a wildcard.
+        let res = ao
+            .lookup(
+                &LowerName::from_str("foo.com.").unwrap(),
+                RecordType::A,
+                LookupOptions::default(),
+            )
+            .await;
+        match res {
+            Ok(Some(l)) => {
+                if !l.iter().all(|x| {
+                    x.name() == &Name::from_str("foo.com.").unwrap()
+                        && x.data() == &RData::A(A::new(0, 0, 0, 0))
+                }) {
+                    panic!("foo.com lookup data is incorrect.");
+                }
+            }
+            Ok(None) => {
+                panic!("Lookup returned Ok(None); expected Ok(Some)");
+            }
+            Err(e) => {
+                panic!("Lookup error: {e}!");
+            }
+        }
+
+        // Test: lookup a record that is not in the blocklist, but would match a wildcard; this should fail.
+        let res = ao
+            .lookup(
+                &LowerName::from_str("www.foo.com.").unwrap(),
+                RecordType::A,
+                LookupOptions::default(),
+            )
+            .await;
+        match res {
+            Ok(Some(_l)) => {
+                panic!("www.foo.com lookup returned Ok(Some); expected Ok(None)");
+            }
+            Ok(None) => {}
+            Err(_e) => {
+                panic!("www.foo.com lookup returned Err; expected Ok(None)");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_nodata_types() {
+        let config = BlocklistConfig {
+            wildcard_match: true,
+            min_wildcard_depth: 2,
+            lists: vec![ListEntry::from_path("default/blocklist.txt")],
+            ..BlocklistConfig::default()
+        };
+
+        let authority = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            Some(Path::new("../../tests/test-data/test_configs/")),
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+
+        let ao = Box::new(Arc::new(authority)) as Box<dyn AuthorityObject>;
+
+        // Test: service binding and service discovery queries for a blocked name get NODATA instead of a sinkhole address.
+        for rtype in [
+            RecordType::HTTPS,
+            RecordType::SVCB,
+            RecordType::SRV,
+            RecordType::NAPTR,
+        ] {
+            let res = ao
+                .lookup(
+                    &LowerName::from_str("www.foo.com.").unwrap(),
+                    rtype,
+                    LookupOptions::default(),
+                )
+                .await;
+            match res {
+                Err(e) if e.is_name_exists() => {}
+                Err(e) => panic!("{rtype} lookup returned unexpected error: {e}"),
+                Ok(_) => panic!("{rtype} lookup returned Ok; expected NameExists"),
+            }
+        }
+
+        // Test: service binding queries for names which are not blocked are not handled.
+        let res = ao
+            .lookup(
+                &LowerName::from_str("test.com.").unwrap(),
+                RecordType::HTTPS,
+                LookupOptions::default(),
+            )
+            .await;
+        match res {
+            Ok(None) => {}
+            Ok(Some(_l)) => panic!("test.com HTTPS lookup returned Ok(Some); expected Ok(None)"),
+            Err(e) => panic!("test.com HTTPS lookup returned Err: {e}; expected Ok(None)"),
+        }
+
+        // Test: service discovery queries get NXDOMAIN where that is the block response, like every other type.
+        let authority = BlocklistAuthorityBuilder::new(Name::root())
+            .block_response(BlockResponse::NxDomain)
+            .add_entries([Name::from_str("_sip._udp.foo.com.").unwrap()])
+            .build()
+            .expect("Unable to create blocklist authority");
+        for rtype in [RecordType::SRV, RecordType::NAPTR] {
+            let res = authority
+                .lookup(
+                    &LowerName::from_str("_sip._udp.foo.com.").unwrap(),
+                    rtype,
+                    LookupOptions::default(),
+                )
+                .await;
+            match res {
+                Err(e) if e.is_nx_domain() => {}
+                Err(e) => panic!("{rtype} lookup returned unexpected error: {e}"),
+                Ok(_) => panic!("{rtype} lookup returned Ok; expected NXDomain"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_sinkhole() {
+        let mut config = BlocklistConfig {
+            lists: vec![ListEntry::from_path("default/blocklist.txt")],
+            sinkhole_ipv4: Ipv4Addr::new(192, 168, 1, 1),
+            sinkhole_ipv6: Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 1),
+            public_sinkhole: PublicSinkholePolicy::Refuse,
+            ..BlocklistConfig::default()
+        };
+
+        let authority = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            Some(Path::new("../../tests/test-data/test_configs/")),
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+
+        let ao = Box::new(Arc::new(authority)) as Box<dyn AuthorityObject>;
+
+        // Test: A and AAAA queries are answered with the configured sinkhole addresses.
+        for (rtype, rdata) in [
+            (RecordType::A, RData::A(A::new(192, 168, 1, 1))),
+            (
+                RecordType::AAAA,
+                RData::AAAA(AAAA::new(0xfd00, 0, 0, 0, 0, 0, 0, 1)),
+            ),
+        ] {
+            let res = ao
+                .lookup(
+                    &LowerName::from_str("foo.com.").unwrap(),
+                    rtype,
+                    LookupOptions::default(),
+                )
+                .await;
+            match res {
+                Ok(Some(l)) => {
+                    if !l.iter().all(|x| x.data() == &rdata) {
+                        panic!("foo.com {rtype} lookup data is incorrect.");
+                    }
+                }
+                Ok(None) => panic!("Lookup returned Ok(None); expected Ok(Some)"),
+                Err(e) => panic!("Lookup error: {e}!"),
+            }
+        }
+
+        // Test: a publicly routable sinkhole address is refused...
+        config.sinkhole_ipv4 = Ipv4Addr::new(192, 0, 2, 1);
+        let res = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            Some(Path::new("../../tests/test-data/test_configs/")),
+            None,
+        )
+        .await;
+        assert!(res.is_err(), "public sinkhole address was not refused");
+
+        // Test: ...unless it is within one of the configured sinkhole networks...
+        config.sinkhole_networks = vec!["192.0.2.0/24".parse().unwrap()];
+        let res = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            Some(Path::new("../../tests/test-data/test_configs/")),
+            None,
+        )
+        .await;
+        assert!(
+            res.is_ok(),
+            "sinkhole address in sinkhole_networks was refused"
+        );
+
+        // Test: ...or the policy only warns about it.
+        config.sinkhole_networks = vec![];
+        config.public_sinkhole = PublicSinkholePolicy::Warn;
+        let res = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            Some(Path::new("../../tests/test-data/test_configs/")),
+            None,
+        )
+        .await;
+        assert!(
+            res.is_ok(),
+            "public sinkhole address was refused with a warn policy"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_list_settings() {
+        let mut config = BlocklistConfig {
+            lists: vec![
+                ListEntry::from_path("default/blocklist.txt"),
+                ListEntry {
+                    response: Some(BlockResponse::NxDomain),
+                    priority: 10,
+                    ..ListEntry::from_path("default/blocklist2.txt")
+                },
+                ListEntry {
+                    format: ListFormat::Hosts,
+                    sinkhole_ipv4: Some(Ipv4Addr::new(10, 0, 0, 1)),
+                    ttl: Some(60),
+                    ..ListEntry::from_path("default/blocklist_hosts.txt")
+                },
+            ],
+            ..BlocklistConfig::default()
+        };
+
+        let authority = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            Some(Path::new("../../tests/test-data/test_configs/")),
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+
+        let ao = Box::new(Arc::new(authority)) as Box<dyn AuthorityObject>;
+
+        // Test: a name on two lists is answered using the settings of the higher priority list.
+        let res = ao
+            .lookup(
+                &LowerName::from_str("malware.com.").unwrap(),
+                RecordType::A,
+                LookupOptions::default(),
+            )
+            .await;
+        match res {
+            Err(e) if e.is_nx_domain() => {}
+            Err(e) => panic!("malware.com lookup returned unexpected error: {e}"),
+            Ok(_) => panic!("malware.com lookup returned Ok; expected NXDOMAIN"),
+        }
+
+        // Test: names on a list without overrides use the blocklist-wide settings.
+        let res = ao
+            .lookup(
+                &LowerName::from_str("foo.com.").unwrap(),
+                RecordType::A,
+                LookupOptions::default(),
+            )
+            .await;
+        match res {
+            Ok(Some(l)) => {
+                if !l
+                    .iter()
+                    .all(|x| x.data() == &RData::A(A::new(0, 0, 0, 0)) && x.ttl() == config.ttl)
+                {
                     panic!("foo.com lookup data is incorrect.");
                 }
             }
-            Ok(None) => {
-                panic!("Lookup returned Ok(None); expected Ok(Some)");
+            Ok(None) => panic!("Lookup returned Ok(None); expected Ok(Some)"),
+            Err(e) => panic!("Lookup error: {e}!"),
+        }
+
+        // Test: names from a hosts formatted list use the list's sinkhole and TTL.
+        let res = ao
+            .lookup(
+                &LowerName::from_str("tracker.example.net.").unwrap(),
+                RecordType::A,
+                LookupOptions::default(),
+            )
+            .await;
+        match res {
+            Ok(Some(l)) => {
+                if !l
+                    .iter()
+                    .all(|x| x.data() == &RData::A(A::new(10, 0, 0, 1)) && x.ttl() == 60)
+                {
+                    panic!("tracker.example.net lookup data is incorrect.");
+                }
+            }
+            Ok(None) => panic!("Lookup returned Ok(None); expected Ok(Some)"),
+            Err(e) => panic!("Lookup error: {e}!"),
+        }
+
+        // Test: local names in a hosts formatted list are not blocked.
+        let res = ao
+            .lookup(
+                &LowerName::from_str("localhost.").unwrap(),
+                RecordType::A,
+                LookupOptions::default(),
+            )
+            .await;
+        assert!(matches!(res, Ok(None)), "localhost should not be blocked");
+
+        // Test: remote lists are rejected rather than silently ignored.
+        config.lists.push(ListEntry {
+            path: None,
+            url: Some("https://example.com/list.txt".to_string()),
+            ..ListEntry::from_path("")
+        });
+        let res = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            Some(Path::new("../../tests/test-data/test_configs/")),
+            None,
+        )
+        .await;
+        assert!(res.is_err(), "remote block list was not rejected");
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_depth() {
+        let config = BlocklistConfig {
+            lists: vec![ListEntry {
+                depth: Some(1),
+                ..ListEntry::from_path("default/blocklist_depth.txt")
+            }],
+            ..BlocklistConfig::default()
+        };
+
+        let authority = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            Some(Path::new("../../tests/test-data/test_configs/")),
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+
+        let ao = Box::new(Arc::new(authority)) as Box<dyn AuthorityObject>;
+
+        // Test: only names exactly one label below the wildcard entry are blocked.
+        for (name, blocked) in [
+            ("x.ads.example.com.", true),
+            ("ads.example.com.", false),
+            ("y.x.ads.example.com.", false),
+        ] {
+            let res = ao
+                .lookup(
+                    &LowerName::from_str(name).unwrap(),
+                    RecordType::A,
+                    LookupOptions::default(),
+                )
+                .await;
+            match res {
+                Ok(Some(_l)) if blocked => {}
+                Ok(None) if !blocked => {}
+                Ok(_) => {
+                    panic!("{name} lookup returned the wrong result; expected blocked: {blocked}")
+                }
+                Err(e) => panic!("{name} lookup error: {e}!"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_client_response() {
+        let config = BlocklistConfig {
+            lists: vec![ListEntry::from_path("default/blocklist.txt")],
+            block_response: BlockResponse::Client,
+            ..BlocklistConfig::default()
+        };
+
+        let authority = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            Some(Path::new("../../tests/test-data/test_configs/")),
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+
+        let header = Header::new();
+
+        // Test: blocked names resolve to the address of whichever client asked, falling back to the sinkhole address when the
+        // client's address family does not match the query.
+        for (client, rtype, rdata) in [
+            (
+                "192.168.1.10:5353",
+                RecordType::A,
+                RData::A(A::new(192, 168, 1, 10)),
+            ),
+            (
+                "10.1.1.1:5353",
+                RecordType::A,
+                RData::A(A::new(10, 1, 1, 1)),
+            ),
+            (
+                "[fd00::10]:5353",
+                RecordType::AAAA,
+                RData::AAAA(AAAA::new(0xfd00, 0, 0, 0, 0, 0, 0, 0x10)),
+            ),
+            (
+                "10.1.1.1:5353",
+                RecordType::AAAA,
+                RData::AAAA(AAAA::from(Ipv6Addr::UNSPECIFIED)),
+            ),
+        ] {
+            let query = Query::query(Name::from_str("foo.com.").unwrap(), rtype).into();
+            let request_info = RequestInfo::new(
+                client.parse::<SocketAddr>().unwrap(),
+                Protocol::Udp,
+                &header,
+                &query,
+            );
+
+            let res = authority
+                .search(request_info, LookupOptions::default())
+                .await;
+            match res {
+                Ok(Some(l)) => {
+                    if !l.lookup.record_iter().all(|x| x.data() == &rdata) {
+                        panic!("foo.com {rtype} lookup from {client} data is incorrect.");
+                    }
+                }
+                Ok(None) => panic!("Lookup returned Ok(None); expected Ok(Some)"),
+                Err(e) => panic!("Lookup error: {e}!"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_filter_answers() {
+        let config = BlocklistConfig {
+            lists: vec![ListEntry::from_path("default/blocklist.txt")],
+            strip_answer_networks: vec!["192.0.2.10/32".parse().unwrap()],
+            ..BlocklistConfig::default()
+        };
+
+        let authority = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            Some(Path::new("../../tests/test-data/test_configs/")),
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+
+        let name = Name::from_str("www.test.com.").unwrap();
+        let answers = vec![
+            Record::from_rdata(name.clone(), 300, RData::A(A::new(192, 0, 2, 10))),
+            Record::from_rdata(name.clone(), 300, RData::A(A::new(192, 0, 2, 11))),
+            Record::from_rdata(
+                name.clone(),
+                300,
+                RData::AAAA(AAAA::from(Ipv6Addr::LOCALHOST)),
+            ),
+        ];
+
+        // Test: only the stripped address is removed from an upstream answer set.
+        let filtered = authority
+            .filter_answers(&LowerName::from(&name), RecordType::A, answers)
+            .expect("filter_answers failed");
+        assert_eq!(filtered.len(), 2);
+        assert!(filtered
+            .iter()
+            .all(|x| x.data() != &RData::A(A::new(192, 0, 2, 10))));
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_cname_targets() {
+        let config = BlocklistConfig {
+            lists: vec![ListEntry::from_path("default/blocklist.txt")],
+            block_cname_targets: true,
+            ..BlocklistConfig::default()
+        };
+
+        let authority = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            Some(Path::new("../../tests/test-data/test_configs/")),
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+
+        let name = Name::from_str("www.test.com.").unwrap();
+        let cname = |target: &str| {
+            Record::from_rdata(
+                name.clone(),
+                300,
+                RData::CNAME(CNAME(Name::from_str(target).unwrap())),
+            )
+        };
+
+        // Test: an alias of a blocked name is answered with the block response for the original query.
+        let answers = vec![
+            cname("cdn.test.com."),
+            cname("www.foo.com."),
+            Record::from_rdata(
+                Name::from_str("www.foo.com.").unwrap(),
+                300,
+                RData::A(A::new(192, 0, 2, 10)),
+            ),
+        ];
+        let filtered = authority
+            .filter_answers(&LowerName::from(&name), RecordType::A, answers)
+            .expect("filter_answers failed");
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].name(), &name);
+        assert_eq!(filtered[0].data(), &RData::A(A::new(0, 0, 0, 0)));
+
+        // Test: aliases of names which are not blocked are left alone.
+        let answers = vec![
+            cname("cdn.test.com."),
+            Record::from_rdata(
+                Name::from_str("cdn.test.com.").unwrap(),
+                300,
+                RData::A(A::new(192, 0, 2, 10)),
+            ),
+        ];
+        let filtered = authority
+            .filter_answers(&LowerName::from(&name), RecordType::A, answers.clone())
+            .expect("filter_answers failed");
+        assert_eq!(filtered, answers);
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_builder() {
+        let authority = BlocklistAuthorityBuilder::new(Name::root())
+            .sinkhole_ipv4(Ipv4Addr::LOCALHOST)
+            .add_entries([
+                Name::from_str("ads.example.com.").unwrap(),
+                Name::from_str("*.tracker.example.com.").unwrap(),
+            ])
+            .add_allow([Name::from_str("ok.tracker.example.com.").unwrap()])
+            .build()
+            .expect("Unable to build blocklist authority");
+
+        // Test: names added through the builder are blocked, using the builder's settings, unless they are allowed.
+        for (name, blocked) in [
+            ("ads.example.com.", true),
+            ("x.tracker.example.com.", true),
+            ("ok.tracker.example.com.", false),
+            ("example.com.", false),
+        ] {
+            let res = authority
+                .lookup(
+                    &LowerName::from_str(name).unwrap(),
+                    RecordType::A,
+                    LookupOptions::default(),
+                )
+                .await;
+            match res {
+                Ok(Some(l)) if blocked => {
+                    if !l
+                        .lookup
+                        .record_iter()
+                        .all(|x| x.data() == &RData::A(A::from(Ipv4Addr::LOCALHOST)))
+                    {
+                        panic!("{name} lookup data is incorrect.");
+                    }
+                }
+                Ok(None) if !blocked => {}
+                Ok(_) => panic!("{name} lookup returned an unexpected result"),
+                Err(e) => panic!("{name} lookup error: {e}!"),
+            }
+        }
+
+        // Test: the builder enforces the public sinkhole policy.
+        let authority = BlocklistAuthorityBuilder::new(Name::root())
+            .sinkhole_ipv4(Ipv4Addr::new(192, 0, 2, 1))
+            .public_sinkhole(PublicSinkholePolicy::Refuse)
+            .build();
+        assert!(authority.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_monitor() {
+        let config = BlocklistConfig {
+            lists: vec![
+                ListEntry::from_path("default/blocklist.txt"),
+                ListEntry::from_path("default/blocklist2.txt"),
+            ],
+            monitor_lists: vec![
+                ListEntry::from_path("default/blocklist2.txt"),
+                ListEntry {
+                    format: ListFormat::Hosts,
+                    ..ListEntry::from_path("default/blocklist_hosts.txt")
+                },
+            ],
+            ..BlocklistConfig::default()
+        };
+
+        let authority = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            Some(Path::new("../../tests/test-data/test_configs/")),
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+
+        // Test: monitored names which are not blocked are passed on.
+        let res = authority
+            .lookup(
+                &LowerName::from_str("ads.example.net.").unwrap(),
+                RecordType::A,
+                LookupOptions::default(),
+            )
+            .await;
+        match res {
+            Ok(None) => {}
+            Ok(Some(_l)) => panic!("ads.example.net lookup returned Ok(Some); expected Ok(None)"),
+            Err(e) => panic!("ads.example.net lookup returned {e}; expected Ok(None)"),
+        }
+
+        // Test: monitored names which are also blocked are still blocked.
+        let res = authority
+            .lookup(
+                &LowerName::from_str("malware.com.").unwrap(),
+                RecordType::A,
+                LookupOptions::default(),
+            )
+            .await;
+        match res {
+            Ok(Some(_l)) => {}
+            Ok(None) => panic!("malware.com lookup returned Ok(None); expected Ok(Some)"),
+            Err(e) => panic!("malware.com lookup error: {e}!"),
+        }
+
+        // Test: both hits are counted against the monitor lists.
+        let stats = authority.stats();
+        assert_eq!(stats.monitored, 2);
+        assert_eq!(stats.blocked, 1);
+        assert_eq!(stats.passed, 1);
+        assert_eq!(stats.lists.len(), 4);
+        assert_eq!(stats.lists[1].action, ListAction::Block);
+        assert_eq!(stats.lists[1].blocked, 1);
+        assert_eq!(stats.lists[2].action, ListAction::Monitor);
+        assert_eq!(stats.lists[2].blocked, 1);
+        assert_eq!(stats.lists[3].action, ListAction::Monitor);
+        assert_eq!(stats.lists[3].blocked, 1);
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_origin() {
+        let config = BlocklistConfig {
+            lists: vec![ListEntry::from_path("default/blocklist.txt")],
+            ..BlocklistConfig::default()
+        };
+
+        let authority = BlocklistAuthority::try_from_config(
+            Name::from_str("foo.com.").unwrap(),
+            ZoneType::Hint,
+            &config,
+            Some(Path::new("../../tests/test-data/test_configs/")),
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+
+        // Test: only names within the origin, other than the origin itself, are matched against the block table.
+        for (name, blocked) in [
+            ("foo.com.", false),
+            ("www.foo.com.", true),
+            ("baddomain.com.", false),
+            ("example.com.", false),
+        ] {
+            let res = authority
+                .lookup(
+                    &LowerName::from_str(name).unwrap(),
+                    RecordType::A,
+                    LookupOptions::default(),
+                )
+                .await;
+            match res {
+                Ok(Some(_l)) if blocked => {}
+                Ok(None) if !blocked => {}
+                Ok(_) => panic!("{name} lookup returned an unexpected result"),
+                Err(e) => panic!("{name} lookup error: {e}!"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_reload() {
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+
+        let dir =
+            std::env::temp_dir().join(format!("hickory-blocklist-reload-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let write_gz = |contents: &[u8]| {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(contents).unwrap();
+            std::fs::write(dir.join("b.txt.gz"), encoder.finish().unwrap()).unwrap();
+        };
+        std::fs::write(dir.join("a.txt"), "one.example.\n").unwrap();
+        write_gz(b"two.example.\n");
+
+        let config = BlocklistConfig {
+            lists: vec![
+                ListEntry::from_path("a.txt"),
+                ListEntry::from_path("b.txt.gz"),
+            ],
+            ..BlocklistConfig::default()
+        };
+
+        let authority = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            Some(&dir),
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+
+        let lookup = |name: &'static str| {
+            let authority = &authority;
+            async move {
+                match authority
+                    .lookup(
+                        &LowerName::from_str(name).unwrap(),
+                        RecordType::A,
+                        LookupOptions::default(),
+                    )
+                    .await
+                {
+                    Ok(Some(_l)) => true,
+                    Ok(None) => false,
+                    Err(e) => panic!("{name} lookup error: {e}!"),
+                }
+            }
+        };
+
+        // Test: a reload where one list is corrupt fails, and the lists loaded before it are not used.
+        std::fs::write(dir.join("a.txt"), "one.example.\nthree.example.\n").unwrap();
+        std::fs::write(dir.join("b.txt.gz"), "not gzip").unwrap();
+        let report = authority.reload().await;
+        assert!(!report.is_ok());
+        assert_eq!(report.errors.len(), 1);
+        assert!(report.errors[0].contains("b.txt.gz"), "{report}");
+        assert!(lookup("one.example.").await);
+        assert!(lookup("two.example.").await);
+        assert!(!lookup("three.example.").await);
+
+        // Test: once the list is repaired, the reload replaces the old lists.
+        write_gz(b"four.example.\n");
+        let report = authority.reload().await;
+        assert!(report.is_ok(), "{report}");
+        assert!(lookup("one.example.").await);
+        assert!(!lookup("two.example.").await);
+        assert!(lookup("three.example.").await);
+        assert!(lookup("four.example.").await);
+
+        // Test: the report counts the names added and removed, and the entries in each list.
+        assert_eq!(report.added, 2);
+        assert_eq!(report.removed, 1);
+        assert_eq!(
+            report.lists,
+            vec![
+                ListReload {
+                    source: dir.join("a.txt").display().to_string(),
+                    before: Some(1),
+                    after: Some(2),
+                },
+                ListReload {
+                    source: dir.join("b.txt.gz").display().to_string(),
+                    before: Some(1),
+                    after: Some(1),
+                },
+            ]
+        );
+
+        // Test: every list which fails is reported, not just the first.
+        std::fs::remove_file(dir.join("a.txt")).unwrap();
+        std::fs::write(dir.join("b.txt.gz"), "not gzip").unwrap();
+        let report = authority.reload().await;
+        assert_eq!(report.errors.len(), 2, "{report}");
+        assert!(lookup("four.example.").await);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_ttl_jitter() {
+        let authority = BlocklistAuthorityBuilder::new(Name::root())
+            .ttl(10)
+            .ttl_jitter(30)
+            .add_entries([Name::from_str("foo.com.").unwrap()])
+            .build()
+            .expect("Unable to create blocklist authority");
+
+        let mut ttls = Vec::new();
+        for _ in 0..200 {
+            let res = authority
+                .lookup(
+                    &LowerName::from_str("foo.com.").unwrap(),
+                    RecordType::A,
+                    LookupOptions::default(),
+                )
+                .await;
+            match res {
+                Ok(Some(l)) => ttls.extend(l.lookup.record_iter().map(|record| record.ttl())),
+                Ok(None) => panic!("foo.com lookup returned Ok(None); expected Ok(Some)"),
+                Err(e) => panic!("foo.com lookup error: {e}!"),
+            }
+        }
+
+        // Test: TTLs vary within the jitter range, and are clamped to one second rather than going to or below zero.
+        assert!(ttls.iter().all(|ttl| (1..=40).contains(ttl)), "{ttls:?}");
+        assert!(ttls.iter().any(|ttl| *ttl != 10), "{ttls:?}");
+        assert_eq!(ttls.iter().min(), Some(&1));
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_negation() {
+        let config = BlocklistConfig {
+            lists: vec![
+                ListEntry::from_path("default/blocklist_negation.txt"),
+                ListEntry::from_path("default/blocklist.txt"),
+            ],
+            ..BlocklistConfig::default()
+        };
+
+        let authority = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            Some(Path::new("../../tests/test-data/test_configs/")),
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+
+        // Test: negation lines remove names added earlier in the same list, and names added by lists loaded after it.
+        for (name, blocked) in [
+            ("ads.example.com.", false),
+            ("tracker.example.com.", true),
+            ("www.cdn.example.com.", false),
+            ("baddomain.com.", false),
+            ("foo.com.", true),
+        ] {
+            let res = authority
+                .lookup(
+                    &LowerName::from_str(name).unwrap(),
+                    RecordType::A,
+                    LookupOptions::default(),
+                )
+                .await;
+            match res {
+                Ok(Some(_l)) if blocked => {}
+                Ok(None) if !blocked => {}
+                Ok(_) => panic!("{name} lookup returned an unexpected result"),
+                Err(e) => panic!("{name} lookup error: {e}!"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_refresh() {
+        use super::backoff;
+        use std::time::Duration;
+
+        // Test: the retry delay doubles with each failure, is capped, and is shortened by at most half.
+        for (failures, full) in [(1, 10), (2, 20), (3, 40), (4, 60), (10, 60)] {
+            let delay = backoff(Duration::from_secs(10), Duration::from_secs(60), failures);
+            let full = Duration::from_secs(full);
+            assert!(delay >= full / 2 && delay <= full, "{failures}: {delay:?}");
+        }
+
+        // The first fetch succeeds, the first refresh fails, and the retry succeeds with a new list.
+        let addr = serve_sequence(
+            vec![
+                Some(b"old.example.org\n".to_vec()),
+                None,
+                Some(b"new.example.org\n".to_vec()),
+            ],
+            64,
+        )
+        .await;
+
+        let config = BlocklistConfig {
+            lists: vec![
+                ListEntry::from_path("default/blocklist.txt"),
+                ListEntry {
+                    path: None,
+                    url: Some(format!("http://{addr}/list.txt")),
+                    ..ListEntry::from_path("")
+                },
+            ],
+            refresh_interval: 1,
+            refresh_retry: 1,
+            ..BlocklistConfig::default()
+        };
+
+        let authority = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            Some(Path::new("../../tests/test-data/test_configs/")),
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+        let authority = Arc::new(authority);
+        let task = authority.spawn_refresh().expect("refresh task not started");
+
+        let blocked = |name: &'static str| {
+            let authority = authority.clone();
+            async move {
+                match authority
+                    .lookup(
+                        &LowerName::from_str(name).unwrap(),
+                        RecordType::A,
+                        LookupOptions::default(),
+                    )
+                    .await
+                {
+                    Ok(Some(_l)) => true,
+                    Ok(None) => false,
+                    Err(e) => panic!("{name} lookup error: {e}!"),
+                }
+            }
+        };
+
+        // Test: the failed refresh leaves the last good copy in place, until the retry replaces it.
+        let mut refreshed = false;
+        for _ in 0..100 {
+            if blocked("new.example.org.").await {
+                refreshed = true;
+                break;
+            }
+            assert!(blocked("old.example.org.").await);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert!(refreshed, "remote list was not refreshed");
+        assert!(!blocked("old.example.org.").await);
+        assert!(blocked("foo.com.").await);
+
+        // Test: the task is stopped once the authority is dropped.
+        drop(authority);
+        let result = tokio::time::timeout(Duration::from_secs(5), task)
+            .await
+            .expect("refresh task did not finish");
+        assert!(result.map_or_else(|e| e.is_cancelled(), |()| true));
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_refresh_reuse() {
+        // Each server fails every request after its first, so refreshing one list fails if the other is fetched again.
+        let refreshed = serve_sequence(
+            vec![
+                Some(b"old.example.org\n".to_vec()),
+                Some(b"new.example.org\n".to_vec()),
+                None,
+            ],
+            64,
+        )
+        .await;
+        let other = serve_sequence(vec![Some(b"other.example.org\n".to_vec()), None], 4).await;
+        let refreshed = format!("http://{refreshed}/list.txt");
+
+        let config = BlocklistConfig {
+            lists: [&refreshed, &format!("http://{other}/list.txt")]
+                .into_iter()
+                .map(|url| ListEntry {
+                    path: None,
+                    url: Some(url.clone()),
+                    ..ListEntry::from_path("")
+                })
+                .collect(),
+            ..BlocklistConfig::default()
+        };
+        let authority = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            None,
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+
+        // Test: the other list is added again from its spooled copy, and only the refreshed list changes.
+        authority.refresh_remote(&refreshed).await.unwrap();
+        let policy = authority.policy();
+        let blocked = |name: &str| {
+            policy
+                .blocklist
+                .matches(&LowerName::from_str(name).unwrap())
+        };
+        assert_eq!(blocked("new.example.org."), Some(0));
+        assert_eq!(blocked("old.example.org."), None);
+        assert_eq!(blocked("other.example.org."), Some(1));
+        assert!(policy.lists.iter().all(|list| list.remote));
+
+        // Test: the spooled copies are removed along with the fetcher.
+        let spools = policy
+            .remote
+            .values()
+            .map(|list| list.spool.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(spools.len(), 2);
+        assert!(spools.iter().all(|spool| spool.exists()));
+        drop(policy);
+        drop(authority);
+        assert!(spools.iter().all(|spool| !spool.exists()));
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_query_class() {
+        let authority = BlocklistAuthorityBuilder::new(Name::root())
+            .add_entries([Name::from_str("version.bind.").unwrap()])
+            .build()
+            .expect("Unable to create blocklist authority");
+
+        let header = Header::new();
+
+        // Test: only IN class queries are blocked, here with NODATA; CHAOS queries for a blocked name are passed on.
+        for (class, blocked) in [(DNSClass::IN, true), (DNSClass::CH, false)] {
+            let mut query = Query::query(Name::from_str("version.bind.").unwrap(), RecordType::TXT);
+            query.set_query_class(class);
+            let query = query.into();
+            let request_info = RequestInfo::new(
+                "127.0.0.1:5353".parse::<SocketAddr>().unwrap(),
+                Protocol::Udp,
+                &header,
+                &query,
+            );
+
+            let res = authority
+                .search(request_info, LookupOptions::default())
+                .await;
+            match res {
+                Ok(Some(l))
+                    if blocked
+                        && l.lookup.is_empty()
+                        && l.response_code == ResponseCode::NoError => {}
+                Ok(None) if !blocked => {}
+                Ok(_) => panic!("{class} lookup returned an unexpected result"),
+                Err(e) => panic!("{class} lookup error: {e}!"),
+            }
+        }
+    }
+
+    /// Captures the response sent by the catalog
+    #[derive(Clone, Default)]
+    struct CaptureResponse(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    #[async_trait::async_trait]
+    impl ResponseHandler for CaptureResponse {
+        async fn send_response<'a>(
+            &mut self,
+            response: MessageResponse<
+                '_,
+                'a,
+                impl Iterator<Item = &'a Record> + Send + 'a,
+                impl Iterator<Item = &'a Record> + Send + 'a,
+                impl Iterator<Item = &'a Record> + Send + 'a,
+                impl Iterator<Item = &'a Record> + Send + 'a,
+            >,
+        ) -> std::io::Result<ResponseInfo> {
+            let mut buf = self.0.lock().unwrap();
+            let mut encoder = BinEncoder::new(&mut buf);
+            Ok(response
+                .destructive_emit(&mut encoder)
+                .expect("could not encode"))
+        }
+    }
+
+    /// The next store in the chain, which answers every query with `records`, or fails it with `response_code` if that is
+    /// not NOERROR, as a forwarder would
+    struct Upstream {
+        records: Vec<Record>,
+        response_code: ResponseCode,
+        origin: LowerName,
+    }
+
+    impl Upstream {
+        fn answering(records: Vec<Record>) -> Self {
+            Self {
+                records,
+                response_code: ResponseCode::NoError,
+                origin: LowerName::from(Name::root()),
+            }
+        }
+
+        fn failing(response_code: ResponseCode) -> Self {
+            Self {
+                records: Vec::new(),
+                response_code,
+                origin: LowerName::from(Name::root()),
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Authority for Upstream {
+        type Lookup = BlocklistLookup;
+
+        fn zone_type(&self) -> ZoneType {
+            ZoneType::Forward
+        }
+
+        fn is_axfr_allowed(&self) -> bool {
+            false
+        }
+
+        async fn update(&self, _update: &MessageRequest) -> super::UpdateResult<bool> {
+            Err(ResponseCode::NotImp)
+        }
+
+        fn origin(&self) -> &LowerName {
+            &self.origin
+        }
+
+        async fn lookup(
+            &self,
+            _name: &LowerName,
+            _rtype: RecordType,
+            _lookup_options: LookupOptions,
+        ) -> Result<Option<Self::Lookup>, super::LookupError> {
+            Ok(None)
+        }
+
+        async fn search(
+            &self,
+            request_info: RequestInfo<'_>,
+            _lookup_options: LookupOptions,
+        ) -> Result<Option<Self::Lookup>, super::LookupError> {
+            if self.response_code != ResponseCode::NoError {
+                return Err(super::LookupError::from(self.response_code));
+            }
+
+            let query = Query::query(
+                request_info.query.name().into(),
+                request_info.query.query_type(),
+            );
+            Ok(Some(BlocklistLookup::new(query, self.records.clone())))
+        }
+
+        async fn get_nsec_records(
+            &self,
+            _name: &LowerName,
+            _lookup_options: LookupOptions,
+        ) -> Result<Self::Lookup, super::LookupError> {
+            Err(super::LookupError::from(ResponseCode::NotImp))
+        }
+    }
+
+    /// The response to a query for `name` and `rtype`, handled by a catalog in which `authority` passes the queries it
+    /// does not block on to `upstream`
+    async fn resolve_upstream(
+        authority: Arc<BlocklistAuthority>,
+        upstream: Upstream,
+        name: &str,
+        rtype: RecordType,
+    ) -> Message {
+        use crate::server::RequestHandler;
+
+        let mut catalog = Catalog::new();
+        catalog.upsert(
+            LowerName::from(Name::root()),
+            vec![
+                Box::new(authority) as Box<dyn AuthorityObject>,
+                Box::new(Arc::new(upstream)) as Box<dyn AuthorityObject>,
+            ],
+        );
+
+        let mut message = Message::new();
+        message
+            .set_recursion_desired(true)
+            .add_query(Query::query(Name::from_str(name).unwrap(), rtype));
+        let bytes = message.to_vec().unwrap();
+        let request = Request::new(
+            MessageRequest::from_bytes(&bytes).unwrap(),
+            "127.0.0.1:5353".parse().unwrap(),
+            Protocol::Udp,
+        );
+
+        let capture = CaptureResponse::default();
+        catalog.handle_request(&request, capture.clone()).await;
+        let response = Message::from_vec(&capture.0.lock().unwrap()).unwrap();
+        response
+    }
+
+    /// The SOA carried by the negative response `authority` gives to a query for `name` and `rtype`, if any
+    async fn negative_soa(
+        authority: &BlocklistAuthority,
+        name: &str,
+        rtype: RecordType,
+    ) -> Option<BlocklistLookup> {
+        let header = Header::new();
+        let query = Query::query(Name::from_str(name).unwrap(), rtype).into();
+        let request_info = RequestInfo::new(
+            "127.0.0.1:5353".parse::<SocketAddr>().unwrap(),
+            Protocol::Udp,
+            &header,
+            &query,
+        );
+        let lookup = authority
+            .search(request_info, LookupOptions::default())
+            .await
+            .ok()??;
+        lookup.soa.map(|soa| *soa)
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_strip_answer_networks_catalog() {
+        let config = BlocklistConfig {
+            strip_answer_networks: vec!["192.0.2.10/32".parse().unwrap()],
+            ..BlocklistConfig::default()
+        };
+        let authority = BlocklistAuthorityBuilder::with_config(Name::root(), config)
+            .add_entries([Name::from_str("foo.com.").unwrap()])
+            .build()
+            .expect("Unable to create blocklist authority");
+        let authority = Arc::new(authority);
+
+        let name = Name::from_str("www.test.com.").unwrap();
+        let upstream = || {
+            Upstream::answering(vec![
+                Record::from_rdata(name.clone(), 300, RData::A(A::new(192, 0, 2, 10))),
+                Record::from_rdata(name.clone(), 300, RData::A(A::new(192, 0, 2, 11))),
+            ])
+        };
+
+        // Test: the stripped address is removed from the answers the upstream resolved for a query passed on.
+        let response = resolve_upstream(
+            authority.clone(),
+            upstream(),
+            "www.test.com.",
+            RecordType::A,
+        )
+        .await;
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert_eq!(response.answers().len(), 1);
+        assert_eq!(
+            response.answers()[0].data(),
+            &RData::A(A::new(192, 0, 2, 11))
+        );
+
+        // Test: blocked names are still answered by the blocklist, and never reach the upstream.
+        let response = resolve_upstream(authority, upstream(), "foo.com.", RecordType::A).await;
+        assert_eq!(response.answers().len(), 1);
+        assert_eq!(response.answers()[0].data(), &RData::A(A::new(0, 0, 0, 0)));
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_cname_targets_catalog() {
+        let config = BlocklistConfig {
+            block_cname_targets: true,
+            ..BlocklistConfig::default()
+        };
+        let authority = BlocklistAuthorityBuilder::with_config(Name::root(), config)
+            .add_entries([Name::from_str("tracker.example.net.").unwrap()])
+            .build()
+            .expect("Unable to create blocklist authority");
+        let authority = Arc::new(authority);
+
+        let name = Name::from_str("metrics.test.com.").unwrap();
+        let tracker = Name::from_str("tracker.example.net.").unwrap();
+        let upstream = Upstream::answering(vec![
+            Record::from_rdata(name.clone(), 300, RData::CNAME(CNAME(tracker.clone()))),
+            Record::from_rdata(tracker, 300, RData::A(A::new(203, 0, 113, 5))),
+        ]);
+
+        // Test: an answer which cloaks a blocked name behind a CNAME is replaced with the block response for the query.
+        let response =
+            resolve_upstream(authority, upstream, "metrics.test.com.", RecordType::A).await;
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert_eq!(response.answers().len(), 1);
+        assert_eq!(response.answers()[0].name(), &name);
+        assert_eq!(response.answers()[0].data(), &RData::A(A::new(0, 0, 0, 0)));
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_max_cname_targets_catalog() {
+        async fn resolve(max_cname_targets: usize) -> Message {
+            let config = BlocklistConfig {
+                block_cname_targets: true,
+                max_cname_targets,
+                ..BlocklistConfig::default()
+            };
+            let authority = BlocklistAuthorityBuilder::with_config(Name::root(), config)
+                .add_entries([Name::from_str("tracker.example.net.").unwrap()])
+                .build()
+                .expect("Unable to create blocklist authority");
+
+            let name = Name::from_str("metrics.test.com.").unwrap();
+            let hop = Name::from_str("edge.cdn.example.org.").unwrap();
+            let tracker = Name::from_str("tracker.example.net.").unwrap();
+            let upstream = Upstream::answering(vec![
+                Record::from_rdata(name, 300, RData::CNAME(CNAME(hop.clone()))),
+                Record::from_rdata(hop, 300, RData::CNAME(CNAME(tracker.clone()))),
+                Record::from_rdata(tracker, 300, RData::A(A::new(203, 0, 113, 5))),
+            ]);
+            resolve_upstream(
+                Arc::new(authority),
+                upstream,
+                "metrics.test.com.",
+                RecordType::A,
+            )
+            .await
+        }
+
+        // Test: a blocked name at the end of a CNAME chain is found when the chain is within the limit...
+        let response = resolve(2).await;
+        assert_eq!(response.answers().len(), 1);
+        assert_eq!(response.answers()[0].data(), &RData::A(A::new(0, 0, 0, 0)));
+
+        // Test: ...but not when it is past it, leaving the answer unchanged.
+        let response = resolve(1).await;
+        assert_eq!(response.answers().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_ip_blocklists_catalog() {
+        let config = BlocklistConfig {
+            ip_blocklists: vec!["default/blocklist_ips.txt".to_string()],
+            ..BlocklistConfig::default()
+        };
+        let authority = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            Some(Path::new("../../tests/test-data/test_configs/")),
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+        let authority = Arc::new(authority);
+
+        let name = Name::from_str("www.test.com.").unwrap();
+        let upstream = |addr: A| {
+            Upstream::answering(vec![Record::from_rdata(name.clone(), 300, RData::A(addr))])
+        };
+
+        // Test: an answer resolving to a blocked address is replaced with the block response.
+        let response = resolve_upstream(
+            authority.clone(),
+            upstream(A::new(192, 0, 2, 10)),
+            "www.test.com.",
+            RecordType::A,
+        )
+        .await;
+        assert_eq!(response.answers().len(), 1);
+        assert_eq!(response.answers()[0].data(), &RData::A(A::new(0, 0, 0, 0)));
+
+        // Test: an answer resolving to any other address is passed through.
+        let response = resolve_upstream(
+            authority,
+            upstream(A::new(198, 51, 100, 1)),
+            "www.test.com.",
+            RecordType::A,
+        )
+        .await;
+        assert_eq!(response.answers().len(), 1);
+        assert_eq!(
+            response.answers()[0].data(),
+            &RData::A(A::new(198, 51, 100, 1))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_upstream_sink_addresses_catalog() {
+        let config = BlocklistConfig {
+            upstream_sink_addresses: vec!["0.0.0.0/32".parse().unwrap()],
+            upstream_sink_response: Some(BlockResponse::NxDomain),
+            ..BlocklistConfig::default()
+        };
+        let authority = BlocklistAuthorityBuilder::with_config(Name::root(), config)
+            .build()
+            .expect("Unable to create blocklist authority");
+
+        let upstream = Upstream::answering(vec![Record::from_rdata(
+            Name::from_str("www.test.com.").unwrap(),
+            60,
+            RData::A(A::new(0, 0, 0, 0)),
+        )]);
+
+        // Test: an answer the upstream resolver sank itself is converted to the upstream sink response.
+        let response = resolve_upstream(
+            Arc::new(authority),
+            upstream,
+            "www.test.com.",
+            RecordType::A,
+        )
+        .await;
+        assert_eq!(response.response_code(), ResponseCode::NXDomain);
+        assert!(response.answers().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_ptr_targets_catalog() {
+        use crate::proto::rr::rdata::PTR;
+
+        let config = BlocklistConfig {
+            block_ptr_targets: true,
+            ..BlocklistConfig::default()
+        };
+        let authority = BlocklistAuthorityBuilder::with_config(Name::root(), config)
+            .add_entries([Name::from_str("tracker.example.net.").unwrap()])
+            .build()
+            .expect("Unable to create blocklist authority");
+        let authority = Arc::new(authority);
+
+        let reverse = "10.2.0.192.in-addr.arpa.";
+        let upstream = |target: &str| {
+            Upstream::answering(vec![Record::from_rdata(
+                Name::from_str(reverse).unwrap(),
+                300,
+                RData::PTR(PTR(Name::from_str(target).unwrap())),
+            )])
+        };
+
+        // Test: a reverse lookup whose answer points at a blocked name is answered with NODATA.
+        let response = resolve_upstream(
+            authority.clone(),
+            upstream("tracker.example.net."),
+            reverse,
+            RecordType::PTR,
+        )
+        .await;
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert!(response.answers().is_empty());
+
+        // Test: one pointing at any other name is passed through.
+        let response = resolve_upstream(
+            authority,
+            upstream("www.test.com."),
+            reverse,
+            RecordType::PTR,
+        )
+        .await;
+        assert_eq!(response.answers().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_learn_catalog() {
+        let config = BlocklistConfig {
+            learn: true,
+            ..BlocklistConfig::default()
+        };
+        let authority = BlocklistAuthorityBuilder::with_config(Name::root(), config)
+            .add_entries([Name::from_str("foo.com.").unwrap()])
+            .build()
+            .expect("Unable to create blocklist authority");
+        let authority = Arc::new(authority);
+
+        // Test: a query passed on which the upstream fails is collected as a candidate entry.
+        let response = resolve_upstream(
+            authority.clone(),
+            Upstream::failing(ResponseCode::NXDomain),
+            "typo.example.",
+            RecordType::A,
+        )
+        .await;
+        assert_eq!(response.response_code(), ResponseCode::NXDomain);
+
+        // Test: queries which the upstream answers, and queries which are blocked, are not.
+        let answered = Upstream::answering(vec![Record::from_rdata(
+            Name::from_str("www.test.com.").unwrap(),
+            300,
+            RData::A(A::new(192, 0, 2, 1)),
+        )]);
+        resolve_upstream(authority.clone(), answered, "www.test.com.", RecordType::A).await;
+        resolve_upstream(
+            authority.clone(),
+            Upstream::failing(ResponseCode::NXDomain),
+            "foo.com.",
+            RecordType::A,
+        )
+        .await;
+
+        let learned = authority.learned();
+        assert_eq!(learned.len(), 1);
+        assert_eq!(learned[0].name, Name::from_str("typo.example.").unwrap());
+        assert_eq!(learned[0].nxdomain, 1);
+    }
+    #[tokio::test]
+    async fn test_blocklist_authoritative() {
+        let authority = BlocklistAuthorityBuilder::new(Name::root())
+            .add_entries([Name::from_str("foo.com.").unwrap()])
+            .build()
+            .expect("Unable to create blocklist authority");
+
+        let mut catalog = Catalog::new();
+        catalog.upsert(
+            LowerName::from(Name::root()),
+            vec![Box::new(Arc::new(authority)) as Box<dyn AuthorityObject>],
+        );
+
+        let mut message = Message::new();
+        message.set_recursion_desired(true).add_query(Query::query(
+            Name::from_str("foo.com.").unwrap(),
+            RecordType::A,
+        ));
+        let bytes = message.to_vec().unwrap();
+        let request = Request::new(
+            MessageRequest::from_bytes(&bytes).unwrap(),
+            "127.0.0.1:5353".parse().unwrap(),
+            Protocol::Udp,
+        );
+
+        let capture = CaptureResponse::default();
+        catalog.lookup(&request, None, capture.clone()).await;
+        let response = Message::from_vec(&capture.0.lock().unwrap()).unwrap();
+
+        // Test: the synthesized answer to a blocked query is marked authoritative, even though the zone is a hint zone.
+        assert_eq!(response.answers().len(), 1);
+        assert!(response.authoritative());
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_wildcard_token() {
+        let load = |wildcard_token: &str| {
+            let config = BlocklistConfig {
+                lists: vec![ListEntry::from_path("default/blocklist_wildcard_token.txt")],
+                wildcard_token: wildcard_token.to_string(),
+                ..BlocklistConfig::default()
+            };
+
+            async move {
+                BlocklistAuthority::try_from_config(
+                    Name::from_str(".").unwrap(),
+                    ZoneType::Hint,
+                    &config,
+                    Some(Path::new("../../tests/test-data/test_configs/")),
+                    None,
+                )
+                .await
+                .expect("Unable to create blocklist authority")
+            }
+        };
+        async fn blocked(authority: &BlocklistAuthority, name: &str) -> bool {
+            match authority
+                .lookup(
+                    &LowerName::from_str(name).unwrap(),
+                    RecordType::A,
+                    LookupOptions::default(),
+                )
+                .await
+            {
+                Ok(Some(_l)) => true,
+                Ok(None) => false,
+                Err(e) => panic!("{name} lookup error: {e}!"),
+            }
+        }
+
+        let authority = load("*").await;
+
+        // Test: a leading dot blocks the name itself and every name below it.
+        assert!(blocked(&authority, "dot.example.com.").await);
+        assert!(blocked(&authority, "www.dot.example.com.").await);
+        assert!(blocked(&authority, "a.b.dot.example.com.").await);
+        assert!(!blocked(&authority, "notdot.example.com.").await);
+        assert!(!blocked(&authority, "example.com.").await);
+
+        // Test: a negation with a leading dot removes both the name and its wildcard.
+        assert!(!blocked(&authority, "gone.example.com.").await);
+        assert!(!blocked(&authority, "www.gone.example.com.").await);
+
+        // Test: entries using another token are not wildcards by default.
+        assert!(!blocked(&authority, "www.pct.example.com.").await);
+
+        // Test: a configured token matches the names below the entry, but not the entry itself.
+        let authority = load("%").await;
+        assert!(blocked(&authority, "www.pct.example.com.").await);
+        assert!(!blocked(&authority, "pct.example.com.").await);
+        assert!(blocked(&authority, "www.dot.example.com.").await);
+
+        // Test: asterisks are still wildcards when another token is configured.
+        assert!(blocked(&authority, "www.star.example.com.").await);
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_shutdown() {
+        use std::time::Duration;
+
+        let addr = serve_chunked(b"remote.example.org\n".to_vec(), 64).await;
+        let config = BlocklistConfig {
+            lists: vec![ListEntry {
+                path: None,
+                url: Some(format!("http://{addr}/list.txt")),
+                ..ListEntry::from_path("")
+            }],
+            refresh_interval: 3600,
+            ..BlocklistConfig::default()
+        };
+
+        let mut tasks = Vec::new();
+        for _ in 0..32 {
+            let authority = BlocklistAuthority::try_from_config(
+                Name::from_str(".").unwrap(),
+                ZoneType::Hint,
+                &config,
+                None,
+                None,
+            )
+            .await
+            .expect("Unable to create blocklist authority");
+            let authority = Arc::new(authority);
+            tasks.push(authority.spawn_refresh().expect("refresh task not started"));
+        }
+
+        // Test: dropping each authority stops its refresh task, rather than leaving it asleep until the next refresh.
+        for task in tasks {
+            let result = tokio::time::timeout(Duration::from_secs(5), task)
+                .await
+                .expect("refresh task leaked");
+            assert!(result.expect_err("refresh task finished").is_cancelled());
+        }
+
+        // Test: shutting an authority down stops its tasks while it is still in use.
+        let authority = Arc::new(
+            BlocklistAuthority::try_from_config(
+                Name::from_str(".").unwrap(),
+                ZoneType::Hint,
+                &config,
+                None,
+                None,
+            )
+            .await
+            .expect("Unable to create blocklist authority"),
+        );
+        let task = authority.spawn_refresh().expect("refresh task not started");
+        authority.shutdown();
+        let result = tokio::time::timeout(Duration::from_secs(5), task)
+            .await
+            .expect("refresh task leaked");
+        assert!(result.expect_err("refresh task finished").is_cancelled());
+        authority.shutdown();
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_wildcard_parent() {
+        async fn blocked(authority: &BlocklistAuthority, name: &str) -> bool {
+            match authority
+                .lookup(
+                    &LowerName::from_str(name).unwrap(),
+                    RecordType::A,
+                    LookupOptions::default(),
+                )
+                .await
+            {
+                Ok(Some(_l)) => true,
+                Ok(None) => false,
+                Err(e) => panic!("{name} lookup error: {e}!"),
+            }
+        }
+
+        let build = |wildcard_blocks_parent| {
+            BlocklistAuthorityBuilder::new(Name::root())
+                .wildcard_blocks_parent(wildcard_blocks_parent)
+                .add_entries([
+                    Name::from_str("*.ads.example.com.").unwrap(),
+                    Name::from_str("*.com.").unwrap(),
+                ])
+                .build()
+                .expect("Unable to create blocklist authority")
+        };
+
+        // Test: by default, a wildcard blocks the names below its parent, but not the parent itself.
+        let authority = build(false);
+        assert!(blocked(&authority, "www.ads.example.com.").await);
+        assert!(blocked(&authority, "*.ads.example.com.").await);
+        assert!(!blocked(&authority, "ads.example.com.").await);
+
+        // Test: with the option on, the parent is blocked as well.
+        let authority = build(true);
+        assert!(blocked(&authority, "www.ads.example.com.").await);
+        assert!(blocked(&authority, "*.ads.example.com.").await);
+        assert!(blocked(&authority, "ads.example.com.").await);
+        assert!(!blocked(&authority, "example.com.").await);
+
+        // Test: the parent of a wildcard shallower than min_wildcard_depth is not blocked.
+        assert!(!blocked(&authority, "com.").await);
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_share_lists() {
+        let dir =
+            std::env::temp_dir().join(format!("hickory-blocklist-share-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.txt"), "one.example.com.\n").unwrap();
+
+        let config = BlocklistConfig {
+            lists: vec![ListEntry::from_path("a.txt")],
+            share_lists: true,
+            ..BlocklistConfig::default()
+        };
+        let load = |origin: &str, config: BlocklistConfig| {
+            let origin = Name::from_str(origin).unwrap();
+            let dir = dir.clone();
+            async move {
+                BlocklistAuthority::try_from_config(
+                    origin,
+                    ZoneType::Hint,
+                    &config,
+                    Some(&dir),
+                    None,
+                )
+                .await
+                .expect("Unable to create blocklist authority")
+            }
+        };
+        async fn blocked(authority: &BlocklistAuthority, name: &str) -> bool {
+            match authority
+                .lookup(
+                    &LowerName::from_str(name).unwrap(),
+                    RecordType::A,
+                    LookupOptions::default(),
+                )
+                .await
+            {
+                Ok(Some(_l)) => true,
+                Ok(None) => false,
+                Err(e) => panic!("{name} lookup error: {e}!"),
+            }
+        }
+
+        let root = load(".", config.clone()).await;
+        let mut com = load("com.", config.clone()).await;
+        let unshared = load(
+            ".",
+            BlocklistConfig {
+                share_lists: false,
+                ..config.clone()
+            },
+        )
+        .await;
+
+        // Test: authorities with the same lists share one copy of them; others load their own.
+        assert!(Arc::ptr_eq(&root.shared, &com.shared));
+        assert!(!Arc::ptr_eq(&root.shared, &unshared.shared));
+        assert!(blocked(&com, "one.example.com.").await);
+
+        // Test: a reload by one authority updates every authority sharing the lists.
+        std::fs::write(dir.join("a.txt"), "two.example.com.\n").unwrap();
+        assert!(root.reload().await.is_ok());
+        assert!(!blocked(&com, "one.example.com.").await);
+        assert!(blocked(&com, "two.example.com.").await);
+        assert!(blocked(&unshared, "one.example.com.").await);
+
+        // Test: shared lists can not be modified through one of the authorities.
+        assert!(!com.remove(&LowerName::from_str("two.example.com.").unwrap()));
+        assert!(blocked(&root, "two.example.com.").await);
+
+        // Test: the lists stay available for sharing for as long as any authority is using them.
+        drop(root);
+        let root = load(".", config.clone()).await;
+        assert!(Arc::ptr_eq(&root.shared, &com.shared));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_upstream_sink_addresses() {
+        let load = |upstream_sink_response| async move {
+            let config = BlocklistConfig {
+                lists: vec![ListEntry::from_path("default/blocklist.txt")],
+                upstream_sink_addresses: vec![
+                    "0.0.0.0/32".parse().unwrap(),
+                    "127.0.0.1/32".parse().unwrap(),
+                    "::/128".parse().unwrap(),
+                ],
+                upstream_sink_response,
+                sinkhole_ipv4: Ipv4Addr::new(192, 168, 1, 1),
+                ..BlocklistConfig::default()
+            };
+
+            BlocklistAuthority::try_from_config(
+                Name::from_str(".").unwrap(),
+                ZoneType::Hint,
+                &config,
+                Some(Path::new("../../tests/test-data/test_configs/")),
+                None,
+            )
+            .await
+            .expect("Unable to create blocklist authority")
+        };
+
+        let name = Name::from_str("www.test.com.").unwrap();
+        let lower = LowerName::from(&name);
+        let sunk = vec![Record::from_rdata(
+            name.clone(),
+            60,
+            RData::A(A::new(127, 0, 0, 1)),
+        )];
+
+        // Test: an upstream sink address is replaced with this blocklist's block response.
+        let authority = load(None).await;
+        let filtered = authority
+            .filter_answers(&lower, RecordType::A, sunk.clone())
+            .expect("filter_answers failed");
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].data(), &RData::A(A::new(192, 168, 1, 1)));
+        assert_eq!(authority.stats().blocked, 1);
+
+        // Test: answers without a sink address are returned unchanged.
+        let answers = vec![Record::from_rdata(
+            name.clone(),
+            60,
+            RData::A(A::new(192, 0, 2, 1)),
+        )];
+        let filtered = authority
+            .filter_answers(&lower, RecordType::A, answers.clone())
+            .expect("filter_answers failed");
+        assert_eq!(filtered, answers);
+
+        // Test: the conversion can use its own response.
+        let authority = load(Some(BlockResponse::NxDomain)).await;
+        match authority.filter_answers(&lower, RecordType::A, sunk) {
+            Err(e) if e.is_nx_domain() => {}
+            Ok(answers) => panic!("sunk answer returned {answers:?}; expected NXDOMAIN"),
+            Err(e) => panic!("sunk answer returned {e}; expected NXDOMAIN"),
+        }
+        let unspecified = vec![Record::from_rdata(
+            name.clone(),
+            60,
+            RData::AAAA(AAAA::from(Ipv6Addr::UNSPECIFIED)),
+        )];
+        assert!(authority
+            .filter_answers(&lower, RecordType::AAAA, unspecified)
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_extended_format() {
+        use crate::store::blocklist::policy::{parse_entry_response, EntryResponse};
+
+        // Test: the action, sinkhole and TTL columns are parsed, and anything else is rejected.
+        let parse = |columns: &str| parse_entry_response(columns.split_whitespace());
+        assert_eq!(parse(""), Ok(None));
+        assert_eq!(
+            parse("NXDomain"),
+            Ok(Some(EntryResponse {
+                response: Some(BlockResponse::NxDomain),
+                sinkhole: None,
+                ttl: None,
+            }))
+        );
+        assert_eq!(
+            parse("sinkhole fd00::1"),
+            Ok(Some(EntryResponse {
+                response: Some(BlockResponse::Sinkhole),
+                sinkhole: Some(IpAddr::V6(Ipv6Addr::from_str("fd00::1").unwrap())),
+                ttl: None,
+            }))
+        );
+        assert_eq!(
+            parse("sinkhole 10.0.0.1 TTL=30"),
+            Ok(Some(EntryResponse {
+                response: Some(BlockResponse::Sinkhole),
+                sinkhole: Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))),
+                ttl: Some(30),
+            }))
+        );
+        assert_eq!(
+            parse("ttl=10"),
+            Ok(Some(EntryResponse {
+                response: None,
+                sinkhole: None,
+                ttl: Some(10),
+            }))
+        );
+        assert!(parse("explode").is_err());
+        assert!(parse("sinkhole not-an-address").is_err());
+        assert!(parse("nodata 10.0.0.1").is_err());
+        assert!(parse("sinkhole 10.0.0.1 extra").is_err());
+        assert!(parse("ttl=soon").is_err());
+        assert!(parse("ttl=-1").is_err());
+        assert!(parse("ttl=10 nxdomain").is_err());
+
+        let config = BlocklistConfig {
+            lists: vec![ListEntry {
+                format: ListFormat::Extended,
+                ..ListEntry::from_path("default/blocklist_extended.txt")
+            }],
+            public_sinkhole: PublicSinkholePolicy::Refuse,
+            ..BlocklistConfig::default()
+        };
+
+        let authority = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            Some(Path::new("../../tests/test-data/test_configs/")),
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+
+        async fn lookup(
+            authority: &BlocklistAuthority,
+            name: &str,
+            rtype: RecordType,
+        ) -> Result<Option<super::BlocklistLookup>, super::LookupError> {
+            authority
+                .lookup(
+                    &LowerName::from_str(name).unwrap(),
+                    rtype,
+                    LookupOptions::default(),
+                )
+                .await
+        }
+        let address = |res: Result<Option<super::BlocklistLookup>, super::LookupError>| match res {
+            Ok(Some(l)) => l.lookup.record_iter().next().unwrap().data().clone(),
+            Ok(None) => panic!("lookup returned Ok(None); expected a sinkhole address"),
+            Err(e) => panic!("lookup returned {e}; expected a sinkhole address"),
+        };
+
+        // Test: lines without an action use the list's settings.
+        assert_eq!(
+            address(lookup(&authority, "plain.example.com.", RecordType::A).await),
+            RData::A(A::new(0, 0, 0, 0))
+        );
+
+        // Test: each line's action overrides the list's settings, including for wildcards.
+        for name in ["nx.example.com.", "www.wild.example.com."] {
+            match lookup(&authority, name, RecordType::A).await {
+                Err(e) if e.is_nx_domain() => {}
+                Ok(_) => panic!("{name} lookup returned Ok; expected NXDOMAIN"),
+                Err(e) => panic!("{name} lookup returned {e}; expected NXDOMAIN"),
+            }
+        }
+        match lookup(&authority, "nodata.example.com.", RecordType::A).await {
+            Err(e) if e.is_name_exists() => {}
+            Ok(_) => panic!("nodata.example.com lookup returned Ok; expected NODATA"),
+            Err(e) => panic!("nodata.example.com lookup returned {e}; expected NODATA"),
+        }
+
+        // Test: a sinkhole address replaces only the list's sinkhole address of the same family.
+        assert_eq!(
+            address(lookup(&authority, "cdn.example.com.", RecordType::A).await),
+            RData::A(A::new(10, 0, 0, 1))
+        );
+        assert_eq!(
+            address(lookup(&authority, "cdn.example.com.", RecordType::AAAA).await),
+            RData::AAAA(AAAA::from(Ipv6Addr::UNSPECIFIED))
+        );
+        assert_eq!(
+            address(lookup(&authority, "cdn6.example.com.", RecordType::AAAA).await),
+            RData::AAAA(AAAA::from_str("fd00::1").unwrap())
+        );
+
+        // Test: a TTL column overrides the TTL of the response, keeping the list's response if there is no action.
+        let lookup_ttl = match lookup(&authority, "short.example.com.", RecordType::A).await {
+            Ok(Some(lookup)) => lookup.lookup.record_iter().next().unwrap().ttl(),
+            _ => panic!("short.example.com. lookup was not answered with a sinkhole address"),
+        };
+        assert_eq!(lookup_ttl, 10);
+        assert_eq!(
+            address(lookup(&authority, "short.example.com.", RecordType::A).await),
+            RData::A(A::new(0, 0, 0, 0))
+        );
+        match lookup(&authority, "shortnx.example.com.", RecordType::A).await {
+            Err(e) if e.is_nx_domain() => {}
+            _ => panic!("shortnx.example.com. lookup was not answered with NXDOMAIN"),
+        }
+        let soa = negative_soa(&authority, "shortnx.example.com.", RecordType::A)
+            .await
+            .expect("no SOA for shortnx.example.com.");
+        assert_eq!(soa.lookup.record_iter().next().unwrap().ttl(), 5);
+
+        // Test: invalid lines, and lines with a refused public sinkhole address, are skipped.
+        for name in [
+            "bad.example.com.",
+            "extra.example.com.",
+            "public.example.com.",
+            "badttl.example.com.",
+        ] {
+            assert!(
+                matches!(lookup(&authority, name, RecordType::A).await, Ok(None)),
+                "{name}"
+            );
+        }
+    }
+    #[tokio::test]
+    async fn test_blocklist_ptr_targets() {
+        use crate::proto::rr::rdata::PTR;
+
+        let load = |block_ptr_targets, block_response| async move {
+            let config = BlocklistConfig {
+                lists: vec![ListEntry::from_path("default/blocklist.txt")],
+                block_ptr_targets,
+                block_response,
+                ..BlocklistConfig::default()
+            };
+
+            BlocklistAuthority::try_from_config(
+                Name::from_str(".").unwrap(),
+                ZoneType::Hint,
+                &config,
+                Some(Path::new("../../tests/test-data/test_configs/")),
+                None,
+            )
+            .await
+            .expect("Unable to create blocklist authority")
+        };
+
+        let name = Name::from_str("10.2.0.192.in-addr.arpa.").unwrap();
+        let lower = LowerName::from(&name);
+        let ptr = |target: &str| {
+            vec![Record::from_rdata(
+                name.clone(),
+                300,
+                RData::PTR(PTR(Name::from_str(target).unwrap())),
+            )]
+        };
+
+        // Test: a reverse lookup pointing at a blocked name is answered with NODATA, rather than a sinkhole address.
+        let authority = load(true, BlockResponse::Sinkhole).await;
+        match authority.filter_answers(&lower, RecordType::PTR, ptr("www.foo.com.")) {
+            Err(e) if e.is_name_exists() => {}
+            Ok(answers) => panic!("filter_answers returned {answers:?}; expected NODATA"),
+            Err(e) => panic!("filter_answers returned {e}; expected NODATA"),
+        }
+        assert_eq!(authority.stats().blocked, 1);
+
+        // Test: reverse lookups pointing at names which are not blocked are left alone.
+        let answers = ptr("www.test.com.");
+        let filtered = authority
+            .filter_answers(&lower, RecordType::PTR, answers.clone())
+            .expect("filter_answers failed");
+        assert_eq!(filtered, answers);
+
+        // Test: lists answering with NXDOMAIN answer reverse lookups with NXDOMAIN.
+        let authority = load(true, BlockResponse::NxDomain).await;
+        match authority.filter_answers(&lower, RecordType::PTR, ptr("baddomain.com.")) {
+            Err(e) if e.is_nx_domain() => {}
+            Ok(answers) => panic!("filter_answers returned {answers:?}; expected NXDOMAIN"),
+            Err(e) => panic!("filter_answers returned {e}; expected NXDOMAIN"),
+        }
+
+        // Test: PTR answers are not checked unless enabled.
+        let authority = load(false, BlockResponse::Sinkhole).await;
+        let answers = ptr("www.foo.com.");
+        let filtered = authority
+            .filter_answers(&lower, RecordType::PTR, answers.clone())
+            .expect("filter_answers failed");
+        assert_eq!(filtered, answers);
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_startup_policy() {
+        use super::StartupPolicy;
+        use std::time::Duration;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        async fn lookup(
+            authority: &BlocklistAuthority,
+            name: &str,
+        ) -> Result<Option<super::BlocklistLookup>, super::LookupError> {
+            authority
+                .lookup(
+                    &LowerName::from_str(name).unwrap(),
+                    RecordType::A,
+                    LookupOptions::default(),
+                )
+                .await
+        }
+
+        for startup_policy in [StartupPolicy::Open, StartupPolicy::Closed] {
+            // The list is not served until the test is ready, so the load stays in progress until then.
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let config = BlocklistConfig {
+                lists: vec![ListEntry {
+                    path: None,
+                    url: Some(format!("http://{addr}/list.txt")),
+                    ..ListEntry::from_path("")
+                }],
+                startup_policy,
+                ..BlocklistConfig::default()
+            };
+
+            let authority = BlocklistAuthority::try_from_config(
+                Name::from_str(".").unwrap(),
+                ZoneType::Hint,
+                &config,
+                None,
+                None,
+            )
+            .await
+            .expect("Unable to create blocklist authority");
+
+            // Test: queries before the lists have loaded are answered according to the startup policy.
+            let early = lookup(&authority, "baddomain.com.").await;
+            match startup_policy {
+                StartupPolicy::Open => assert!(matches!(early, Ok(None)), "{startup_policy:?}"),
+                _ => assert!(
+                    matches!(&early, Err(e) if e.is_refused()),
+                    "{startup_policy:?}"
+                ),
+            }
+
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                let n = stream.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 14\r\nConnection: close\r\n\r\nbaddomain.com\n")
+                .await
+                .unwrap();
+
+            // Test: once the lists have loaded, queries are matched against them.
+            let loaded = async {
+                while !matches!(lookup(&authority, "baddomain.com.").await, Ok(Some(_))) {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            };
+            tokio::time::timeout(Duration::from_secs(5), loaded)
+                .await
+                .unwrap_or_else(|_| panic!("{startup_policy:?} blocklist never finished loading"));
+            assert!(matches!(
+                lookup(&authority, "www.test.com.").await,
+                Ok(None)
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_allow_regex() {
+        async fn blocked(authority: &BlocklistAuthority, name: &str) -> bool {
+            matches!(
+                authority
+                    .lookup(
+                        &LowerName::from_str(name).unwrap(),
+                        RecordType::A,
+                        LookupOptions::default(),
+                    )
+                    .await,
+                Ok(Some(_))
+            )
+        }
+
+        let config = BlocklistConfig {
+            lists: vec![ListEntry::from_path("default/blocklist.txt")],
+            allow_regex_lists: vec!["default/allow_regex.txt".to_string()],
+            block_cname_targets: true,
+            ..BlocklistConfig::default()
+        };
+        let authority = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            Some(Path::new("../../tests/test-data/test_configs/")),
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+
+        // Test: a pattern match overrides exact and wildcard blocks, regardless of case; other names are still blocked.
+        assert!(!blocked(&authority, "example.com.").await);
+        assert!(!blocked(&authority, "safe1.foo.com.").await);
+        assert!(!blocked(&authority, "SAFE22.foo.com.").await);
+        assert!(blocked(&authority, "www.foo.com.").await);
+        assert!(blocked(&authority, "safe.foo.com.").await);
+        assert!(blocked(&authority, "baddomain.com.").await);
+
+        // Test: aliases of names matching a pattern are not blocked either.
+        let name = Name::from_str("www.test.com.").unwrap();
+        let answers = vec![Record::from_rdata(
+            name.clone(),
+            300,
+            RData::CNAME(CNAME(Name::from_str("safe7.foo.com.").unwrap())),
+        )];
+        let filtered = authority
+            .filter_answers(&LowerName::from(&name), RecordType::A, answers.clone())
+            .expect("filter_answers failed");
+        assert_eq!(filtered, answers);
+
+        // Test: lists with more valid patterns than the limit fail the load; skipped patterns do not count.
+        for (max_allow_patterns, loads) in [(3, true), (2, false)] {
+            let config = BlocklistConfig {
+                max_allow_patterns,
+                ..config.clone()
+            };
+            let authority = BlocklistAuthority::try_from_config(
+                Name::from_str(".").unwrap(),
+                ZoneType::Hint,
+                &config,
+                Some(Path::new("../../tests/test-data/test_configs/")),
+                None,
+            )
+            .await;
+            assert_eq!(authority.is_ok(), loads, "{max_allow_patterns}");
+        }
+
+        // Test: a missing pattern list fails the load.
+        let config = BlocklistConfig {
+            allow_regex_lists: vec!["default/missing_allow_regex.txt".to_string()],
+            ..config
+        };
+        assert!(BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            Some(Path::new("../../tests/test-data/test_configs/")),
+            None,
+        )
+        .await
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_dname() {
+        let target = Name::from_str("blocked.example.net.").unwrap();
+        let authority = BlocklistAuthorityBuilder::new(Name::root())
+            .block_response(BlockResponse::Dname)
+            .dname_target(target.clone())
+            .add_entries([
+                Name::from_str("*.ads.example.com.").unwrap(),
+                Name::from_str("tracker.example.com.").unwrap(),
+            ])
+            .build()
+            .expect("Unable to build blocklist authority");
+
+        async fn lookup(authority: &BlocklistAuthority, name: &str) -> Vec<Record> {
+            let lookup = authority
+                .lookup(
+                    &LowerName::from_str(name).unwrap(),
+                    RecordType::A,
+                    LookupOptions::default(),
+                )
+                .await
+                .unwrap_or_else(|e| panic!("{name} lookup error: {e}"))
+                .unwrap_or_else(|| panic!("{name} was not blocked"));
+            lookup.lookup.record_iter().cloned().collect()
+        }
+
+        // Test: a nested query under a wildcard entry is answered with a DNAME for the entry's subtree, followed by the
+        // CNAME it synthesizes.
+        let records = lookup(&authority, "a.b.ads.example.com.").await;
+        assert_eq!(records.len(), 2);
+        assert_eq!(
+            records[0].name(),
+            &Name::from_str("ads.example.com.").unwrap()
+        );
+        assert_eq!(records[0].record_type(), RecordType::Unknown(39));
+        let RData::Unknown { rdata, .. } = records[0].data() else {
+            panic!("unexpected DNAME data {:?}", records[0].data());
+        };
+        assert_eq!(rdata.anything(), b"\x07blocked\x07example\x03net\x00");
+        assert_eq!(
+            records[1].name(),
+            &Name::from_str("a.b.ads.example.com.").unwrap()
+        );
+        assert_eq!(
+            records[1].data(),
+            &RData::CNAME(CNAME(Name::from_str("a.b.blocked.example.net.").unwrap()))
+        );
+
+        // Test: an exact entry only blocks the name itself, so it is answered with a CNAME to the target.
+        let records = lookup(&authority, "tracker.example.com.").await;
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].data(), &RData::CNAME(CNAME(target.clone())));
+
+        // Test: a substitution which is longer than a name may be on the wire, 255 bytes, is answered with YXDOMAIN, and
+        // one which is exactly as long is answered.
+        for (len, substituted) in [(41, true), (42, false)] {
+            let labels = "a".repeat(63);
+            let name = format!(
+                "{labels}.{labels}.{labels}.{}.ads.example.com.",
+                "b".repeat(len)
+            );
+            let lookup = authority
+                .lookup(
+                    &LowerName::from_str(&name).unwrap(),
+                    RecordType::A,
+                    LookupOptions::default(),
+                )
+                .await;
+            match substituted {
+                true => assert!(matches!(lookup, Ok(Some(_))), "{len}"),
+                false => assert!(
+                    matches!(
+                        lookup,
+                        Err(super::LookupError::ResponseCode(ResponseCode::YXDomain))
+                    ),
+                    "{len}"
+                ),
+            }
+        }
+
+        // Test: a dname response requires a fully qualified target.
+        let builder =
+            BlocklistAuthorityBuilder::new(Name::root()).block_response(BlockResponse::Dname);
+        assert!(builder.clone().build().is_err());
+        let relative = builder.dname_target(Name::from_str("blocked.example.net").unwrap());
+        assert!(relative.build().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_response_ttls() {
+        async fn load(block_response: BlockResponse, lists: Vec<ListEntry>) -> BlocklistAuthority {
+            let config = BlocklistConfig {
+                lists,
+                block_response,
+                dname_target: Some(Name::from_str("blocked.example.net.").unwrap()),
+                ttl: 100,
+                sinkhole_ttl: Some(10),
+                client_ttl: Some(20),
+                dname_ttl: Some(30),
+                nxdomain_ttl: 40,
+                nodata_ttl: 50,
+                ..BlocklistConfig::default()
+            };
+
+            BlocklistAuthority::try_from_config(
+                Name::root(),
+                ZoneType::Hint,
+                &config,
+                Some(Path::new("../../tests/test-data/test_configs/")),
+                None,
+            )
+            .await
+            .expect("Unable to create blocklist authority")
+        }
+
+        let name = LowerName::from_str("foo.com.").unwrap();
+        let lists = || vec![ListEntry::from_path("default/blocklist.txt")];
+
+        // Test: records synthesized for blocked names have the TTL of their response mode.
+        for (block_response, ttl) in [
+            (BlockResponse::Sinkhole, 10),
+            (BlockResponse::Client, 20),
+            (BlockResponse::Dname, 30),
+        ] {
+            let authority = load(block_response, lists()).await;
+            let lookup = authority
+                .lookup(&name, RecordType::A, LookupOptions::default())
+                .await
+                .expect("lookup failed")
+                .expect("foo.com was not blocked");
+            assert!(
+                lookup
+                    .lookup
+                    .record_iter()
+                    .all(|record| record.ttl() == ttl),
+                "{block_response:?}"
+            );
+        }
+
+        // Test: negative responses carry an SOA whose TTL and minimum are the negative TTL of their response mode,
+        // including NODATA responses for the nodata_types.
+        for (block_response, rtype, ttl) in [
+            (BlockResponse::NxDomain, RecordType::A, 40),
+            (BlockResponse::NoData, RecordType::A, 50),
+            (BlockResponse::Sinkhole, RecordType::HTTPS, 50),
+        ] {
+            let authority = load(block_response, lists()).await;
+            let lookup = authority
+                .lookup(&name, rtype, LookupOptions::default())
+                .await;
+            assert!(lookup.is_err(), "{block_response:?}");
+
+            let soa = negative_soa(&authority, "foo.com.", rtype)
+                .await
+                .expect("no negative SOA");
+            let soa = soa.lookup.record_iter().next().unwrap();
+            assert_eq!(soa.ttl(), ttl, "{block_response:?}");
+            let RData::SOA(soa) = soa.data() else {
+                panic!("unexpected SOA data {:?}", soa.data());
+            };
+            assert_eq!(soa.minimum(), ttl, "{block_response:?}");
+        }
+
+        // Test: a list's own TTL overrides that of the response mode.
+        let list = ListEntry {
+            ttl: Some(60),
+            ..ListEntry::from_path("default/blocklist.txt")
+        };
+        let authority = load(BlockResponse::NxDomain, vec![list]).await;
+        let soa = negative_soa(&authority, "foo.com.", RecordType::A)
+            .await
+            .expect("no negative SOA");
+        assert_eq!(soa.lookup.record_iter().next().unwrap().ttl(), 60);
+
+        // Test: so do negative responses to names which are not matched by name, e.g. homographs of protected domains.
+        let config = BlocklistConfig {
+            block_response: BlockResponse::NxDomain,
+            nxdomain_ttl: 40,
+            nodata_ttl: 50,
+            homograph_protected: vec![Name::from_str("paypal.com.").unwrap()],
+            ..BlocklistConfig::default()
+        };
+        let authority = BlocklistAuthorityBuilder::with_config(Name::root(), config)
+            .build()
+            .expect("Unable to create blocklist authority");
+        let soa = negative_soa(&authority, "xn--pypal-4ve.com.", RecordType::A)
+            .await
+            .expect("no negative SOA for a homograph");
+        assert_eq!(soa.lookup.record_iter().next().unwrap().ttl(), 40);
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_negative_soa() {
+        let authority = BlocklistAuthorityBuilder::new(Name::root())
+            .block_response(BlockResponse::NxDomain)
+            .add_entries([Name::from_str("foo.com.").unwrap()])
+            .build()
+            .expect("Unable to create blocklist authority");
+
+        let mut catalog = Catalog::new();
+        catalog.upsert(
+            LowerName::from(Name::root()),
+            vec![Box::new(Arc::new(authority)) as Box<dyn AuthorityObject>],
+        );
+
+        let mut message = Message::new();
+        message.set_recursion_desired(true).add_query(Query::query(
+            Name::from_str("foo.com.").unwrap(),
+            RecordType::A,
+        ));
+        let bytes = message.to_vec().unwrap();
+        let request = Request::new(
+            MessageRequest::from_bytes(&bytes).unwrap(),
+            "127.0.0.1:5353".parse().unwrap(),
+            Protocol::Udp,
+        );
+
+        let capture = CaptureResponse::default();
+        catalog.lookup(&request, None, capture.clone()).await;
+        let response = Message::from_vec(&capture.0.lock().unwrap()).unwrap();
+
+        // Test: an NXDOMAIN response for a blocked name carries the blocklist's SOA, with the NXDOMAIN TTL.
+        assert_eq!(response.response_code(), ResponseCode::NXDomain);
+        assert_eq!(response.name_servers().len(), 1);
+        assert_eq!(response.name_servers()[0].record_type(), RecordType::SOA);
+        assert_eq!(response.name_servers()[0].ttl(), 3600);
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_grace_period() {
+        let dir = std::env::temp_dir().join(format!(
+            "hickory-blocklist-grace-period-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("grace.txt"), "old.example.\n").unwrap();
+        std::fs::write(dir.join("strict.txt"), "strict.example.\n").unwrap();
+
+        let config = BlocklistConfig {
+            lists: vec![
+                ListEntry {
+                    grace_period: Some(3600),
+                    ..ListEntry::from_path("grace.txt")
+                },
+                ListEntry::from_path("strict.txt"),
+            ],
+            query_log: Some(dir.join("queries.log").display().to_string()),
+            ..BlocklistConfig::default()
+        };
+
+        let authority = BlocklistAuthority::try_from_config(
+            Name::root(),
+            ZoneType::Hint,
+            &config,
+            Some(&dir),
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+
+        async fn blocked(authority: &BlocklistAuthority, name: &str) -> bool {
+            let lookup = authority
+                .lookup(
+                    &LowerName::from_str(name).unwrap(),
+                    RecordType::A,
+                    LookupOptions::default(),
+                )
+                .await;
+            match lookup {
+                Ok(lookup) => lookup.is_some(),
+                Err(e) => panic!("{name} lookup error: {e}"),
             }
-            Err(e) => {
-                panic!("Lookup error: {e}!");
+        }
+
+        // Test: entries on the list when it first loads are enforced immediately.
+        assert!(blocked(&authority, "old.example.").await);
+
+        // Test: an entry a reload adds is passed on while it is in its grace period, but logged and counted as monitored;
+        // entries the list already had, and new entries on lists without a grace period, are still blocked.
+        std::fs::write(dir.join("grace.txt"), "old.example.\nnew.example.\n").unwrap();
+        std::fs::write(
+            dir.join("strict.txt"),
+            "strict.example.\nnew.strict.example.\n",
+        )
+        .unwrap();
+        assert!(authority.reload().await.is_ok());
+        assert!(!blocked(&authority, "new.example.").await);
+        assert!(blocked(&authority, "old.example.").await);
+        assert!(blocked(&authority, "new.strict.example.").await);
+        assert_eq!(authority.stats().monitored, 1);
+
+        let log = std::fs::read_to_string(dir.join("queries.log")).unwrap();
+        let grace = log
+            .lines()
+            .find(|line| line.contains(r#""qname":"new.example.""#))
+            .expect("grace period query was not logged");
+        assert!(grace.contains(r#""action":"monitor""#), "{grace}");
+
+        // Test: the time an entry was first seen is kept across reloads, so reloading does not restart its grace period.
+        let name = LowerName::from_str("new.example.").unwrap();
+        let first_seen = authority.policy().first_seen[&name];
+        assert!(authority.reload().await.is_ok());
+        assert_eq!(authority.policy().first_seen[&name], first_seen);
+        assert!(!blocked(&authority, "new.example.").await);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_lookup_records() {
+        use super::BlocklistLookup;
+        use crate::authority::LookupObject;
+
+        let authority = BlocklistAuthorityBuilder::new(Name::root())
+            .block_response(BlockResponse::Dname)
+            .dname_target(Name::from_str("blocked.example.net.").unwrap())
+            .add_entries([Name::from_str("*.ads.example.com.").unwrap()])
+            .build()
+            .expect("Unable to build blocklist authority");
+
+        // Test: every record of a multi-record answer is exposed, in order, through the lookup object.
+        let lookup = authority
+            .lookup(
+                &LowerName::from_str("a.ads.example.com.").unwrap(),
+                RecordType::A,
+                LookupOptions::default(),
+            )
+            .await
+            .expect("lookup failed")
+            .expect("a.ads.example.com was not blocked");
+        let lookup: Box<dyn LookupObject> = Box::new(lookup);
+        assert!(!lookup.is_empty());
+        let records = lookup.iter().collect::<Vec<_>>();
+        assert_eq!(records.len(), 2);
+        assert_eq!(
+            records[0].name(),
+            &Name::from_str("ads.example.com.").unwrap()
+        );
+        assert_eq!(records[1].record_type(), RecordType::CNAME);
+
+        // Test: a lookup is only empty when it holds no records.
+        let query = Query::query(Name::from_str("example.com.").unwrap(), RecordType::A);
+        assert!(BlocklistLookup::new(query, Vec::new()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_strip_client_subnet() {
+        use super::{BlocklistLookup, LookupError, UpdateResult};
+        use crate::proto::op::Edns;
+        use crate::proto::rr::rdata::opt::{EdnsCode, EdnsOption};
+        use std::sync::Mutex;
+
+        /// The next store in the chain, which records the EDNS payload size of each request passed on to it, and whether
+        /// it has a client subnet
+        struct Subnets(Mutex<Vec<Option<(u16, bool)>>>, LowerName);
+
+        #[async_trait::async_trait]
+        impl Authority for Subnets {
+            type Lookup = BlocklistLookup;
+
+            fn zone_type(&self) -> ZoneType {
+                ZoneType::Hint
+            }
+
+            fn is_axfr_allowed(&self) -> bool {
+                false
+            }
+
+            async fn update(&self, _update: &MessageRequest) -> UpdateResult<bool> {
+                Err(ResponseCode::NotImp)
+            }
+
+            fn origin(&self) -> &LowerName {
+                &self.1
+            }
+
+            async fn lookup(
+                &self,
+                _name: &LowerName,
+                _rtype: RecordType,
+                _lookup_options: LookupOptions,
+            ) -> Result<Option<Self::Lookup>, LookupError> {
+                Ok(None)
+            }
+
+            async fn search(
+                &self,
+                request_info: RequestInfo<'_>,
+                _lookup_options: LookupOptions,
+            ) -> Result<Option<Self::Lookup>, LookupError> {
+                let edns = request_info
+                    .edns()
+                    .map(|edns| (edns.max_payload(), edns.option(EdnsCode::Subnet).is_some()));
+                self.0.lock().unwrap().push(edns);
+                Ok(None)
+            }
+
+            async fn get_nsec_records(
+                &self,
+                _name: &LowerName,
+                _lookup_options: LookupOptions,
+            ) -> Result<Self::Lookup, LookupError> {
+                Err(LookupError::from(ResponseCode::NotImp))
+            }
+        }
+
+        async fn passed_on(strip_client_subnet: bool, name: &str) -> Vec<Option<(u16, bool)>> {
+            let config = BlocklistConfig {
+                strip_client_subnet,
+                ..BlocklistConfig::default()
+            };
+            let authority = BlocklistAuthorityBuilder::with_config(Name::root(), config)
+                .add_entries([Name::from_str("foo.com.").unwrap()])
+                .build()
+                .expect("Unable to create blocklist authority");
+            let subnets = Arc::new(Subnets(Mutex::default(), LowerName::from(Name::root())));
+
+            let mut catalog = Catalog::new();
+            catalog.upsert(
+                LowerName::from(Name::root()),
+                vec![
+                    Box::new(Arc::new(authority)) as Box<dyn AuthorityObject>,
+                    Box::new(subnets.clone()) as Box<dyn AuthorityObject>,
+                ],
+            );
+
+            let mut edns = Edns::new();
+            edns.set_max_payload(1232);
+            edns.options_mut()
+                .insert(EdnsOption::Subnet("192.0.2.0/24".parse().unwrap()));
+            let mut message = Message::new();
+            message
+                .set_recursion_desired(true)
+                .add_query(Query::query(Name::from_str(name).unwrap(), RecordType::A))
+                .set_edns(edns);
+            let bytes = message.to_vec().unwrap();
+            let request = Request::new(
+                MessageRequest::from_bytes(&bytes).unwrap(),
+                "127.0.0.1:5353".parse().unwrap(),
+                Protocol::Udp,
+            );
+
+            catalog
+                .lookup(&request, None, CaptureResponse::default())
+                .await;
+            let passed_on = subnets.0.lock().unwrap().clone();
+            passed_on
+        }
+
+        // Test: by default, a query passed on keeps its client subnet.
+        assert_eq!(passed_on(false, "bar.com.").await, vec![Some((1232, true))]);
+
+        // Test: with strip_client_subnet, the next store sees the query without it, but with the rest of its EDNS.
+        assert_eq!(passed_on(true, "bar.com.").await, vec![Some((1232, false))]);
+
+        // Test: blocked queries are answered here, and never passed on.
+        assert!(passed_on(true, "foo.com.").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_type_nodata() {
+        let authority = BlocklistAuthorityBuilder::new(Name::root())
+            .add_entries([Name::from_str("foo.com.").unwrap()])
+            .build()
+            .expect("Unable to create blocklist authority");
+        let authority = Arc::new(authority);
+
+        // Test: address queries are answered with a sinkhole address of the same type.
+        for (rtype, rdata) in [
+            (RecordType::A, RData::A(A::new(0, 0, 0, 0))),
+            (
+                RecordType::AAAA,
+                RData::AAAA(AAAA::from(Ipv6Addr::UNSPECIFIED)),
+            ),
+        ] {
+            let lookup = authority
+                .lookup(
+                    &LowerName::from_str("foo.com.").unwrap(),
+                    rtype,
+                    LookupOptions::default(),
+                )
+                .await
+                .expect("lookup failed")
+                .expect("lookup was not handled");
+            let records = lookup.iter().collect::<Vec<_>>();
+            assert_eq!(records.len(), 1);
+            assert_eq!(records[0].data(), &rdata);
+        }
+
+        let mut catalog = Catalog::new();
+        catalog.upsert(
+            LowerName::from(Name::root()),
+            vec![Box::new(authority) as Box<dyn AuthorityObject>],
+        );
+
+        for rtype in [RecordType::TXT, RecordType::MX, RecordType::SRV] {
+            let mut message = Message::new();
+            message
+                .set_recursion_desired(true)
+                .add_query(Query::query(Name::from_str("foo.com.").unwrap(), rtype));
+            let bytes = message.to_vec().unwrap();
+            let request = Request::new(
+                MessageRequest::from_bytes(&bytes).unwrap(),
+                "127.0.0.1:5353".parse().unwrap(),
+                Protocol::Udp,
+            );
+
+            let capture = CaptureResponse::default();
+            catalog.lookup(&request, None, capture.clone()).await;
+            let response = Message::from_vec(&capture.0.lock().unwrap()).unwrap();
+
+            // Test: other types get an empty answer rather than a sinkhole address, with the SOA for negative caching.
+            assert_eq!(response.response_code(), ResponseCode::NoError, "{rtype}");
+            assert!(response.answers().is_empty(), "{rtype}");
+            assert_eq!(response.queries()[0].query_type(), rtype);
+            assert_eq!(response.name_servers().len(), 1, "{rtype}");
+            assert_eq!(response.name_servers()[0].record_type(), RecordType::SOA);
+            assert_eq!(response.name_servers()[0].ttl(), 3600, "{rtype}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_extended_error() {
+        use crate::proto::op::Edns;
+        use crate::proto::rr::rdata::opt::{EdnsCode, EdnsOption};
+
+        let dir = std::env::temp_dir().join(format!(
+            "hickory-blocklist-extended-error-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("malware.txt"), "malware.example.\n").unwrap();
+        std::fs::write(dir.join("ads.txt"), "ads.example.\n").unwrap();
+        std::fs::write(dir.join("plain.txt"), "plain.example.\n").unwrap();
+        std::fs::write(dir.join("reverse.txt"), "192.0.2.0/24\n").unwrap();
+
+        let malware = ListEntry {
+            ede_code: Some(16),
+            ede_text: Some("malware".to_string()),
+            ..ListEntry::from_path("malware.txt")
+        };
+        let config = BlocklistConfig {
+            lists: vec![
+                malware.clone(),
+                ListEntry {
+                    response: Some(BlockResponse::NxDomain),
+                    ede_code: Some(15),
+                    ..ListEntry::from_path("ads.txt")
+                },
+                ListEntry::from_path("plain.txt"),
+                ListEntry {
+                    format: ListFormat::Reverse,
+                    ede_code: Some(17),
+                    ..ListEntry::from_path("reverse.txt")
+                },
+            ],
+            response_cache_size: 16,
+            ..BlocklistConfig::default()
+        };
+        let authority = BlocklistAuthority::try_from_config(
+            Name::root(),
+            ZoneType::Hint,
+            &config,
+            Some(&dir),
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+
+        /// The extended error of the response `catalog` gives to a query for `name` from a client which sent `edns`
+        async fn extended_error(
+            catalog: &Catalog,
+            name: &str,
+            edns: Option<Edns>,
+        ) -> Option<EdnsOption> {
+            let mut message = Message::new();
+            message
+                .set_recursion_desired(true)
+                .add_query(Query::query(Name::from_str(name).unwrap(), RecordType::A));
+            let bytes = message.to_vec().unwrap();
+            let request = Request::new(
+                MessageRequest::from_bytes(&bytes).unwrap(),
+                "127.0.0.1:5353".parse().unwrap(),
+                Protocol::Udp,
+            );
+
+            let capture = CaptureResponse::default();
+            catalog.lookup(&request, edns, capture.clone()).await;
+            let response = Message::from_vec(&capture.0.lock().unwrap()).unwrap();
+            response
+                .extensions()
+                .as_ref()
+                .and_then(|edns| edns.option(EdnsCode::from(15)).cloned())
+        }
+
+        let catalog = |authority: BlocklistAuthority| {
+            let mut catalog = Catalog::new();
+            catalog.upsert(
+                LowerName::from(Name::root()),
+                vec![Box::new(Arc::new(authority)) as Box<dyn AuthorityObject>],
+            );
+            catalog
+        };
+        let blocklist = catalog(authority);
+
+        // Test: a blocked response carries the extended error of the list which blocked the name, whatever the response,
+        // including when it is answered from the response cache.
+        for _ in 0..2 {
+            assert_eq!(
+                extended_error(&blocklist, "malware.example.", Some(Edns::new())).await,
+                Some(EdnsOption::Unknown(15, b"\x00\x10malware".to_vec()))
+            );
+        }
+        assert_eq!(
+            extended_error(&blocklist, "ads.example.", Some(Edns::new())).await,
+            Some(EdnsOption::Unknown(15, vec![0, 15]))
+        );
+
+        // Test: so do names blocked by a reverse range.
+        assert_eq!(
+            extended_error(&blocklist, "5.2.0.192.in-addr.arpa.", Some(Edns::new())).await,
+            Some(EdnsOption::Unknown(15, vec![0, 17]))
+        );
+
+        // Test: names on lists without an extended error, and clients which did not send EDNS, get none.
+        assert_eq!(
+            extended_error(&blocklist, "plain.example.", Some(Edns::new())).await,
+            None
+        );
+        assert_eq!(
+            extended_error(&blocklist, "malware.example.", None).await,
+            None
+        );
+
+        // Test: a query refused for exceeding the client rate limit gets none, even for a name on a list with one.
+        let config = BlocklistConfig {
+            lists: vec![malware],
+            client_rate_limit: 1,
+            client_rate_burst: 1,
+            ..BlocklistConfig::default()
+        };
+        let authority = BlocklistAuthority::try_from_config(
+            Name::root(),
+            ZoneType::Hint,
+            &config,
+            Some(&dir),
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+        let limited = catalog(authority);
+        assert!(
+            extended_error(&limited, "malware.example.", Some(Edns::new()))
+                .await
+                .is_some()
+        );
+        assert_eq!(
+            extended_error(&limited, "malware.example.", Some(Edns::new())).await,
+            None
+        );
+
+        // Test: invalid extended errors are refused when the configuration is loaded.
+        for entry in [
+            ListEntry {
+                ede_code: Some(1000),
+                ..ListEntry::from_path("malware.txt")
+            },
+            ListEntry {
+                ede_text: Some("malware".to_string()),
+                ..ListEntry::from_path("malware.txt")
+            },
+        ] {
+            let config = BlocklistConfig {
+                lists: vec![entry],
+                ..BlocklistConfig::default()
+            };
+            assert!(BlocklistAuthority::try_from_config(
+                Name::root(),
+                ZoneType::Hint,
+                &config,
+                Some(&dir),
+                None,
+            )
+            .await
+            .is_err());
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_wildcard_order() {
+        let dir = std::env::temp_dir().join(format!(
+            "hickory-blocklist-wildcard-order-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("broad.txt"), "*.example.com.\n").unwrap();
+        std::fs::write(dir.join("narrow.txt"), "*.ads.example.com.\n").unwrap();
+
+        let broad = ListEntry {
+            response: Some(BlockResponse::NxDomain),
+            ..ListEntry::from_path("broad.txt")
+        };
+        let narrow = ListEntry {
+            response: Some(BlockResponse::NoData),
+            ..ListEntry::from_path("narrow.txt")
+        };
+
+        // Test: the most specific matching wildcard decides the response, whichever order the lists are loaded in.
+        for lists in [
+            vec![broad.clone(), narrow.clone()],
+            vec![narrow.clone(), broad.clone()],
+        ] {
+            let config = BlocklistConfig {
+                lists,
+                ..BlocklistConfig::default()
+            };
+            let authority = BlocklistAuthority::try_from_config(
+                Name::root(),
+                ZoneType::Hint,
+                &config,
+                Some(&dir),
+                None,
+            )
+            .await
+            .expect("Unable to create blocklist authority");
+
+            assert_eq!(
+                authority
+                    .policy()
+                    .match_list(&LowerName::from_str("x.y.ads.example.com.").unwrap()),
+                [
+                    "x.y.ads.example.com.",
+                    "*.y.ads.example.com.",
+                    "*.ads.example.com.",
+                    "*.example.com."
+                ]
+                .into_iter()
+                .map(|name| LowerName::from_str(name).unwrap())
+                .collect::<Vec<_>>()
+            );
+
+            for (name, nx_domain) in [
+                ("x.y.ads.example.com.", false),
+                ("x.ads.example.com.", false),
+                ("x.example.com.", true),
+            ] {
+                let lookup = authority
+                    .lookup(
+                        &LowerName::from_str(name).unwrap(),
+                        RecordType::A,
+                        LookupOptions::default(),
+                    )
+                    .await;
+                match lookup {
+                    Err(e) if nx_domain => assert!(e.is_nx_domain(), "{name}: {e}"),
+                    Err(e) => assert!(e.is_name_exists(), "{name}: {e}"),
+                    Ok(_) => panic!("{name} was not blocked"),
+                }
+            }
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_block_origin() {
+        async fn blocked(authority: &BlocklistAuthority, name: &str) -> bool {
+            let lookup = authority
+                .lookup(
+                    &LowerName::from_str(name).unwrap(),
+                    RecordType::NS,
+                    LookupOptions::default(),
+                )
+                .await;
+            match lookup {
+                Ok(lookup) => lookup.is_some(),
+                Err(e) => e.is_nx_domain() || e.is_name_exists(),
+            }
+        }
+
+        let build = |origin: &str, entries: &[&str], block_origin: bool| {
+            BlocklistAuthorityBuilder::with_config(
+                Name::from_str(origin).unwrap(),
+                BlocklistConfig {
+                    block_origin,
+                    wildcard_blocks_parent: true,
+                    ..BlocklistConfig::default()
+                },
+            )
+            .add_entries(entries.iter().map(|name| Name::from_str(name).unwrap()))
+            .build()
+            .expect("Unable to build blocklist authority")
+        };
+
+        // Test: a query for the origin is passed on, even when an entry or a wildcard names it.
+        let zone = build("example.com.", &["example.com.", "*.example.com."], false);
+        assert!(!blocked(&zone, "example.com.").await);
+        assert!(blocked(&zone, "www.example.com.").await);
+        let root = build(".", &["."], false);
+        assert!(!blocked(&root, ".").await);
+        assert_eq!(root.stats().passed, 1);
+
+        // Test: with block_origin set, the origin is matched like any other name.
+        let zone = build("example.com.", &["example.com."], true);
+        assert!(blocked(&zone, "example.com.").await);
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_dnssec_bits() {
+        use crate::proto::op::Edns;
+
+        let authority = BlocklistAuthorityBuilder::new(Name::root())
+            .add_entries([Name::from_str("foo.com.").unwrap()])
+            .build()
+            .expect("Unable to create blocklist authority");
+
+        let mut catalog = Catalog::new();
+        catalog.upsert(
+            LowerName::from(Name::root()),
+            vec![Box::new(Arc::new(authority)) as Box<dyn AuthorityObject>],
+        );
+
+        // A query from a validating client, which sets DO, and AD to ask whether the answer was authenticated
+        async fn query(catalog: &Catalog, checking_disabled: bool) -> Message {
+            let mut message = Message::new();
+            message
+                .set_recursion_desired(true)
+                .set_authentic_data(true)
+                .set_checking_disabled(checking_disabled)
+                .add_query(Query::query(
+                    Name::from_str("foo.com.").unwrap(),
+                    RecordType::A,
+                ));
+            let bytes = message.to_vec().unwrap();
+            let request = Request::new(
+                MessageRequest::from_bytes(&bytes).unwrap(),
+                "127.0.0.1:5353".parse().unwrap(),
+                Protocol::Udp,
+            );
+
+            let mut edns = Edns::new();
+            edns.set_dnssec_ok(true);
+            let capture = CaptureResponse::default();
+            catalog.lookup(&request, Some(edns), capture.clone()).await;
+            let response = Message::from_vec(&capture.0.lock().unwrap()).unwrap();
+            response
+        }
+
+        // Test: a synthesized block response is answered, never marked as authenticated data, and echoes CD.
+        for checking_disabled in [false, true] {
+            let response = query(&catalog, checking_disabled).await;
+            assert_eq!(response.response_code(), ResponseCode::NoError);
+            assert_eq!(response.answers().len(), 1);
+            assert!(!response.authentic_data());
+            assert_eq!(response.checking_disabled(), checking_disabled);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_categories() {
+        use crate::store::blocklist::Category;
+
+        let dir = std::env::temp_dir().join(format!(
+            "hickory-blocklist-categories-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (file, name) in [
+            ("malware1.txt", "one.malware.example."),
+            ("malware2.txt", "two.malware.example."),
+            ("phishing.txt", "phishing.malware.example."),
+            ("ads.txt", "ads.example."),
+        ] {
+            std::fs::write(dir.join(file), format!("{name}\n")).unwrap();
+        }
+
+        let categorized = |path: &str, category: &str| ListEntry {
+            category: Some(category.to_string()),
+            ..ListEntry::from_path(path)
+        };
+        let config = BlocklistConfig {
+            lists: vec![
+                categorized("malware1.txt", "malware"),
+                categorized("malware2.txt", "malware"),
+                ListEntry {
+                    response: Some(BlockResponse::NoData),
+                    ..categorized("phishing.txt", "malware")
+                },
+                categorized("ads.txt", "ads"),
+            ],
+            categories: [
+                (
+                    "malware".to_string(),
+                    Category {
+                        action: None,
+                        response: Some(BlockResponse::NxDomain),
+                    },
+                ),
+                (
+                    "ads".to_string(),
+                    Category {
+                        action: Some(ListAction::Monitor),
+                        response: None,
+                    },
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            ..BlocklistConfig::default()
+        };
+        let load = |config: BlocklistConfig| {
+            let dir = dir.clone();
+            async move {
+                BlocklistAuthority::try_from_config(
+                    Name::root(),
+                    ZoneType::Hint,
+                    &config,
+                    Some(&dir),
+                    None,
+                )
+                .await
+            }
+        };
+        let authority = load(config.clone())
+            .await
+            .expect("Unable to create blocklist authority");
+
+        async fn lookup(authority: &BlocklistAuthority, name: &str) -> Option<ResponseCode> {
+            let res = authority
+                .lookup(
+                    &LowerName::from_str(name).unwrap(),
+                    RecordType::A,
+                    LookupOptions::default(),
+                )
+                .await;
+            match res {
+                Ok(None) => None,
+                Ok(Some(_)) => Some(ResponseCode::NoError),
+                Err(e) if e.is_nx_domain() => Some(ResponseCode::NXDomain),
+                Err(e) if e.is_name_exists() => Some(ResponseCode::NoError),
+                Err(e) => panic!("{name} lookup returned {e}"),
+            }
+        }
+
+        // Test: both lists in a category are answered with the response the category sets once.
+        for name in ["one.malware.example.", "two.malware.example."] {
+            assert_eq!(lookup(&authority, name).await, Some(ResponseCode::NXDomain));
+        }
+
+        // Test: a list's own response overrides its category's.
+        assert!(matches!(
+            authority
+                .lookup(
+                    &LowerName::from_str("phishing.malware.example.").unwrap(),
+                    RecordType::A,
+                    LookupOptions::default(),
+                )
+                .await,
+            Err(e) if e.is_name_exists()
+        ));
+
+        // Test: a category's action applies to its lists, so names on the ads list are only monitored.
+        assert_eq!(lookup(&authority, "ads.example.").await, None);
+        let stats = authority.stats();
+        assert_eq!(stats.monitored, 1);
+        assert_eq!(stats.lists[3].action, ListAction::Monitor);
+
+        // Test: a list in a category which is not configured is refused.
+        let mut unknown = config;
+        unknown.lists.push(categorized("ads.txt", "trackers"));
+        assert!(load(unknown).await.is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_minimal_responses() {
+        async fn response(minimal_responses: bool) -> (Message, usize) {
+            let authority = BlocklistAuthorityBuilder::new(Name::root())
+                .block_response(BlockResponse::NxDomain)
+                .minimal_responses(minimal_responses)
+                .add_entries([Name::from_str("foo.com.").unwrap()])
+                .build()
+                .expect("Unable to create blocklist authority");
+
+            let mut catalog = Catalog::new();
+            catalog.upsert(
+                LowerName::from(Name::root()),
+                vec![Box::new(Arc::new(authority)) as Box<dyn AuthorityObject>],
+            );
+
+            let mut message = Message::new();
+            message.set_recursion_desired(true).add_query(Query::query(
+                Name::from_str("foo.com.").unwrap(),
+                RecordType::A,
+            ));
+            let bytes = message.to_vec().unwrap();
+            let request = Request::new(
+                MessageRequest::from_bytes(&bytes).unwrap(),
+                "127.0.0.1:5353".parse().unwrap(),
+                Protocol::Udp,
+            );
+
+            let capture = CaptureResponse::default();
+            catalog.lookup(&request, None, capture.clone()).await;
+            let bytes = capture.0.lock().unwrap().clone();
+            (Message::from_vec(&bytes).unwrap(), bytes.len())
+        }
+
+        // Test: by default, an NXDOMAIN response carries the blocklist's SOA, so that clients can cache it.
+        let (full, full_size) = response(false).await;
+        assert_eq!(full.response_code(), ResponseCode::NXDomain);
+        assert_eq!(full.name_servers().len(), 1);
+
+        // Test: minimal responses leave the SOA out, and are smaller for it.
+        let (minimal, minimal_size) = response(true).await;
+        assert_eq!(minimal.response_code(), ResponseCode::NXDomain);
+        assert!(minimal.name_servers().is_empty());
+        assert!(minimal.additionals().is_empty());
+        assert!(
+            minimal_size < full_size,
+            "minimal response is {minimal_size} bytes, full response {full_size}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_malformed_query() {
+        use super::MalformedQueryPolicy;
+
+        let header = Header::new();
+        let degenerate = [
+            Name::new(),
+            Name::from_ascii("foo.com").unwrap(),
+            Name::from_labels([b"foo.com".as_slice()]).unwrap(),
+            Name::from_labels([b"*.foo".as_slice(), b"com".as_slice()]).unwrap(),
+        ];
+
+        for malformed_query in [MalformedQueryPolicy::Pass, MalformedQueryPolicy::Refuse] {
+            let authority = BlocklistAuthorityBuilder::new(Name::root())
+                .add_entries([
+                    Name::from_str("foo.com.").unwrap(),
+                    Name::from_str("*.foo.com.").unwrap(),
+                ])
+                .malformed_query(malformed_query)
+                .build()
+                .expect("Unable to create blocklist authority");
+
+            // Test: names which can not be matched against the lists are never blocked, and are passed on or refused
+            // according to the policy.
+            for name in &degenerate {
+                let query = Query::query(name.clone(), RecordType::A).into();
+                let request_info = RequestInfo::new(
+                    "127.0.0.1:5353".parse::<SocketAddr>().unwrap(),
+                    Protocol::Udp,
+                    &header,
+                    &query,
+                );
+                let res = authority
+                    .search(request_info, LookupOptions::default())
+                    .await;
+                match malformed_query {
+                    MalformedQueryPolicy::Pass => {
+                        assert!(matches!(res, Ok(None)), "{name:?}: {malformed_query:?}")
+                    }
+                    _ => assert!(
+                        matches!(&res, Err(e) if e.is_refused()),
+                        "{name:?}: {malformed_query:?}"
+                    ),
+                }
             }
+
+            // Test: well formed names are matched as usual.
+            let res = authority
+                .lookup(
+                    &LowerName::from_str("foo.com.").unwrap(),
+                    RecordType::A,
+                    LookupOptions::default(),
+                )
+                .await;
+            assert!(matches!(res, Ok(Some(_))), "{malformed_query:?}");
+            assert_eq!(authority.stats().blocked, 1);
         }
+    }
 
-        // Test: lookup a record that is not in the blocklist. This test should fail.
-        let res = ao
-            .lookup(
-                &LowerName::from_str("test.com.").unwrap(),
-                RecordType::A,
-                LookupOptions::default(),
+    #[tokio::test]
+    async fn test_blocklist_first_party_domains() {
+        async fn blocked(authority: &BlocklistAuthority, name: &str) -> bool {
+            matches!(
+                authority
+                    .lookup(
+                        &LowerName::from_str(name).unwrap(),
+                        RecordType::A,
+                        LookupOptions::default(),
+                    )
+                    .await,
+                Ok(Some(_))
             )
-            .await;
-        match res {
-            Ok(Some(_l)) => {
-                panic!("test.com lookup returned Ok; expected failure");
-            }
-            Ok(None) => {}
-            Err(_e) => {
-                panic!("test.com lookup returned Err; expected Ok(None)");
-            }
         }
 
-        // Test: lookup a record that will match a wildcard that is in the blocklist.
-        let res = ao
-            .lookup(
-                &LowerName::from_str("www.foo.com.").unwrap(),
-                RecordType::A,
-                LookupOptions::default(),
-            )
-            .await;
-        match res {
-            Ok(Some(l)) => {
-                if !l.iter().all(|x| {
-                    x.name() == &Name::from_str("www.foo.com.").unwrap()
-                        && x.data() == Some(&RData::A(A::new(0, 0, 0, 0)))
-                }) {
-                    panic!("www.foo.com lookup data is incorrect.");
+        let authority = BlocklistAuthorityBuilder::new(Name::root())
+            .add_entries([
+                Name::from_str("tracker.shop.example.").unwrap(),
+                Name::from_str("*.cdn.shop.example.").unwrap(),
+                Name::from_str("shop.example.").unwrap(),
+                Name::from_str("tracker.ads.example.").unwrap(),
+                Name::from_str("shop.example.ads.example.").unwrap(),
+            ])
+            .first_party_domains(vec![Name::from_str("shop.example").unwrap()])
+            .build()
+            .expect("Unable to create blocklist authority");
+
+        // Test: the first party domain and every name below it are carved out of exact and wildcard entries alike.
+        assert!(!blocked(&authority, "shop.example.").await);
+        assert!(!blocked(&authority, "tracker.shop.example.").await);
+        assert!(!blocked(&authority, "img.cdn.shop.example.").await);
+
+        // Test: third party names stay blocked, including those which merely contain the first party domain.
+        assert!(blocked(&authority, "tracker.ads.example.").await);
+        assert!(blocked(&authority, "shop.example.ads.example.").await);
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_cname_response() {
+        let target = Name::from_str("blockpage.example.net.").unwrap();
+        let config = BlocklistConfig {
+            block_response: BlockResponse::Cname,
+            dname_target: Some(target.clone()),
+            sinkhole_ipv4: Ipv4Addr::new(10, 0, 0, 1),
+            sinkhole_ipv6: Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 1),
+            ..BlocklistConfig::default()
+        };
+        let authority = BlocklistAuthorityBuilder::with_config(Name::root(), config.clone())
+            .add_entries([Name::from_str("foo.com.").unwrap()])
+            .build()
+            .expect("Unable to create blocklist authority");
+
+        let mut catalog = Catalog::new();
+        catalog.upsert(
+            LowerName::from(Name::root()),
+            vec![Box::new(Arc::new(authority)) as Box<dyn AuthorityObject>],
+        );
+
+        async fn query(catalog: &Catalog, rtype: RecordType) -> Message {
+            let mut message = Message::new();
+            message
+                .set_recursion_desired(true)
+                .add_query(Query::query(Name::from_str("foo.com.").unwrap(), rtype));
+            let bytes = message.to_vec().unwrap();
+            let request = Request::new(
+                MessageRequest::from_bytes(&bytes).unwrap(),
+                "127.0.0.1:5353".parse().unwrap(),
+                Protocol::Udp,
+            );
+
+            let capture = CaptureResponse::default();
+            catalog.lookup(&request, None, capture.clone()).await;
+            let response = Message::from_vec(&capture.0.lock().unwrap()).unwrap();
+            response
+        }
+
+        // Test: address queries are answered with the CNAME and the target's address together, in the order a client
+        // follows them.
+        let name = Name::from_str("foo.com.").unwrap();
+        for (rtype, rdata) in [
+            (RecordType::A, RData::A(A::new(10, 0, 0, 1))),
+            (
+                RecordType::AAAA,
+                RData::AAAA(AAAA::new(0xfd00, 0, 0, 0, 0, 0, 0, 1)),
+            ),
+        ] {
+            let response = query(&catalog, rtype).await;
+            assert_eq!(response.response_code(), ResponseCode::NoError);
+            let answers = response.answers();
+            assert_eq!(answers.len(), 2, "{rtype}");
+            assert_eq!(answers[0].name(), &name);
+            assert_eq!(answers[0].data(), &RData::CNAME(CNAME(target.clone())));
+            assert_eq!(answers[1].name(), &target);
+            assert_eq!(answers[1].data(), &rdata);
+        }
+
+        // Test: other types are answered with the CNAME alone.
+        let response = query(&catalog, RecordType::MX).await;
+        assert_eq!(response.answers().len(), 1);
+        assert_eq!(
+            response.answers()[0].data(),
+            &RData::CNAME(CNAME(target.clone()))
+        );
+
+        // Test: the target is required, and so is an address for it.
+        for config in [
+            BlocklistConfig {
+                dname_target: None,
+                ..config.clone()
+            },
+            BlocklistConfig {
+                sinkhole_ipv4: Ipv4Addr::UNSPECIFIED,
+                sinkhole_ipv6: Ipv6Addr::UNSPECIFIED,
+                ..config.clone()
+            },
+            BlocklistConfig {
+                block_response: BlockResponse::Sinkhole,
+                lists: vec![ListEntry {
+                    response: Some(BlockResponse::Cname),
+                    sinkhole_ipv4: Some(Ipv4Addr::UNSPECIFIED),
+                    sinkhole_ipv6: Some(Ipv6Addr::UNSPECIFIED),
+                    ..ListEntry::from_path("default/blocklist.txt")
+                }],
+                ..config.clone()
+            },
+        ] {
+            assert!(BlocklistAuthorityBuilder::with_config(Name::root(), config)
+                .build()
+                .is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_truncation() {
+        use crate::proto::op::Edns;
+        use crate::proto::xfer::BufDnsStreamHandle;
+        use crate::server::ResponseHandle;
+        use futures_util::StreamExt;
+
+        // A DNAME answer for a long name below a wildcard entry, whose two records take more than 512 bytes.  The CNAME
+        // the DNAME synthesizes is 255 bytes long, as long as a name may be.
+        let label = |c: char, len: usize| c.to_string().repeat(len);
+        let name = Name::from_str(&format!(
+            "{}.{}.{}.blocked.example.",
+            label('a', 61),
+            label('b', 61),
+            label('c', 61)
+        ))
+        .unwrap();
+        let target = Name::from_str(&format!("{}.target.", label('t', 60))).unwrap();
+        let authority = BlocklistAuthorityBuilder::new(Name::root())
+            .block_response(BlockResponse::Dname)
+            .dname_target(target)
+            .add_entries([Name::from_str("*.blocked.example.").unwrap()])
+            .build()
+            .expect("Unable to create blocklist authority");
+
+        let mut catalog = Catalog::new();
+        catalog.upsert(
+            LowerName::from(Name::root()),
+            vec![Box::new(Arc::new(authority)) as Box<dyn AuthorityObject>],
+        );
+
+        let mut edns = Edns::new();
+        edns.set_max_payload(512);
+        let mut message = Message::new();
+        message
+            .set_recursion_desired(true)
+            .add_query(Query::query(name, RecordType::A))
+            .set_edns(edns.clone());
+        let bytes = message.to_vec().unwrap();
+
+        // Test: the synthesized answer goes through the server's truncation, so it is truncated to fit UDP, with TC set
+        // for the client to retry over TCP, where it is answered in full.
+        for (protocol, truncated) in [(Protocol::Udp, true), (Protocol::Tcp, false)] {
+            let src = "127.0.0.1:5353".parse::<SocketAddr>().unwrap();
+            let request = Request::new(MessageRequest::from_bytes(&bytes).unwrap(), src, protocol);
+            let (stream_handle, mut receiver) = BufDnsStreamHandle::new(src);
+            catalog
+                .lookup(
+                    &request,
+                    Some(edns.clone()),
+                    ResponseHandle::new(src, stream_handle, protocol),
+                )
+                .await;
+
+            let response = receiver.next().await.expect("no response sent");
+            let response = Message::from_vec(response.bytes()).unwrap();
+            assert_eq!(response.truncated(), truncated, "{protocol}");
+            match truncated {
+                true => {
+                    assert!(response.to_vec().unwrap().len() <= 512);
+                    assert!(response.answers().len() < 2);
+                }
+                false => {
+                    assert!(response.to_vec().unwrap().len() > 512);
+                    assert_eq!(response.answers().len(), 2);
                 }
             }
-            Ok(None) => {
-                panic!("Lookup returned Ok(None); expected Ok(Some)");
-            }
-            Err(e) => {
-                panic!("Lookup error: {e}!");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_list_min_wildcard_depth() {
+        let dir = std::env::temp_dir().join(format!(
+            "hickory-blocklist-list-depth-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("strict.txt"), "*.example.com\n*.ads.example.net\n").unwrap();
+        std::fs::write(dir.join("loose.txt"), "*.test\n*.example.net\n").unwrap();
+
+        let config = BlocklistConfig {
+            lists: vec![
+                ListEntry {
+                    min_wildcard_depth: Some(3),
+                    ..ListEntry::from_path("strict.txt")
+                },
+                ListEntry {
+                    min_wildcard_depth: Some(1),
+                    ..ListEntry::from_path("loose.txt")
+                },
+            ],
+            ..BlocklistConfig::default()
+        };
+        let authority = BlocklistAuthority::try_from_config(
+            Name::root(),
+            ZoneType::Hint,
+            &config,
+            Some(&dir),
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+
+        async fn blocked(authority: &BlocklistAuthority, name: &str) -> bool {
+            authority
+                .lookup(
+                    &LowerName::from_str(name).unwrap(),
+                    RecordType::A,
+                    LookupOptions::default(),
+                )
+                .await
+                .map_or(false, |lookup| lookup.is_some())
+        }
+
+        // Test: each list's wildcards are held to its own depth, rather than the blocklist-wide depth of 2, in either
+        // direction.
+        assert!(!blocked(&authority, "www.example.com.").await);
+        assert!(blocked(&authority, "www.ads.example.net.").await);
+        assert!(blocked(&authority, "www.test.").await);
+
+        // Test: where the lists' wildcards overlap, each name is blocked by the most specific entry at or above its
+        // list's depth.
+        assert!(blocked(&authority, "www.example.net.").await);
+        let lists = authority.stats().lists;
+        assert_eq!(lists[0].blocked, 1);
+        assert_eq!(lists[1].blocked, 2);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_short_names() {
+        let names = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| LowerName::from_str(name).unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        for wildcard_blocks_parent in [false, true] {
+            let authority = BlocklistAuthorityBuilder::new(Name::root())
+                .min_wildcard_depth(0)
+                .wildcard_blocks_parent(wildcard_blocks_parent)
+                .add_entries([Name::from_str("*.com.").unwrap()])
+                .build()
+                .expect("Unable to create blocklist authority");
+
+            // Test: with a minimum wildcard depth of 0, the match lists of the root and of single label names end at the
+            // wildcard for the root, rather than overflowing.  The root is the parent of the wildcard for the root.
+            let policy = authority.policy();
+            let (root, com) = match wildcard_blocks_parent {
+                true => (names(&[".", "*."]), names(&["com.", "*.com.", "*."])),
+                false => (names(&["."]), names(&["com.", "*."])),
+            };
+            assert_eq!(policy.match_list(&LowerName::from(Name::root())), root);
+            assert_eq!(
+                policy.match_list(&LowerName::from_str("com.").unwrap()),
+                com
+            );
+
+            for (name, blocked) in [
+                ("x.com.", true),
+                ("com.", wildcard_blocks_parent),
+                ("x.org.", false),
+            ] {
+                let lookup = authority
+                    .lookup(
+                        &LowerName::from_str(name).unwrap(),
+                        RecordType::A,
+                        LookupOptions::default(),
+                    )
+                    .await;
+                assert_eq!(matches!(lookup, Ok(Some(_))), blocked, "{name}");
             }
         }
+    }
 
-        // Test: lookup a record that will match a wildcard that is in the blocklist.
-        let res = ao
-            .lookup(
-                &LowerName::from_str("www.com.foo.com.").unwrap(),
-                RecordType::A,
-                LookupOptions::default(),
-            )
-            .await;
-        match res {
-            Ok(Some(l)) => {
-                if !l.iter().all(|x| {
-                    x.name() == &Name::from_str("www.com.foo.com.").unwrap()
-                        && x.data() == Some(&RData::A(A::new(0, 0, 0, 0)))
-                }) {
-                    panic!("www.com.foo.com lookup data is incorrect.");
+    #[tokio::test]
+    async fn test_blocklist_sinkhole_pool() {
+        use crate::authority::LookupObject;
+
+        let pool = [
+            Ipv4Addr::new(192, 0, 2, 1),
+            Ipv4Addr::new(192, 0, 2, 2),
+            Ipv4Addr::new(192, 0, 2, 3),
+        ];
+        let authority = BlocklistAuthorityBuilder::new(Name::root())
+            .add_entries([Name::from_str("foo.com.").unwrap()])
+            .sinkhole_pool(pool.iter().copied().map(IpAddr::V4).collect())
+            .response_cache_size(100)
+            .build()
+            .expect("Unable to create blocklist authority");
+        let name = LowerName::from_str("foo.com.").unwrap();
+
+        // Test: each response carries a single address from the pool, and over many lookups every address is used, even
+        // with the response cache enabled.
+        let mut seen = HashSet::new();
+        for _ in 0..300 {
+            let lookup = authority
+                .lookup(&name, RecordType::A, LookupOptions::default())
+                .await
+                .unwrap()
+                .unwrap();
+            let answers = lookup.iter().collect::<Vec<_>>();
+            assert_eq!(answers.len(), 1);
+            match answers[0].data() {
+                RData::A(addr) if pool.contains(&addr.0) => {
+                    seen.insert(addr.0);
                 }
-            }
-            Ok(None) => {
-                panic!("lookup returned Ok(None); expected Ok(Some)");
-            }
-            Err(e) => {
-                panic!("Lookup error: {e}!");
+                data => panic!("unexpected answer {data}"),
             }
         }
+        assert_eq!(seen.len(), pool.len());
+
+        // Test: a family with no address in the pool is answered with its sinkhole address.
+        let lookup = authority
+            .lookup(&name, RecordType::AAAA, LookupOptions::default())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            lookup
+                .iter()
+                .map(|record| record.data())
+                .collect::<Vec<_>>(),
+            [&RData::AAAA(AAAA::from(Ipv6Addr::UNSPECIFIED))]
+        );
     }
 
     #[tokio::test]
-    async fn test_blocklist_wildcard_disabled() {
-        let config = super::BlocklistConfig {
-            min_wildcard_depth: 2,
+    async fn test_blocklist_set_wildcard_match() {
+        let dir = std::env::temp_dir().join(format!(
+            "hickory-blocklist-set-wildcard-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("list.txt"), "*.example.com\n*.ads.example.net\n").unwrap();
+
+        let config = BlocklistConfig {
             wildcard_match: false,
-            lists: vec!["default/blocklist.txt".to_string()],
+            lists: vec![ListEntry::from_path("list.txt")],
+            ..BlocklistConfig::default()
         };
-
-        let blocklist = super::BlocklistAuthority::try_from_config(
-            Name::from_str(".").unwrap(),
+        let authority = BlocklistAuthority::try_from_config(
+            Name::root(),
             ZoneType::Hint,
             &config,
-            Some(Path::new("../../tests/test-data/test_configs/")),
-        );
+            Some(&dir),
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+        async fn blocked(authority: &BlocklistAuthority, name: &str) -> bool {
+            authority
+                .lookup(
+                    &LowerName::from_str(name).unwrap(),
+                    RecordType::A,
+                    LookupOptions::default(),
+                )
+                .await
+                .unwrap()
+                .is_some()
+        }
 
-        let authority = blocklist.await;
+        // Test: subdomains are only blocked by wildcard entries once wildcard matching is turned on.
+        assert!(!blocked(&authority, "www.example.com.").await);
+        authority.set_wildcard_match(true, 2).await.unwrap();
+        assert!(blocked(&authority, "www.example.com.").await);
+        assert!(blocked(&authority, "one.ads.example.net.").await);
 
-        // Test: verify the blocklist authority was successfully created.
-        match authority {
-            Ok(ref _authority) => {}
-            Err(e) => {
-                panic!("Unable to create blocklist authority: {e}");
+        // Test: the settings are changed without reading the list again; only a reload sees the new entry.
+        std::fs::write(
+            dir.join("list.txt"),
+            "*.example.com\n*.ads.example.net\n*.tracker.example.org\n",
+        )
+        .unwrap();
+        authority.set_wildcard_match(true, 3).await.unwrap();
+        assert!(!blocked(&authority, "one.tracker.example.org.").await);
+
+        // Test: raising the minimum depth stops the shallower wildcard from matching, and the settings outlast a reload.
+        authority.reload().await;
+        assert!(!blocked(&authority, "www.example.com.").await);
+        assert!(blocked(&authority, "one.ads.example.net.").await);
+        assert!(blocked(&authority, "one.tracker.example.org.").await);
+
+        // Test: turning wildcard matching off again unblocks the subdomains.
+        authority.set_wildcard_match(false, 2).await.unwrap();
+        assert!(!blocked(&authority, "one.ads.example.net.").await);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_dnssec_types() {
+        use crate::proto::op::Edns;
+
+        // A query from a validating client, which sets DO
+        async fn query(catalog: &Catalog, rtype: RecordType) -> Message {
+            let mut message = Message::new();
+            message
+                .set_recursion_desired(true)
+                .add_query(Query::query(Name::from_str("foo.com.").unwrap(), rtype));
+            let bytes = message.to_vec().unwrap();
+            let request = Request::new(
+                MessageRequest::from_bytes(&bytes).unwrap(),
+                "127.0.0.1:5353".parse().unwrap(),
+                Protocol::Udp,
+            );
+
+            let mut edns = Edns::new();
+            edns.set_dnssec_ok(true);
+            let capture = CaptureResponse::default();
+            catalog.lookup(&request, Some(edns), capture.clone()).await;
+            let response = Message::from_vec(&capture.0.lock().unwrap()).unwrap();
+            response
+        }
+
+        for (block_response, response_code) in [
+            (BlockResponse::Sinkhole, ResponseCode::NoError),
+            (BlockResponse::Client, ResponseCode::NoError),
+            (BlockResponse::Cname, ResponseCode::NoError),
+            (BlockResponse::Dname, ResponseCode::NoError),
+            (BlockResponse::NoData, ResponseCode::NoError),
+            (BlockResponse::NxDomain, ResponseCode::NXDomain),
+        ] {
+            let config = BlocklistConfig {
+                block_response,
+                sinkhole_ipv4: Ipv4Addr::new(10, 0, 0, 1),
+                dname_target: Some(Name::from_str("blocked.example.").unwrap()),
+                ..BlocklistConfig::default()
+            };
+            let authority = BlocklistAuthorityBuilder::with_config(Name::root(), config)
+                .add_entries([Name::from_str("foo.com.").unwrap()])
+                .build()
+                .expect("Unable to create blocklist authority");
+            let mut catalog = Catalog::new();
+            catalog.upsert(
+                LowerName::from(Name::root()),
+                vec![Box::new(Arc::new(authority)) as Box<dyn AuthorityObject>],
+            );
+
+            // Test: DS, DNSKEY and the other DNSSEC types are answered with a negative response, never an address or a
+            // redirect, whatever the block response.
+            for rtype in [
+                RecordType::DS,
+                RecordType::DNSKEY,
+                RecordType::RRSIG,
+                RecordType::NSEC,
+                RecordType::CDS,
+            ] {
+                let response = query(&catalog, rtype).await;
+                assert_eq!(
+                    response.response_code(),
+                    response_code,
+                    "{block_response:?} {rtype}"
+                );
+                assert!(
+                    response.answers().is_empty(),
+                    "{block_response:?} {rtype}: {:?}",
+                    response.answers()
+                );
             }
         }
+    }
 
-        let ao = Box::new(Arc::new(authority.unwrap())) as Box<dyn AuthorityObject>;
+    #[tokio::test]
+    async fn test_blocklist_negative_soa_serial() {
+        let dir = std::env::temp_dir().join(format!(
+            "hickory-blocklist-soa-serial-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("list.txt"), "foo.com\n").unwrap();
 
-        // Test: lookup a record that is explicitly in the blocklist and that should match with a wildcard.
-        let res = ao
-            .lookup(
-                &LowerName::from_str("foo.com.").unwrap(),
-                RecordType::A,
-                LookupOptions::default(),
+        let config = BlocklistConfig {
+            lists: vec![ListEntry::from_path("list.txt")],
+            block_response: BlockResponse::NxDomain,
+            ..BlocklistConfig::default()
+        };
+        let authority = BlocklistAuthority::try_from_config(
+            Name::root(),
+            ZoneType::Hint,
+            &config,
+            Some(&dir),
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+        let authority = &authority;
+        let soa = |rtype| async move {
+            negative_soa(authority, "foo.com.", rtype)
+                .await
+                .expect("no SOA in the negative response")
+        };
+        let serial = |soa: &BlocklistLookup| match soa.lookup.records()[0].data() {
+            RData::SOA(soa) => soa.serial(),
+            data => panic!("unexpected SOA data {data:?}"),
+        };
+
+        // Test: the negative responses served with the same TTL share a single SOA record.
+        let first = soa(RecordType::A).await;
+        let second = soa(RecordType::AAAA).await;
+        assert!(std::ptr::eq(
+            first.lookup.records(),
+            second.lookup.records()
+        ));
+
+        // Test: each reload increases the serial, even several within a second.
+        let mut previous = serial(&first);
+        for _ in 0..3 {
+            assert!(authority.reload().await.is_ok());
+            let current = serial(&soa(RecordType::A).await);
+            assert!(current > previous, "{current} <= {previous}");
+            previous = current;
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_cname_cloaking() {
+        let config = BlocklistConfig {
+            block_cname_targets: true,
+            max_cname_targets: 2,
+            block_response: BlockResponse::NxDomain,
+            ..BlocklistConfig::default()
+        };
+        let authority = BlocklistAuthorityBuilder::with_config(Name::root(), config)
+            .add_entries([Name::from_str("*.tracker.example.net.").unwrap()])
+            .build()
+            .expect("Unable to create blocklist authority");
+        let chain = |names: &[&str]| {
+            let names = names
+                .iter()
+                .map(|name| Name::from_str(name).unwrap())
+                .collect::<Vec<_>>();
+            let mut answers = names
+                .windows(2)
+                .map(|pair| {
+                    Record::from_rdata(pair[0].clone(), 300, RData::CNAME(CNAME(pair[1].clone())))
+                })
+                .collect::<Vec<_>>();
+            answers.push(Record::from_rdata(
+                names.last().unwrap().clone(),
+                300,
+                RData::A(A::new(192, 0, 2, 1)),
+            ));
+            answers
+        };
+        let name = LowerName::from_str("metrics.shop.example.").unwrap();
+
+        // Test: a first-party subdomain which CNAMEs to a tracker is answered with the block response for the query.
+        let answers = chain(&["metrics.shop.example.", "shop.tracker.example.net."]);
+        match authority.filter_answers(&name, RecordType::A, answers) {
+            Err(e) if e.is_nx_domain() => {}
+            result => panic!("expected NXDOMAIN; got {result:?}"),
+        }
+
+        // Test: a tracker within the first max_cname_targets CNAMEs of a chain is found.
+        let answers = chain(&[
+            "metrics.shop.example.",
+            "edge.cdn.example.",
+            "shop.tracker.example.net.",
+        ]);
+        assert!(authority
+            .filter_answers(&name, RecordType::A, answers)
+            .is_err());
+
+        // Test: CNAMEs past max_cname_targets are not checked, and answers aliasing no tracker are unchanged.
+        for names in [
+            &[
+                "metrics.shop.example.",
+                "a.cdn.example.",
+                "b.cdn.example.",
+                "shop.tracker.example.net.",
+            ][..],
+            &["metrics.shop.example.", "edge.cdn.example."][..],
+        ] {
+            let answers = chain(names);
+            let filtered = authority
+                .filter_answers(&name, RecordType::A, answers.clone())
+                .expect("filter_answers failed");
+            assert_eq!(filtered, answers, "{names:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_include_apex() {
+        let dir = std::env::temp_dir().join(format!(
+            "hickory-blocklist-include-apex-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("apex.txt"), ".example.com\n").unwrap();
+        std::fs::write(
+            dir.join("subdomains.txt"),
+            ".example.net\n-.gone.example.net\n",
+        )
+        .unwrap();
+
+        let config = BlocklistConfig {
+            lists: vec![
+                ListEntry::from_path("apex.txt"),
+                ListEntry {
+                    include_apex: false,
+                    ..ListEntry::from_path("subdomains.txt")
+                },
+            ],
+            ..BlocklistConfig::default()
+        };
+        let authority = BlocklistAuthority::try_from_config(
+            Name::root(),
+            ZoneType::Hint,
+            &config,
+            Some(&dir),
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+
+        async fn blocked(authority: &BlocklistAuthority, name: &str) -> bool {
+            authority
+                .lookup(
+                    &LowerName::from_str(name).unwrap(),
+                    RecordType::A,
+                    LookupOptions::default(),
+                )
+                .await
+                .map_or(false, |lookup| lookup.is_some())
+        }
+
+        // Test: by default, a leading dot blocks the apex as well as the names below it.
+        assert!(blocked(&authority, "example.com.").await);
+        assert!(blocked(&authority, "www.example.com.").await);
+
+        // Test: without the apex, only the names below it are blocked.
+        assert!(!blocked(&authority, "example.net.").await);
+        assert!(blocked(&authority, "www.example.net.").await);
+        assert!(blocked(&authority, "a.b.example.net.").await);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_local_addresses() {
+        let authority = BlocklistAuthorityBuilder::new(Name::root())
+            .local_addresses(vec![IpAddr::from([192, 168, 1, 1])])
+            .add_entries([Name::from_str("foo.com.").unwrap()])
+            .build()
+            .expect("Unable to create blocklist authority");
+
+        let mut message = Message::new();
+        message.add_query(Query::query(
+            Name::from_str("foo.com.").unwrap(),
+            RecordType::A,
+        ));
+        let bytes = message.to_vec().unwrap();
+        let search = |local: Option<&str>| {
+            let request = Request::new(
+                MessageRequest::from_bytes(&bytes).unwrap(),
+                "192.168.1.100:5353".parse().unwrap(),
+                Protocol::Udp,
             )
-            .await;
-        match res {
-            Ok(Some(l)) => {
-                if !l.iter().all(|x| {
-                    x.name() == &Name::from_str("foo.com.").unwrap()
-                        && x.data() == Some(&RData::A(A::new(0, 0, 0, 0)))
-                }) {
-                    panic!("foo.com lookup data is incorrect.");
-                }
-            }
-            Ok(None) => {
-                panic!("Lookup returned Ok(None); expected Ok(Some)");
-            }
-            Err(e) => {
-                panic!("Lookup error: {e}!");
+            .with_local_addr(local.map(|local| local.parse().unwrap()));
+            let authority = &authority;
+            async move {
+                authority
+                    .search(request.request_info(), LookupOptions::default())
+                    .await
             }
+        };
+
+        // Test: queries received on a listed local address are blocked.
+        assert!(matches!(search(Some("192.168.1.1:53")).await, Ok(Some(_))));
+
+        // Test: queries received on any other local address, e.g. a management interface, are passed on.
+        assert!(matches!(search(Some("10.0.0.1:53")).await, Ok(None)));
+
+        // Test: queries whose local address is not known are blocked as usual.
+        assert!(matches!(search(None).await, Ok(Some(_))));
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_set_sinkhole() {
+        use crate::authority::LookupObject;
+
+        let config = BlocklistConfig {
+            public_sinkhole: PublicSinkholePolicy::Refuse,
+            response_cache_size: 16,
+            ..BlocklistConfig::default()
+        };
+        let authority = BlocklistAuthorityBuilder::with_config(Name::root(), config)
+            .add_entries([Name::from_str("foo.com.").unwrap()])
+            .build()
+            .expect("Unable to create blocklist authority");
+
+        async fn answer(authority: &BlocklistAuthority, rtype: RecordType) -> RData {
+            let lookup = authority
+                .lookup(
+                    &LowerName::from_str("foo.com.").unwrap(),
+                    rtype,
+                    LookupOptions::default(),
+                )
+                .await
+                .unwrap()
+                .unwrap();
+            let record = lookup.iter().next().unwrap();
+            record.data().clone()
         }
+        assert_eq!(
+            answer(&authority, RecordType::A).await,
+            RData::A(A::new(0, 0, 0, 0))
+        );
 
-        // Test: lookup a record that is not in the blocklist, but would match a wildcard; this should fail.
-        let res = ao
-            .lookup(
-                &LowerName::from_str("www.foo.com.").unwrap(),
-                RecordType::A,
-                LookupOptions::default(),
+        // Test: once changed, blocked names are answered with the new addresses, rather than the cached responses.  The
+        // lists are unchanged, so the serial of negative responses is kept.
+        let serial = authority.policy().serial;
+        authority
+            .set_sinkhole(
+                Ipv4Addr::new(10, 0, 0, 2),
+                Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 2),
             )
-            .await;
-        match res {
-            Ok(Some(_l)) => {
-                panic!("www.foo.com lookup returned Ok(Some); expected Ok(None)");
-            }
-            Ok(None) => {}
-            Err(_e) => {
-                panic!("www.foo.com lookup returned Err; expected Ok(None)");
+            .await
+            .unwrap();
+        assert_eq!(authority.policy().serial, serial);
+        assert_eq!(
+            answer(&authority, RecordType::A).await,
+            RData::A(A::new(10, 0, 0, 2))
+        );
+        assert_eq!(
+            answer(&authority, RecordType::AAAA).await,
+            RData::AAAA(AAAA::new(0xfd00, 0, 0, 0, 0, 0, 0, 2))
+        );
+
+        // Test: publicly routable addresses are refused, and the previous addresses stay in use.
+        assert!(authority
+            .set_sinkhole(Ipv4Addr::new(192, 0, 2, 1), Ipv6Addr::UNSPECIFIED)
+            .await
+            .is_err());
+        assert_eq!(
+            answer(&authority, RecordType::A).await,
+            RData::A(A::new(10, 0, 0, 2))
+        );
+
+        // Test: the new addresses are kept by a reload.
+        authority.reload().await;
+        assert_eq!(
+            answer(&authority, RecordType::A).await,
+            RData::A(A::new(10, 0, 0, 2))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_subnet_sinkholes() {
+        use crate::authority::LookupObject;
+        use crate::proto::op::Edns;
+        use crate::proto::rr::rdata::opt::EdnsOption;
+        use crate::store::blocklist::SubnetSinkhole;
+
+        let authority = BlocklistAuthorityBuilder::new(Name::root())
+            .subnet_sinkholes(vec![
+                SubnetSinkhole {
+                    subnet: "192.0.2.0/24".parse().unwrap(),
+                    sinkhole_ipv4: Some(Ipv4Addr::new(10, 0, 0, 1)),
+                    sinkhole_ipv6: None,
+                },
+                SubnetSinkhole {
+                    subnet: "198.51.100.0/24".parse().unwrap(),
+                    sinkhole_ipv4: Some(Ipv4Addr::new(10, 0, 0, 2)),
+                    sinkhole_ipv6: None,
+                },
+            ])
+            .add_entries([Name::from_str("foo.com.").unwrap()])
+            .build()
+            .expect("Unable to create blocklist authority");
+
+        async fn answer(authority: &BlocklistAuthority, subnet: Option<&str>) -> RData {
+            let mut message = Message::new();
+            message.add_query(Query::query(
+                Name::from_str("foo.com.").unwrap(),
+                RecordType::A,
+            ));
+            if let Some(subnet) = subnet {
+                let mut edns = Edns::new();
+                edns.options_mut()
+                    .insert(EdnsOption::Subnet(subnet.parse().unwrap()));
+                message.set_edns(edns);
             }
+            let request = Request::new(
+                MessageRequest::from_bytes(&message.to_vec().unwrap()).unwrap(),
+                "192.168.1.100:5353".parse().unwrap(),
+                Protocol::Udp,
+            );
+            let lookup = authority
+                .search(request.request_info(), LookupOptions::default())
+                .await
+                .unwrap()
+                .unwrap();
+            let answer = lookup.iter().next().unwrap().data().clone();
+            answer
         }
+
+        // Test: clients in each configured subnet get that subnet's sinkhole.
+        assert_eq!(
+            answer(&authority, Some("192.0.2.0/24")).await,
+            RData::A(A::new(10, 0, 0, 1))
+        );
+        assert_eq!(
+            answer(&authority, Some("198.51.100.0/24")).await,
+            RData::A(A::new(10, 0, 0, 2))
+        );
+
+        // Test: a more specific client subnet within a configured one matches it.
+        assert_eq!(
+            answer(&authority, Some("192.0.2.128/25")).await,
+            RData::A(A::new(10, 0, 0, 1))
+        );
+
+        // Test: queries without a client subnet, or with one in no configured subnet, get the default sinkhole.
+        assert_eq!(answer(&authority, None).await, RData::A(A::new(0, 0, 0, 0)));
+        assert_eq!(
+            answer(&authority, Some("203.0.113.0/24")).await,
+            RData::A(A::new(0, 0, 0, 0))
+        );
     }
 }
@@ -0,0 +1,665 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Binary encoding of a blocklist policy, compiled ahead of time from the configured lists
+
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use ipnet::IpNet;
+use ring::digest;
+use serde::Serialize;
+
+use crate::{
+    proto::rr::LowerName,
+    store::blocklist::{BlockResponse, ListAction, ListFormat, PublicSinkholePolicy},
+};
+
+/// Identifies a compiled policy file
+const MAGIC: &[u8; 8] = b"HDNSBLP\0";
+
+/// Version of the encoding.  This must be changed whenever the encoding, or the settings its key covers, change, so that
+/// older and newer servers reject policies they can not read, rather than misreading them.
+pub(crate) const FORMAT_VERSION: u32 = 8;
+
+/// The tables read from the configured lists, with list indices relative to the first configured list
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct CompiledPolicy {
+    /// The digest of the [`CompileKey`] of the list configuration the policy was compiled from
+    pub(crate) key: String,
+    pub(crate) lists: Vec<CompiledList>,
+    pub(crate) blocked: Vec<(LowerName, u32)>,
+    pub(crate) monitored: Vec<(LowerName, u32)>,
+    /// Responses, sinkhole addresses and TTLs set by individual lines of extended format lists
+    pub(crate) responses: Vec<CompiledResponse>,
+    pub(crate) networks: Vec<(IpNet, u32)>,
+    /// Ranges read from reverse format lists
+    pub(crate) reverse: Vec<(IpNet, u32)>,
+}
+
+/// A single list read when compiling a policy
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct CompiledList {
+    pub(crate) kind: ListKind,
+    pub(crate) source: String,
+    pub(crate) remote: bool,
+    pub(crate) entries: u64,
+    pub(crate) loaded: SystemTime,
+}
+
+/// The response set for an entry by its line in an extended format list
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct CompiledResponse {
+    pub(crate) name: LowerName,
+    pub(crate) list: u32,
+    pub(crate) response: Option<BlockResponse>,
+    pub(crate) sinkhole: Option<IpAddr>,
+    pub(crate) ttl: Option<u32>,
+}
+
+/// Every setting which affects the entries read from the lists, which list each entry belongs to, and how the entries are
+/// matched.  Settings which are only applied when answering queries are left out, so that they can be changed without
+/// compiling the policy again.
+#[derive(Debug, Serialize)]
+pub(crate) struct CompileKey<'a> {
+    pub(crate) lists: Vec<CompileListKey<'a>>,
+    pub(crate) use_default_lists: bool,
+    pub(crate) ip_blocklists: &'a [String],
+    pub(crate) max_line_length: usize,
+    pub(crate) wildcard_match: bool,
+    pub(crate) min_wildcard_depth: u8,
+    pub(crate) wildcard_token: &'a str,
+    /// Sinkhole addresses set by lines of extended format lists are checked against these when the lines are read
+    pub(crate) public_sinkhole: PublicSinkholePolicy,
+    pub(crate) sinkhole_networks: &'a [IpNet],
+}
+
+/// The settings of a single configured list which are part of a [`CompileKey`]
+#[derive(Debug, Serialize)]
+pub(crate) struct CompileListKey<'a> {
+    pub(crate) path: Option<&'a str>,
+    pub(crate) url: Option<&'a str>,
+    pub(crate) sha256: Option<&'a str>,
+    pub(crate) format: ListFormat,
+    pub(crate) encoding: Option<&'a str>,
+    pub(crate) action: Option<ListAction>,
+    pub(crate) priority: i32,
+    pub(crate) enabled: bool,
+    pub(crate) depth: Option<u8>,
+    pub(crate) min_wildcard_depth: Option<u8>,
+    pub(crate) include_apex: bool,
+}
+
+impl CompileKey<'_> {
+    /// The hex SHA-256 digest of the key, which is recorded in the header of a compiled policy
+    pub(crate) fn digest(&self) -> String {
+        let key = toml::to_string(self).expect("blocklist compile key is not serializable");
+        digest::digest(&digest::SHA256, key.as_bytes())
+            .as_ref()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+}
+
+/// Which part of the configuration a compiled list was read from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ListKind {
+    /// The name list at this index of the configured lists, followed by the monitor lists
+    Names(u32),
+    /// An address list
+    Addresses,
+    /// The default list bundled with the server
+    Default,
+}
+
+impl CompiledPolicy {
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut out = Encoder(Vec::new());
+        out.0.extend_from_slice(MAGIC);
+        out.u32(FORMAT_VERSION);
+        out.str(&self.key);
+
+        out.len(self.lists.len());
+        for list in &self.lists {
+            match list.kind {
+                ListKind::Names(index) => {
+                    out.u8(0);
+                    out.u32(index);
+                }
+                ListKind::Addresses => out.u8(1),
+                ListKind::Default => out.u8(2),
+            }
+            out.str(&list.source);
+            out.u8(u8::from(list.remote));
+            out.u64(list.entries);
+            let loaded = list.loaded.duration_since(UNIX_EPOCH).unwrap_or_default();
+            out.u64(loaded.as_secs());
+        }
+
+        for table in [&self.blocked, &self.monitored] {
+            out.len(table.len());
+            for (name, list) in table {
+                out.name(name);
+                out.u32(*list);
+            }
+        }
+
+        out.len(self.responses.len());
+        for response in &self.responses {
+            out.name(&response.name);
+            out.u32(response.list);
+            out.u8(match response.response {
+                Some(BlockResponse::Sinkhole) => 0,
+                Some(BlockResponse::NxDomain) => 1,
+                Some(BlockResponse::NoData) => 2,
+                Some(BlockResponse::Client) => 3,
+                Some(BlockResponse::Dname) => 4,
+                Some(BlockResponse::Cname) => 5,
+                None => u8::MAX,
+            });
+            out.addr(response.sinkhole);
+            out.ttl(response.ttl);
+        }
+
+        for table in [&self.networks, &self.reverse] {
+            out.len(table.len());
+            for (net, list) in table {
+                out.addr(Some(net.addr()));
+                out.u8(net.prefix_len());
+                out.u32(*list);
+            }
+        }
+
+        out.0
+    }
+
+    pub(crate) fn decode(data: &[u8]) -> Result<Self, String> {
+        let mut input = Decoder(data);
+        if input.take(MAGIC.len())? != MAGIC {
+            return Err("not a compiled blocklist policy".to_string());
+        }
+
+        let version = input.u32()?;
+        if version != FORMAT_VERSION {
+            return Err(format!(
+                "compiled blocklist policy has format version {version}, but this server reads version {FORMAT_VERSION}"
+            ));
+        }
+
+        let mut policy = Self {
+            key: input.str()?,
+            ..Self::default()
+        };
+
+        for _ in 0..input.u32()? {
+            let kind = match input.u8()? {
+                0 => ListKind::Names(input.u32()?),
+                1 => ListKind::Addresses,
+                2 => ListKind::Default,
+                kind => return Err(format!("invalid list kind {kind}")),
+            };
+            policy.lists.push(CompiledList {
+                kind,
+                source: input.str()?,
+                remote: input.u8()? != 0,
+                entries: input.u64()?,
+                loaded: UNIX_EPOCH + Duration::from_secs(input.u64()?),
+            });
+        }
+
+        for table in [&mut policy.blocked, &mut policy.monitored] {
+            for _ in 0..input.u32()? {
+                table.push((input.name()?, input.u32()?));
+            }
+        }
+
+        for _ in 0..input.u32()? {
+            let name = input.name()?;
+            let list = input.u32()?;
+            let response = match input.u8()? {
+                0 => Some(BlockResponse::Sinkhole),
+                1 => Some(BlockResponse::NxDomain),
+                2 => Some(BlockResponse::NoData),
+                3 => Some(BlockResponse::Client),
+                4 => Some(BlockResponse::Dname),
+                5 => Some(BlockResponse::Cname),
+                u8::MAX => None,
+                response => return Err(format!("invalid block response {response}")),
+            };
+            policy.responses.push(CompiledResponse {
+                name,
+                list,
+                response,
+                sinkhole: input.addr()?,
+                ttl: input.ttl()?,
+            });
+        }
+
+        for table in [&mut policy.networks, &mut policy.reverse] {
+            for _ in 0..input.u32()? {
+                let addr = input.addr()?.ok_or("missing network address")?;
+                let net = IpNet::new(addr, input.u8()?).map_err(|e| e.to_string())?;
+                table.push((net, input.u32()?));
+            }
+        }
+
+        if !input.0.is_empty() {
+            return Err(format!("{} unexpected trailing bytes", input.0.len()));
+        }
+
+        Ok(policy)
+    }
+}
+
+/// Appends values in little endian byte order; strings and tables are preceded by their length
+struct Encoder(Vec<u8>);
+
+impl Encoder {
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn len(&mut self, len: usize) {
+        self.u32(u32::try_from(len).expect("compiled blocklist table too large"));
+    }
+
+    fn str(&mut self, value: &str) {
+        self.len(value.len());
+        self.0.extend_from_slice(value.as_bytes());
+    }
+
+    fn name(&mut self, name: &LowerName) {
+        self.str(&name.to_string());
+    }
+
+    fn addr(&mut self, addr: Option<IpAddr>) {
+        match addr {
+            None => self.u8(0),
+            Some(IpAddr::V4(addr)) => {
+                self.u8(4);
+                self.0.extend_from_slice(&addr.octets());
+            }
+            Some(IpAddr::V6(addr)) => {
+                self.u8(6);
+                self.0.extend_from_slice(&addr.octets());
+            }
+        }
+    }
+
+    fn ttl(&mut self, ttl: Option<u32>) {
+        match ttl {
+            None => self.u8(0),
+            Some(ttl) => {
+                self.u8(1);
+                self.u32(ttl);
+            }
+        }
+    }
+}
+
+/// Reads the values written by [`Encoder`], failing on truncated or malformed input
+struct Decoder<'a>(&'a [u8]);
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.0.len() < len {
+            return Err("compiled blocklist policy is truncated".to_string());
+        }
+
+        let (value, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(value)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        self.array().map(u64::from_le_bytes)
+    }
+
+    fn str(&mut self) -> Result<String, String> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|e| e.to_string())
+    }
+
+    fn name(&mut self) -> Result<LowerName, String> {
+        let name = self.str()?;
+        LowerName::from_str(&name).map_err(|e| format!("invalid name {name:?}: {e}"))
+    }
+
+    fn addr(&mut self) -> Result<Option<IpAddr>, String> {
+        match self.u8()? {
+            0 => Ok(None),
+            4 => Ok(Some(IpAddr::V4(Ipv4Addr::from(self.array::<4>()?)))),
+            6 => Ok(Some(IpAddr::V6(Ipv6Addr::from(self.array::<16>()?)))),
+            family => Err(format!("invalid address family {family}")),
+        }
+    }
+
+    fn ttl(&mut self) -> Result<Option<u32>, String> {
+        match self.u8()? {
+            0 => Ok(None),
+            1 => self.u32().map(Some),
+            tag => Err(format!("invalid TTL tag {tag}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::proto::rr::Record;
+    use crate::store::blocklist::authority::BlocklistLookup;
+    use crate::store::blocklist::{
+        BlockResponse, BlocklistAuthority, BlocklistConfig, ListAction, ListEntry, ListFormat,
+        PublicSinkholePolicy,
+    };
+    use crate::{
+        authority::{Authority, LookupError, LookupOptions, ZoneType},
+        proto::rr::domain::Name,
+        proto::rr::{rdata::A, LowerName, RData, RecordType},
+    };
+    use std::path::Path;
+    use std::str::FromStr;
+
+    #[tokio::test]
+    async fn test_blocklist_compiled_policy() {
+        async fn lookup(
+            authority: &BlocklistAuthority,
+            name: &str,
+            rtype: RecordType,
+        ) -> Result<Option<BlocklistLookup>, LookupError> {
+            authority
+                .lookup(
+                    &LowerName::from_str(name).unwrap(),
+                    rtype,
+                    LookupOptions::default(),
+                )
+                .await
+        }
+
+        let dir =
+            std::env::temp_dir().join(format!("hickory-blocklist-compiled-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let compiled = dir.join("policy.bin");
+        let root_dir = Some(Path::new("../../tests/test-data/test_configs/"));
+
+        let config = BlocklistConfig {
+            lists: vec![
+                ListEntry::from_path("default/blocklist.txt"),
+                ListEntry {
+                    format: ListFormat::Extended,
+                    ..ListEntry::from_path("default/blocklist_extended.txt")
+                },
+            ],
+            monitor_lists: vec![ListEntry::from_path("default/blocklist2.txt")],
+            ip_blocklists: vec!["default/blocklist_ips.txt".to_string()],
+            compiled_policy: Some(compiled.display().to_string()),
+            ..BlocklistConfig::default()
+        };
+        let load = |config: BlocklistConfig| async move {
+            BlocklistAuthority::try_from_config(
+                Name::from_str(".").unwrap(),
+                ZoneType::Hint,
+                &config,
+                root_dir,
+                None,
+            )
+            .await
+        };
+
+        // Test: loading fails until the policy has been compiled.
+        assert!(load(config.clone()).await.is_err());
+
+        let path = BlocklistAuthority::compile(Name::root(), &config, root_dir, None)
+            .await
+            .expect("compile failed");
+        assert_eq!(path, compiled);
+
+        // Test: the compiled policy holds the same lists and entries as the lists themselves.
+        let authority = load(config.clone()).await.expect("compiled policy failed");
+        let parsed = load(BlocklistConfig {
+            compiled_policy: None,
+            ..config.clone()
+        })
+        .await
+        .unwrap();
+        let summary = |authority: &BlocklistAuthority| {
+            authority
+                .list_sources()
+                .into_iter()
+                .map(|list| (list.source, list.action, list.entries))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(summary(&authority), summary(&parsed));
+
+        for name in ["baddomain.com.", "www.foo.com.", "plain.example.com."] {
+            assert!(
+                matches!(lookup(&authority, name, RecordType::A).await, Ok(Some(_))),
+                "{name}"
+            );
+        }
+        assert!(matches!(
+            lookup(&authority, "www.test.com.", RecordType::A).await,
+            Ok(None)
+        ));
+
+        // Test: per-line responses and address lists are part of the compiled policy.
+        match lookup(&authority, "nx.example.com.", RecordType::A).await {
+            Err(e) if e.is_nx_domain() => {}
+            Ok(_) => panic!("lookup returned Ok; expected NXDOMAIN"),
+            Err(e) => panic!("lookup returned {e}; expected NXDOMAIN"),
+        }
+        let name = Name::from_str("www.test.com.").unwrap();
+        let answers = vec![Record::from_rdata(
+            name.clone(),
+            300,
+            RData::A(A::new(192, 0, 2, 10)),
+        )];
+        let filtered = authority
+            .filter_answers(&LowerName::from(&name), RecordType::A, answers)
+            .expect("filter_answers failed");
+        assert_eq!(filtered[0].data(), &RData::A(A::new(0, 0, 0, 0)));
+
+        // Test: settings applied when answering queries can change without compiling the policy again.
+        let authority = load(BlocklistConfig {
+            block_response: BlockResponse::NxDomain,
+            ..config.clone()
+        })
+        .await
+        .expect("compiled policy failed");
+        match lookup(&authority, "baddomain.com.", RecordType::A).await {
+            Err(e) if e.is_nx_domain() => {}
+            Ok(_) => panic!("lookup returned Ok; expected NXDOMAIN"),
+            Err(e) => panic!("lookup returned {e}; expected NXDOMAIN"),
+        }
+
+        // Test: a policy compiled from different lists is rejected.
+        let err = load(BlocklistConfig {
+            lists: vec![ListEntry::from_path("default/blocklist.txt")],
+            ..config.clone()
+        })
+        .await
+        .err()
+        .expect("mismatched compiled policy loaded");
+        assert!(err.contains("different list configuration"), "{err}");
+
+        // Test: so is a policy compiled with a different value for any part of its key: each setting of each list, and each
+        // blocklist-wide setting which affects parsing or matching.
+        let list = |change: fn(&mut ListEntry)| {
+            let mut lists = config.lists.clone();
+            change(&mut lists[0]);
+            BlocklistConfig {
+                lists,
+                ..config.clone()
+            }
+        };
+        let changed = [
+            list(|entry| entry.path = Some("default/blocklist2.txt".to_string())),
+            list(|entry| {
+                entry.path = None;
+                entry.url = Some("http://127.0.0.1/blocklist.txt".to_string());
+            }),
+            list(|entry| entry.sha256 = Some("0".repeat(64))),
+            list(|entry| entry.format = ListFormat::Hosts),
+            list(|entry| entry.encoding = Some("latin1".to_string())),
+            list(|entry| entry.action = Some(ListAction::Monitor)),
+            list(|entry| entry.priority = 1),
+            list(|entry| entry.enabled = false),
+            list(|entry| entry.depth = Some(2)),
+            list(|entry| entry.min_wildcard_depth = Some(3)),
+            list(|entry| entry.include_apex = false),
+            BlocklistConfig {
+                use_default_lists: true,
+                ..config.clone()
+            },
+            BlocklistConfig {
+                ip_blocklists: Vec::new(),
+                ..config.clone()
+            },
+            BlocklistConfig {
+                max_line_length: 10,
+                ..config.clone()
+            },
+            BlocklistConfig {
+                wildcard_match: false,
+                ..config.clone()
+            },
+            BlocklistConfig {
+                min_wildcard_depth: 3,
+                ..config.clone()
+            },
+            BlocklistConfig {
+                wildcard_token: "@".to_string(),
+                ..config.clone()
+            },
+            BlocklistConfig {
+                public_sinkhole: PublicSinkholePolicy::Allow,
+                ..config.clone()
+            },
+            BlocklistConfig {
+                sinkhole_networks: vec!["192.0.2.0/24".parse().unwrap()],
+                ..config.clone()
+            },
+        ];
+        for changed in changed {
+            let err = load(changed)
+                .await
+                .err()
+                .expect("stale compiled policy loaded");
+            assert!(err.contains("different list configuration"), "{err}");
+        }
+
+        // Test: a policy with a different format version is rejected.
+        let mut data = std::fs::read(&compiled).unwrap();
+        data[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+        std::fs::write(&compiled, data).unwrap();
+        let err = load(config)
+            .await
+            .err()
+            .expect("incompatible compiled policy loaded");
+        assert!(err.contains("format version"), "{err}");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_compiled_default_list() {
+        let dir = std::env::temp_dir().join(format!(
+            "hickory-blocklist-compiled-default-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("list.txt"), "ads.example.com\n-coinhive.com\n").unwrap();
+
+        let config = BlocklistConfig {
+            lists: vec![ListEntry::from_path("list.txt")],
+            use_default_lists: true,
+            compiled_policy: Some("policy.bin".to_string()),
+            ..BlocklistConfig::default()
+        };
+        let load = |config: BlocklistConfig| {
+            let dir = dir.clone();
+            async move {
+                BlocklistAuthority::try_from_config(
+                    Name::root(),
+                    ZoneType::Hint,
+                    &config,
+                    Some(&dir),
+                    None,
+                )
+                .await
+            }
+        };
+        BlocklistAuthority::compile(Name::root(), &config, Some(&dir), None)
+            .await
+            .expect("compile failed");
+        let authority = load(config.clone()).await.expect("compiled policy failed");
+        let parsed = load(BlocklistConfig {
+            compiled_policy: None,
+            ..config.clone()
+        })
+        .await
+        .unwrap();
+
+        // Test: the default list is compiled into the policy, with the names negated by the configured lists removed, as
+        // when the lists are parsed.
+        for authority in [&authority, &parsed] {
+            let policy = authority.policy();
+            let blocked = |name: &str| {
+                let name = LowerName::from_str(name).unwrap();
+                let match_list = policy.match_list(&name);
+                policy.find_list(&name, &match_list).is_some()
+            };
+            assert!(blocked("ads.example.com."));
+            assert!(blocked("www.coin-hive.com."));
+            assert!(!blocked("coinhive.com."));
+
+            let sources = authority
+                .list_sources()
+                .into_iter()
+                .map(|list| list.source)
+                .collect::<Vec<_>>();
+            assert_eq!(sources.last().map(String::as_str), Some("default"));
+        }
+
+        // Test: a policy compiled with the default list is rejected once it is disabled.
+        let err = load(BlocklistConfig {
+            use_default_lists: false,
+            ..config
+        })
+        .await
+        .err()
+        .expect("stale compiled policy loaded");
+        assert!(err.contains("different list configuration"), "{err}");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -5,7 +5,15 @@
 // https://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+};
+
+use ipnet::IpNet;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::proto::rr::{Name, RecordType};
 
 /// Configuration for file based zones
 #[derive(Clone, Deserialize, Eq, PartialEq, Debug)]
@@ -14,27 +22,1052 @@ pub struct BlocklistConfig {
     #[serde(default = "wildcard_match_default")]
     pub wildcard_match: bool,
 
+    /// The label which marks a wildcard entry, for lists which use a convention other than `*`, e.g. `%.example.com`.
+    /// Defaults to `*`, which is matched as a wildcard whatever the token.  An entry with a leading dot, such as
+    /// `.example.com`, blocks the name and every name below it.
+    #[serde(default = "wildcard_token_default")]
+    pub wildcard_token: String,
+
+    /// Whether a wildcard entry also blocks the name it is a wildcard of: if set, `*.ads.example.com` blocks
+    /// `ads.example.com` as well as the names below it.  Defaults to false, so a wildcard only blocks names below its
+    /// parent; a leading dot entry, `.ads.example.com`, blocks both regardless.  A query for the literal name
+    /// `*.ads.example.com` always matches the entry.  The parent is only matched if it has at least `min_wildcard_depth`
+    /// labels.
+    #[serde(default)]
+    pub wildcard_blocks_parent: bool,
+
+    /// Whether queries for the origin of the blocklist itself, e.g. `.` for a blocklist at the root, are matched against
+    /// the lists.  Defaults to false: such queries are always passed on, so that the SOA and NS records of the zone are
+    /// answered by the next store, even if a list or a wildcard entry names the origin.
+    #[serde(default)]
+    pub block_origin: bool,
+
+    /// How queries whose name can not be matched against the lists are answered: names which are not fully qualified,
+    /// such as the empty name, and names with a label containing a dot.  These should never reach the blocklist from the
+    /// server, and are never blocked.  Defaults to passing them on to the next store.
+    #[serde(default)]
+    pub malformed_query: MalformedQueryPolicy,
+
     /// Minimum wildcard depth.  Defaults to 2.  Any wildcard entries without at least this many static elements will not be expanded
     /// (e.g., *.com has a depth of 1; *.example.com has a depth of two.)
+    /// Lists can override this with their own `min_wildcard_depth`.
     #[serde(default = "min_wildcard_depth_default")]
     pub min_wildcard_depth: u8,
 
     /// This is meant as a safeguard against an errant block list entry, such as * or *.com that
     /// might block many more hosts than intended.  
     /// block lists to load.  These should be specified as relative (to the server zone directory) paths in the config file.
-    pub lists: Vec<String>,
+    /// Each list may be given as a bare path, or as a table of [`ListEntry`] settings.
+    #[serde(deserialize_with = "deserialize_lists")]
+    pub lists: Vec<ListEntry>,
+
+    /// Also load the default list bundled with the server, for protection without sourcing lists first.  The list is small
+    /// and conservative, holding only names with no legitimate use, currently the domains of in-browser cryptocurrency
+    /// miners; see `default_list.txt` in the blocklist store's source for its contents.  It is loaded after the `lists`,
+    /// with the blocklist-wide settings, and is named `default` in logs and stats.  Its names can be let through with a
+    /// negation line, such as `-coinhive.com`, on any configured list, or with `first_party_domains`.  Defaults to false.
+    #[serde(default)]
+    pub use_default_lists: bool,
+
+    /// Lists of names which are logged and counted when queried, but not answered, so resolution proceeds as if they were
+    /// not listed; names which are also on a block list are still blocked.  These are shorthand for lists with the monitor
+    /// action.
+    #[serde(default, deserialize_with = "deserialize_lists")]
+    pub monitor_lists: Vec<ListEntry>,
+
+    /// Manifest files, each naming further lists to load after the `lists`, so that a catalog of lists can be curated and
+    /// shared apart from the server configuration.  A manifest is a TOML file with a single `lists` array, whose entries take the same form as those of `lists`, e.g.
+    /// `lists = [{ url = "https://example.com/ads.txt", format = "hosts", category = "ads" }]`.  Relative manifest paths
+    /// are resolved in the same way as list paths, and relative list paths within a manifest against the directory
+    /// containing it.  Manifests are read once, when the blocklist is created.  Defaults to none.
+    #[serde(default)]
+    pub manifests: Vec<String>,
+
+    /// Settings shared by the lists in each category, e.g. malware or ads, by the name lists give in their `category`.
+    /// A list's own settings override those of its category.
+    #[serde(default)]
+    pub categories: HashMap<String, Category>,
+
+    /// Address record types which are answered with NODATA, rather than a sinkhole address, when the queried name is
+    /// blocked, e.g. AAAA where there is no IPv6 sinkhole.  Sinkhole and client responses only answer A, AAAA and ANY
+    /// queries with an address; every other type, including the HTTPS and SVCB records which carry address hints and the
+    /// SRV and NAPTR records of service discovery, is always answered with NODATA, so that no answer is of a different
+    /// type than the question.  Defaults to none.
+    #[serde(default)]
+    pub nodata_types: Vec<RecordType>,
+
+    /// How queries for blocked names are answered.  Defaults to sinkhole.
+    #[serde(default)]
+    pub block_response: BlockResponse,
+
+    /// The address returned for A queries for blocked names.  Defaults to 0.0.0.0.
+    #[serde(default = "sinkhole_ipv4_default")]
+    pub sinkhole_ipv4: Ipv4Addr,
+
+    /// The address returned for AAAA queries for blocked names.  Defaults to ::.
+    #[serde(default = "sinkhole_ipv6_default")]
+    pub sinkhole_ipv6: Ipv6Addr,
+
+    /// Addresses to answer A and AAAA queries for blocked names with in place of `sinkhole_ipv4` and `sinkhole_ipv6`, e.g.
+    /// those of several block page servers.  Each response carries a single address of the query's family, picked at
+    /// random for each query, so clients are spread across the pool while responses stay small.  A family with no address
+    /// in the pool is answered with its sinkhole address, and lists and entries which set a sinkhole address of their own
+    /// keep it.  Defaults to none.
+    #[serde(default)]
+    pub sinkhole_pool: Vec<IpAddr>,
+
+    /// Sinkhole addresses for clients in particular networks, as given by the EDNS client subnet option (RFC 7871) of
+    /// their queries, e.g. to send the clients of each region to its own block page.  The most specific network which
+    /// contains the client's subnet and sets an address of the query's family is used, ahead of the `sinkhole_pool`.
+    /// Queries without the option, or whose subnet is in none of the networks, are answered as if this were not set, and
+    /// lists and entries which set a sinkhole address of their own keep it.  Defaults to none.
+    ///
+    ///   subnet_sinkholes = [{ subnet = "192.0.2.0/24", sinkhole_ipv4 = "10.0.0.1" }]
+    #[serde(default)]
+    pub subnet_sinkholes: Vec<SubnetSinkhole>,
+
+    /// The name queries are redirected to by lists which answer with `dname` or `cname`, e.g. `blocked.example.net.`; this
+    /// is required if any list does.  It must be fully qualified, and should not itself be blocked.
+    #[serde(default)]
+    pub dname_target: Option<Name>,
+
+    /// TTL of the records synthesized for blocked names, for response modes without a TTL of their own.  Defaults to
+    /// 86400.
+    #[serde(default = "ttl_default")]
+    pub ttl: u32,
+
+    /// TTL of the sinkhole address records synthesized for blocked names.  Defaults to the `ttl`.
+    #[serde(default)]
+    pub sinkhole_ttl: Option<u32>,
+
+    /// TTL of the client address records synthesized for blocked names.  Defaults to the `ttl`.
+    #[serde(default)]
+    pub client_ttl: Option<u32>,
+
+    /// TTL of the DNAME and CNAME records synthesized for blocked names, and of the address records which follow a CNAME.
+    /// Defaults to the `ttl`.
+    #[serde(default)]
+    pub dname_ttl: Option<u32>,
+
+    /// How long NXDOMAIN responses for blocked names are cached: the TTL and minimum of the SOA record returned with them.
+    /// Defaults to 3600, within the one to three hours RFC 2308 recommends for negative caching.
+    #[serde(default = "negative_ttl_default")]
+    pub nxdomain_ttl: u32,
+
+    /// How long NODATA responses for blocked names, including those for the `nodata_types`, are cached.  Defaults to 3600.
+    #[serde(default = "negative_ttl_default")]
+    pub nodata_ttl: u32,
+
+    /// Answer blocked queries with only the records the response needs, leaving the SOA record out of NXDOMAIN and NODATA
+    /// responses.  This keeps the responses as small as possible, reducing bandwidth and the amplification an attacker can
+    /// get from spoofed queries, but clients can then only cache negative responses for as long as their own defaults
+    /// allow, and the `nxdomain_ttl` and `nodata_ttl` go unused.  Defaults to false.
+    #[serde(default)]
+    pub minimal_responses: bool,
+
+    /// What to do when a sinkhole address is publicly routable.  A typo in a sinkhole address can send every blocked client
+    /// to an unrelated third party, so by default a warning is logged for any address that is not loopback, unspecified,
+    /// private (RFC 1918 or unique local,) link-local, or within one of the `sinkhole_networks`.
+    #[serde(default)]
+    pub public_sinkhole: PublicSinkholePolicy,
+
+    /// Additional networks which are considered safe sinkhole destinations, e.g. the network hosting a block page.
+    #[serde(default)]
+    pub sinkhole_networks: Vec<IpNet>,
+
+    /// Resolve relative list paths against the directory containing the server configuration file, rather than the server
+    /// zone directory.  Defaults to false.  Absolute list paths are always used as given.
+    #[serde(default)]
+    pub config_relative: bool,
+
+    /// What to do when the block lists can not be loaded at startup.  Defaults to failing, so that the server never runs
+    /// unfiltered without the operator choosing to.
+    #[serde(default)]
+    pub on_load_failure: LoadFailurePolicy,
+
+    /// How queries are answered while the lists are first loaded.  Defaults to loading the lists before the blocklist is
+    /// used, so that no query is answered unfiltered.
+    #[serde(default)]
+    pub startup_policy: StartupPolicy,
+
+    /// Directory where a copy of each list is kept after it loads successfully, for use by the `cached` load failure
+    /// policy.  Relative paths are resolved in the same way as list paths.  Defaults to none, which disables the cache.
+    #[serde(default)]
+    pub cache_dir: Option<String>,
+
+    /// Binary policy file compiled ahead of time from the configured lists, and the default list if `use_default_lists` is
+    /// set, e.g. with `hickory-dns --compile-blocklists`.
+    /// When set, the policy is loaded from this file at startup and on reload, instead of reading and parsing the lists,
+    /// and remote lists are not refreshed.  The file records the list configuration it was compiled from, and is
+    /// rejected if that differs from this configuration, or if it was written by an incompatible version of the server.
+    /// Relative paths are resolved in the same way as list paths.  Defaults to none.
+    #[serde(default)]
+    pub compiled_policy: Option<String>,
+
+    /// Share a single copy of the loaded lists with every other blocklist in the server which has identical settings and
+    /// resolves relative list paths against the same directory, e.g. when the same lists are used for several zones.  The
+    /// lists are loaded once, and a reload or refresh by any of the blocklists updates them all.  Per list query counters
+    /// are shared as well.  Lists loaded to be shared can not be changed with `add_list` or `remove`.  Defaults to false.
+    #[serde(default)]
+    pub share_lists: bool,
+
+    /// File recording the lists added while the server runs with `add_runtime_list`, so that they are loaded again, after
+    /// the configured lists, when the server restarts.  Each line holds the path or URL of a list, optionally followed by
+    /// `action=`, `format=`, `response=` and `priority=` settings, e.g. `extra.txt format=hosts action=monitor`.  Relative
+    /// paths are resolved in the same way as list paths.  Can not be combined with `compiled_policy` or `share_lists`.
+    /// Defaults to none, which leaves lists added at runtime in place until the server restarts.
+    #[serde(default)]
+    pub runtime_lists: Option<String>,
+
+    /// File which an event is appended to for each blocked or monitored query, as a JSON object per line, for ingestion by
+    /// log pipelines.  Each event has the fields `timestamp`, `client`, `qname`, `qtype`, `action`, `list`, and
+    /// `match_kind`.  Use `-` to write events to stdout.  Relative paths are resolved in the same way as list paths.
+    /// Defaults to none, which disables the query log.
+    ///
+    /// The log is not rotated by the server.  The file is reopened when the lists are reloaded, so it can be rotated by
+    /// moving it aside and then reloading, or by an external tool which truncates it in place.
+    #[serde(default)]
+    pub query_log: Option<String>,
+
+    /// Names whose blocked and monitored queries are left out of the `query_log`, e.g. high volume telemetry names which
+    /// would otherwise drown out the rest.  Entries may be wildcards, such as `*.telemetry.example.com`, which match every
+    /// name below the domain.  Queries for these names are still counted in the blocklist statistics.  Names are always
+    /// treated as fully qualified.  Defaults to none.
+    #[serde(default)]
+    pub log_exclude: Vec<Name>,
+
+    /// Number of seconds for which names removed from the block table are answered with NODATA, so clients holding a
+    /// cached sinkhole answer re-query promptly.  The answer carries no SOA record, so it is not negatively cached
+    /// downstream.  Defaults to 0, which disables tombstones.
+    #[serde(default)]
+    pub tombstone_window: u32,
+
+    /// TTL of the NODATA answers given for names within the `tombstone_window`, which is kept short so that the names are
+    /// resolved again soon after they are unblocked, rather than for the `nodata_ttl`.  Defaults to 2.
+    #[serde(default = "tombstone_ttl_default")]
+    pub tombstone_ttl: u32,
+
+    /// Maximum number of tombstones kept at once; the oldest is evicted when the set is full.  Defaults to 1024.
+    #[serde(default = "tombstone_capacity_default")]
+    pub tombstone_capacity: usize,
+
+    /// Addresses which are removed from the answers to queries this blocklist passes on to the next store, e.g. a
+    /// sibling's address which should not be reachable even though the name itself resolves.
+    #[serde(default)]
+    pub strip_answer_networks: Vec<IpNet>,
+
+    /// Apply the block response to queries passed on to the next store whose answers alias a blocked name through a
+    /// CNAME, e.g. a site which CNAMEs around a blocked apex, or a first-party subdomain which CNAMEs to a tracker to
+    /// cloak it.  Defaults to false.
+    #[serde(default)]
+    pub block_cname_targets: bool,
+
+    /// Maximum number of CNAME records in an answer whose targets are checked when `block_cname_targets` is set, bounding
+    /// the cost of an answer with a long CNAME chain.  The first records in the answer are checked, which are those
+    /// nearest the queried name.  Defaults to 8.
+    #[serde(default = "max_cname_targets_default")]
+    pub max_cname_targets: usize,
+
+    /// Apply the block response to reverse lookups passed on to the next store whose PTR answers point at a blocked name,
+    /// so that a blocked domain can not be discovered through the addresses it uses.  A sinkhole address is no answer to a
+    /// PTR query, so lists which answer with a sinkhole or the client address answer these with NODATA.  Defaults to false.
+    #[serde(default)]
+    pub block_ptr_targets: bool,
+
+    /// Remove the EDNS client subnet (RFC 7871) from queries passed on to the next store, so that the client's network is
+    /// not revealed to the forwarder or recursor, or to the servers they query.  Defaults to false.
+    #[serde(default)]
+    pub strip_client_subnet: bool,
+
+    /// Addresses which upstream resolvers answer queries for the names they block themselves with, e.g. `0.0.0.0/32` or
+    /// `127.0.0.1/32`.  Answers to queries this blocklist passes on to the next store which contain one of these are
+    /// treated as blocked, and replaced with the `upstream_sink_response`, so that clients see the same answer whichever
+    /// resolver blocked the name.  Defaults to none.
+    #[serde(default)]
+    pub upstream_sink_addresses: Vec<IpNet>,
+
+    /// How answers containing one of the `upstream_sink_addresses` are answered.  Defaults to the `block_response`.
+    #[serde(default)]
+    pub upstream_sink_response: Option<BlockResponse>,
+
+    /// Lists of addresses and networks, one per line in CIDR notation, which are blocked when they appear in the answers to
+    /// queries this blocklist passes on to the next store.  The whole answer is replaced with the block response, catching
+    /// names which resolve to known malicious infrastructure whatever they are called.  Relative paths are resolved in the
+    /// same way as list paths.
+    #[serde(default)]
+    pub ip_blocklists: Vec<String>,
+
+    /// Domains whose names are never blocked, along with every name below them, e.g. `example.com` to let a site's own
+    /// subdomains through while third party trackers on the same lists stay blocked.  These are checked after the
+    /// `allow_regex_lists`, and take precedence over every block list entry, exact or wildcard.  Names are always treated
+    /// as fully qualified.  Defaults to none.
+    #[serde(default)]
+    pub first_party_domains: Vec<Name>,
+
+    /// Domains protected against homograph lookalikes: queries for internationalized names which are visually confusable
+    /// with one of these, such as `xn--pypal-4ve.com` (`pаypal.com`, with a Cyrillic `а`) for `paypal.com`, or for names
+    /// below such a lookalike, are blocked with the blocklist-wide settings.  Names are compared by their skeletons, with
+    /// characters commonly substituted for Latin letters mapped to those letters; only names with a punycode label are
+    /// checked, so ordinary queries cost nothing extra.  The protected domains themselves are never blocked this way.
+    /// Allowed names take precedence.  Defaults to none, which disables the check.
+    #[serde(default)]
+    pub homograph_protected: Vec<Name>,
+
+    /// Lists of regular expressions, one per line, for names which are never blocked, e.g. `^.*\.internal\.corp\.$`.
+    /// Patterns are matched against the lowercase, fully qualified queried name, including the trailing dot, and are not
+    /// anchored unless they say so.  Lines starting with `#` are comments, and invalid patterns are logged and skipped.
+    ///
+    /// A name matching any of the patterns is passed on to the next store before anything else is checked: these take
+    /// precedence over allowed names and over every block list entry, exact or wildcard.  Relative paths are resolved in
+    /// the same way as list paths.
+    #[serde(default)]
+    pub allow_regex_lists: Vec<String>,
+
+    /// Maximum number of patterns read from the `allow_regex_lists`.  The patterns are compiled into a single set, which
+    /// every query is matched against in one pass, but compiling it takes time and memory which grow with the number of
+    /// patterns, so the load fails if there are more than this.  Defaults to 10000.
+    #[serde(default = "max_allow_patterns_default")]
+    pub max_allow_patterns: usize,
+
+    /// Longest line, in bytes, read from a block list.  Longer lines are logged and skipped as they are read, without
+    /// being held in memory, so that a corrupt or malicious list with no line breaks can not exhaust it.  Defaults to
+    /// 4096.
+    #[serde(default = "max_line_length_default")]
+    pub max_line_length: usize,
+
+    /// How the block table is stored and searched: `hash` for a hash table of whole names, `tree` for a tree of labels
+    /// from the root down, or `patricia` for a byte-level radix trie over the labels from the root down, each preceded by
+    /// its length, whose edges split wherever names diverge, even part way through a label.  All match the same names;
+    /// they differ in how they use memory and time while loading and looking up large lists.  Defaults to `hash`.
+    #[serde(default)]
+    pub matcher: MatchBackend,
+
+    /// Number of seconds allowed for downloading each remote list, covering the whole transfer rather than just the
+    /// connection.  Defaults to 60.
+    #[serde(default = "remote_timeout_default")]
+    pub remote_timeout: u64,
+
+    /// Maximum number of remote lists downloaded at once.  Downloads share a pool of connections.  Defaults to 4.
+    #[serde(default = "remote_concurrency_default")]
+    pub remote_concurrency: usize,
+
+    /// Endpoint of the S3 compatible object store `s3://bucket/key` lists are fetched from, such as a MinIO server at
+    /// `http://127.0.0.1:9000`.  Objects are addressed by path, as `{endpoint}/{bucket}/{key}`.  Defaults to AWS S3 in
+    /// `s3_region`, with the bucket addressed by virtual host.
+    ///
+    /// Requests are signed with credentials from the `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and
+    /// `AWS_SESSION_TOKEN` environment variables or, if those are not set, from the instance profile of the EC2 instance
+    /// the server runs on.  The only permission these need is `s3:GetObject` on each list object.  Requires the
+    /// `blocklist-s3` feature.
+    pub s3_endpoint: Option<String>,
+
+    /// Region object store requests are signed for.  Defaults to the `AWS_REGION` or `AWS_DEFAULT_REGION` environment
+    /// variable, or `us-east-1` if neither is set.
+    pub s3_region: Option<String>,
+
+    /// Randomly adjust the TTL of each synthesized record by up to this many seconds in either direction, never going below
+    /// one second.  Clients which cached a blocked name at the same time with identical TTLs would otherwise all expire it
+    /// at once, and re-query it in a burst; jitter spreads those re-queries out.  Defaults to 0, for deterministic TTLs.
+    #[serde(default)]
+    pub ttl_jitter: u32,
+
+    /// Number of blocked names whose responses are kept for the names queried again, which are then answered without
+    /// matching them against the lists.  Cached answers are still counted and logged as usual.  Responses are not cached
+    /// while `ttl_jitter` is set or any list has a schedule, nor for monitored names or lists answering with the client's
+    /// own address.  The cache is emptied whenever the lists are reloaded.  Defaults to 0, which disables the cache.
+    #[serde(default)]
+    pub response_cache_size: usize,
+
+    /// Number of seconds between refreshes of each remote list.  A failed refresh leaves the last successfully fetched copy
+    /// of the list in place.  Defaults to 0, which disables refreshing.
+    #[serde(default)]
+    pub refresh_interval: u64,
+
+    /// Number of seconds to wait before retrying a failed refresh.  The delay doubles with each consecutive failure of the
+    /// same list, up to `refresh_max_backoff`, and is randomly shortened by up to half so that retries do not align.
+    /// Defaults to 60.
+    #[serde(default = "refresh_retry_default")]
+    pub refresh_retry: u64,
+
+    /// Maximum number of seconds to wait before retrying a failed refresh.  Defaults to 3600.
+    #[serde(default = "refresh_max_backoff_default")]
+    pub refresh_max_backoff: u64,
+
+    /// Collect the names of passed on queries which the next store answers with NXDOMAIN or SERVFAIL, as candidate block
+    /// list entries for review.  Names which many clients fail to resolve are often generated by malware.  Defaults to
+    /// false.
+    #[serde(default)]
+    pub learn: bool,
+
+    /// Maximum number of names collected in learn mode; the least recently seen name is evicted when full.  Defaults to
+    /// 10000.
+    #[serde(default = "learn_capacity_default")]
+    pub learn_capacity: usize,
+
+    /// Maximum number of queries per second from a single client address.  A client which exceeds it has every query
+    /// refused, whether or not the name is blocked, for `client_block_seconds`.  Defaults to 0, which disables the limit.
+    #[serde(default)]
+    pub client_rate_limit: u32,
+
+    /// Number of queries a client may send at once, before `client_rate_limit` applies.  Defaults to 0, which allows a
+    /// burst of one second's worth of queries.
+    #[serde(default)]
+    pub client_rate_burst: u32,
+
+    /// Number of seconds for which a client exceeding `client_rate_limit` has its queries refused.  Defaults to 60.
+    #[serde(default = "client_block_seconds_default")]
+    pub client_block_seconds: u32,
+
+    /// Maximum number of clients whose query rates are tracked; the least recently seen client is evicted when full.
+    /// Defaults to 10000.
+    #[serde(default = "client_rate_capacity_default")]
+    pub client_rate_capacity: usize,
+
+    /// Local addresses the blocklist answers queries received on, e.g. that of the LAN-facing listener.  Queries received
+    /// on any other address, such as that of a management interface, are passed on to the next store as if the blocklist
+    /// were not configured.  A UDP listener bound to an unspecified address, such as `0.0.0.0`, receives its queries on
+    /// that address, so listeners should be bound to the addresses given here.  Queries whose local address is not known
+    /// are answered as usual.  Defaults to none, answering queries received on any address.
+    #[serde(default)]
+    pub local_addresses: Vec<IpAddr>,
 }
 
 impl BlocklistConfig {
     /// the set of block lists which should be loaded
-    pub fn get_block_lists(&self) -> &Vec<String> {
+    pub fn get_block_lists(&self) -> &Vec<ListEntry> {
         &self.lists
     }
 }
 
+impl Default for BlocklistConfig {
+    fn default() -> Self {
+        Self {
+            wildcard_match: wildcard_match_default(),
+            wildcard_token: wildcard_token_default(),
+            wildcard_blocks_parent: false,
+            block_origin: false,
+            malformed_query: MalformedQueryPolicy::default(),
+            min_wildcard_depth: min_wildcard_depth_default(),
+            lists: vec![],
+            use_default_lists: false,
+            monitor_lists: vec![],
+            manifests: vec![],
+            categories: HashMap::new(),
+            nodata_types: vec![],
+            block_response: BlockResponse::default(),
+            sinkhole_ipv4: sinkhole_ipv4_default(),
+            sinkhole_ipv6: sinkhole_ipv6_default(),
+            sinkhole_pool: vec![],
+            subnet_sinkholes: vec![],
+            dname_target: None,
+            ttl: ttl_default(),
+            sinkhole_ttl: None,
+            client_ttl: None,
+            dname_ttl: None,
+            nxdomain_ttl: negative_ttl_default(),
+            nodata_ttl: negative_ttl_default(),
+            minimal_responses: false,
+            public_sinkhole: PublicSinkholePolicy::default(),
+            sinkhole_networks: vec![],
+            config_relative: false,
+            on_load_failure: LoadFailurePolicy::default(),
+            startup_policy: StartupPolicy::default(),
+            cache_dir: None,
+            compiled_policy: None,
+            share_lists: false,
+            runtime_lists: None,
+            query_log: None,
+            log_exclude: vec![],
+            tombstone_window: 0,
+            tombstone_ttl: tombstone_ttl_default(),
+            tombstone_capacity: tombstone_capacity_default(),
+            strip_answer_networks: vec![],
+            block_cname_targets: false,
+            max_cname_targets: max_cname_targets_default(),
+            block_ptr_targets: false,
+            strip_client_subnet: false,
+            upstream_sink_addresses: vec![],
+            upstream_sink_response: None,
+            ip_blocklists: vec![],
+            first_party_domains: vec![],
+            homograph_protected: vec![],
+            allow_regex_lists: vec![],
+            max_allow_patterns: max_allow_patterns_default(),
+            max_line_length: max_line_length_default(),
+            matcher: MatchBackend::default(),
+            remote_timeout: remote_timeout_default(),
+            remote_concurrency: remote_concurrency_default(),
+            s3_endpoint: None,
+            s3_region: None,
+            ttl_jitter: 0,
+            response_cache_size: 0,
+            refresh_interval: 0,
+            refresh_retry: refresh_retry_default(),
+            refresh_max_backoff: refresh_max_backoff_default(),
+            learn: false,
+            learn_capacity: learn_capacity_default(),
+            client_rate_limit: 0,
+            client_rate_burst: 0,
+            client_block_seconds: client_block_seconds_default(),
+            client_rate_capacity: client_rate_capacity_default(),
+            local_addresses: vec![],
+        }
+    }
+}
+
+/// A block list to load, and the settings used to answer queries for the names on it.  Settings which are not specified
+/// fall back to the blocklist-wide values.
+///
+/// A list given as a bare string in the configuration file is shorthand for a list with only a `path`:
+///
+///   lists = ["default/ads.txt", { path = "default/malware.txt", response = "nxdomain", priority = 10 }]
+#[derive(Clone, Deserialize, Eq, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ListEntry {
+    /// Path of the list.  Relative paths are resolved against the server zone directory, or the directory containing the
+    /// server configuration file if `config_relative` is set.  Exactly one of `path` or `url` must be set.
+    ///
+    /// Lists ending in `.gz` are decompressed as they are loaded.  A `.zip` archive is expanded, and each file in it is
+    /// loaded as a separate list using these settings.
+    ///
+    /// A path of `-` reads the list from standard input, e.g. to check names against a list piped in by a script.  The
+    /// input is read to its end when the lists are first loaded, and reloads use the same contents.
+    pub path: Option<String>,
+
+    /// URL of a remote list, fetched over HTTP or HTTPS when the blocklist is loaded.  Lists ending in `.gz`, or served with a
+    /// gzip content type, are decompressed as they are downloaded.
+    ///
+    /// With the `blocklist-s3` feature, a `s3://bucket/key` URL fetches the list from an S3 compatible object store, see
+    /// [`BlocklistConfig::s3_endpoint`].
+    pub url: Option<String>,
+
+    /// Expected SHA-256 digest of the list, in hex.  The digest covers the file or download as it is published, before
+    /// any decompression, and a list which does not match it fails to load, as set by [`BlocklistConfig::on_load_failure`].
+    /// Defaults to none, accepting the list as it is.
+    pub sha256: Option<String>,
+
+    /// Format of the list.  Defaults to domains.
+    #[serde(default)]
+    pub format: ListFormat,
+
+    /// Character encoding of the list, as a WHATWG label such as `latin1` or `windows-1252`, which the list is transcoded
+    /// from as it is loaded.  Only encodings which leave ASCII unchanged may be used.  Defaults to UTF-8, with any invalid
+    /// byte sequence replaced, which costs the entry it appears in.
+    pub encoding: Option<String>,
+
+    /// Category of the list, one of the `categories`, whose settings the list uses in place of any it does not set
+    pub category: Option<String>,
+
+    /// What is done with queries for names on the list.  Defaults to the action of the list's category, or block.
+    pub action: Option<ListAction>,
+
+    /// How queries for names on this list are answered.  Defaults to the response of the list's category, or the
+    /// blocklist's `block_response`.
+    pub response: Option<BlockResponse>,
+
+    /// The address returned for A queries for names on this list
+    pub sinkhole_ipv4: Option<Ipv4Addr>,
+
+    /// The address returned for AAAA queries for names on this list
+    pub sinkhole_ipv6: Option<Ipv6Addr>,
+
+    /// TTL of the responses for names on this list, overriding the TTL of its response mode
+    pub ttl: Option<u32>,
+
+    /// INFO-CODE of the extended DNS error (RFC 8914) attached to responses for names on this list, e.g. 15 (Blocked), 16
+    /// (Censored) or 17 (Filtered), so that clients can tell why a name was blocked.  Only clients which send EDNS receive
+    /// it.  Must be a code registered with IANA, 0 through 30, or one in the private use range from 49152.  Defaults to
+    /// none.
+    pub ede_code: Option<u16>,
+
+    /// EXTRA-TEXT of the extended DNS error, e.g. "malware".  Requires `ede_code`.  Defaults to none.
+    pub ede_text: Option<String>,
+
+    /// Whether the list is loaded at all.  Defaults to true.
+    #[serde(default = "enabled_default")]
+    pub enabled: bool,
+
+    /// When a name appears on more than one list, the settings of the list with the highest priority are used.  Lists with
+    /// equal priority are resolved in favor of the list which was loaded first.  Defaults to 0.
+    #[serde(default)]
+    pub priority: i32,
+
+    /// Only block names exactly this many labels below a wildcard entry on this list.  With a depth of 1, `*.ads.example.com`
+    /// blocks `x.ads.example.com`, but neither `ads.example.com` nor `y.x.ads.example.com`.  By default wildcard entries
+    /// block names at any depth.
+    pub depth: Option<u8>,
+
+    /// Minimum number of labels, not counting the `*`, of the wildcard entries on this list which block anything, e.g. 3 to
+    /// ignore `*.example.com` while honoring `*.ads.example.com`.  Overrides the blocklist-wide `min_wildcard_depth` for
+    /// this list, in either direction.  Defaults to the blocklist-wide setting.
+    pub min_wildcard_depth: Option<u8>,
+
+    /// Whether an entry with a leading dot, such as `.example.com`, blocks `example.com` itself as well as the names below
+    /// it.  Without the apex, the entry blocks the same names as `*.example.com`.  Defaults to true.
+    #[serde(default = "include_apex_default")]
+    pub include_apex: bool,
+
+    /// Daily window, in UTC, during which the list is enforced, e.g. "09:00-17:00".  Windows which end before they start
+    /// wrap around midnight.  Lists without a schedule are always enforced.
+    pub schedule: Option<Schedule>,
+
+    /// Number of seconds after a reload adds an entry to the list before the entry is enforced.  Until then, queries for
+    /// it are logged and counted as monitored, but passed on, so that a false positive can be caught before it blocks
+    /// anything.  Entries on the list when the blocklist first loads are enforced immediately.  Defaults to none.
+    pub grace_period: Option<u32>,
+}
+
+impl ListEntry {
+    /// A list at `path`, loaded with the blocklist-wide settings
+    pub fn from_path(path: impl Into<String>) -> Self {
+        Self {
+            path: Some(path.into()),
+            url: None,
+            sha256: None,
+            format: ListFormat::default(),
+            encoding: None,
+            category: None,
+            action: None,
+            response: None,
+            sinkhole_ipv4: None,
+            sinkhole_ipv6: None,
+            ttl: None,
+            ede_code: None,
+            ede_text: None,
+            enabled: true,
+            priority: 0,
+            depth: None,
+            min_wildcard_depth: None,
+            include_apex: true,
+            schedule: None,
+            grace_period: None,
+        }
+    }
+}
+
+/// What is done with queries for names on a list
+#[derive(Deserialize, Serialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum ListAction {
+    /// Answer queries using the list's block response
+    #[default]
+    Block,
+    /// Log and count queries, but pass them on to the next store
+    Monitor,
+}
+
+/// Format of a block list file
+///
+/// In any format, a line of the form `-name` or `@@name` is a negation: it removes `name` from the block table rather
+/// than adding it, even if it was added by an earlier line or another list.  Negations are applied once every list has
+/// been added, so the result does not depend on the order of the lines or of the lists.  A negation only removes the
+/// entry with exactly that name; use `-*.example.com` to remove a wildcard entry.
+#[derive(Deserialize, Serialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum ListFormat {
+    /// One name per line
+    #[default]
+    Domains,
+    /// hosts(5) format: an address followed by one or more names per line.  The address is ignored.
+    Hosts,
+    /// A name per line, optionally followed by how queries for it are answered, overriding the list's settings:
+    /// `sinkhole`, `nxdomain`, `nodata` or `client`.  `sinkhole` may be followed by the address to answer with, e.g.
+    /// `cdn.example.com sinkhole 10.0.0.1`, which replaces the sinkhole address of the same family.  A final `ttl=` column,
+    /// with or without an action, sets the TTL of the response, overriding the list's and the response mode's, e.g.
+    /// `volatile.example.com ttl=10`.  Lines with an unknown action, an invalid address or an invalid TTL are skipped.
+    Extended,
+    /// An address, a network in CIDR notation, or a reverse lookup name under `in-addr.arpa` or `ip6.arpa` per line, e.g.
+    /// `192.0.2.0/24` or `2.0.192.in-addr.arpa`.  Queries for the reverse names of every address in the range, PTR
+    /// queries included, are answered using the list's settings.  Lines which are none of these are skipped; negations
+    /// do not apply to these lists.
+    Reverse,
+}
+
+/// How a query for a blocked name is answered
+#[derive(Deserialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum BlockResponse {
+    /// Answer A and AAAA queries with the sinkhole addresses
+    #[default]
+    Sinkhole,
+    /// Answer with NXDOMAIN
+    NxDomain,
+    /// Answer with NODATA
+    NoData,
+    /// Answer A and AAAA queries with the address of the querying client, so software on the client treats the blocked name
+    /// as local.  The sinkhole addresses are used when the client's address family does not match the query type, or the
+    /// client address is unknown.
+    Client,
+    /// Redirect queries to the `dname_target`.  A query below a wildcard entry is answered with a DNAME record, which
+    /// redirects the entry's whole subtree, along with the CNAME it synthesizes for the queried name (RFC 6672); any other
+    /// blocked name is answered with a CNAME alone.  Queries for the DNSSEC types, e.g. DS and DNSKEY, are answered with
+    /// NODATA.
+    Dname,
+    /// Answer with a CNAME to the `dname_target`, e.g. a block page, followed by an address record for the target with the
+    /// sinkhole address of the query's type, so that clients reach the target without resolving it themselves.  Queries
+    /// for types other than A, AAAA and ANY, and for the `nodata_types`, are answered with the CNAME alone, except for the
+    /// DNSSEC types, e.g. DS and DNSKEY, which are answered with NODATA.  The sinkhole addresses can not both be unspecified.
+    Cname,
+}
+
+/// Settings shared by the lists in a category
+#[derive(Deserialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Category {
+    /// What is done with queries for names on lists in the category.  Defaults to block.
+    pub action: Option<ListAction>,
+
+    /// How queries for names on lists in the category are answered.  Defaults to the blocklist's `block_response`.
+    pub response: Option<BlockResponse>,
+}
+
+/// The sinkhole addresses for clients whose EDNS client subnet is within `subnet`
+#[derive(Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct SubnetSinkhole {
+    /// Network of the clients, e.g. "192.0.2.0/24"
+    pub subnet: IpNet,
+
+    /// The address returned for A queries for blocked names from the clients.  Defaults to none, leaving A queries to a
+    /// less specific network or the blocklist-wide settings.
+    pub sinkhole_ipv4: Option<Ipv4Addr>,
+
+    /// The address returned for AAAA queries for blocked names from the clients.  Defaults to none.
+    pub sinkhole_ipv6: Option<Ipv6Addr>,
+}
+
+/// A daily window of time, in UTC, written as "HH:MM-HH:MM"
+#[derive(Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+#[serde(try_from = "String")]
+pub struct Schedule {
+    start: u16,
+    end: u16,
+}
+
+impl Schedule {
+    /// Returns true if the time of day falls within this window
+    pub fn contains(&self, hour: u8, minute: u8) -> bool {
+        let minute = u16::from(hour) * 60 + u16::from(minute);
+        if self.start <= self.end {
+            self.start <= minute && minute < self.end
+        } else {
+            minute >= self.start || minute < self.end
+        }
+    }
+}
+
+impl TryFrom<String> for Schedule {
+    type Error = String;
+
+    fn try_from(schedule: String) -> Result<Self, Self::Error> {
+        fn parse_time(time: &str) -> Option<u16> {
+            let (hour, minute) = time.trim().split_once(':')?;
+            let (hour, minute) = (hour.parse::<u16>().ok()?, minute.parse::<u16>().ok()?);
+            (hour < 24 && minute < 60).then_some(hour * 60 + minute)
+        }
+
+        schedule
+            .split_once('-')
+            .and_then(|(start, end)| Some((parse_time(start)?, parse_time(end)?)))
+            .map(|(start, end)| Self { start, end })
+            .ok_or_else(|| format!("invalid schedule {schedule:?}, expected HH:MM-HH:MM"))
+    }
+}
+
+/// How to handle a configured sinkhole address which is publicly routable
+#[derive(Deserialize, Serialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum PublicSinkholePolicy {
+    /// Use the address without complaint
+    Allow,
+    /// Use the address, but log a warning
+    #[default]
+    Warn,
+    /// Refuse to load the blocklist
+    Refuse,
+}
+
+/// How to handle block lists which can not be loaded at startup
+#[derive(Deserialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum LoadFailurePolicy {
+    /// Refuse to start
+    #[default]
+    Fail,
+    /// Start without blocking anything, logging an error
+    Empty,
+    /// Start with the copies of the lists kept in `cache_dir` from the last successful load.  If the cache can not be
+    /// loaded either, refuse to start.
+    Cached,
+}
+
+/// The backend the block table is stored in
+#[derive(Deserialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum MatchBackend {
+    /// A hash table, keyed by the whole name
+    #[default]
+    Hash,
+    /// A tree of labels, from the root down
+    Tree,
+    /// A byte-level radix trie over the labels, from the root down, each preceded by its length.  Edges are split
+    /// wherever names diverge, which may be inside a label, so names sharing a prefix share its bytes.
+    Patricia,
+}
+
+/// How queries are answered while the block lists are first loaded
+#[derive(Deserialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum StartupPolicy {
+    /// Load the lists before the blocklist is created, delaying server startup until they have loaded
+    #[default]
+    Wait,
+    /// Load the lists in the background, passing queries on to the next store until they have loaded
+    Open,
+    /// Load the lists in the background, answering queries with REFUSED until they have loaded
+    Closed,
+}
+
+/// How queries whose name can not be matched against the block lists are answered
+#[derive(Deserialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum MalformedQueryPolicy {
+    /// Pass the query on to the next store
+    #[default]
+    Pass,
+    /// Answer the query with REFUSED
+    Refuse,
+}
+
+fn deserialize_lists<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<ListEntry>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum ListEntryConfig {
+        Path(String),
+        Entry(ListEntry),
+    }
+
+    Ok(Vec::<ListEntryConfig>::deserialize(deserializer)?
+        .into_iter()
+        .map(|entry| match entry {
+            ListEntryConfig::Path(path) => ListEntry::from_path(path),
+            ListEntryConfig::Entry(entry) => entry,
+        })
+        .collect())
+}
+
+/// Parse the lists named by a manifest.  Each invalid entry is reported separately, by its position in the manifest.
+pub(super) fn parse_manifest(contents: &str) -> Result<Vec<ListEntry>, Vec<String>> {
+    #[derive(Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct Manifest {
+        lists: Vec<toml::Value>,
+    }
+
+    let manifest = toml::from_str::<Manifest>(contents).map_err(|e| vec![e.to_string()])?;
+    let mut lists = Vec::new();
+    let mut errors = Vec::new();
+    for (index, value) in manifest.lists.into_iter().enumerate() {
+        // Entries are parsed one at a time, as tables where they are not bare paths, so that errors say what is wrong
+        // with each.
+        let entry = match value {
+            toml::Value::String(path) => Ok(ListEntry::from_path(path)),
+            value => ListEntry::deserialize(value),
+        };
+        match entry {
+            Ok(entry) if entry.path.is_some() == entry.url.is_some() => errors.push(format!(
+                "list {}: requires exactly one of path or url",
+                index + 1
+            )),
+            Ok(entry) => lists.push(entry),
+            Err(e) => errors.push(format!("list {}: {}", index + 1, e.to_string().trim_end())),
+        }
+    }
+
+    match errors.is_empty() {
+        true => Ok(lists),
+        false => Err(errors),
+    }
+}
+
 fn wildcard_match_default() -> bool {
     true
 }
+fn wildcard_token_default() -> String {
+    "*".to_string()
+}
 fn min_wildcard_depth_default() -> u8 {
     2
 }
+fn sinkhole_ipv4_default() -> Ipv4Addr {
+    Ipv4Addr::UNSPECIFIED
+}
+fn sinkhole_ipv6_default() -> Ipv6Addr {
+    Ipv6Addr::UNSPECIFIED
+}
+fn ttl_default() -> u32 {
+    86400
+}
+fn negative_ttl_default() -> u32 {
+    3600
+}
+fn tombstone_ttl_default() -> u32 {
+    2
+}
+fn tombstone_capacity_default() -> usize {
+    1024
+}
+fn remote_timeout_default() -> u64 {
+    60
+}
+fn remote_concurrency_default() -> usize {
+    4
+}
+fn max_allow_patterns_default() -> usize {
+    10_000
+}
+fn max_line_length_default() -> usize {
+    4096
+}
+fn refresh_retry_default() -> u64 {
+    60
+}
+fn refresh_max_backoff_default() -> u64 {
+    3600
+}
+fn learn_capacity_default() -> usize {
+    10000
+}
+fn max_cname_targets_default() -> usize {
+    8
+}
+fn client_block_seconds_default() -> u32 {
+    60
+}
+fn client_rate_capacity_default() -> usize {
+    10000
+}
+fn enabled_default() -> bool {
+    true
+}
+fn include_apex_default() -> bool {
+    true
+}
+
+#[cfg(all(test, feature = "toml"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_list_shorthand() {
+        let input = r#"lists = ["default/blocklist.txt", "default/blocklist2.txt"]"#;
+
+        let config: BlocklistConfig = toml::from_str(input).unwrap();
+
+        assert_eq!(
+            config.lists,
+            vec![
+                ListEntry::from_path("default/blocklist.txt"),
+                ListEntry::from_path("default/blocklist2.txt")
+            ]
+        );
+        assert_eq!(
+            config,
+            BlocklistConfig {
+                lists: config.lists.clone(),
+                ..BlocklistConfig::default()
+            }
+        );
+    }
+
+    #[test]
+    fn can_parse_list_entries() {
+        let input = r#"lists = [
+    "default/blocklist.txt",
+    { path = "default/hosts.txt", format = "hosts", response = "nxdomain", ttl = 60, priority = 10, depth = 1 },
+    { url = "https://example.com/list.txt", sinkhole_ipv4 = "10.0.0.1", sinkhole_ipv6 = "fd00::1", enabled = false, schedule = "22:00-06:30" },
+]"#;
+
+        let config: BlocklistConfig = toml::from_str(input).unwrap();
+
+        assert_eq!(
+            config.lists[0],
+            ListEntry::from_path("default/blocklist.txt")
+        );
+
+        let hosts = &config.lists[1];
+        assert_eq!(hosts.path.as_deref(), Some("default/hosts.txt"));
+        assert_eq!(hosts.format, ListFormat::Hosts);
+        assert_eq!(hosts.response, Some(BlockResponse::NxDomain));
+        assert_eq!(hosts.ttl, Some(60));
+        assert_eq!(hosts.priority, 10);
+        assert_eq!(hosts.depth, Some(1));
+        assert!(hosts.enabled);
+
+        let remote = &config.lists[2];
+        assert_eq!(remote.path, None);
+        assert_eq!(remote.url.as_deref(), Some("https://example.com/list.txt"));
+        assert_eq!(remote.format, ListFormat::Domains);
+        assert_eq!(remote.sinkhole_ipv4, Some(Ipv4Addr::new(10, 0, 0, 1)));
+        assert_eq!(
+            remote.sinkhole_ipv6,
+            Some(Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 1))
+        );
+        assert!(!remote.enabled);
+
+        let schedule = remote.schedule.unwrap();
+        assert!(schedule.contains(23, 0));
+        assert!(schedule.contains(6, 29));
+        assert!(!schedule.contains(6, 30));
+        assert!(!schedule.contains(12, 0));
+    }
+
+    #[test]
+    fn rejects_invalid_list_entries() {
+        assert!(
+            toml::from_str::<BlocklistConfig>(r#"lists = [{ path = "a", bogus = 1 }]"#).is_err()
+        );
+        assert!(toml::from_str::<BlocklistConfig>(
+            r#"lists = [{ path = "a", schedule = "25:00-01:00" }]"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn can_parse_categories() {
+        let input = r#"
+lists = [{ path = "malware.txt", category = "malware" }]
+
+[categories.malware]
+response = "nxdomain"
+
+[categories.ads]
+action = "monitor"
+"#;
+
+        let config: BlocklistConfig = toml::from_str(input).unwrap();
+
+        assert_eq!(config.lists[0].category.as_deref(), Some("malware"));
+        assert_eq!(
+            config.categories["malware"],
+            Category {
+                action: None,
+                response: Some(BlockResponse::NxDomain),
+            }
+        );
+        assert_eq!(config.categories["ads"].action, Some(ListAction::Monitor));
+        assert!(
+            toml::from_str::<BlocklistConfig>("lists = []\n[categories.ads]\nbogus = 1").is_err()
+        );
+    }
+}
@@ -0,0 +1,186 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Detection of internationalized names which are visually confusable with protected domains
+
+use std::{borrow::Borrow, collections::HashMap};
+
+use crate::proto::rr::{LowerName, Name};
+use crate::store::blocklist::policy::canonical_name;
+
+/// Characters which are commonly substituted for Latin letters in homograph domains, with the letters they are confusable
+/// with.  This is the subset of the Unicode confusables data (UTS #39) for the Cyrillic, Greek, Armenian and Latin
+/// letters which render identically, or nearly so, to the ASCII letters and digits allowed in host names.
+const CONFUSABLES: &[(char, char)] = &[
+    // ASCII digits
+    ('0', 'o'),
+    ('1', 'l'),
+    // Latin
+    ('ı', 'i'),
+    ('ɩ', 'i'),
+    ('ɑ', 'a'),
+    ('ɡ', 'g'),
+    ('ɪ', 'i'),
+    ('ℓ', 'l'),
+    ('ꞵ', 'b'),
+    // Greek
+    ('α', 'a'),
+    ('ε', 'e'),
+    ('ι', 'i'),
+    ('κ', 'k'),
+    ('ν', 'v'),
+    ('ο', 'o'),
+    ('ρ', 'p'),
+    ('υ', 'u'),
+    ('χ', 'x'),
+    ('ϲ', 'c'),
+    ('ϳ', 'j'),
+    // Cyrillic
+    ('а', 'a'),
+    ('в', 'b'),
+    ('е', 'e'),
+    ('з', '3'),
+    ('к', 'k'),
+    ('м', 'm'),
+    ('н', 'h'),
+    ('о', 'o'),
+    ('р', 'p'),
+    ('с', 'c'),
+    ('т', 't'),
+    ('у', 'y'),
+    ('х', 'x'),
+    ('ѕ', 's'),
+    ('і', 'i'),
+    ('ј', 'j'),
+    ('ԁ', 'd'),
+    ('һ', 'h'),
+    ('ӏ', 'l'),
+    ('ԛ', 'q'),
+    ('ԝ', 'w'),
+    ('ү', 'y'),
+    // Armenian
+    ('ո', 'n'),
+    ('ս', 'u'),
+    ('օ', 'o'),
+    ('ց', 'g'),
+];
+
+/// The protected domains, by their skeletons, so that a name is checked with one lookup per label
+#[derive(Clone)]
+pub(crate) struct Homographs {
+    protected: HashMap<String, LowerName>,
+}
+
+impl Homographs {
+    pub(crate) fn new(domains: &[Name]) -> Self {
+        let protected = domains
+            .iter()
+            .map(|domain| {
+                let domain = canonical_name(domain.clone());
+                (skeleton(&domain.to_string()), domain)
+            })
+            .collect();
+        Self { protected }
+    }
+
+    /// The protected domain `name` is a homograph of, or is below a homograph of.  Only names with an internationalized
+    /// label are checked, and the protected domains themselves, and the names below them, never match.
+    pub(crate) fn find(&self, name: &LowerName) -> Option<&LowerName> {
+        let labels: &Name = name.borrow();
+        let internationalized = labels
+            .iter()
+            .any(|label| label.len() > 4 && label[..4].eq_ignore_ascii_case(b"xn--"));
+        if !internationalized {
+            return None;
+        }
+
+        // The skeleton keeps the dots between labels, so each suffix starting after a dot is the skeleton of a parent.
+        let skeleton = skeleton(&name.to_string());
+        let suffixes = std::iter::once(0).chain(skeleton.match_indices('.').map(|(i, _)| i + 1));
+        suffixes
+            .filter_map(|start| self.protected.get(&skeleton[start..]))
+            .find(|domain| !domain.zone_of(name))
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.protected.len()
+    }
+}
+
+/// The skeleton of `name`: the name in lower case, with each confusable character replaced by the letter it is
+/// confusable with, so that homographs share a skeleton
+pub(crate) fn skeleton(name: &str) -> String {
+    name.chars()
+        .flat_map(char::to_lowercase)
+        .map(|c| {
+            CONFUSABLES
+                .iter()
+                .find(|(confusable, _)| *confusable == c)
+                .map_or(c, |(_, letter)| *letter)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::store::blocklist::{BlocklistAuthority, BlocklistAuthorityBuilder};
+    use crate::{
+        authority::{Authority, LookupOptions},
+        proto::rr::domain::Name,
+        proto::rr::{LowerName, RecordType},
+    };
+    use std::str::FromStr;
+
+    #[tokio::test]
+    async fn test_blocklist_homographs() {
+        let authority = BlocklistAuthorityBuilder::new(Name::root())
+            .homograph_protected(vec![
+                Name::from_str("paypal.com").unwrap(),
+                Name::from_str("google.com.").unwrap(),
+            ])
+            .build()
+            .expect("Unable to create blocklist authority");
+
+        async fn blocked(authority: &BlocklistAuthority, name: &str) -> bool {
+            match authority
+                .lookup(
+                    &LowerName::from_str(name).unwrap(),
+                    RecordType::A,
+                    LookupOptions::default(),
+                )
+                .await
+            {
+                Ok(Some(_)) => true,
+                Ok(None) => false,
+                Err(e) => panic!("{name} lookup returned {e}"),
+            }
+        }
+
+        // Test: homographs of protected domains, with a Cyrillic a or Greek omicrons, are blocked, as are the names below
+        // them.
+        for name in [
+            "xn--pypal-4ve.com.",
+            "www.xn--pypal-4ve.com.",
+            "XN--GGLE-0NDA.com.",
+        ] {
+            assert!(blocked(&authority, name).await, "{name}");
+        }
+
+        // Test: the protected domains, internationalized names below them, and other internationalized names pass.
+        for name in [
+            "paypal.com.",
+            "www.paypal.com.",
+            "xn--bcher-kva.paypal.com.",
+            "xn--bcher-kva.com.",
+            "pypal.com.",
+        ] {
+            assert!(!blocked(&authority, name).await, "{name}");
+        }
+
+        assert_eq!(authority.stats().matches.homograph, 3);
+    }
+}
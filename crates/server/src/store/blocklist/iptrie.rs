@@ -0,0 +1,324 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! A binary trie of IP networks, for longest prefix matching of addresses
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use ipnet::{IpNet, Ipv4Net, Ipv6Net};
+
+/// Maps IPv4 and IPv6 networks to values.  A lookup walks at most one node per bit of the address, however many networks
+/// are stored, so large address feeds can be checked against every answer.
+#[derive(Clone)]
+pub(crate) struct IpTrie<T> {
+    v4: Node<T>,
+    v6: Node<T>,
+    len: usize,
+}
+
+#[derive(Clone)]
+struct Node<T> {
+    value: Option<T>,
+    children: [Option<Box<Self>>; 2],
+}
+
+impl<T> Default for Node<T> {
+    fn default() -> Self {
+        Self {
+            value: None,
+            children: [None, None],
+        }
+    }
+}
+
+impl<T> IpTrie<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            v4: Node::default(),
+            v6: Node::default(),
+            len: 0,
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Insert `net`, replacing the value of any identical network already in the trie.  Host bits set in `net` are ignored.
+    pub(crate) fn insert(&mut self, net: IpNet, value: T) {
+        let (mut node, bits) = match net {
+            IpNet::V4(net) => (&mut self.v4, u128::from(u32::from(net.network())) << 96),
+            IpNet::V6(net) => (&mut self.v6, u128::from(net.network())),
+        };
+
+        for bit in 0..net.prefix_len() {
+            node = node.children[branch(bits, bit)].get_or_insert_with(Box::default);
+        }
+
+        if node.value.replace(value).is_none() {
+            self.len += 1;
+        }
+    }
+
+    /// The value of the most specific network containing `addr`, if any
+    pub(crate) fn get(&self, addr: IpAddr) -> Option<&T> {
+        self.get_net(IpNet::from(addr))
+    }
+
+    /// The value of the most specific network containing all of `net`, if any
+    pub(crate) fn get_net(&self, net: IpNet) -> Option<&T> {
+        let (mut node, bits) = match net {
+            IpNet::V4(net) => (&self.v4, u128::from(u32::from(net.network())) << 96),
+            IpNet::V6(net) => (&self.v6, u128::from(net.network())),
+        };
+
+        let mut found = node.value.as_ref();
+        for bit in 0..net.prefix_len() {
+            let Some(child) = &node.children[branch(bits, bit)] else {
+                break;
+            };
+
+            node = child;
+            found = node.value.as_ref().or(found);
+        }
+
+        found
+    }
+
+    /// The value of exactly `net`, if it is in the trie
+    pub(crate) fn get_exact(&self, net: IpNet) -> Option<&T> {
+        let (mut node, bits) = match net {
+            IpNet::V4(net) => (&self.v4, u128::from(u32::from(net.network())) << 96),
+            IpNet::V6(net) => (&self.v6, u128::from(net.network())),
+        };
+
+        for bit in 0..net.prefix_len() {
+            node = node.children[branch(bits, bit)].as_ref()?;
+        }
+
+        node.value.as_ref()
+    }
+
+    /// Every network in the trie, with its value
+    pub(crate) fn entries(&self) -> Vec<(IpNet, &T)> {
+        let mut entries = Vec::with_capacity(self.len);
+        self.v4.collect(0, 0, &mut |bits, len, value| {
+            let addr = Ipv4Addr::from((bits >> 96) as u32);
+            entries.push((
+                IpNet::from(Ipv4Net::new(addr, len).expect("valid prefix")),
+                value,
+            ));
+        });
+        self.v6.collect(0, 0, &mut |bits, len, value| {
+            let net = Ipv6Net::new(Ipv6Addr::from(bits), len).expect("valid prefix");
+            entries.push((IpNet::from(net), value));
+        });
+        entries
+    }
+}
+
+impl<T> Node<T> {
+    /// Pass each value below this node to `found`, along with the bits and length of its prefix
+    fn collect<'a>(&'a self, bits: u128, len: u8, found: &mut impl FnMut(u128, u8, &'a T)) {
+        if let Some(value) = &self.value {
+            found(bits, len, value);
+        }
+
+        for (branch, child) in self.children.iter().enumerate() {
+            if let Some(child) = child {
+                child.collect(bits | ((branch as u128) << (127 - len)), len + 1, found);
+            }
+        }
+    }
+}
+
+/// Which child to follow for bit `bit` of an address, counting from the most significant
+fn branch(bits: u128, bit: u8) -> usize {
+    ((bits >> (127 - bit)) & 1) as usize
+}
+
+#[cfg(test)]
+mod test {
+    use crate::proto::rr::Record;
+    use crate::store::blocklist::{
+        BlockResponse, BlocklistAuthority, BlocklistConfig, ListEntry, ListFormat,
+    };
+    use crate::{
+        authority::{Authority, LookupOptions, ZoneType},
+        proto::rr::domain::Name,
+        proto::rr::{
+            rdata::{A, AAAA},
+            LowerName, RData, RecordType,
+        },
+    };
+    use std::net::Ipv6Addr;
+    use std::path::Path;
+    use std::str::FromStr;
+
+    #[tokio::test]
+    async fn test_blocklist_ip_blocklists() {
+        let config = BlocklistConfig {
+            lists: vec![ListEntry::from_path("default/blocklist.txt")],
+            ip_blocklists: vec!["default/blocklist_ips.txt".to_string()],
+            ..BlocklistConfig::default()
+        };
+
+        let authority = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            Some(Path::new("../../tests/test-data/test_configs/")),
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+
+        let name = Name::from_str("www.test.com.").unwrap();
+        let lower = LowerName::from(&name);
+
+        // Test: an answer containing an address within a blocked network is replaced with the block response.
+        let answers = vec![
+            Record::from_rdata(name.clone(), 300, RData::A(A::new(198, 51, 100, 1))),
+            Record::from_rdata(name.clone(), 300, RData::A(A::new(192, 0, 2, 10))),
+        ];
+        let filtered = authority
+            .filter_answers(&lower, RecordType::A, answers)
+            .expect("filter_answers failed");
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].data(), &RData::A(A::new(0, 0, 0, 0)));
+
+        // Test: single addresses are blocked as well as networks.
+        let answers = vec![Record::from_rdata(
+            name.clone(),
+            300,
+            RData::AAAA(AAAA::from_str("2001:db8::1").unwrap()),
+        )];
+        let filtered = authority
+            .filter_answers(&lower, RecordType::AAAA, answers)
+            .expect("filter_answers failed");
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(
+            filtered[0].data(),
+            &RData::AAAA(AAAA::from(Ipv6Addr::UNSPECIFIED))
+        );
+
+        // Test: answers outside the blocked networks are returned unchanged.
+        let answers = vec![
+            Record::from_rdata(name.clone(), 300, RData::A(A::new(192, 0, 3, 1))),
+            Record::from_rdata(
+                name.clone(),
+                300,
+                RData::AAAA(AAAA::from_str("2001:db8::2").unwrap()),
+            ),
+        ];
+        let filtered = authority
+            .filter_answers(&lower, RecordType::A, answers.clone())
+            .expect("filter_answers failed");
+        assert_eq!(filtered, answers);
+
+        // Test: blocks are counted against the address list; the invalid line is skipped.
+        let stats = authority.stats();
+        assert_eq!(stats.blocked, 2);
+        let list = stats
+            .lists
+            .iter()
+            .find(|list| list.source.ends_with("blocklist_ips.txt"))
+            .expect("address list missing from stats");
+        assert_eq!(list.blocked, 2);
+        let sources = authority.list_sources();
+        let source = sources
+            .iter()
+            .find(|list| list.source.ends_with("blocklist_ips.txt"))
+            .expect("address list missing from list sources");
+        assert_eq!(source.entries, 2);
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_reverse() {
+        async fn lookup(authority: &BlocklistAuthority, name: &str) -> Result<bool, bool> {
+            match authority
+                .lookup(
+                    &LowerName::from_str(name).unwrap(),
+                    RecordType::PTR,
+                    LookupOptions::default(),
+                )
+                .await
+            {
+                Ok(None) => Ok(false),
+                Ok(Some(_)) => Ok(true),
+                Err(e) => Err(e.is_nx_domain()),
+            }
+        }
+
+        let dir =
+            std::env::temp_dir().join(format!("hickory-blocklist-reverse-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("reverse.txt"),
+            "# Ranges whose reverse names are blocked\n192.0.2.0/24\n198.51.100.7\n1.0.0.10.in-addr.arpa\n8.b.d.0.1.0.0.2.ip6.arpa\nnot-a-range\n",
+        )
+        .unwrap();
+
+        let config = BlocklistConfig {
+            lists: vec![ListEntry {
+                format: ListFormat::Reverse,
+                response: Some(BlockResponse::NxDomain),
+                ..ListEntry::from_path("reverse.txt")
+            }],
+            ..BlocklistConfig::default()
+        };
+        let authority = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            Some(&dir),
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+
+        // Test: PTR queries for addresses within an IPv4 CIDR are blocked, and those outside it are passed on.
+        assert_eq!(
+            lookup(&authority, "5.2.0.192.in-addr.arpa.").await,
+            Err(true)
+        );
+        assert_eq!(
+            lookup(&authority, "255.2.0.192.in-addr.arpa.").await,
+            Err(true)
+        );
+        assert_eq!(
+            lookup(&authority, "5.3.0.192.in-addr.arpa.").await,
+            Ok(false)
+        );
+
+        // Test: a reverse name for the range itself is blocked, but not one for a wider range containing it.
+        assert_eq!(lookup(&authority, "2.0.192.in-addr.arpa.").await, Err(true));
+        assert_eq!(lookup(&authority, "0.192.in-addr.arpa.").await, Ok(false));
+
+        // Test: single addresses, reverse names and IPv6 ranges are read too.
+        assert_eq!(
+            lookup(&authority, "7.100.51.198.in-addr.arpa.").await,
+            Err(true)
+        );
+        assert_eq!(
+            lookup(&authority, "8.100.51.198.in-addr.arpa.").await,
+            Ok(false)
+        );
+        assert_eq!(
+            lookup(&authority, "1.0.0.10.in-addr.arpa.").await,
+            Err(true)
+        );
+        let v6 = "1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa.";
+        assert_eq!(lookup(&authority, v6).await, Err(true));
+
+        // Test: the invalid line is skipped.
+        assert_eq!(authority.list_sources()[0].entries, 4);
+        assert_eq!(authority.stats().matches.reverse, 6);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
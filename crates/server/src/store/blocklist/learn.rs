@@ -0,0 +1,152 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Collection of candidate block list entries from failed queries
+
+use std::{fmt, sync::Mutex, time::SystemTime};
+
+use lru_cache::LruCache;
+
+use crate::proto::{
+    op::ResponseCode,
+    rr::{LowerName, Name},
+};
+
+/// A name which clients queried, and which the next store in the chain failed to resolve
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LearnedName {
+    /// The queried name
+    pub name: Name,
+    /// Queries for the name which were answered with NXDOMAIN
+    pub nxdomain: u64,
+    /// Queries for the name which were answered with SERVFAIL
+    pub servfail: u64,
+    /// When the name was last queried
+    pub last_seen: SystemTime,
+}
+
+impl LearnedName {
+    /// Total number of failed queries for the name
+    pub fn queries(&self) -> u64 {
+        self.nxdomain + self.servfail
+    }
+}
+
+/// Formats the name as a block list line, with the query counts in a comment, so that a report can be reviewed and then
+/// used as a list directly.
+impl fmt::Display for LearnedName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} # {} NXDOMAIN, {} SERVFAIL",
+            self.name, self.nxdomain, self.servfail
+        )
+    }
+}
+
+/// The most recently seen names which failed to resolve.  Once full, the least recently seen name is evicted to make room.
+pub(crate) struct Learner {
+    names: Mutex<LruCache<LowerName, LearnedName>>,
+}
+
+impl Learner {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            names: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// Record the response code of a query which was passed on; only NXDOMAIN and SERVFAIL responses are counted.
+    pub(crate) fn observe(&self, name: &LowerName, response_code: ResponseCode) {
+        let (nxdomain, servfail) = match response_code {
+            ResponseCode::NXDomain => (1, 0),
+            ResponseCode::ServFail => (0, 1),
+            _ => return,
+        };
+
+        let mut names = self.names.lock().expect("learn lock poisoned");
+        if let Some(learned) = names.get_mut(name) {
+            learned.nxdomain += nxdomain;
+            learned.servfail += servfail;
+            learned.last_seen = SystemTime::now();
+            return;
+        }
+
+        names.insert(
+            name.clone(),
+            LearnedName {
+                name: name.into(),
+                nxdomain,
+                servfail,
+                last_seen: SystemTime::now(),
+            },
+        );
+    }
+
+    /// The learned names, most frequently failed first
+    pub(crate) fn report(&self) -> Vec<LearnedName> {
+        let names = self.names.lock().expect("learn lock poisoned");
+        let mut report = names
+            .iter()
+            .map(|(_, learned)| learned.clone())
+            .collect::<Vec<_>>();
+        report.sort_by_key(|learned| std::cmp::Reverse(learned.queries()));
+        report
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::store::blocklist::{BlocklistAuthority, BlocklistConfig};
+    use crate::{
+        authority::{Authority, ZoneType},
+        proto::op::ResponseCode,
+        proto::rr::domain::Name,
+        proto::rr::LowerName,
+    };
+    use std::str::FromStr;
+
+    #[tokio::test]
+    async fn test_blocklist_learn() {
+        let config = BlocklistConfig {
+            learn: true,
+            learn_capacity: 2,
+            ..BlocklistConfig::default()
+        };
+
+        let authority = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            None,
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+
+        let name = |name| LowerName::from_str(name).unwrap();
+        authority.observe_response(&name("a.example."), ResponseCode::NXDomain);
+        authority.observe_response(&name("a.example."), ResponseCode::NXDomain);
+        authority.observe_response(&name("b.example."), ResponseCode::ServFail);
+        authority.observe_response(&name("a.example."), ResponseCode::ServFail);
+        authority.observe_response(&name("c.example."), ResponseCode::NoError);
+        authority.observe_response(&name("d.example."), ResponseCode::NXDomain);
+
+        // Test: only failed queries are collected, most frequent first, and the least recently seen name is evicted when
+        // the collection is full.
+        let learned = authority.learned();
+        assert_eq!(learned.len(), 2);
+        assert_eq!(learned[0].name, Name::from_str("a.example.").unwrap());
+        assert_eq!(learned[0].queries(), 3);
+        assert_eq!(
+            learned[0].to_string(),
+            "a.example. # 2 NXDOMAIN, 1 SERVFAIL"
+        );
+        assert_eq!(learned[1].name, Name::from_str("d.example.").unwrap());
+        assert_eq!(learned[1].nxdomain, 1);
+    }
+}
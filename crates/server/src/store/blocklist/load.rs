@@ -0,0 +1,1727 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Loading of the lists and settings which make up a blocklist policy
+
+use std::{
+    cmp::Reverse,
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use encoding_rs::Encoding;
+use futures_util::future::join_all;
+use regex::{Regex, RegexSet};
+use ring::digest;
+use serde::{
+    de::{value::StrDeserializer, IntoDeserializer},
+    Deserialize,
+};
+use tracing::{debug, info, warn};
+
+use crate::store::blocklist::{
+    authority::BlocklistAuthorityBuilder,
+    compiled::{CompileKey, CompileListKey, CompiledPolicy, ListKind},
+    config::parse_manifest,
+    homograph::Homographs,
+    iptrie::IpTrie,
+    matcher::{new_matcher, BlockMatcher},
+    policy::{
+        canonical_name, is_safe_sinkhole, list_encoding, EntryResponse, Fetch, FetchedList, Policy,
+        DEFAULT_LIST_SOURCE,
+    },
+    remote::{verify_sha256, Fetcher},
+    responses::ResponseCache,
+    BlockResponse, BlocklistConfig, ListAction, ListEntry, ListFormat, PolicyEvent,
+    PublicSinkholePolicy,
+};
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::prelude::*;
+
+/// The list path which reads the list from standard input, as `-` does for the query log's standard output
+const STDIN_PATH: &str = "-";
+
+impl BlocklistAuthorityBuilder {
+    /// Check the sinkhole addresses, with `sinkhole_ipv4` and `sinkhole_ipv6` as the blocklist-wide ones: publicly routable
+    /// addresses are refused if `public_sinkhole` is `refuse`, as is answering with CNAME records without an address for
+    /// the `dname_target`.
+    pub(super) fn check_sinkholes(
+        &self,
+        sinkhole_ipv4: Ipv4Addr,
+        sinkhole_ipv6: Ipv6Addr,
+    ) -> Result<(), String> {
+        let config = &self.config;
+        let list_sinkholes = config.lists.iter().flat_map(|entry| {
+            let ipv4 = entry.sinkhole_ipv4.map(IpAddr::V4);
+            let ipv6 = entry.sinkhole_ipv6.map(IpAddr::V6);
+            ipv4.into_iter().chain(ipv6)
+        });
+        let subnet_addrs = config.subnet_sinkholes.iter().flat_map(|sinkholes| {
+            let ipv4 = sinkholes.sinkhole_ipv4.map(IpAddr::V4);
+            let ipv6 = sinkholes.sinkhole_ipv6.map(IpAddr::V6);
+            ipv4.into_iter().chain(ipv6)
+        });
+        for addr in [IpAddr::V4(sinkhole_ipv4), IpAddr::V6(sinkhole_ipv6)]
+            .into_iter()
+            .chain(config.sinkhole_pool.iter().copied())
+            .chain(subnet_addrs)
+            .chain(list_sinkholes)
+        {
+            if is_safe_sinkhole(addr, &config.sinkhole_networks) {
+                continue;
+            }
+
+            match config.public_sinkhole {
+                PublicSinkholePolicy::Allow => {}
+                PublicSinkholePolicy::Warn => {
+                    warn!("blocklist sinkhole address {addr} is publicly routable; blocked clients will be sent to it")
+                }
+                PublicSinkholePolicy::Refuse => {
+                    return Err(format!(
+                        "blocklist sinkhole address {addr} is publicly routable; add it to sinkhole_networks if this is intended"
+                    ))
+                }
+            }
+        }
+
+        // The address following a CNAME is the one clients connect to, so it has to be somewhere they can reach.
+        let unreachable =
+            |ipv4: Ipv4Addr, ipv6: Ipv6Addr| ipv4.is_unspecified() && ipv6.is_unspecified();
+        let global = [Some(config.block_response), config.upstream_sink_response]
+            .contains(&Some(BlockResponse::Cname))
+            && unreachable(sinkhole_ipv4, sinkhole_ipv6);
+        let list = config.lists.iter().find(|entry| {
+            entry.response == Some(BlockResponse::Cname)
+                && unreachable(
+                    entry.sinkhole_ipv4.unwrap_or(sinkhole_ipv4),
+                    entry.sinkhole_ipv6.unwrap_or(sinkhole_ipv6),
+                )
+        });
+        if global || list.is_some() {
+            return Err(format!(
+                "blocklist answers with cname, but has no sinkhole address for the dname_target{}",
+                list.map_or_else(String::new, |entry| format!(": {entry:?}"))
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Build a policy holding the entries and allowed names added to the builder, but none of the configured lists.
+    pub(super) fn policy(&self) -> Result<Policy, String> {
+        let config = &self.config;
+        let (wildcard_match, min_wildcard_depth) = self
+            .wildcards
+            .lock()
+            .expect("blocklist wildcard settings lock poisoned")
+            .unwrap_or((config.wildcard_match, config.min_wildcard_depth));
+        let (sinkhole_ipv4, sinkhole_ipv6) = self
+            .sinkholes
+            .lock()
+            .expect("blocklist sinkhole settings lock poisoned")
+            .unwrap_or((config.sinkhole_ipv4, config.sinkhole_ipv6));
+        self.check_sinkholes(sinkhole_ipv4, sinkhole_ipv6)?;
+
+        for entry in self.list_entries() {
+            if let Some(category) = &entry.category {
+                if !config.categories.contains_key(category) {
+                    return Err(format!(
+                        "blocklist category {category:?} is not one of the configured categories: {entry:?}"
+                    ));
+                }
+            }
+            list_encoding(&entry)?;
+        }
+
+        for entry in config.lists.iter().chain(&config.monitor_lists) {
+            match (entry.ede_code, &entry.ede_text) {
+                (None, Some(_)) => {
+                    return Err(format!(
+                        "blocklist entry has ede_text, but no ede_code: {entry:?}"
+                    ))
+                }
+                (Some(code), _) if !is_extended_error_code(code) => {
+                    return Err(format!(
+                        "blocklist ede_code {code} is neither a registered extended DNS error nor a private use one: {entry:?}"
+                    ))
+                }
+                _ => {}
+            }
+        }
+
+        let responses = config.lists.iter().map(|entry| entry.response);
+        let dname = [Some(config.block_response), config.upstream_sink_response]
+            .into_iter()
+            .chain(responses)
+            .any(|response| {
+                matches!(
+                    response,
+                    Some(BlockResponse::Dname) | Some(BlockResponse::Cname)
+                )
+            });
+        match &config.dname_target {
+            None if dname => {
+                return Err(
+                    "blocklist answers with dname or cname, but no dname_target is configured"
+                        .to_string(),
+                )
+            }
+            Some(target) if !target.is_fqdn() => {
+                return Err(format!(
+                    "blocklist dname_target {target} is not fully qualified"
+                ))
+            }
+            Some(target) if target.is_root() => {
+                return Err("blocklist dname_target can not be the root".to_string())
+            }
+            _ => {}
+        }
+
+        let mut subnet_sinkholes = config.subnet_sinkholes.clone();
+        subnet_sinkholes.sort_by_key(|sinkholes| Reverse(sinkholes.subnet.prefix_len()));
+        let new_table = || -> Arc<dyn BlockMatcher> {
+            match &self.matcher {
+                Some(matcher) => matcher().into(),
+                None => new_matcher(config.matcher).into(),
+            }
+        };
+        let mut policy = Policy {
+            blocklist: new_table(),
+            lists: Vec::new(),
+            allow_regex: None,
+            allowlist: self.allow.iter().cloned().collect(),
+            first_party_domains: config
+                .first_party_domains
+                .iter()
+                .cloned()
+                .map(canonical_name)
+                .collect(),
+            monitorlist: new_table(),
+            negations: Vec::new(),
+            remote: HashMap::new(),
+            wildcard_match,
+            min_wildcard_depth,
+            shallowest_wildcard_depth: min_wildcard_depth,
+            wildcard_token: config.wildcard_token.clone(),
+            max_line_length: config.max_line_length,
+            wildcard_blocks_parent: config.wildcard_blocks_parent,
+            nodata_types: config.nodata_types.clone(),
+            block_response: config.block_response,
+            sinkhole_ipv4,
+            sinkhole_ipv6,
+            sinkhole_pool_ipv4: config
+                .sinkhole_pool
+                .iter()
+                .filter_map(|addr| match addr {
+                    IpAddr::V4(addr) => Some(*addr),
+                    IpAddr::V6(_) => None,
+                })
+                .collect(),
+            sinkhole_pool_ipv6: config
+                .sinkhole_pool
+                .iter()
+                .filter_map(|addr| match addr {
+                    IpAddr::V4(_) => None,
+                    IpAddr::V6(addr) => Some(*addr),
+                })
+                .collect(),
+            subnet_sinkholes,
+            dname_target: config.dname_target.clone(),
+            sinkhole_ttl: config.sinkhole_ttl.unwrap_or(config.ttl),
+            client_ttl: config.client_ttl.unwrap_or(config.ttl),
+            dname_ttl: config.dname_ttl.unwrap_or(config.ttl),
+            nxdomain_ttl: config.nxdomain_ttl,
+            nodata_ttl: config.nodata_ttl,
+            ttl_jitter: config.ttl_jitter,
+            strip_answer_networks: config.strip_answer_networks.clone(),
+            block_cname_targets: config.block_cname_targets,
+            max_cname_targets: config.max_cname_targets,
+            block_ptr_targets: config.block_ptr_targets,
+            strip_client_subnet: config.strip_client_subnet,
+            ip_blocklist: IpTrie::new(),
+            reverse_blocklist: IpTrie::new(),
+            reverse_monitorlist: IpTrie::new(),
+            upstream_sinks: None,
+            homographs: None,
+            entry_responses: HashMap::new(),
+            first_seen: HashMap::new(),
+            public_sinkhole: config.public_sinkhole,
+            sinkhole_networks: config.sinkhole_networks.clone(),
+            response_cache: (config.response_cache_size > 0)
+                .then(|| ResponseCache::new(config.response_cache_size)),
+            // The serial starts from the time, so that it does not go backwards when the server restarts.
+            serial: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(1, |now| now.as_secs() as u32),
+            negative_soas: Mutex::default(),
+        };
+
+        if !config.upstream_sink_addresses.is_empty() {
+            let entry = ListEntry {
+                response: config.upstream_sink_response,
+                ..ListEntry::from_path("upstream sink addresses")
+            };
+            let list = policy.push_list("upstream sink addresses".to_string(), &entry);
+            policy.lists[list].entries = config.upstream_sink_addresses.len();
+            policy.upstream_sinks = Some((config.upstream_sink_addresses.clone(), list));
+        }
+
+        if !config.homograph_protected.is_empty() {
+            let source = "homographs of protected domains";
+            let list = policy.push_list(source.to_string(), &ListEntry::from_path(source));
+            let homographs = Homographs::new(&config.homograph_protected);
+            policy.lists[list].entries = homographs.len();
+            policy.homographs = Some((homographs, list));
+        }
+
+        if !self.entries.is_empty() {
+            let list = policy.push_list("builder".to_string(), &ListEntry::from_path("builder"));
+            for name in &self.entries {
+                policy.insert_name(name.clone(), list);
+            }
+            policy.compact(&self.origin);
+        }
+
+        Ok(policy)
+    }
+
+    /// Build a policy holding every configured list, resolving relative list paths against `list_dir`.  This fails if any
+    /// list can not be loaded, with an error for each list which failed.
+    pub(super) async fn load(
+        &self,
+        list_dir: Option<&Path>,
+        fetch: Fetch<'_>,
+        fetcher: &Fetcher,
+    ) -> Result<Policy, Vec<String>> {
+        if let Some(path) = &self.config.compiled_policy {
+            let mut policy = self
+                .load_compiled(&resolve_path(list_dir, path))
+                .map_err(|e| vec![e])?;
+            policy.allow_regex = self.load_allow_regex(list_dir).map_err(|e| vec![e])?;
+            policy.warn_allow_overlaps(&self.origin);
+            return Ok(policy);
+        }
+
+        let mut policy = self.policy().map_err(|e| vec![e])?;
+        let entries = self
+            .list_entries()
+            .enumerate()
+            .filter(|(_, entry)| {
+                if !entry.enabled {
+                    info!("Skipping disabled blocklist {entry:?}");
+                }
+                entry.enabled
+            })
+            .collect::<Vec<_>>();
+
+        let mut errors = Vec::new();
+        let mut remote = Vec::new();
+        for (index, entry) in &entries {
+            let first = policy.lists.len();
+            let added = match (&entry.path, &entry.url) {
+                (Some(path), None) if path == STDIN_PATH => {
+                    info!("Adding blocklist from standard input");
+                    self.stdin_list().await.and_then(|contents| {
+                        verify_contents("standard input", &contents, entry)?;
+                        policy
+                            .read_list("stdin".to_string(), &contents[..], entry)
+                            .map(drop)
+                    })
+                }
+                (Some(path), None) => {
+                    let path = resolve_path(list_dir, path);
+                    info!("Adding blocklist {path:?}");
+                    if path.extension().map_or(false, |ext| ext == "zip") {
+                        policy
+                            .add_archive(path.display().to_string(), entry)
+                            .map(drop)
+                    } else {
+                        policy.add_list(path.display().to_string(), entry)
+                    }
+                }
+                (None, Some(url)) => match fetch.reuse(url) {
+                    Some(fetched) => policy.add_spooled(url.clone(), fetched.clone(), entry),
+                    None => list_encoding(entry).map(|encoding| {
+                        let list = policy.push_list(url.clone(), entry);
+                        remote.push((list, url.as_str(), entry, encoding));
+                    }),
+                },
+                _ => Err(format!(
+                    "blocklist entries require exactly one of path or url: {entry:?}"
+                )),
+            };
+
+            if let Err(e) = added {
+                errors.push(e);
+            }
+            for list in &mut policy.lists[first..] {
+                list.config = Some(ListKind::Names(*index as u32));
+            }
+        }
+        errors.extend(self.fetch_remote(&mut policy, &remote, fetcher).await);
+
+        if self.config.use_default_lists {
+            match policy.add_default_list() {
+                Ok(list) => policy.lists[list].config = Some(ListKind::Default),
+                Err(e) => errors.push(e),
+            }
+        }
+
+        for path in &self.config.ip_blocklists {
+            let path = resolve_path(list_dir, path);
+            info!("Adding address blocklist {path:?}");
+            match policy.add_ip_list(path.display().to_string()) {
+                Ok(list) => policy.lists[list].config = Some(ListKind::Addresses),
+                Err(e) => errors.push(e),
+            }
+        }
+
+        match self.load_allow_regex(list_dir) {
+            Ok(allow_regex) => policy.allow_regex = allow_regex,
+            Err(e) => errors.push(e),
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        policy.apply_negations();
+        policy.compact(&self.origin);
+        policy.warn_allow_overlaps(&self.origin);
+        Ok(policy)
+    }
+
+    /// Pass the event built by `event` to the handler set with [`BlocklistAuthority::on_event`].  The event is only built if
+    /// there is a handler.
+    pub(super) fn emit(&self, event: impl FnOnce() -> PolicyEvent) {
+        let handler = self
+            .events
+            .lock()
+            .expect("blocklist event handler lock poisoned")
+            .clone();
+        if let Some(handler) = handler {
+            handler(&event());
+        }
+    }
+
+    /// The list given on standard input, which is read to its end the first time it is loaded.  The read blocks, so it is
+    /// made on a blocking thread; loads which need the list while it is being read wait for that read.
+    async fn stdin_list(&self) -> Result<Arc<[u8]>, String> {
+        let read = || async {
+            let contents = tokio::task::spawn_blocking(|| {
+                let mut contents = Vec::new();
+                io::stdin()
+                    .lock()
+                    .read_to_end(&mut contents)
+                    .map(|_| contents)
+            })
+            .await
+            .map_err(io::Error::from)
+            .and_then(|read| read)
+            .map_err(|e| format!("unable to read block list from standard input: {e}"))?;
+            Ok(Arc::from(contents))
+        };
+        self.stdin.get_or_try_init(read).await.cloned()
+    }
+
+    /// Compile the patterns on the `allow_regex_lists`, resolving relative paths against `list_dir`.  Invalid patterns are
+    /// logged and skipped, and the load fails if there are more than `max_allow_patterns`.
+    fn load_allow_regex(&self, list_dir: Option<&Path>) -> Result<Option<RegexSet>, String> {
+        if self.config.allow_regex_lists.is_empty() {
+            return Ok(None);
+        }
+
+        let start = Instant::now();
+        let mut patterns = Vec::new();
+        for path in &self.config.allow_regex_lists {
+            let path = resolve_path(list_dir, path);
+            info!("Adding allow regex list {path:?}");
+            let list = fs::read_to_string(&path)
+                .map_err(|e| format!("unable to read allow regex list {path:?}: {e}"))?;
+            for (line_number, line) in list.lines().enumerate() {
+                let pattern = line.trim();
+                if pattern.is_empty() || pattern.starts_with('#') {
+                    continue;
+                }
+
+                if let Err(e) = Regex::new(pattern) {
+                    warn!(
+                        "skipping line {} of allow regex list {path:?}: {e}",
+                        line_number + 1
+                    );
+                    continue;
+                }
+                patterns.push(pattern.to_string());
+            }
+
+            if patterns.len() > self.config.max_allow_patterns {
+                return Err(format!(
+                    "allow regex lists have more than {} patterns, at {path:?}; raise max_allow_patterns to load them",
+                    self.config.max_allow_patterns
+                ));
+            }
+        }
+
+        let count = patterns.len();
+        let allow_regex = RegexSet::new(patterns)
+            .map_err(|e| format!("unable to compile allow regex lists: {e}"))?;
+        info!(
+            "compiled {count} allow patterns from {} lists in {:?}",
+            self.config.allow_regex_lists.len(),
+            start.elapsed()
+        );
+        Ok(Some(allow_regex))
+    }
+
+    /// Fetch the remote lists in `remote`, each given by the index of the list in `policy` its lines are added to, and the
+    /// settings of that list.  Lists are downloaded concurrently, up to the fetcher's limit, and each line is added to the
+    /// policy as it arrives; lists which share a url are fetched once.  Returns an error for each list which failed.
+    async fn fetch_remote(
+        &self,
+        policy: &mut Policy,
+        remote: &[(usize, &str, &ListEntry, Option<&'static Encoding>)],
+        fetcher: &Fetcher,
+    ) -> Vec<String> {
+        let mut urls: Vec<&str> = Vec::new();
+        for &(_, url, _, _) in remote {
+            if !urls.contains(&url) {
+                urls.push(url);
+            }
+        }
+        if urls.is_empty() {
+            return Vec::new();
+        }
+
+        info!(
+            "Fetching {} remote blocklists, {} at a time",
+            urls.len(),
+            fetcher.concurrency().min(urls.len())
+        );
+        let timeout = Duration::from_secs(self.config.remote_timeout);
+        let shared = &Mutex::new(&mut *policy);
+        let fetches = urls.iter().map(|&url| {
+            let lists = remote
+                .iter()
+                .filter(|(_, list_url, _, _)| *list_url == url)
+                .collect::<Vec<_>>();
+            let sha256 = lists
+                .iter()
+                .find_map(|(_, _, entry, _)| entry.sha256.as_deref());
+            async move {
+                let spool = fetcher
+                    .fetch(url, sha256, timeout, |line_number, line| {
+                        let mut policy = shared.lock().expect("blocklist policy lock poisoned");
+                        for &&(list, _, entry, encoding) in &lists {
+                            policy.read_line(url, line_number, line, list, entry.format, encoding);
+                        }
+                    })
+                    .await?;
+                Ok::<_, String>((url, spool))
+            }
+        });
+        let fetched = join_all(fetches).await;
+
+        let mut errors = Vec::new();
+        for fetched in fetched {
+            let (url, spool) = match fetched {
+                Ok(fetched) => fetched,
+                Err(e) => {
+                    errors.push(e);
+                    continue;
+                }
+            };
+
+            let now = SystemTime::now();
+            for &(list, _, _, _) in remote.iter().filter(|(_, list_url, _, _)| *list_url == url) {
+                policy.lists[list].remote = true;
+                policy.lists[list].loaded = now;
+            }
+            if let Some(spool) = spool {
+                let fetched = FetchedList {
+                    spool,
+                    fetched: now,
+                };
+                policy.remote.insert(url.to_string(), fetched);
+            }
+        }
+
+        errors
+    }
+
+    /// Build a policy from the copies of the lists kept in `cache_dir`.  Address lists are not cached, and are read from
+    /// their configured paths, resolved against `list_dir`.
+    pub(super) async fn load_cached(
+        &self,
+        list_dir: Option<&Path>,
+        cache_dir: &Path,
+        fetcher: &Fetcher,
+    ) -> Result<Policy, String> {
+        let mut cached = self.clone();
+        for path in &mut cached.config.ip_blocklists {
+            *path = resolve_path(list_dir, path).display().to_string();
+        }
+
+        let entries = cached
+            .config
+            .lists
+            .iter_mut()
+            .chain(cached.config.monitor_lists.iter_mut());
+        for entry in entries {
+            if let Some(name) = cache_name(entry) {
+                entry.path = Some(cache_dir.join(name).display().to_string());
+                // Remote lists are cached after decompression, so their checksums no longer apply.
+                if entry.url.take().is_some() {
+                    entry.sha256 = None;
+                }
+            }
+        }
+
+        cached
+            .load(None, Fetch::All, fetcher)
+            .await
+            .map_err(|errors| errors.join("; "))
+    }
+
+    /// Build a policy from the compiled policy file at `path`, instead of reading the configured lists.  This fails if the
+    /// file was compiled from a different list configuration.
+    fn load_compiled(&self, path: &Path) -> Result<Policy, String> {
+        info!("Loading compiled blocklist policy {path:?}");
+        let compiled = fs::read(path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| CompiledPolicy::decode(&data))
+            .map_err(|e| format!("unable to load compiled blocklist policy {path:?}: {e}"))?;
+        if compiled.key != self.compile_key() {
+            return Err(format!(
+                "compiled blocklist policy {path:?} was compiled from a different list configuration; compile it again"
+            ));
+        }
+
+        let mut policy = self.policy()?;
+        let entries = self.list_entries().collect::<Vec<_>>();
+        let first = policy.lists.len();
+        for list in compiled.lists {
+            let entry = match list.kind {
+                ListKind::Names(index) => entries
+                    .get(index as usize)
+                    .cloned()
+                    .ok_or_else(|| format!("compiled blocklist policy {path:?} refers to list {index}, which is not configured"))?,
+                ListKind::Addresses => ListEntry::from_path(&list.source),
+                ListKind::Default => ListEntry::from_path(DEFAULT_LIST_SOURCE),
+            };
+
+            let index = policy.push_list(list.source, &entry);
+            let loaded = &mut policy.lists[index];
+            loaded.remote = list.remote;
+            loaded.entries = list.entries as usize;
+            loaded.loaded = list.loaded;
+            loaded.config = Some(list.kind);
+        }
+
+        let count = policy.lists.len();
+        let list = |index: u32| {
+            match first + index as usize {
+            list if list < count => Ok(list),
+            _ => Err(format!(
+                "compiled blocklist policy {path:?} refers to list {index}, which it does not contain"
+            )),
+        }
+        };
+        let (blocklist, monitorlist) = policy.tables_mut();
+        for (name, index) in compiled.blocked {
+            blocklist.insert(name, list(index)?);
+        }
+        for (name, index) in compiled.monitored {
+            monitorlist.insert(name, list(index)?);
+        }
+        for response in compiled.responses {
+            let entry = EntryResponse {
+                response: response.response,
+                sinkhole: response.sinkhole,
+                ttl: response.ttl,
+            };
+            policy
+                .entry_responses
+                .insert((response.name, list(response.list)?), entry);
+        }
+        for (net, index) in compiled.networks {
+            policy.ip_blocklist.insert(net, list(index)?);
+        }
+        for (net, index) in compiled.reverse {
+            let list = list(index)?;
+            policy.reverse_table(list).insert(net, list);
+        }
+
+        policy.compact(&self.origin);
+        Ok(policy)
+    }
+
+    /// The digest of the [`CompileKey`] identifying the list configuration a compiled policy is built from
+    pub(super) fn compile_key(&self) -> String {
+        let config = &self.config;
+        let entries = self.list_entries().collect::<Vec<_>>();
+        let lists = entries
+            .iter()
+            .map(|entry| CompileListKey {
+                path: entry.path.as_deref(),
+                url: entry.url.as_deref(),
+                sha256: entry.sha256.as_deref(),
+                format: entry.format,
+                encoding: entry.encoding.as_deref(),
+                action: entry.action,
+                priority: entry.priority,
+                enabled: entry.enabled,
+                depth: entry.depth,
+                min_wildcard_depth: entry.min_wildcard_depth,
+                include_apex: entry.include_apex,
+            })
+            .collect();
+
+        CompileKey {
+            lists,
+            use_default_lists: config.use_default_lists,
+            ip_blocklists: &config.ip_blocklists,
+            max_line_length: config.max_line_length,
+            wildcard_match: config.wildcard_match,
+            min_wildcard_depth: config.min_wildcard_depth,
+            wildcard_token: &config.wildcard_token,
+            public_sinkhole: config.public_sinkhole,
+            sinkhole_networks: &config.sinkhole_networks,
+        }
+        .digest()
+    }
+
+    /// The directory lists are cached in, if the cache is enabled
+    pub(super) fn cache_dir(&self, list_dir: Option<&Path>) -> Option<PathBuf> {
+        let cache_dir = self.config.cache_dir.as_ref()?;
+        Some(resolve_path(list_dir, cache_dir))
+    }
+
+    /// Keep a copy of each list `policy` was loaded from in the cache, if it is enabled.  Each copy replaces the previous
+    /// one in a single step, so a failure part way through leaves the cache usable.
+    pub(super) fn save_cache(&self, list_dir: Option<&Path>, policy: &Policy) {
+        let Some(cache_dir) = self.cache_dir(list_dir) else {
+            return;
+        };
+        if self.config.compiled_policy.is_some() {
+            return;
+        }
+
+        let save = || -> io::Result<()> {
+            fs::create_dir_all(&cache_dir)?;
+            for entry in self.list_entries().filter(|entry| entry.enabled) {
+                let Some(name) = cache_name(&entry) else {
+                    continue;
+                };
+
+                let target = cache_dir.join(&name);
+                let partial = cache_dir.join(format!("{name}.partial"));
+                match (&entry.path, &entry.url) {
+                    (Some(path), None) if path == STDIN_PATH => match self.stdin.get() {
+                        Some(contents) => fs::write(&partial, contents)?,
+                        None => continue,
+                    },
+                    (Some(path), None) => {
+                        fs::copy(resolve_path(list_dir, path), &partial)?;
+                    }
+                    (None, Some(url)) => match policy.remote.get(url) {
+                        Some(list) => {
+                            fs::copy(&list.spool, &partial)?;
+                        }
+                        None => continue,
+                    },
+                    _ => continue,
+                }
+                fs::rename(&partial, &target)?;
+            }
+
+            Ok(())
+        };
+
+        match save() {
+            Ok(()) => debug!("saved blocklist cache to {cache_dir:?}"),
+            Err(e) => warn!("unable to save blocklist cache to {cache_dir:?}: {e}"),
+        }
+    }
+
+    /// Every configured list, followed by the lists added at runtime, with the settings of their categories applied.
+    /// Monitor lists are shorthand for lists with the monitor action.
+    pub(super) fn list_entries(&self) -> impl Iterator<Item = ListEntry> + '_ {
+        let monitor_lists = self.config.monitor_lists.iter().map(|entry| ListEntry {
+            action: Some(ListAction::Monitor),
+            ..entry.clone()
+        });
+        let runtime = self
+            .runtime
+            .lock()
+            .expect("blocklist runtime lists lock poisoned")
+            .clone();
+        self.config
+            .lists
+            .iter()
+            .cloned()
+            .chain(monitor_lists)
+            .chain(runtime)
+            .map(|entry| {
+                let Some(category) = entry
+                    .category
+                    .as_ref()
+                    .and_then(|category| self.config.categories.get(category))
+                else {
+                    return entry;
+                };
+
+                ListEntry {
+                    action: entry.action.or(category.action),
+                    response: entry.response.or(category.response),
+                    ..entry
+                }
+            })
+    }
+
+    /// Read the `manifests`, adding the lists they name to the configured lists.  Every invalid manifest entry is reported.
+    pub(super) fn load_manifests(&mut self, list_dir: Option<&Path>) -> Result<(), String> {
+        let mut errors = Vec::new();
+        for manifest in &self.config.manifests {
+            let path = resolve_path(list_dir, manifest);
+            let contents = match fs::read_to_string(&path) {
+                Ok(contents) => contents,
+                Err(e) => {
+                    errors.push(format!("unable to read blocklist manifest {path:?}: {e}"));
+                    continue;
+                }
+            };
+
+            let lists = match parse_manifest(&contents) {
+                Ok(lists) => lists,
+                Err(e) => {
+                    errors.extend(
+                        e.into_iter()
+                            .map(|e| format!("invalid blocklist manifest {path:?}: {e}")),
+                    );
+                    continue;
+                }
+            };
+
+            info!("blocklist manifest {path:?} names {} lists", lists.len());
+            // List paths are kept relative to the directory lists are resolved against, as those of the configured
+            // lists are.
+            let manifest_dir = Path::new(manifest).parent();
+            self.config
+                .lists
+                .extend(lists.into_iter().map(|entry| ListEntry {
+                    path: entry.path.map(|list| {
+                        resolve_path(manifest_dir, &list)
+                            .to_string_lossy()
+                            .into_owned()
+                    }),
+                    ..entry
+                }));
+        }
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(errors.join("; ")),
+        }
+    }
+
+    /// Read the lists recorded in the `runtime_lists` file, if any, so that they are loaded along with the configured
+    /// lists.  A missing file holds no lists.
+    pub(super) fn load_runtime_lists(&self, list_dir: Option<&Path>) -> Result<(), String> {
+        let Some(path) = &self.config.runtime_lists else {
+            return Ok(());
+        };
+        if self.config.compiled_policy.is_some() || self.config.share_lists {
+            return Err(
+                "blocklist runtime_lists can not be combined with compiled_policy or share_lists"
+                    .to_string(),
+            );
+        }
+
+        let path = resolve_path(list_dir, path);
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => {
+                return Err(format!(
+                    "unable to read blocklist runtime lists {path:?}: {e}"
+                ))
+            }
+        };
+
+        let mut runtime = self
+            .runtime
+            .lock()
+            .expect("blocklist runtime lists lock poisoned");
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let entry = parse_runtime_list(line).map_err(|e| {
+                format!(
+                    "invalid blocklist runtime list at {path:?} line {}: {e}",
+                    number + 1
+                )
+            })?;
+            info!("Adding blocklist {line:?} recorded in {path:?}");
+            runtime.push(entry);
+        }
+
+        Ok(())
+    }
+
+    /// Write the lists added at runtime to the `runtime_lists` file, if one is configured
+    pub(super) fn save_runtime_lists(&self, list_dir: Option<&Path>) -> Result<(), String> {
+        let Some(path) = &self.config.runtime_lists else {
+            return Ok(());
+        };
+
+        let path = resolve_path(list_dir, path);
+        let mut contents = String::from("# Lists added at runtime; written by hickory-dns\n");
+        for entry in self
+            .runtime
+            .lock()
+            .expect("blocklist runtime lists lock poisoned")
+            .iter()
+        {
+            contents.push_str(&format_runtime_list(entry)?);
+            contents.push('\n');
+        }
+
+        let partial = path.with_extension("partial");
+        fs::write(&partial, contents)
+            .and_then(|()| fs::rename(&partial, &path))
+            .map_err(|e| format!("unable to write blocklist runtime lists {path:?}: {e}"))
+    }
+}
+
+/// The directory relative list paths are resolved against: `config_dir` when `config_relative` is set, otherwise
+/// `root_dir`.
+pub(super) fn list_dir<'a>(
+    config: &BlocklistConfig,
+    root_dir: Option<&'a Path>,
+    config_dir: Option<&'a Path>,
+) -> Result<Option<&'a Path>, String> {
+    match (config.config_relative, config_dir) {
+        (false, _) => Ok(root_dir),
+        (true, Some(config_dir)) => Ok(Some(config_dir)),
+        (true, None) => Err(
+            "blocklist config_relative is set, but the configuration file location is unknown"
+                .to_string(),
+        ),
+    }
+}
+
+/// Resolve a configured path against `list_dir`, if it is relative
+pub(super) fn resolve_path(list_dir: Option<&Path>, path: &str) -> PathBuf {
+    match list_dir {
+        Some(dir) if Path::new(path).is_relative() => dir.join(path),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// The name of the cached copy of the list configured by `entry`.  Remote lists are cached as they are parsed, after any
+/// decompression, so their copies are given a plain extension.
+fn cache_name(entry: &ListEntry) -> Option<String> {
+    match (&entry.path, &entry.url) {
+        (Some(path), None) => Some(file_name(path)),
+        (None, Some(url)) => Some(format!("{}.list", file_name(url))),
+        _ => None,
+    }
+}
+
+/// `name` with every character which is not safe in a file name replaced
+pub(super) fn file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+/// Read a line of the `runtime_lists` file: the path or URL of a list, followed by any of its settings
+pub(super) fn parse_runtime_list(line: &str) -> Result<ListEntry, String> {
+    let mut fields = line.split_whitespace();
+    let source = fields.next().ok_or("expected the path or URL of a list")?;
+    let mut entry = match source.contains("://") {
+        true => ListEntry {
+            path: None,
+            url: Some(source.to_string()),
+            ..ListEntry::from_path("")
+        },
+        false => ListEntry::from_path(source),
+    };
+
+    for field in fields {
+        let (key, value) = field
+            .split_once('=')
+            .ok_or_else(|| format!("expected a setting of the form key=value: {field}"))?;
+        // Settings are named as in the configuration file.
+        let name: StrDeserializer<'_, serde::de::value::Error> = value.into_deserializer();
+        let parsed = match key {
+            "action" => ListAction::deserialize(name).map(|action| entry.action = Some(action)),
+            "format" => ListFormat::deserialize(name).map(|format| entry.format = format),
+            "response" => BlockResponse::deserialize(name).map(|response| {
+                entry.response = Some(response);
+            }),
+            "priority" => {
+                entry.priority = value
+                    .parse()
+                    .map_err(|e| format!("invalid priority {value}: {e}"))?;
+                Ok(())
+            }
+            "category" => {
+                entry.category = Some(value.to_string());
+                Ok(())
+            }
+            "sha256" => {
+                entry.sha256 = Some(value.to_string());
+                Ok(())
+            }
+            _ => return Err(format!("unknown setting {key}")),
+        };
+        parsed.map_err(|e| format!("invalid {key} {value}: {e}"))?;
+    }
+
+    Ok(entry)
+}
+
+/// The line of the `runtime_lists` file which records `entry`
+fn format_runtime_list(entry: &ListEntry) -> Result<String, String> {
+    let source = entry
+        .url
+        .as_ref()
+        .or(entry.path.as_ref())
+        .map_or("", String::as_str);
+    if source.is_empty() || source.contains(char::is_whitespace) {
+        return Err(format!(
+            "blocklist {source:?} can not be recorded in runtime_lists, which do not allow whitespace in paths"
+        ));
+    }
+
+    // Settings are configured by their names lower cased, which is how they are written here.
+    let name = |setting: &dyn std::fmt::Debug| format!("{setting:?}").to_lowercase();
+    let mut line = format!("{source} format={}", name(&entry.format));
+    if let Some(category) = &entry.category {
+        line.push_str(&format!(" category={category}"));
+    }
+    if let Some(action) = &entry.action {
+        line.push_str(&format!(" action={}", name(action)));
+    }
+    if let Some(response) = &entry.response {
+        line.push_str(&format!(" response={}", name(response)));
+    }
+    if entry.priority != 0 {
+        line.push_str(&format!(" priority={}", entry.priority));
+    }
+    if let Some(sha256) = &entry.sha256 {
+        line.push_str(&format!(" sha256={sha256}"));
+    }
+
+    Ok(line)
+}
+
+/// Check `contents`, read from `source`, against the `sha256` checksum set for the list, if any
+fn verify_contents(source: &str, contents: &[u8], entry: &ListEntry) -> Result<(), String> {
+    match &entry.sha256 {
+        Some(expected) => {
+            verify_sha256(source, expected, digest::digest(&digest::SHA256, contents))
+        }
+        None => Ok(()),
+    }
+}
+
+/// Checks whether `code` is an extended DNS error INFO-CODE registered with IANA, or one in the private use range
+fn is_extended_error_code(code: u16) -> bool {
+    code <= 30 || code >= 49152
+}
+
+#[cfg(test)]
+mod test {
+    use crate::store::blocklist::authority::BlocklistLookup;
+    use crate::store::blocklist::policy::DEFAULT_LIST;
+    use crate::store::blocklist::remote::test::serve_chunked;
+    use crate::store::blocklist::{
+        BlockResponse, BlocklistAuthority, BlocklistAuthorityBuilder, BlocklistConfig, ListAction,
+        ListEntry, ListFormat, LoadFailurePolicy,
+    };
+    use crate::{
+        authority::{Authority, AuthorityObject, LookupError, LookupOptions, ZoneType},
+        proto::op::ResponseCode,
+        proto::rr::domain::Name,
+        proto::rr::{LowerName, RecordType},
+    };
+    use std::path::Path;
+    use std::str::FromStr;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_blocklist_config_relative() {
+        let config = BlocklistConfig {
+            lists: vec![ListEntry::from_path("default/blocklist.txt")],
+            config_relative: true,
+            ..BlocklistConfig::default()
+        };
+
+        // Test: relative list paths are resolved against the config file directory, rather than the zone directory.
+        let authority = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            Some(Path::new("/nonexistent")),
+            Some(Path::new("../../tests/test-data/test_configs/")),
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+
+        let res = authority
+            .lookup(
+                &LowerName::from_str("foo.com.").unwrap(),
+                RecordType::A,
+                LookupOptions::default(),
+            )
+            .await;
+        match res {
+            Ok(Some(_l)) => {}
+            Ok(None) => panic!("Lookup returned Ok(None); expected Ok(Some)"),
+            Err(e) => panic!("Lookup error: {e}!"),
+        }
+
+        // Test: config relative lists can not be loaded without knowing where the config file is.
+        let authority = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            Some(Path::new("../../tests/test-data/test_configs/")),
+            None,
+        )
+        .await;
+        assert!(authority.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_archive() {
+        let config = BlocklistConfig {
+            lists: vec![ListEntry::from_path("default/blocklist_bundle.zip")],
+            ..BlocklistConfig::default()
+        };
+
+        let authority = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            Some(Path::new("../../tests/test-data/test_configs/")),
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+
+        // Test: every member of the archive is loaded, including gzip compressed members.
+        for name in ["ads.example.org.", "malware.example.org."] {
+            let res = authority
+                .lookup(
+                    &LowerName::from_str(name).unwrap(),
+                    RecordType::A,
+                    LookupOptions::default(),
+                )
+                .await;
+            match res {
+                Ok(Some(_l)) => {}
+                Ok(None) => panic!("{name} lookup returned Ok(None); expected Ok(Some)"),
+                Err(e) => panic!("{name} lookup error: {e}!"),
+            }
+        }
+
+        // Test: each member is tracked as a separate list.
+        let stats = authority.stats();
+        assert_eq!(stats.lists.len(), 2);
+        assert!(stats.lists[0]
+            .source
+            .ends_with("blocklist_bundle.zip/lists/ads.txt"));
+        assert!(stats.lists[1]
+            .source
+            .ends_with("blocklist_bundle.zip/lists/malware.txt.gz"));
+
+        // Test: a malformed archive is reported as an error.
+        let config = BlocklistConfig {
+            lists: vec![ListEntry::from_path("default/blocklist_bad.zip")],
+            ..BlocklistConfig::default()
+        };
+
+        let authority = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            Some(Path::new("../../tests/test-data/test_configs/")),
+            None,
+        )
+        .await;
+        assert!(authority.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_load_failure() {
+        let dir = std::env::temp_dir().join(format!(
+            "hickory-blocklist-load-failure-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.txt"), "foo.example.\n").unwrap();
+
+        let config = BlocklistConfig {
+            lists: vec![ListEntry::from_path("a.txt")],
+            cache_dir: Some("cache".to_string()),
+            ..BlocklistConfig::default()
+        };
+        let load = |on_load_failure, cache_dir: &str| {
+            let config = BlocklistConfig {
+                on_load_failure,
+                cache_dir: Some(cache_dir.to_string()),
+                ..config.clone()
+            };
+            let dir = dir.clone();
+            async move {
+                BlocklistAuthority::try_from_config(
+                    Name::from_str(".").unwrap(),
+                    ZoneType::Hint,
+                    &config,
+                    Some(&dir),
+                    None,
+                )
+                .await
+            }
+        };
+        let blocked = |authority: BlocklistAuthority| async move {
+            match authority
+                .lookup(
+                    &LowerName::from_str("foo.example.").unwrap(),
+                    RecordType::A,
+                    LookupOptions::default(),
+                )
+                .await
+            {
+                Ok(Some(_l)) => true,
+                Ok(None) => false,
+                Err(e) => panic!("foo.example lookup error: {e}!"),
+            }
+        };
+
+        // Test: a successful load keeps a copy of each list in the cache.
+        let authority = load(LoadFailurePolicy::Fail, "cache")
+            .await
+            .expect("Unable to create blocklist authority");
+        assert!(blocked(authority).await);
+        assert!(dir.join("cache").join("a.txt").exists());
+
+        std::fs::remove_file(dir.join("a.txt")).unwrap();
+
+        // Test: by default, a list which fails to load is fatal.
+        assert!(load(LoadFailurePolicy::Fail, "cache").await.is_err());
+
+        // Test: the empty policy serves without blocking anything.
+        let authority = load(LoadFailurePolicy::Empty, "cache")
+            .await
+            .expect("empty policy failed");
+        assert!(!blocked(authority).await);
+
+        // Test: the cached policy serves the last successfully loaded lists...
+        let authority = load(LoadFailurePolicy::Cached, "cache")
+            .await
+            .expect("cached policy failed");
+        assert!(blocked(authority).await);
+
+        // Test: ...and fails if there is no cached copy.
+        assert!(load(LoadFailurePolicy::Cached, "missing").await.is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_invalid_utf8() {
+        let config = BlocklistConfig {
+            lists: vec![ListEntry::from_path("default/blocklist_invalid_utf8.txt")],
+            ..BlocklistConfig::default()
+        };
+
+        let authority = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            Some(Path::new("../../tests/test-data/test_configs/")),
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+
+        let ao = Box::new(Arc::new(authority)) as Box<dyn AuthorityObject>;
+
+        // Test: valid entries before and after the invalid line are loaded, including one with an invalid comment.
+        for name in ["valid1.example.com.", "valid2.example.com."] {
+            let res = ao
+                .lookup(
+                    &LowerName::from_str(name).unwrap(),
+                    RecordType::A,
+                    LookupOptions::default(),
+                )
+                .await;
+            match res {
+                Ok(Some(_l)) => {}
+                Ok(None) => panic!("{name} lookup returned Ok(None); expected Ok(Some)"),
+                Err(e) => panic!("{name} lookup error: {e}!"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_max_line_length() {
+        async fn blocked(authority: &BlocklistAuthority, name: &str) -> bool {
+            matches!(
+                authority
+                    .lookup(
+                        &LowerName::from_str(name).unwrap(),
+                        RecordType::A,
+                        LookupOptions::default(),
+                    )
+                    .await,
+                Ok(Some(_))
+            )
+        }
+
+        // A name padded out to a megabyte, with no line break, both in the middle of the list and at its end.
+        let giant = |name: &str| format!("{name}{}", " ".repeat(1 << 20));
+        let body = format!(
+            "before.example.com\n{}\nafter.example.com\n{}",
+            giant("giant.example.com"),
+            giant("last.example.com")
+        );
+
+        let dir =
+            std::env::temp_dir().join(format!("hickory-blocklist-max-line-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("list.txt"), &body).unwrap();
+        let remote = serve_chunked(body.into_bytes(), 4096).await;
+
+        let config = BlocklistConfig {
+            lists: vec![
+                ListEntry::from_path("list.txt"),
+                ListEntry {
+                    path: None,
+                    url: Some(format!("http://{remote}/list.txt")),
+                    ..ListEntry::from_path("")
+                },
+            ],
+            ..BlocklistConfig::default()
+        };
+        let authority = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            Some(&dir),
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+
+        // Test: lines over the limit are skipped, in local and remote lists, and the lines around them are still loaded.
+        assert!(blocked(&authority, "before.example.com.").await);
+        assert!(blocked(&authority, "after.example.com.").await);
+        assert!(!blocked(&authority, "giant.example.com.").await);
+        assert!(!blocked(&authority, "last.example.com.").await);
+        for list in authority.list_sources() {
+            assert_eq!(list.entries, 2, "{}", list.source);
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_list_encoding() {
+        let dir =
+            std::env::temp_dir().join(format!("hickory-blocklist-encoding-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // "bücher.example", with a comment naming its curator, "José", both in Latin-1.
+        let list =
+            b"# Liste gepflegt von Jos\xe9\nb\xfccher.example\nads.example # Werbung f\xfcr alle\n";
+        std::fs::write(dir.join("latin1.txt"), list).unwrap();
+
+        let load = |encoding: Option<&str>| {
+            let config = BlocklistConfig {
+                lists: vec![ListEntry {
+                    encoding: encoding.map(str::to_string),
+                    ..ListEntry::from_path("latin1.txt")
+                }],
+                ..BlocklistConfig::default()
+            };
+            let dir = dir.clone();
+            async move {
+                BlocklistAuthority::try_from_config(
+                    Name::root(),
+                    ZoneType::Hint,
+                    &config,
+                    Some(&dir),
+                    None,
+                )
+                .await
+            }
+        };
+        async fn blocked(authority: &BlocklistAuthority, name: &str) -> bool {
+            authority
+                .lookup(
+                    &LowerName::from_str(name).unwrap(),
+                    RecordType::A,
+                    LookupOptions::default(),
+                )
+                .await
+                .map_or(false, |lookup| lookup.is_some())
+        }
+
+        // Test: a list declared as Latin-1 is transcoded, so its internationalized entry is blocked by its punycode name.
+        let authority = load(Some("latin1")).await.unwrap();
+        assert!(blocked(&authority, "xn--bcher-kva.example.").await);
+        assert!(blocked(&authority, "ads.example.").await);
+
+        // Test: read as UTF-8, only the entry with the invalid byte is lost.
+        let authority = load(None).await.unwrap();
+        assert!(!blocked(&authority, "xn--bcher-kva.example.").await);
+        assert!(blocked(&authority, "ads.example.").await);
+
+        // Test: unknown encodings, and those which do not leave ASCII unchanged, are refused.
+        assert!(load(Some("klingon")).await.is_err());
+        assert!(load(Some("utf-16le")).await.is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_stdin_list() {
+        use crate::store::blocklist::{policy::Fetch, remote::Fetcher};
+
+        let config = BlocklistConfig {
+            lists: vec![ListEntry::from_path("-")],
+            ..BlocklistConfig::default()
+        };
+        let source = BlocklistAuthorityBuilder::with_config(Name::root(), config.clone());
+        source
+            .stdin
+            .set(
+                b"# piped in by a script\nAds.Example.com\n.tracker.example.net\n"
+                    .as_slice()
+                    .into(),
+            )
+            .unwrap();
+        let fetcher = Fetcher::new(&config).unwrap();
+
+        // Test: a list on standard input is parsed as a list file would be, and loading it again uses the same contents.
+        for _ in 0..2 {
+            let policy = source.load(None, Fetch::All, &fetcher).await.unwrap();
+            let blocked = |name: &str| {
+                let name = LowerName::from_str(name).unwrap();
+                let match_list = policy.match_list(&name);
+                policy.find_list(&name, &match_list).is_some()
+            };
+            assert!(blocked("ads.example.com."));
+            assert!(blocked("tracker.example.net."));
+            assert!(blocked("one.tracker.example.net."));
+            assert!(!blocked("example.com."));
+            assert_eq!(policy.lists[0].source, "stdin");
+            assert_eq!(policy.lists[0].entries, 3);
+        }
+
+        // Test: a checksum set for the list is checked against the input.
+        let config = BlocklistConfig {
+            lists: vec![ListEntry {
+                sha256: Some("0".repeat(64)),
+                ..ListEntry::from_path("-")
+            }],
+            ..config
+        };
+        let source = BlocklistAuthorityBuilder { config, ..source };
+        assert!(source.load(None, Fetch::All, &fetcher).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_default_lists() {
+        let config = BlocklistConfig {
+            lists: vec![ListEntry::from_path("default/blocklist.txt")],
+            use_default_lists: true,
+            ..BlocklistConfig::default()
+        };
+
+        let authority = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            Some(Path::new("../../tests/test-data/test_configs/")),
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+
+        // Test: the default list is loaded after the configured list, and every entry on it is a valid name.
+        let sources = authority.list_sources();
+        assert_eq!(sources.len(), 2);
+        assert_eq!(sources[1].source, "default");
+        let entries = DEFAULT_LIST
+            .lines()
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .count();
+        assert!(entries > 0);
+        assert_eq!(sources[1].entries, entries * 2);
+
+        // Test: names on the default list, and the names below them, are blocked along with those on the configured list.
+        for name in ["coinhive.com.", "www.coinhive.com.", "foo.com."] {
+            let lookup = authority
+                .lookup(
+                    &LowerName::from_str(name).unwrap(),
+                    RecordType::A,
+                    LookupOptions::default(),
+                )
+                .await;
+            assert!(matches!(lookup, Ok(Some(_))), "{name} was not blocked");
+        }
+
+        // Test: without the flag, only the configured list is loaded.
+        let config = BlocklistConfig {
+            use_default_lists: false,
+            ..config
+        };
+        let authority = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            Some(Path::new("../../tests/test-data/test_configs/")),
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+        assert_eq!(authority.list_sources().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_manifests() {
+        use crate::store::blocklist::Category;
+
+        async fn lookup(
+            authority: &BlocklistAuthority,
+            name: &str,
+        ) -> Result<Option<BlocklistLookup>, LookupError> {
+            authority
+                .lookup(
+                    &LowerName::from_str(name).unwrap(),
+                    RecordType::A,
+                    LookupOptions::default(),
+                )
+                .await
+        }
+
+        async fn load(config: &BlocklistConfig) -> Result<BlocklistAuthority, String> {
+            BlocklistAuthority::try_from_config(
+                Name::root(),
+                ZoneType::Hint,
+                config,
+                Some(Path::new("../../tests/test-data/test_configs/")),
+                None,
+            )
+            .await
+        }
+
+        let config = BlocklistConfig {
+            manifests: vec!["default/blocklist_manifest.toml".to_string()],
+            categories: [(
+                "trackers".to_string(),
+                Category {
+                    action: None,
+                    response: Some(BlockResponse::NxDomain),
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..BlocklistConfig::default()
+        };
+        let authority = load(&config)
+            .await
+            .expect("Unable to create blocklist authority");
+
+        // Test: the lists a manifest names are loaded with their own formats and settings, relative to the manifest.
+        let stats = authority.stats();
+        assert_eq!(stats.lists.len(), 3);
+        assert!(stats.lists[0].source.ends_with("default/blocklist.txt"));
+        assert!(stats.lists[1]
+            .source
+            .ends_with("default/blocklist_hosts.txt"));
+        assert_eq!(stats.lists[2].action, ListAction::Monitor);
+        assert!(matches!(lookup(&authority, "foo.com.").await, Ok(Some(_))));
+        assert!(matches!(
+            lookup(&authority, "ads.example.net.").await,
+            Err(e) if e.is_nx_domain()
+        ));
+        assert!(matches!(
+            lookup(&authority, "plain.example.com.").await,
+            Ok(None)
+        ));
+
+        // Test: every invalid entry of a manifest is reported, and the blocklist is not created.
+        let dir = std::env::temp_dir().join(format!(
+            "hickory-blocklist-manifests-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let manifest = dir.join("manifest.toml");
+        std::fs::write(
+            &manifest,
+            r#"lists = [
+                { path = "a.txt", bogus = 1 },
+                { path = "b.txt", url = "https://example.com/b.txt" },
+                "c.txt",
+                { path = "d.txt", format = "csv" },
+            ]"#,
+        )
+        .unwrap();
+        let invalid = BlocklistConfig {
+            manifests: vec![manifest.to_string_lossy().into_owned()],
+            ..BlocklistConfig::default()
+        };
+        let Err(e) = load(&invalid).await else {
+            panic!("invalid manifest loaded");
+        };
+        assert!(e.contains("list 1: ") && e.contains("bogus"), "{e}");
+        assert!(
+            e.contains("list 2: requires exactly one of path or url"),
+            "{e}"
+        );
+        assert!(!e.contains("list 3"), "{e}");
+        assert!(e.contains("list 4: ") && e.contains("csv"), "{e}");
+
+        // Test: a missing manifest fails the load.
+        let missing = BlocklistConfig {
+            manifests: vec![dir.join("missing.toml").to_string_lossy().into_owned()],
+            ..BlocklistConfig::default()
+        };
+        assert!(load(&missing).await.is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_runtime_list() {
+        use super::parse_runtime_list;
+
+        let dir = std::env::temp_dir().join(format!(
+            "hickory-blocklist-runtime-list-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("base.txt"), "base.example.\n").unwrap();
+        std::fs::write(dir.join("extra.txt"), "extra.example.\n").unwrap();
+
+        let config = BlocklistConfig {
+            lists: vec![ListEntry::from_path("base.txt")],
+            runtime_lists: Some("runtime.txt".to_string()),
+            ..BlocklistConfig::default()
+        };
+        let load = || {
+            BlocklistAuthority::try_from_config(
+                Name::root(),
+                ZoneType::Hint,
+                &config,
+                Some(&dir),
+                None,
+            )
+        };
+        async fn lookup(authority: &BlocklistAuthority, name: &str) -> Option<ResponseCode> {
+            let lookup = authority
+                .lookup(
+                    &LowerName::from_str(name).unwrap(),
+                    RecordType::A,
+                    LookupOptions::default(),
+                )
+                .await;
+            match lookup {
+                Ok(lookup) => lookup.map(|_| ResponseCode::NoError),
+                Err(e) if e.is_nx_domain() => Some(ResponseCode::NXDomain),
+                Err(e) => panic!("{name} lookup error: {e}"),
+            }
+        }
+        let authority = load().await.expect("Unable to create blocklist authority");
+
+        // Test: a list added at runtime is loaded with its settings, and swapped in alongside the configured lists.
+        assert_eq!(lookup(&authority, "extra.example.").await, None);
+        let report = authority
+            .add_runtime_list(ListEntry {
+                response: Some(BlockResponse::NxDomain),
+                ..ListEntry::from_path("extra.txt")
+            })
+            .await
+            .expect("Unable to add runtime list");
+        assert_eq!(report.added, 1);
+        assert_eq!(report.removed, 0);
+        assert_eq!(
+            lookup(&authority, "extra.example.").await,
+            Some(ResponseCode::NXDomain)
+        );
+        assert_eq!(
+            lookup(&authority, "base.example.").await,
+            Some(ResponseCode::NoError)
+        );
+
+        // Test: a list which is already loaded, or which can not be loaded, is refused, and the current lists stay in use.
+        assert!(authority
+            .add_runtime_list(ListEntry::from_path("base.txt"))
+            .await
+            .is_err());
+        assert!(authority
+            .add_runtime_list(ListEntry::from_path("missing.txt"))
+            .await
+            .is_err());
+        assert_eq!(
+            lookup(&authority, "extra.example.").await,
+            Some(ResponseCode::NXDomain)
+        );
+
+        // Test: the list is recorded in the runtime_lists file, and is kept by a reload.
+        let recorded = std::fs::read_to_string(dir.join("runtime.txt")).unwrap();
+        assert!(recorded
+            .lines()
+            .any(|line| line == "extra.txt format=domains response=nxdomain"));
+        assert!(!recorded.contains("missing.txt"));
+        assert!(authority.reload().await.errors.is_empty());
+        assert_eq!(
+            lookup(&authority, "extra.example.").await,
+            Some(ResponseCode::NXDomain)
+        );
+
+        // Test: the list is loaded again when the server restarts.
+        drop(authority);
+        let restarted = load().await.expect("Unable to create blocklist authority");
+        assert_eq!(
+            lookup(&restarted, "extra.example.").await,
+            Some(ResponseCode::NXDomain)
+        );
+
+        // Test: lines of the runtime_lists file are read with the names the configuration file uses.
+        let entry = parse_runtime_list(
+            "https://lists.example/ads.txt format=hosts action=monitor priority=-2",
+        )
+        .unwrap();
+        assert_eq!(entry.url.as_deref(), Some("https://lists.example/ads.txt"));
+        assert_eq!(entry.path, None);
+        assert_eq!(entry.format, ListFormat::Hosts);
+        assert_eq!(entry.action, Some(ListAction::Monitor));
+        assert_eq!(entry.priority, -2);
+        assert!(parse_runtime_list("ads.txt format=bogus").is_err());
+        assert!(parse_runtime_list("ads.txt color=red").is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,732 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Tables of block list entries, and the built-in backends for them
+
+use std::{borrow::Borrow, collections::HashMap};
+
+use crate::proto::rr::{LowerName, Name};
+use crate::store::blocklist::MatchBackend;
+
+/// A table of block list entries, mapping each listed name to the index of the list it is on.
+///
+/// A blocklist keeps one table for blocked names and one for monitored names.  Wildcards, allowed names, list priorities
+/// and schedules are all handled by the blocklist: a table only stores entries and finds them by name, with wildcard
+/// entries stored as names with a leading `*` label.  A query is matched by looking up the queried name and then each of
+/// its wildcards, so [`Self::matches`] is called several times for every query.
+///
+/// The backend is selected with the `matcher` setting, or replaced with any other implementation through
+/// [`BlocklistAuthorityBuilder::matcher`](crate::store::blocklist::BlocklistAuthorityBuilder::matcher).
+pub trait BlockMatcher: Send + Sync {
+    /// Add `name`, on the list with index `list`, returning the list it was on before, if any
+    fn insert(&mut self, name: LowerName, list: usize) -> Option<usize>;
+
+    /// Remove `name`, returning the list it was on, if any
+    fn remove(&mut self, name: &LowerName) -> Option<usize>;
+
+    /// The list `name` is an entry of, if any.  Only an entry for exactly `name` matches.
+    fn matches(&self, name: &LowerName) -> Option<usize>;
+
+    /// How `name` was looked up, and what it matched, for debug logging
+    fn explain(&self, name: &LowerName) -> String;
+
+    /// Number of entries
+    fn len(&self) -> usize;
+
+    /// Whether there are no entries
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Every entry, with the list it is on, in no particular order
+    fn entries(&self) -> Box<dyn Iterator<Item = (&LowerName, usize)> + '_>;
+
+    /// Release memory left unused once every entry has been added.  Does nothing by default.
+    fn shrink_to_fit(&mut self) {}
+}
+
+/// An empty table using `backend`
+pub(super) fn new_matcher(backend: MatchBackend) -> Box<dyn BlockMatcher> {
+    match backend {
+        MatchBackend::Hash => Box::<HashMatcher>::default(),
+        MatchBackend::Tree => Box::<TreeMatcher>::default(),
+        MatchBackend::Patricia => Box::<PatriciaMatcher>::default(),
+    }
+}
+
+/// Entries in a hash table, keyed by the whole name.  Each lookup hashes the name once, however many entries there are.
+#[derive(Default)]
+pub struct HashMatcher {
+    entries: HashMap<LowerName, usize>,
+}
+
+impl BlockMatcher for HashMatcher {
+    fn insert(&mut self, name: LowerName, list: usize) -> Option<usize> {
+        self.entries.insert(name, list)
+    }
+
+    fn remove(&mut self, name: &LowerName) -> Option<usize> {
+        self.entries.remove(name)
+    }
+
+    fn matches(&self, name: &LowerName) -> Option<usize> {
+        self.entries.get(name).copied()
+    }
+
+    fn explain(&self, name: &LowerName) -> String {
+        match self.entries.get(name) {
+            Some(list) => format!("{name} is in the hash table, on list {list}"),
+            None => format!("{name} is not in the hash table"),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn entries(&self) -> Box<dyn Iterator<Item = (&LowerName, usize)> + '_> {
+        Box::new(self.entries.iter().map(|(name, list)| (name, *list)))
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.entries.shrink_to_fit();
+    }
+}
+
+/// Entries in a tree of labels, from the root down, so that names under the same domain share the nodes of its labels.
+/// Each lookup compares one label per level instead of hashing the whole name, and the tree grows a node at a time, without
+/// the reallocations of a hash table doubling in size while a large list loads.
+#[derive(Default)]
+pub struct TreeMatcher {
+    root: Node,
+    len: usize,
+}
+
+#[derive(Default)]
+struct Node {
+    /// Child nodes, sorted by label
+    children: Vec<(Box<[u8]>, Self)>,
+    entry: Option<(LowerName, usize)>,
+}
+
+impl Node {
+    fn child(&self, label: &[u8]) -> Option<&Self> {
+        let index = self
+            .children
+            .binary_search_by(|(child, _)| (**child).cmp(label))
+            .ok()?;
+        Some(&self.children[index].1)
+    }
+
+    fn child_mut(&mut self, label: &[u8]) -> &mut Self {
+        let index = match self
+            .children
+            .binary_search_by(|(child, _)| (**child).cmp(label))
+        {
+            Ok(index) => index,
+            Err(index) => {
+                self.children.insert(index, (label.into(), Self::default()));
+                index
+            }
+        };
+        &mut self.children[index].1
+    }
+
+    /// Remove the entry at the end of `labels`, given from the root down, along with any nodes left without entries below
+    fn remove<'a>(&mut self, mut labels: impl Iterator<Item = &'a [u8]>) -> Option<usize> {
+        let Some(label) = labels.next() else {
+            return self.entry.take().map(|(_, list)| list);
+        };
+
+        let index = self
+            .children
+            .binary_search_by(|(child, _)| (**child).cmp(label))
+            .ok()?;
+        let child = &mut self.children[index].1;
+        let removed = child.remove(labels);
+        if child.entry.is_none() && child.children.is_empty() {
+            self.children.remove(index);
+        }
+        removed
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.children.shrink_to_fit();
+        for (_, child) in &mut self.children {
+            child.shrink_to_fit();
+        }
+    }
+}
+
+impl TreeMatcher {
+    /// The node for `name`, and the number of its labels the lookup got through before the tree ran out
+    fn find(&self, name: &LowerName) -> (Option<&Node>, usize) {
+        let name: &Name = name.borrow();
+        let mut node = &self.root;
+        for (depth, label) in name.iter().rev().enumerate() {
+            match node.child(label) {
+                Some(child) => node = child,
+                None => return (None, depth),
+            }
+        }
+
+        (Some(node), name.num_labels() as usize)
+    }
+}
+
+impl BlockMatcher for TreeMatcher {
+    fn insert(&mut self, name: LowerName, list: usize) -> Option<usize> {
+        let mut node = &mut self.root;
+        for label in Borrow::<Name>::borrow(&name).iter().rev() {
+            node = node.child_mut(label);
+        }
+
+        let previous = node.entry.replace((name, list)).map(|(_, list)| list);
+        if previous.is_none() {
+            self.len += 1;
+        }
+        previous
+    }
+
+    fn remove(&mut self, name: &LowerName) -> Option<usize> {
+        let name: &Name = name.borrow();
+        let removed = self.root.remove(name.iter().rev());
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        removed
+    }
+
+    fn matches(&self, name: &LowerName) -> Option<usize> {
+        self.find(name).0?.entry.as_ref().map(|(_, list)| *list)
+    }
+
+    fn explain(&self, name: &LowerName) -> String {
+        match self.find(name) {
+            (
+                Some(Node {
+                    entry: Some((_, list)),
+                    ..
+                }),
+                _,
+            ) => {
+                format!("{name} is in the label tree, on list {list}")
+            }
+            (Some(_), _) => format!("{name} is in the label tree, but only as a parent of entries"),
+            (None, depth) => {
+                format!("{name} is not in the label tree, which only holds its last {depth} labels")
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn entries(&self) -> Box<dyn Iterator<Item = (&LowerName, usize)> + '_> {
+        let mut pending = vec![&self.root];
+        Box::new(std::iter::from_fn(move || loop {
+            let node = pending.pop()?;
+            pending.extend(node.children.iter().map(|(_, child)| child));
+            if let Some((name, list)) = &node.entry {
+                return Some((name, *list));
+            }
+        }))
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.root.shrink_to_fit();
+    }
+}
+
+/// Entries in a patricia trie over the labels of each name, from the root down, with each label preceded by its length.
+/// Like [`TreeMatcher`], names under the same domain share the start of their path, but a run of nodes with a single child
+/// and no entry is merged into one edge, so a list of long names under few domains needs far fewer nodes.  Each lookup
+/// compares the name a byte at a time against the edges on its path, without hashing it.
+#[derive(Default)]
+pub struct PatriciaMatcher {
+    root: PatriciaNode,
+    len: usize,
+}
+
+#[derive(Default)]
+struct PatriciaNode {
+    /// Child nodes, with the bytes of the edge leading to each, sorted by the first byte of the edge, which no two
+    /// children share
+    children: Vec<(Box<[u8]>, Self)>,
+    /// Boxed, so that the nodes where names diverge, which have no entry, stay small
+    entry: Option<Box<(LowerName, usize)>>,
+}
+
+impl PatriciaNode {
+    fn child_index(&self, first: u8) -> Result<usize, usize> {
+        self.children
+            .binary_search_by(|(edge, _)| edge[0].cmp(&first))
+    }
+
+    /// Remove the entry at the end of `key`, merging any node left with a single child and no entry into the edge to it
+    fn remove(&mut self, key: &[u8]) -> Option<usize> {
+        let Some(&first) = key.first() else {
+            return self.entry.take().map(|entry| entry.1);
+        };
+
+        let index = self.child_index(first).ok()?;
+        let (edge, child) = &mut self.children[index];
+        let removed = child.remove(key.strip_prefix(&**edge)?)?;
+        if child.entry.is_none() {
+            match child.children.len() {
+                0 => {
+                    self.children.remove(index);
+                }
+                1 => {
+                    let (next, grandchild) = child.children.pop().expect("node has one child");
+                    *edge = [&**edge, &*next].concat().into();
+                    *child = grandchild;
+                }
+                _ => {}
+            }
+        }
+        Some(removed)
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.children.shrink_to_fit();
+        for (_, child) in &mut self.children {
+            child.shrink_to_fit();
+        }
+    }
+}
+
+/// The bytes `name` is stored under: its labels from the root down, each preceded by its length
+fn patricia_key(name: &LowerName) -> impl Iterator<Item = u8> + '_ {
+    let name: &Name = name.borrow();
+    name.iter()
+        .rev()
+        .flat_map(|label| std::iter::once(label.len() as u8).chain(label.iter().copied()))
+}
+
+impl PatriciaMatcher {
+    /// The node for `name`, and the number of bytes of its key the lookup got through before the trie ran out
+    fn find(&self, name: &LowerName) -> (Option<&PatriciaNode>, usize) {
+        let mut key = patricia_key(name).peekable();
+        let mut node = &self.root;
+        let mut depth = 0;
+        while let Some(&first) = key.peek() {
+            let Ok(index) = node.child_index(first) else {
+                return (None, depth);
+            };
+
+            let (edge, child) = &node.children[index];
+            for &byte in edge.iter() {
+                if key.next() != Some(byte) {
+                    return (None, depth);
+                }
+                depth += 1;
+            }
+            node = child;
+        }
+
+        (Some(node), depth)
+    }
+}
+
+impl BlockMatcher for PatriciaMatcher {
+    fn insert(&mut self, name: LowerName, list: usize) -> Option<usize> {
+        let key = patricia_key(&name).collect::<Vec<_>>();
+        let mut key = &key[..];
+        let mut node = &mut self.root;
+        while let Some(&first) = key.first() {
+            let index = match node.child_index(first) {
+                Ok(index) => index,
+                Err(index) => {
+                    let leaf = PatriciaNode {
+                        children: Vec::new(),
+                        entry: Some(Box::new((name, list))),
+                    };
+                    node.children.insert(index, (key.into(), leaf));
+                    self.len += 1;
+                    return None;
+                }
+            };
+
+            // Split the edge where the key leaves it, so that the key's node can branch off there.
+            let (edge, child) = &mut node.children[index];
+            let common = edge
+                .iter()
+                .zip(key)
+                .take_while(|(edge, key)| edge == key)
+                .count();
+            if common < edge.len() {
+                let rest = edge[common..].into();
+                let below = std::mem::take(child);
+                *child = PatriciaNode {
+                    children: vec![(rest, below)],
+                    entry: None,
+                };
+                *edge = edge[..common].into();
+            }
+
+            key = &key[common..];
+            node = &mut node.children[index].1;
+        }
+
+        let previous = node
+            .entry
+            .replace(Box::new((name, list)))
+            .map(|entry| entry.1);
+        if previous.is_none() {
+            self.len += 1;
+        }
+        previous
+    }
+
+    fn remove(&mut self, name: &LowerName) -> Option<usize> {
+        let key = patricia_key(name).collect::<Vec<_>>();
+        let removed = self.root.remove(&key);
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        removed
+    }
+
+    fn matches(&self, name: &LowerName) -> Option<usize> {
+        self.find(name).0?.entry.as_ref().map(|entry| entry.1)
+    }
+
+    fn explain(&self, name: &LowerName) -> String {
+        match self.find(name) {
+            (
+                Some(PatriciaNode {
+                    entry: Some(entry), ..
+                }),
+                _,
+            ) => {
+                format!("{name} is in the patricia trie, on list {}", entry.1)
+            }
+            (Some(_), _) => {
+                format!("{name} is in the patricia trie, but only as a parent of entries")
+            }
+            (None, depth) => {
+                // Count the labels whose length and bytes were all found before the lookup ran out.
+                let key = patricia_key(name).collect::<Vec<_>>();
+                let mut labels = 0;
+                let mut end = 0;
+                while end < key.len() && end + 1 + usize::from(key[end]) <= depth {
+                    end += 1 + usize::from(key[end]);
+                    labels += 1;
+                }
+                format!(
+                    "{name} is not in the patricia trie, which only holds its last {labels} labels"
+                )
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn entries(&self) -> Box<dyn Iterator<Item = (&LowerName, usize)> + '_> {
+        let mut pending = vec![&self.root];
+        Box::new(std::iter::from_fn(move || loop {
+            let node = pending.pop()?;
+            pending.extend(node.children.iter().map(|(_, child)| child));
+            if let Some(entry) = &node.entry {
+                return Some((&entry.0, entry.1));
+            }
+        }))
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.root.shrink_to_fit();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::store::blocklist::{
+        BlocklistAuthority, BlocklistAuthorityBuilder, BlocklistConfig, ListAction, ListEntry,
+    };
+    use crate::{
+        authority::{Authority, LookupOptions, ZoneType},
+        proto::rr::domain::Name,
+        proto::rr::{LowerName, RecordType},
+    };
+    use std::path::Path;
+    use std::str::FromStr;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_blocklist_matcher() {
+        use crate::store::blocklist::{
+            BlockMatcher, HashMatcher, MatchBackend, PatriciaMatcher, TreeMatcher,
+        };
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let expected = [
+            ("foo.com.", true),
+            ("www.foo.com.", true),
+            ("baddomain.com.", true),
+            ("example.com.", true),
+            ("com.", false),
+            ("test.com.", false),
+        ];
+        let check = |authority: BlocklistAuthority, backend: &'static str| async move {
+            for (name, blocked) in expected {
+                let res = authority
+                    .lookup(
+                        &LowerName::from_str(name).unwrap(),
+                        RecordType::A,
+                        LookupOptions::default(),
+                    )
+                    .await;
+                match res {
+                    Ok(Some(_)) if blocked => {}
+                    Ok(None) if !blocked => {}
+                    Ok(_) => panic!("{name} lookup with {backend} returned an unexpected result"),
+                    Err(e) => panic!("{name} lookup with {backend} error: {e}!"),
+                }
+            }
+        };
+
+        // Test: each built-in backend matches the same names.
+        for (backend, label) in [
+            (MatchBackend::Hash, "hash"),
+            (MatchBackend::Tree, "tree"),
+            (MatchBackend::Patricia, "patricia"),
+        ] {
+            let config = BlocklistConfig {
+                wildcard_match: true,
+                min_wildcard_depth: 2,
+                lists: vec![ListEntry::from_path("default/blocklist.txt")],
+                matcher: backend,
+                ..BlocklistConfig::default()
+            };
+            let authority = BlocklistAuthority::try_from_config(
+                Name::root(),
+                ZoneType::Hint,
+                &config,
+                Some(Path::new("../../tests/test-data/test_configs/")),
+                None,
+            )
+            .await
+            .expect("Unable to create blocklist authority");
+            check(authority, label).await;
+        }
+
+        // Test: removing entries from either tree leaves the entries above them in place.
+        let parent = LowerName::from_str("example.com.").unwrap();
+        let child = LowerName::from_str("ads.example.com.").unwrap();
+        for (mut tree, label) in [
+            (
+                Box::<TreeMatcher>::default() as Box<dyn BlockMatcher>,
+                "label tree",
+            ),
+            (Box::<PatriciaMatcher>::default(), "patricia trie"),
+        ] {
+            assert_eq!(tree.insert(child.clone(), 0), None);
+            assert_eq!(tree.insert(parent.clone(), 1), None);
+            assert_eq!(tree.insert(parent.clone(), 2), Some(1));
+            assert_eq!(tree.len(), 2);
+            assert_eq!(tree.remove(&child), Some(0));
+            assert_eq!(tree.remove(&child), None);
+            assert_eq!(tree.matches(&parent), Some(2));
+            assert_eq!(tree.matches(&LowerName::from_str("com.").unwrap()), None);
+            assert_eq!(tree.entries().collect::<Vec<_>>(), vec![(&parent, 2)]);
+            assert!(tree.explain(&child).contains(&format!(
+                "not in the {label}, which only holds its last 2 labels"
+            )));
+        }
+
+        // Test: the patricia trie splits edges where names diverge, even within a label, and merges them again as entries
+        // are removed.
+        let mut trie = PatriciaMatcher::default();
+        let names = [
+            "ads.example.com.",
+            "adserver.example.com.",
+            "*.example.com.",
+            "example.net.",
+            ".",
+        ]
+        .map(|name| LowerName::from_str(name).unwrap());
+        for (list, name) in names.iter().enumerate() {
+            assert_eq!(trie.insert(name.clone(), list), None);
+        }
+        for (list, name) in names.iter().enumerate() {
+            assert_eq!(trie.matches(name), Some(list), "{name}");
+        }
+        for name in ["ad.example.com.", "example.com.", "www.ads.example.com."] {
+            assert_eq!(
+                trie.matches(&LowerName::from_str(name).unwrap()),
+                None,
+                "{name}"
+            );
+        }
+        assert!(trie
+            .explain(&LowerName::from_str("example.com.").unwrap())
+            .contains("only as a parent of entries"));
+        for (list, name) in names.iter().enumerate().rev() {
+            assert_eq!(trie.remove(name), Some(list), "{name}");
+            assert_eq!(trie.len(), list);
+            for (list, name) in names[..list].iter().enumerate() {
+                assert_eq!(trie.matches(name), Some(list), "{name}");
+            }
+        }
+
+        // Test: a backend supplied through the builder is used for every lookup.
+        struct Counting {
+            entries: HashMatcher,
+            lookups: Arc<AtomicUsize>,
+        }
+
+        impl BlockMatcher for Counting {
+            fn insert(&mut self, name: LowerName, list: usize) -> Option<usize> {
+                self.entries.insert(name, list)
+            }
+
+            fn remove(&mut self, name: &LowerName) -> Option<usize> {
+                self.entries.remove(name)
+            }
+
+            fn matches(&self, name: &LowerName) -> Option<usize> {
+                self.lookups.fetch_add(1, Ordering::Relaxed);
+                self.entries.matches(name)
+            }
+
+            fn explain(&self, name: &LowerName) -> String {
+                self.entries.explain(name)
+            }
+
+            fn len(&self) -> usize {
+                self.entries.len()
+            }
+
+            fn entries(&self) -> Box<dyn Iterator<Item = (&LowerName, usize)> + '_> {
+                self.entries.entries()
+            }
+        }
+
+        let lookups = Arc::new(AtomicUsize::new(0));
+        let counter = lookups.clone();
+        let authority = BlocklistAuthorityBuilder::new(Name::root())
+            .min_wildcard_depth(2)
+            .add_entries(
+                ["foo.com.", "*.foo.com.", "baddomain.com.", "example.com."]
+                    .into_iter()
+                    .map(|name| Name::from_str(name).unwrap()),
+            )
+            .matcher(move || {
+                Box::new(Counting {
+                    entries: HashMatcher::default(),
+                    lookups: counter.clone(),
+                })
+            })
+            .build()
+            .expect("Unable to build blocklist authority");
+        check(authority, "a custom backend").await;
+        assert!(lookups.load(Ordering::Relaxed) >= expected.len());
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_canonical_tables() {
+        // Test: names added through the builder, with or without their trailing dots and in any case, match queries in
+        // the block table and the allowlist alike.
+        let authority = BlocklistAuthorityBuilder::new(Name::root())
+            .add_entries([
+                Name::from_str("Blocked.Example.ORG").unwrap(),
+                Name::from_str("*.ADS.example.com").unwrap(),
+            ])
+            .add_allow([
+                Name::from_str("Fine.Ads.Example.COM").unwrap(),
+                Name::from_str("*.safe.ads.EXAMPLE.com.").unwrap(),
+            ])
+            .build()
+            .expect("Unable to create blocklist authority");
+        let blocked = |name: &str| {
+            let policy = authority.policy();
+            let name = LowerName::from_str(name).unwrap();
+            let match_list = policy.match_list(&name);
+            policy.find_list(&name, &match_list).is_some()
+        };
+        for name in [
+            "blocked.example.org.",
+            "BLOCKED.example.ORG.",
+            "one.ads.example.com.",
+            "One.Ads.Example.Com.",
+        ] {
+            assert!(blocked(name), "{name}");
+        }
+        for name in [
+            "fine.ads.example.com.",
+            "FINE.ADS.EXAMPLE.COM.",
+            "one.safe.ads.example.com.",
+            "One.SAFE.ads.example.com.",
+        ] {
+            assert!(!blocked(name), "{name}");
+        }
+
+        // Test: list entries, negations, and names from the configuration are keyed the same way, so that monitor
+        // entries, negations and log exclusions match whichever way they were written.
+        let dir = std::env::temp_dir().join(format!(
+            "hickory-blocklist-canonical-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("block.txt"),
+            "Tracker.Example.NET\nother.example.net.\n@@Tracker.Example.net.\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("monitor.txt"),
+            "Watch.Example.NET\nseen.EXAMPLE.net.\n",
+        )
+        .unwrap();
+        let config = BlocklistConfig {
+            lists: vec![
+                ListEntry::from_path("block.txt"),
+                ListEntry {
+                    action: Some(ListAction::Monitor),
+                    ..ListEntry::from_path("monitor.txt")
+                },
+            ],
+            log_exclude: vec![Name::from_str("Quiet.Example.COM").unwrap()],
+            first_party_domains: vec![Name::from_str("Example.EDU").unwrap()],
+            ..BlocklistConfig::default()
+        };
+        let authority = BlocklistAuthority::try_from_config(
+            Name::root(),
+            ZoneType::Hint,
+            &config,
+            Some(&dir),
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let policy = authority.policy();
+        let key = |name: &str| LowerName::from_str(name).unwrap();
+        assert!(policy
+            .blocklist
+            .matches(&key("OTHER.example.net."))
+            .is_some());
+        assert!(policy
+            .blocklist
+            .matches(&key("tracker.example.net."))
+            .is_none());
+        for name in [
+            "watch.example.net.",
+            "WATCH.example.net.",
+            "Seen.Example.Net.",
+        ] {
+            assert!(policy.monitorlist.matches(&key(name)).is_some(), "{name}");
+        }
+        assert!(policy.first_party_domains.contains(&key("EXAMPLE.edu.")));
+        assert!(authority.log_excluded(&key("quiet.example.com.")));
+    }
+}
@@ -10,7 +10,32 @@
 //! Blocklist resolver related types
 
 mod authority;
+mod compiled;
 mod config;
+mod homograph;
+mod iptrie;
+mod learn;
+mod load;
+mod matcher;
+mod policy;
+mod querylog;
+mod ratelimit;
+mod remote;
+mod responses;
+#[cfg(feature = "blocklist-s3")]
+mod s3;
+mod stats;
+mod tombstone;
 
-pub use self::authority::BlocklistAuthority;
-pub use self::config::BlocklistConfig;
+pub use self::authority::{BlocklistAuthority, BlocklistAuthorityBuilder};
+pub use self::config::{
+    BlockResponse, BlocklistConfig, Category, ListAction, ListEntry, ListFormat, LoadFailurePolicy,
+    MalformedQueryPolicy, MatchBackend, PublicSinkholePolicy, Schedule, StartupPolicy,
+    SubnetSinkhole,
+};
+pub use self::learn::LearnedName;
+pub use self::matcher::{BlockMatcher, HashMatcher, PatriciaMatcher, TreeMatcher};
+pub use self::stats::{
+    BlocklistStats, ListReload, ListSourceInfo, ListStats, MatchStats, NameChanges, PolicyDiff,
+    PolicyEvent, RefreshStatus, ReloadReport,
+};
@@ -0,0 +1,1885 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The block tables built from a set of lists, and the answers synthesized from them
+
+use std::{
+    cmp::Reverse,
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use encoding_rs::Encoding;
+use flate2::read::GzDecoder;
+use ipnet::IpNet;
+use rand::{seq::SliceRandom, Rng};
+use regex::RegexSet;
+use ring::digest;
+use time::OffsetDateTime;
+use tracing::{debug, error, info, trace, warn};
+use zip::ZipArchive;
+
+use crate::{
+    authority::LookupError,
+    proto::{
+        error::ProtoError,
+        op::{Query, ResponseCode},
+        rr::{
+            rdata::{A, AAAA, CNAME, NULL, SOA},
+            LowerName, Name, RData, Record, RecordType,
+        },
+        serialize::binary::BinEncodable,
+    },
+    store::blocklist::{
+        authority::{BlocklistLookup, Client},
+        compiled::{CompiledList, CompiledPolicy, CompiledResponse, ListKind},
+        homograph::Homographs,
+        iptrie::IpTrie,
+        matcher::BlockMatcher,
+        querylog::MatchKind,
+        remote::{verify_sha256, Lines},
+        responses::{CachedResponse, ResponseCache},
+        stats::Counter,
+        BlockResponse, ListAction, ListEntry, ListFormat, ListReload, NameChanges, PolicyDiff,
+        PublicSinkholePolicy, ReloadReport, Schedule, SubnetSinkhole,
+    },
+};
+
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{prelude::*, BufReader, SeekFrom};
+use std::str::FromStr;
+
+/// The DNAME record type, RFC 6672, which has no record data type of its own
+pub(super) const DNAME: u16 = 39;
+
+/// Number of block list entries overridden by allowed names which are logged individually when the lists load
+const MAX_OVERLAP_WARNINGS: usize = 20;
+
+/// The default list bundled with the server, loaded with `use_default_lists`
+pub(super) const DEFAULT_LIST: &str = include_str!("default_list.txt");
+
+/// The source the default list is named by in logs and stats
+pub(super) const DEFAULT_LIST_SOURCE: &str = "default";
+
+/// Number of negative response TTLs whose SOA records are kept for reuse by each policy.  Only a few TTLs are used unless
+/// `ttl_jitter` or per-entry TTLs are set, and the SOA for any other TTL is built for its response.
+const NEGATIVE_SOA_TTLS: usize = 64;
+
+/// Names which hosts files conventionally map to the local machine, rather than to a blocked host.
+const HOSTS_LOCAL_NAMES: &[&str] = &[
+    "localhost",
+    "localhost.localdomain",
+    "local",
+    "broadcasthost",
+    "ip6-localhost",
+    "ip6-loopback",
+];
+
+/// The block tables built from a complete set of lists, and the settings used to answer queries against them
+pub(super) struct Policy {
+    /// Maps each blocked name to the index of the list in `lists` which determines its response
+    pub(super) blocklist: Arc<dyn BlockMatcher>,
+    pub(super) lists: Vec<BlockList>,
+    /// Patterns for names which are never blocked, which are checked before anything else
+    pub(super) allow_regex: Option<RegexSet>,
+    /// Names which are never blocked, regardless of the lists they appear on
+    pub(super) allowlist: HashSet<LowerName>,
+    /// Domains whose names, and every name below them, are never blocked
+    pub(super) first_party_domains: HashSet<LowerName>,
+    /// Maps each monitored name to the index of its list in `lists`
+    pub(super) monitorlist: Arc<dyn BlockMatcher>,
+    /// Names removed by negation lines, with the list they appeared on; these are applied once all lists have been added
+    pub(super) negations: Vec<(LowerName, usize)>,
+    /// Where each remote list was spooled to when it was fetched, so that the policy can be rebuilt without fetching every
+    /// list again
+    pub(super) remote: HashMap<String, FetchedList>,
+    pub(super) wildcard_match: bool,
+    pub(super) min_wildcard_depth: u8,
+    /// The smallest `min_wildcard_depth` of any list, which bounds the wildcards a query is matched against
+    pub(super) shallowest_wildcard_depth: u8,
+    pub(super) wildcard_token: String,
+    pub(super) max_line_length: usize,
+    pub(super) wildcard_blocks_parent: bool,
+    pub(super) nodata_types: Vec<RecordType>,
+    pub(super) block_response: BlockResponse,
+    pub(super) sinkhole_ipv4: Ipv4Addr,
+    pub(super) sinkhole_ipv6: Ipv6Addr,
+    /// The `sinkhole_pool`, by address family
+    pub(super) sinkhole_pool_ipv4: Vec<Ipv4Addr>,
+    pub(super) sinkhole_pool_ipv6: Vec<Ipv6Addr>,
+    /// The `subnet_sinkholes`, from the most specific network to the least
+    pub(super) subnet_sinkholes: Vec<SubnetSinkhole>,
+    pub(super) dname_target: Option<Name>,
+    pub(super) sinkhole_ttl: u32,
+    pub(super) client_ttl: u32,
+    pub(super) dname_ttl: u32,
+    pub(super) nxdomain_ttl: u32,
+    pub(super) nodata_ttl: u32,
+    pub(super) ttl_jitter: u32,
+    pub(super) strip_answer_networks: Vec<IpNet>,
+    pub(super) block_cname_targets: bool,
+    pub(super) max_cname_targets: usize,
+    pub(super) block_ptr_targets: bool,
+    pub(super) strip_client_subnet: bool,
+    /// Maps each blocked answer network to the index of the list in `lists` it was read from
+    pub(super) ip_blocklist: IpTrie<usize>,
+    /// Maps each range read from reverse format lists to the index of the list in `lists` it was read from, for lists
+    /// with the block and monitor actions respectively
+    pub(super) reverse_blocklist: IpTrie<usize>,
+    pub(super) reverse_monitorlist: IpTrie<usize>,
+    /// The addresses upstream resolvers answer their own blocked names with, and the index of the list in `lists` which
+    /// determines the response to answers containing them
+    pub(super) upstream_sinks: Option<(Vec<IpNet>, usize)>,
+    /// The domains protected against homograph lookalikes, and the index of the list in `lists` which determines the
+    /// response to queries for the lookalikes
+    pub(super) homographs: Option<(Homographs, usize)>,
+    /// Responses set by individual lines of lists in the extended format, by name and the index of the list in `lists`
+    pub(super) entry_responses: HashMap<(LowerName, usize), EntryResponse>,
+    /// When each name a reload added to a list with a grace period was first seen, until its grace period ends
+    pub(super) first_seen: HashMap<LowerName, Instant>,
+    pub(super) public_sinkhole: PublicSinkholePolicy,
+    pub(super) sinkhole_networks: Vec<IpNet>,
+    /// Responses already built for blocked names, if enabled
+    pub(super) response_cache: Option<ResponseCache>,
+    /// Serial of the SOA in negative responses, which is increased each time the policy is replaced, so that downstream
+    /// caches can tell the lists have changed
+    pub(super) serial: u32,
+    /// The SOA records of the negative responses built so far, by TTL, shared by every response with the same TTL
+    pub(super) negative_soas: Mutex<HashMap<u32, BlocklistLookup>>,
+}
+
+/// How queries for a name are answered, as set by its line in an extended format list
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) struct EntryResponse {
+    /// Replaces the list's response, if set
+    pub(super) response: Option<BlockResponse>,
+    /// Replaces the list's sinkhole address of the same family
+    pub(super) sinkhole: Option<IpAddr>,
+    /// Overrides the TTL of the list and of the response mode
+    pub(super) ttl: Option<u32>,
+}
+
+/// A loaded block list, and the settings used to answer queries for names on it
+#[derive(Clone)]
+pub(super) struct BlockList {
+    pub(super) source: String,
+    pub(super) action: ListAction,
+    pub(super) response: BlockResponse,
+    pub(super) sinkhole_ipv4: Ipv4Addr,
+    pub(super) sinkhole_ipv6: Ipv6Addr,
+    /// Whether A and AAAA answers for names on the list are picked from the `sinkhole_pool`, as they are unless the list
+    /// sets its own sinkhole address of the family
+    pub(super) pool_ipv4: bool,
+    pub(super) pool_ipv6: bool,
+    /// Overrides the TTL of the response mode
+    pub(super) ttl: Option<u32>,
+    /// INFO-CODE and EXTRA-TEXT of the extended DNS error attached to responses for names on the list
+    pub(super) extended_error: Option<(u16, String)>,
+    pub(super) priority: i32,
+    pub(super) depth: Option<u8>,
+    /// Minimum number of labels of the wildcard entries which match, other than the `*`, if the list sets its own rather
+    /// than using the blocklist-wide `min_wildcard_depth`
+    pub(super) min_wildcard_depth: Option<u8>,
+    /// Whether entries with a leading dot add the name itself as well as its wildcard
+    pub(super) include_apex: bool,
+    pub(super) schedule: Option<Schedule>,
+    /// How long entries a reload adds to the list are monitored before they are enforced
+    pub(super) grace_period: Option<Duration>,
+    /// Number of queries blocked by the list, which is shared with the copies of the policy made by [`Policy::copy`]
+    pub(super) blocked: Arc<Counter>,
+    /// Whether the list was fetched from a URL
+    pub(super) remote: bool,
+    /// Number of entries read from the list
+    pub(super) entries: usize,
+    /// When the list was read, or for remote lists, fetched
+    pub(super) loaded: SystemTime,
+    /// Which part of the configuration the list was read from, for lists which are part of it
+    pub(super) config: Option<ListKind>,
+}
+
+/// The spooled copy of a remote list, as of when it was fetched
+#[derive(Clone)]
+pub(super) struct FetchedList {
+    pub(super) spool: PathBuf,
+    pub(super) fetched: SystemTime,
+}
+
+/// Which remote lists are fetched when building a policy
+#[derive(Clone, Copy)]
+pub(super) enum Fetch<'a> {
+    /// Fetch every remote list
+    All,
+    /// Fetch the list at `url`, reusing the spooled copies of the other remote lists from `previous`
+    One { url: &'a str, previous: &'a Policy },
+}
+
+impl<'a> Fetch<'a> {
+    /// The spooled copy of the list at `url` to add instead of fetching it, if there is one
+    pub(super) fn reuse(self, url: &str) -> Option<&'a FetchedList> {
+        match self {
+            Self::All => None,
+            Self::One {
+                url: refresh,
+                previous,
+            } => previous
+                .remote
+                .get(url)
+                .filter(|list| refresh != url && list.spool.exists()),
+        }
+    }
+}
+
+impl Policy {
+    /// Loading grows the table in steps, which can leave a large amount of unused capacity behind; release it once the table
+    /// contents are final.
+    pub(super) fn compact(&mut self, origin: &Name) {
+        let rss_before = resident_set_size();
+        let (blocklist, monitorlist) = self.tables_mut();
+        blocklist.shrink_to_fit();
+        monitorlist.shrink_to_fit();
+        self.allowlist.shrink_to_fit();
+        info!(
+            "blocklist {} loaded {} entries; resident set size {} -> {}",
+            origin,
+            self.blocklist.len(),
+            display_rss(rss_before),
+            display_rss(resident_set_size()),
+        );
+    }
+
+    /// A copy of the policy, sharing its tables, for changing settings which only affect how queries are matched and
+    /// answered.  The copy starts with an empty response cache, as the new settings may change the response to any name.
+    pub(super) fn copy(&self) -> Self {
+        Self {
+            blocklist: self.blocklist.clone(),
+            lists: self.lists.clone(),
+            allow_regex: self.allow_regex.clone(),
+            allowlist: self.allowlist.clone(),
+            first_party_domains: self.first_party_domains.clone(),
+            monitorlist: self.monitorlist.clone(),
+            negations: self.negations.clone(),
+            remote: self.remote.clone(),
+            wildcard_match: self.wildcard_match,
+            min_wildcard_depth: self.min_wildcard_depth,
+            shallowest_wildcard_depth: self.shallowest_wildcard_depth,
+            wildcard_token: self.wildcard_token.clone(),
+            max_line_length: self.max_line_length,
+            wildcard_blocks_parent: self.wildcard_blocks_parent,
+            nodata_types: self.nodata_types.clone(),
+            block_response: self.block_response,
+            sinkhole_ipv4: self.sinkhole_ipv4,
+            sinkhole_ipv6: self.sinkhole_ipv6,
+            sinkhole_pool_ipv4: self.sinkhole_pool_ipv4.clone(),
+            sinkhole_pool_ipv6: self.sinkhole_pool_ipv6.clone(),
+            subnet_sinkholes: self.subnet_sinkholes.clone(),
+            dname_target: self.dname_target.clone(),
+            sinkhole_ttl: self.sinkhole_ttl,
+            client_ttl: self.client_ttl,
+            dname_ttl: self.dname_ttl,
+            nxdomain_ttl: self.nxdomain_ttl,
+            nodata_ttl: self.nodata_ttl,
+            ttl_jitter: self.ttl_jitter,
+            strip_answer_networks: self.strip_answer_networks.clone(),
+            block_cname_targets: self.block_cname_targets,
+            max_cname_targets: self.max_cname_targets,
+            block_ptr_targets: self.block_ptr_targets,
+            strip_client_subnet: self.strip_client_subnet,
+            ip_blocklist: self.ip_blocklist.clone(),
+            reverse_blocklist: self.reverse_blocklist.clone(),
+            reverse_monitorlist: self.reverse_monitorlist.clone(),
+            upstream_sinks: self.upstream_sinks.clone(),
+            homographs: self.homographs.clone(),
+            entry_responses: self.entry_responses.clone(),
+            first_seen: self.first_seen.clone(),
+            public_sinkhole: self.public_sinkhole,
+            sinkhole_networks: self.sinkhole_networks.clone(),
+            response_cache: self.response_cache.as_ref().map(ResponseCache::empty),
+            serial: self.serial,
+            negative_soas: Mutex::default(),
+        }
+    }
+
+    /// The tables read from the configured lists, for writing to a compiled policy file identified by `key`.  Entries from
+    /// lists which are not part of the configuration are left out.
+    pub(super) fn compile(&self, key: String) -> CompiledPolicy {
+        let mut compiled = CompiledPolicy {
+            key,
+            ..CompiledPolicy::default()
+        };
+
+        // Compiled lists are numbered from zero, in the order they were added.
+        let mut indices = HashMap::new();
+        for (index, list) in self.lists.iter().enumerate() {
+            let Some(kind) = list.config else {
+                continue;
+            };
+
+            indices.insert(index, compiled.lists.len() as u32);
+            compiled.lists.push(CompiledList {
+                kind,
+                source: list.source.clone(),
+                remote: list.remote,
+                entries: list.entries as u64,
+                loaded: list.loaded,
+            });
+        }
+
+        let table = |table: &dyn BlockMatcher| {
+            table
+                .entries()
+                .filter_map(|(name, list)| Some((name.clone(), *indices.get(&list)?)))
+                .collect()
+        };
+        compiled.blocked = table(&*self.blocklist);
+        compiled.monitored = table(&*self.monitorlist);
+        compiled.responses = self
+            .entry_responses
+            .iter()
+            .filter_map(|((name, list), entry)| {
+                Some(CompiledResponse {
+                    name: name.clone(),
+                    list: *indices.get(list)?,
+                    response: entry.response,
+                    sinkhole: entry.sinkhole,
+                    ttl: entry.ttl,
+                })
+            })
+            .collect();
+        let networks = |trie: &IpTrie<usize>| {
+            trie.entries()
+                .into_iter()
+                .filter_map(|(net, list)| Some((net, *indices.get(list)?)))
+                .collect::<Vec<_>>()
+        };
+        compiled.networks = networks(&self.ip_blocklist);
+        compiled.reverse = networks(&self.reverse_blocklist);
+        compiled.reverse.extend(networks(&self.reverse_monitorlist));
+
+        compiled
+    }
+
+    /// Add a block list file, decompressing it as it is read if it has a `.gz` extension.
+    pub(super) fn add_list(&mut self, file: String, entry: &ListEntry) -> Result<(), String> {
+        let handle = File::open(&file)
+            .map_err(|e| format!("unable to open block list file '{file}': {e}"))?;
+        let handle = verify_file(&file, handle, entry)?;
+
+        if file.ends_with(".gz") {
+            self.read_list(file, GzDecoder::new(handle), entry)?;
+        } else {
+            self.read_list(file, handle, entry)?;
+        }
+
+        Ok(())
+    }
+
+    /// Add the default list bundled with the server, with the blocklist-wide settings, returning its index.
+    pub(super) fn add_default_list(&mut self) -> Result<usize, String> {
+        info!("Adding the default blocklist");
+        self.read_list(
+            DEFAULT_LIST_SOURCE.to_string(),
+            DEFAULT_LIST.as_bytes(),
+            &ListEntry::from_path(DEFAULT_LIST_SOURCE),
+        )
+    }
+
+    /// Add every list in a zip archive, returning the number of lists added.
+    pub(super) fn add_archive(&mut self, file: String, entry: &ListEntry) -> Result<usize, String> {
+        let handle = File::open(&file)
+            .map_err(|e| format!("unable to open block list archive '{file}': {e}"))?;
+        let handle = verify_file(&file, handle, entry)?;
+        let mut archive = ZipArchive::new(handle)
+            .map_err(|e| format!("unable to read block list archive '{file}': {e}"))?;
+
+        let mut added = 0;
+        for index in 0..archive.len() {
+            let member = archive
+                .by_index(index)
+                .map_err(|e| format!("unable to read block list archive '{file}': {e}"))?;
+            if member.is_dir() {
+                continue;
+            }
+
+            let source = format!("{file}/{}", member.name());
+            info!("Adding blocklist {source:?}");
+            let read = if member.name().ends_with(".gz") {
+                self.read_list(source, GzDecoder::new(member), entry)
+            } else {
+                self.read_list(source, member, entry)
+            };
+
+            if let Err(e) = read {
+                return Err(format!(
+                    "unable to read block list archive '{file}': member {index} is corrupt: {e}"
+                ));
+            }
+            added += 1;
+        }
+
+        Ok(added)
+    }
+
+    /// Read a block list from `reader`, answering queries for names on it using the settings from `entry`.  `file` names the
+    /// source of the list in logs and stats.  Returns the index of the new list.
+    pub(super) fn read_list(
+        &mut self,
+        file: String,
+        reader: impl Read,
+        entry: &ListEntry,
+    ) -> Result<usize, String> {
+        let encoding = list_encoding(entry)?;
+        let list = self.push_list(file.clone(), entry);
+
+        read_lines(reader, self.max_line_length, |line_number, line| {
+            self.read_line(&file, line_number, line, list, entry.format, encoding)
+        })
+        .map_err(|e| format!("error reading block list '{file}': {e}"))?;
+
+        Ok(list)
+    }
+
+    /// Add a line read from `file` to `list`, or log that it was skipped, if it was `None` for being longer than
+    /// `max_line_length`.
+    pub(super) fn read_line(
+        &mut self,
+        file: &str,
+        line_number: usize,
+        line: Option<&[u8]>,
+        list: usize,
+        format: ListFormat,
+        encoding: Option<&'static Encoding>,
+    ) {
+        match line {
+            Some(line) => self.add_line(file, line_number, line, list, format, encoding),
+            None => warn!(
+                "skipping line {} of block list '{file}': longer than {} bytes",
+                line_number + 1,
+                self.max_line_length
+            ),
+        }
+    }
+
+    /// Add a list of blocked answer addresses and networks from `file`.  Lines which are not an address or a network in CIDR
+    /// notation are logged and skipped.
+    pub(super) fn add_ip_list(&mut self, file: String) -> Result<usize, String> {
+        let handle = File::open(&file)
+            .map_err(|e| format!("unable to open address block list file '{file}': {e}"))?;
+        let list = self.push_list(file.clone(), &ListEntry::from_path(&file));
+
+        for (line_number, line) in BufReader::new(handle).lines().enumerate() {
+            let line =
+                line.map_err(|e| format!("error reading address block list '{file}': {e}"))?;
+            let entry = line.split('#').next().unwrap_or_default().trim();
+            if entry.is_empty() {
+                continue;
+            }
+
+            let net = match entry.parse::<IpNet>() {
+                Ok(net) => net,
+                Err(_) => match entry.parse::<IpAddr>() {
+                    Ok(addr) => IpNet::from(addr),
+                    Err(e) => {
+                        warn!(
+                            "skipping {entry:?} on line {} of address block list '{file}': {e}",
+                            line_number + 1
+                        );
+                        continue;
+                    }
+                },
+            };
+
+            trace!("Inserting address blocklist entry {net}");
+            self.ip_blocklist.insert(net, list);
+            self.lists[list].entries += 1;
+        }
+
+        Ok(list)
+    }
+
+    /// Add a remote block list from the copy spooled when it was fetched.  The copy is remembered, so that the list can be
+    /// added to a later policy without fetching it again.
+    pub(super) fn add_spooled(
+        &mut self,
+        url: String,
+        fetched: FetchedList,
+        entry: &ListEntry,
+    ) -> Result<(), String> {
+        let spool = File::open(&fetched.spool).map_err(|e| {
+            format!(
+                "unable to open block list {url} spooled to {:?}: {e}",
+                fetched.spool
+            )
+        })?;
+        let list = self.read_list(url.clone(), BufReader::new(spool), entry)?;
+        self.lists[list].remote = true;
+        self.lists[list].loaded = fetched.fetched;
+        self.remote.insert(url, fetched);
+        Ok(())
+    }
+
+    /// Parse one line of a block list in `format`, decoded from `encoding`, or UTF-8 if none, adding its names to `list`.
+    fn add_line(
+        &mut self,
+        file: &str,
+        line_number: usize,
+        line: &[u8],
+        list: usize,
+        format: ListFormat,
+        encoding: Option<&'static Encoding>,
+    ) {
+        // Lists are decoded lossily: names are ASCII, so a stray invalid byte sequence (usually in a comment) only needs to
+        // cost the line it appears on, rather than the entire list.
+        let line = match encoding {
+            Some(encoding) => encoding.decode_without_bom_handling(line).0,
+            None => String::from_utf8_lossy(line),
+        };
+        let mut entry_str = line.as_ref();
+
+        // Strip comments and leading/trailing whitespace
+        if let Some(idx) = entry_str.find('#') {
+            entry_str = &entry_str[0..idx];
+        }
+        let entry_str = entry_str.trim();
+
+        if entry_str.is_empty() {
+            return;
+        }
+
+        if entry_str.contains(char::REPLACEMENT_CHARACTER) {
+            warn!(
+                "skipping line {} of block list '{file}': invalid UTF-8",
+                line_number + 1
+            );
+            return;
+        }
+
+        // An invalid name only costs the entry it appears in.
+        let wildcard_token = self.wildcard_token.clone();
+        let include_apex = self.lists[list].include_apex;
+        let parse = |name: &str| match parse_entry(name, &wildcard_token, include_apex) {
+            Ok(names) => names,
+            Err(e) => {
+                warn!(
+                    "skipping {name:?} on line {} of block list '{file}': {e}",
+                    line_number + 1
+                );
+                Vec::new()
+            }
+        };
+
+        if let Some(name) = entry_str
+            .strip_prefix("@@")
+            .or_else(|| entry_str.strip_prefix('-'))
+        {
+            for name in parse(name.trim()) {
+                trace!("Adding blocklist negation {name:?}");
+                self.negations.push((name, list));
+            }
+            return;
+        }
+
+        match format {
+            ListFormat::Domains => {
+                for name in parse(entry_str) {
+                    self.insert(name, list);
+                }
+            }
+            ListFormat::Extended => {
+                let mut columns = entry_str.split_whitespace();
+                let name = columns.next().unwrap_or_default();
+                let response = match parse_entry_response(columns) {
+                    Ok(response) => response,
+                    Err(e) => {
+                        warn!(
+                            "skipping line {} of block list '{file}': {e}",
+                            line_number + 1
+                        );
+                        return;
+                    }
+                };
+
+                if let Some(addr) = response.and_then(|response| response.sinkhole) {
+                    if !is_safe_sinkhole(addr, &self.sinkhole_networks) {
+                        match self.public_sinkhole {
+                            PublicSinkholePolicy::Allow => {}
+                            PublicSinkholePolicy::Warn => warn!(
+                                "sinkhole address {addr} on line {} of block list '{file}' is publicly routable",
+                                line_number + 1
+                            ),
+                            PublicSinkholePolicy::Refuse => {
+                                warn!(
+                                    "skipping line {} of block list '{file}': sinkhole address {addr} is publicly routable",
+                                    line_number + 1
+                                );
+                                return;
+                            }
+                        }
+                    }
+                }
+
+                for name in parse(name) {
+                    if let Some(response) = response {
+                        self.entry_responses.insert((name.clone(), list), response);
+                    }
+                    self.insert(name, list);
+                }
+            }
+            ListFormat::Reverse => {
+                let net = entry_str
+                    .parse::<IpNet>()
+                    .ok()
+                    .or_else(|| entry_str.parse::<IpAddr>().ok().map(IpNet::from))
+                    .or_else(|| reverse_net(&Name::from_str(entry_str).ok()?));
+                match net {
+                    Some(net) => self.insert_reverse(net.trunc(), list),
+                    None => warn!(
+                        "skipping {entry_str:?} on line {} of block list '{file}': not an address, network or reverse name",
+                        line_number + 1
+                    ),
+                }
+            }
+            ListFormat::Hosts => {
+                // The leading address is replaced by this list's response settings.
+                for name in entry_str
+                    .split_whitespace()
+                    .skip(1)
+                    .filter(|name| !HOSTS_LOCAL_NAMES.contains(name))
+                {
+                    for name in parse(name) {
+                        self.insert(name, list);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Track a new list, answering queries for names on it using the settings from `entry`, and return its index.
+    pub(super) fn push_list(&mut self, source: String, entry: &ListEntry) -> usize {
+        let min_wildcard_depth = entry.min_wildcard_depth.unwrap_or(self.min_wildcard_depth);
+        self.shallowest_wildcard_depth = self.shallowest_wildcard_depth.min(min_wildcard_depth);
+        self.lists.push(BlockList {
+            source,
+            response: entry.response.unwrap_or(self.block_response),
+            sinkhole_ipv4: entry.sinkhole_ipv4.unwrap_or(self.sinkhole_ipv4),
+            sinkhole_ipv6: entry.sinkhole_ipv6.unwrap_or(self.sinkhole_ipv6),
+            pool_ipv4: entry.sinkhole_ipv4.is_none(),
+            pool_ipv6: entry.sinkhole_ipv6.is_none(),
+            ttl: entry.ttl,
+            extended_error: entry
+                .ede_code
+                .map(|code| (code, entry.ede_text.clone().unwrap_or_default())),
+            action: entry.action.unwrap_or_default(),
+            priority: entry.priority,
+            depth: entry.depth,
+            min_wildcard_depth: entry.min_wildcard_depth,
+            include_apex: entry.include_apex,
+            schedule: entry.schedule,
+            grace_period: entry
+                .grace_period
+                .map(|grace_period| Duration::from_secs(grace_period.into())),
+            blocked: Arc::default(),
+            remote: false,
+            entries: 0,
+            loaded: SystemTime::now(),
+            config: None,
+        });
+        self.lists.len() - 1
+    }
+
+    /// Insert a single entry, keeping any existing entry for the name which outranks it.
+    fn insert(&mut self, name: LowerName, list: usize) {
+        trace!("Inserting blocklist entry {name:?}");
+        self.insert_name(name, list);
+    }
+
+    /// Remove the entries named by negation lines.  Negations are applied after every addition, whichever list or line the
+    /// addition came from, so that the result does not depend on the order lists are loaded in.
+    pub(super) fn apply_negations(&mut self) {
+        for (name, list) in std::mem::take(&mut self.negations) {
+            if self.table(list).remove(&name).is_some() {
+                debug!("Removed {name} negated by {}", self.lists[list].source);
+            }
+        }
+    }
+
+    /// Insert a range from a reverse format list, keeping any existing entry for the same range which outranks it.
+    fn insert_reverse(&mut self, net: IpNet, list: usize) {
+        trace!("Inserting reverse blocklist entry {net}");
+        self.lists[list].entries += 1;
+        let existing = self.reverse_table(list).get_exact(net).copied();
+        if existing.map_or(true, |existing| self.outranks(list, existing)) {
+            self.reverse_table(list).insert(net, list);
+        }
+    }
+
+    /// The reverse table entries of `list` go in, by its action
+    pub(super) fn reverse_table(&mut self, list: usize) -> &mut IpTrie<usize> {
+        match self.lists[list].action {
+            ListAction::Block => &mut self.reverse_blocklist,
+            ListAction::Monitor => &mut self.reverse_monitorlist,
+        }
+    }
+
+    pub(super) fn insert_name(&mut self, name: LowerName, list: usize) {
+        self.lists[list].entries += 1;
+        let existing = self.table(list).matches(&name);
+        if existing.map_or(true, |existing| self.outranks(list, existing)) {
+            self.table(list).insert(name, list);
+        }
+    }
+
+    /// Whether an entry of `list` replaces an entry of `existing` for the same name.  The higher priority list wins, and
+    /// of lists with the same priority, the one added first, so that the result does not depend on the order the entries
+    /// arrive in when lists are fetched concurrently.
+    fn outranks(&self, list: usize, existing: usize) -> bool {
+        let rank = |list: usize| (self.lists[list].priority, Reverse(list));
+        rank(list) > rank(existing)
+    }
+
+    /// The table the entries of `list` go in, by its action
+    fn table(&mut self, list: usize) -> &mut dyn BlockMatcher {
+        let action = self.lists[list].action;
+        let (blocklist, monitorlist) = self.tables_mut();
+        match action {
+            ListAction::Block => blocklist,
+            ListAction::Monitor => monitorlist,
+        }
+    }
+
+    /// The block and monitor tables, for modification.  The tables are only shared with the copies of the policy made by
+    /// [`Self::copy`], which replace it, so a policy which is being built or modified has them to itself.
+    pub(super) fn tables_mut(&mut self) -> (&mut dyn BlockMatcher, &mut dyn BlockMatcher) {
+        (
+            Arc::get_mut(&mut self.blocklist).expect("blocklist table is shared"),
+            Arc::get_mut(&mut self.monitorlist).expect("blocklist table is shared"),
+        )
+    }
+
+    /// Compare the entries blocked by this policy with those blocked by `new`, keeping up to `limit` names of each kind of
+    /// change.  Entries which are allowed by name, by pattern or as first party are not considered blocked.
+    pub(super) fn diff(&self, new: &Self, limit: usize) -> PolicyDiff {
+        let is_blocked = |policy: &Self, name: &LowerName| {
+            policy.blocklist.matches(name).is_some()
+                && !policy.allowlist.contains(name)
+                && !policy.allowed_by_pattern(name)
+                && policy.first_party_domain(name).is_none()
+        };
+
+        let mut blocked = Vec::new();
+        let mut changed = Vec::new();
+        for (name, list) in new.blocklist.entries() {
+            if !is_blocked(new, name) {
+                continue;
+            }
+
+            match self.blocklist.matches(name) {
+                Some(old) if is_blocked(self, name) => {
+                    if self.answer(name, old) != new.answer(name, list) {
+                        changed.push(name);
+                    }
+                }
+                _ => blocked.push(name),
+            }
+        }
+
+        let unblocked = self
+            .blocklist
+            .entries()
+            .map(|(name, _)| name)
+            .filter(|name| is_blocked(self, name) && !is_blocked(new, name))
+            .collect();
+
+        let summarize = |mut names: Vec<&LowerName>| {
+            names.sort_unstable();
+            NameChanges {
+                count: names.len(),
+                names: names
+                    .iter()
+                    .take(limit)
+                    .map(|name| name.to_string())
+                    .collect(),
+            }
+        };
+        PolicyDiff {
+            blocked: summarize(blocked),
+            unblocked: summarize(unblocked),
+            changed: summarize(changed),
+        }
+    }
+
+    /// How queries for the entry `name` on the list at index `list` are answered: the response, the sinkhole addresses,
+    /// and the TTL
+    fn answer(&self, name: &LowerName, list: usize) -> (BlockResponse, Ipv4Addr, Ipv6Addr, u32) {
+        let entry = self.entry_responses.get(&(name.clone(), list));
+        let list = &self.lists[list];
+        let (sinkhole_ipv4, sinkhole_ipv6) = match entry.and_then(|entry| entry.sinkhole) {
+            Some(IpAddr::V4(sinkhole)) => (sinkhole, list.sinkhole_ipv6),
+            Some(IpAddr::V6(sinkhole)) => (list.sinkhole_ipv4, sinkhole),
+            None => (list.sinkhole_ipv4, list.sinkhole_ipv6),
+        };
+
+        let response = entry
+            .and_then(|entry| entry.response)
+            .unwrap_or(list.response);
+        (
+            response,
+            sinkhole_ipv4,
+            sinkhole_ipv6,
+            self.response_ttl(list, entry.copied(), response),
+        )
+    }
+
+    /// The TTL of `response` for names on `list` whose entry is `entry`: the entry's own TTL if its line sets one, then the
+    /// list's own TTL if it has one, otherwise that of the response mode
+    fn response_ttl(
+        &self,
+        list: &BlockList,
+        entry: Option<EntryResponse>,
+        response: BlockResponse,
+    ) -> u32 {
+        if let Some(ttl) = entry.and_then(|entry| entry.ttl) {
+            return ttl;
+        }
+
+        list.ttl.unwrap_or(match response {
+            BlockResponse::Sinkhole => self.sinkhole_ttl,
+            BlockResponse::NxDomain => self.nxdomain_ttl,
+            BlockResponse::NoData => self.nodata_ttl,
+            BlockResponse::Client => self.client_ttl,
+            BlockResponse::Dname | BlockResponse::Cname => self.dname_ttl,
+        })
+    }
+
+    /// Summarize the changes from `previous` to this policy, for a reload which took `elapsed`.
+    pub(super) fn reload_report(&self, previous: &Self, elapsed: Duration) -> ReloadReport {
+        let entries = |policy: &Self, source: &str| {
+            policy
+                .lists
+                .iter()
+                .find(|list| list.source == source)
+                .map(|list| list.entries)
+        };
+
+        let current = self.lists.iter().map(|list| ListReload {
+            source: list.source.clone(),
+            before: entries(previous, &list.source),
+            after: Some(list.entries),
+        });
+        let dropped = previous
+            .lists
+            .iter()
+            .filter(|list| entries(self, &list.source).is_none())
+            .map(|list| ListReload {
+                source: list.source.clone(),
+                before: Some(list.entries),
+                after: None,
+            });
+
+        ReloadReport {
+            added: self
+                .blocklist
+                .entries()
+                .filter(|(name, _)| previous.blocklist.matches(name).is_none())
+                .count(),
+            removed: previous
+                .blocklist
+                .entries()
+                .filter(|(name, _)| self.blocklist.matches(name).is_none())
+                .count(),
+            lists: current.chain(dropped).collect(),
+            errors: Vec::new(),
+            elapsed,
+        }
+    }
+
+    /// Randomly adjust `ttl` by up to `ttl_jitter` seconds, so that clients which cached a blocked name at the same time
+    /// do not all re-query it at once.  A jittered TTL is never less than one second.
+    fn jitter_ttl(&self, ttl: u32) -> u32 {
+        if self.ttl_jitter == 0 {
+            return ttl;
+        }
+
+        let jitter = i64::from(self.ttl_jitter);
+        let ttl = i64::from(ttl) + rand::thread_rng().gen_range(-jitter..=jitter);
+        u32::try_from(ttl.max(1)).unwrap_or(u32::MAX)
+    }
+
+    /// The block table entries which could match `name`: the name itself, followed by its wildcards if enabled.  If
+    /// `wildcard_blocks_parent` is set, the wildcards include the one directly below `name`, which `name` is the parent of.
+    ///
+    /// The entries are in order from the most specific to the least, and the first entry found in a table decides the
+    /// list a query is answered from, so that the decision depends only on which entries the tables hold, and never on
+    /// the order the lists were loaded in.
+    pub(super) fn match_list(&self, name: &LowerName) -> Vec<LowerName> {
+        let mut match_list = vec![name.to_owned()];
+        if !self.wildcard_match {
+            return match_list;
+        }
+
+        if self.wildcard_blocks_parent && name.num_labels() >= self.shallowest_wildcard_depth {
+            // A name too long to have a label added can not be the parent of an entry.
+            let wildcard = Name::from_ascii("*").and_then(|w| w.append_domain(&Name::from(name)));
+            if let Ok(wildcard) = wildcard {
+                match_list.push(wildcard.into());
+            }
+        }
+
+        // Walk up from the name through its parents, replacing the first label of each with the wildcard label, for as long
+        // as the wildcard keeps at least as many labels as the shallowest `min_wildcard_depth` of any list.  The root has no
+        // labels, so the walk always ends there, whatever the depth.
+        let mut current = name.clone();
+        while current.num_labels() > self.shallowest_wildcard_depth {
+            let parent = current.base_name();
+            match_list.push(current.into_wildcard());
+            current = parent;
+        }
+        debug!("Blocklist match list: {match_list:?}");
+        match_list
+    }
+
+    /// Find the list which blocks `name`, if any, by checking each entry of its `match_list` in turn.  Returns the entry
+    /// which matched, along with its list.
+    pub(super) fn find_list<'a, 'm>(
+        &'a self,
+        name: &LowerName,
+        match_list: &'m [LowerName],
+    ) -> Option<(&'m LowerName, &'a BlockList)> {
+        if self.allowed_by_pattern(name) {
+            debug!("Query '{name}' matched an allowed pattern");
+            return None;
+        }
+
+        if self.allowed_by_name(name, match_list) {
+            return None;
+        }
+
+        self.find_in(&*self.blocklist, name, match_list)
+    }
+
+    /// Whether any entry of the `match_list` of `name` is an allowed name, or `name` is in a first party domain
+    pub(super) fn allowed_by_name(&self, name: &LowerName, match_list: &[LowerName]) -> bool {
+        if let Some(host) = match_list
+            .iter()
+            .find(|host| self.allowlist.contains(*host))
+        {
+            debug!("Query '{name}' matched allowed name {host}");
+            return true;
+        }
+
+        match self.first_party_domain(name) {
+            Some(domain) => {
+                debug!("Query '{name}' is in first party domain {domain}");
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The first party domain `name` is, or is below, if any
+    fn first_party_domain(&self, name: &LowerName) -> Option<LowerName> {
+        if self.first_party_domains.is_empty() {
+            return None;
+        }
+
+        let mut domain = name.clone();
+        loop {
+            if self.first_party_domains.contains(&domain) {
+                return Some(domain);
+            }
+            if domain.is_root() {
+                return None;
+            }
+            domain = domain.base_name();
+        }
+    }
+
+    /// The protected domain `name` is a homograph of, or is below a homograph of, if any, with the list which determines
+    /// the response
+    pub(super) fn find_homograph(&self, name: &LowerName) -> Option<(&LowerName, &BlockList)> {
+        let (homographs, list) = self.homographs.as_ref()?;
+        Some((homographs.find(name)?, &self.lists[*list]))
+    }
+
+    /// Find the list in the reverse `table` with a range containing the addresses `name` is the reverse lookup name of, if
+    /// any.  Lists outside their schedule are skipped.
+    pub(super) fn find_reverse<'a>(
+        &'a self,
+        table: &IpTrie<usize>,
+        name: &LowerName,
+    ) -> Option<&'a BlockList> {
+        if table.is_empty() {
+            return None;
+        }
+
+        let list = &self.lists[*table.get_net(reverse_net(&Name::from(name))?)?];
+        if let Some(schedule) = list.schedule {
+            let now = OffsetDateTime::now_utc();
+            if !schedule.contains(now.hour(), now.minute()) {
+                debug!(
+                    "Query '{name}' matched a range in {}, which is outside its schedule",
+                    list.source
+                );
+                return None;
+            }
+        }
+
+        Some(list)
+    }
+
+    /// Describe each block list entry which an allowed name, a first party domain or an allow pattern overrides, e.g. a
+    /// wildcard entry above an allowed name.  The allowed name always takes precedence; this is a static analysis of the
+    /// loaded rules, so that operators can spot entries which do not block what they appear to.
+    fn allow_overlaps(&self) -> Vec<String> {
+        let mut overlaps = Vec::new();
+        let allowed = self
+            .allowlist
+            .iter()
+            .map(|name| (name, "allowed name"))
+            .chain(
+                self.first_party_domains
+                    .iter()
+                    .map(|name| (name, "first party domain")),
+            );
+        for (name, kind) in allowed {
+            for host in self.match_list(name) {
+                if let Some(list) = self.blocklist.matches(&host) {
+                    overlaps.push(format!(
+                        "{kind} {name} overrides {host} on {}",
+                        self.lists[list].source
+                    ));
+                }
+            }
+        }
+
+        // Entries below a first party domain, or matching a pattern, are only found by going through every entry.
+        if self.allow_regex.is_some() || !self.first_party_domains.is_empty() {
+            for (host, list) in self.blocklist.entries() {
+                let source = &self.lists[list].source;
+                if self.allowed_by_pattern(host) {
+                    overlaps.push(format!("an allow pattern overrides {host} on {source}"));
+                } else if let Some(domain) = self
+                    .first_party_domain(host)
+                    .filter(|domain| domain != host)
+                {
+                    overlaps.push(format!(
+                        "first party domain {domain} overrides {host} on {source}"
+                    ));
+                }
+            }
+        }
+
+        overlaps
+    }
+
+    /// Log the [`Self::allow_overlaps`], up to `MAX_OVERLAP_WARNINGS` of them
+    pub(super) fn warn_allow_overlaps(&self, origin: &Name) {
+        let overlaps = self.allow_overlaps();
+        for overlap in overlaps.iter().take(MAX_OVERLAP_WARNINGS) {
+            warn!("blocklist {origin}: {overlap}");
+        }
+        if overlaps.len() > MAX_OVERLAP_WARNINGS {
+            warn!(
+                "blocklist {origin}: {} more block list entries are overridden by allowed names",
+                overlaps.len() - MAX_OVERLAP_WARNINGS
+            );
+        }
+    }
+
+    /// Whether `name` matches any of the patterns on the `allow_regex_lists`
+    pub(super) fn allowed_by_pattern(&self, name: &LowerName) -> bool {
+        self.allow_regex
+            .as_ref()
+            .map_or(false, |allow_regex| allow_regex.is_match(&name.to_string()))
+    }
+
+    /// Find the list in `table` which matches `name`, if any, by checking each entry of its `match_list` in turn.  Returns
+    /// the entry which matched, along with its list.
+    pub(super) fn find_in<'a, 'm>(
+        &'a self,
+        table: &dyn BlockMatcher,
+        name: &LowerName,
+        match_list: &'m [LowerName],
+    ) -> Option<(&'m LowerName, &'a BlockList)> {
+        for host in match_list {
+            let Some(list) = table.matches(host) else {
+                continue;
+            };
+
+            let list = &self.lists[list];
+            // The number of labels of a wildcard entry discounts the wildcard label.
+            let min_wildcard_depth = list.min_wildcard_depth.unwrap_or(self.min_wildcard_depth);
+            if host.is_wildcard() && host.num_labels() < min_wildcard_depth {
+                debug!(
+                    "Query '{name}' matched {host} in {}, which is shallower than its min_wildcard_depth",
+                    list.source
+                );
+                continue;
+            }
+
+            if let Some(depth) = list.depth {
+                // The number of labels in the query below the matched entry; num_labels discounts the wildcard label.
+                if name.num_labels() - host.num_labels() != depth {
+                    debug!(
+                        "Query '{name}' matched {host} in {}, but not at depth {depth}",
+                        list.source
+                    );
+                    continue;
+                }
+            }
+
+            if let Some(schedule) = list.schedule {
+                let now = OffsetDateTime::now_utc();
+                if !schedule.contains(now.hour(), now.minute()) {
+                    debug!(
+                        "Query '{name}' matched {host} in {}, which is outside its schedule",
+                        list.source
+                    );
+                    continue;
+                }
+            }
+
+            debug!("Query '{name}' matched: {}", table.explain(host));
+            return Some((host, list));
+        }
+
+        None
+    }
+
+    /// Record when each name on a list with a grace period was first seen.  Names which `previous` already blocked keep
+    /// the time it recorded, if their grace period has not ended; any other name is first seen now.
+    pub(super) fn track_first_seen(&mut self, previous: &Self) {
+        let now = Instant::now();
+        for (name, list) in self.blocklist.entries() {
+            let Some(grace_period) = self.lists[list].grace_period else {
+                continue;
+            };
+
+            let first_seen = match previous.blocklist.matches(name) {
+                Some(_) => previous.first_seen.get(name).copied(),
+                None => Some(now),
+            };
+            if let Some(first_seen) = first_seen.filter(|seen| now - *seen < grace_period) {
+                self.first_seen.insert(name.clone(), first_seen);
+            }
+        }
+    }
+
+    /// Whether the entry `host` on `list` was added too recently to be enforced
+    pub(super) fn in_grace_period(&self, host: &LowerName, list: &BlockList) -> bool {
+        let Some(grace_period) = list.grace_period else {
+            return false;
+        };
+
+        self.first_seen
+            .get(host)
+            .map_or(false, |first_seen| first_seen.elapsed() < grace_period)
+    }
+
+    /// The response set for the entry `host` by its line in `list`, if `list` is in the extended format
+    pub(super) fn entry_response(
+        &self,
+        host: &LowerName,
+        list: &BlockList,
+    ) -> Option<EntryResponse> {
+        if self.entry_responses.is_empty() {
+            return None;
+        }
+
+        let list = self
+            .lists
+            .iter()
+            .position(|candidate| std::ptr::eq(candidate, list))?;
+        self.entry_responses.get(&(host.clone(), list)).copied()
+    }
+
+    /// The response cached for a query for `name` and `rtype`, with the list which determined it and how the name matched
+    pub(super) fn cached_response(
+        &self,
+        name: &LowerName,
+        rtype: RecordType,
+    ) -> Option<(&BlockList, MatchKind, BlocklistLookup)> {
+        let (list, match_kind, lookup) = self.response_cache.as_ref()?.get(name, rtype)?;
+        Some((&self.lists[list], match_kind, lookup))
+    }
+
+    /// Add `response`, for a query for `name` and `rtype` which matched `list`, to the response cache.  Responses which
+    /// may differ the next time the name is queried, e.g. because of the time of day or the client, are not added.
+    pub(super) fn cache_response(
+        &self,
+        name: &LowerName,
+        rtype: RecordType,
+        list: &BlockList,
+        entry: Option<EntryResponse>,
+        match_kind: MatchKind,
+        response: &Result<Option<BlocklistLookup>, LookupError>,
+    ) {
+        let Some(cache) = &self.response_cache else {
+            return;
+        };
+        let Ok(Some(lookup)) = response else {
+            return;
+        };
+        if self.ttl_jitter != 0
+            || (list.pool_ipv4 && !self.sinkhole_pool_ipv4.is_empty())
+            || (list.pool_ipv6 && !self.sinkhole_pool_ipv6.is_empty())
+            || ((list.pool_ipv4 || list.pool_ipv6) && !self.subnet_sinkholes.is_empty())
+            || entry
+                .and_then(|entry| entry.response)
+                .unwrap_or(list.response)
+                == BlockResponse::Client
+            || self.lists.iter().any(|list| list.schedule.is_some())
+        {
+            return;
+        }
+
+        let Some(index) = self
+            .lists
+            .iter()
+            .position(|other| std::ptr::eq(other, list))
+        else {
+            return;
+        };
+        cache.insert(
+            name,
+            CachedResponse {
+                rtype,
+                list: index,
+                match_kind,
+                lookup: lookup.clone(),
+            },
+        );
+    }
+
+    /// The response to a query for a name blocked by `list`, as synthesized by `synthesize_response`, which carries the
+    /// extended DNS error of the list, if it sets one
+    pub(super) fn blocked_response(
+        &self,
+        name: &LowerName,
+        host: Option<&LowerName>,
+        rtype: RecordType,
+        list: &BlockList,
+        entry: Option<EntryResponse>,
+        client: Option<Client>,
+    ) -> Result<Option<BlocklistLookup>, LookupError> {
+        let lookup = self.synthesize_response(name, host, rtype, list, entry, client)?;
+        Ok(lookup.map(|mut lookup| {
+            lookup.extended_error = list.extended_error.clone();
+            lookup
+        }))
+    }
+
+    /// Synthesize the response to a query for a name blocked by `list`, using the response set by the entry's own line
+    /// instead of the list's settings, if there is one.  `host` is the entry which matched the queried name, if it was
+    /// the queried name which matched rather than one in the answers to it.
+    fn synthesize_response(
+        &self,
+        name: &LowerName,
+        host: Option<&LowerName>,
+        rtype: RecordType,
+        list: &BlockList,
+        entry: Option<EntryResponse>,
+        client: Option<Client>,
+    ) -> Result<Option<BlocklistLookup>, LookupError> {
+        let response = entry
+            .and_then(|entry| entry.response)
+            .unwrap_or(list.response);
+        let subnet = client.and_then(|client| client.subnet);
+        let (sinkhole_ipv4, sinkhole_ipv6) = match entry.and_then(|entry| entry.sinkhole) {
+            Some(IpAddr::V4(sinkhole)) => (sinkhole, self.sinkhole_ipv6(list, subnet)),
+            Some(IpAddr::V6(sinkhole)) => (self.sinkhole_ipv4(list, subnet), sinkhole),
+            None => (
+                self.sinkhole_ipv4(list, subnet),
+                self.sinkhole_ipv6(list, subnet),
+            ),
+        };
+
+        match response {
+            BlockResponse::NxDomain | BlockResponse::NoData => {
+                let lookup = self.negative_response(name, rtype, list, entry, response);
+                return Ok(Some(lookup));
+            }
+            // A validating resolver does not follow a CNAME or DNAME for the DNSSEC types, e.g. for the DS records at a
+            // delegation, and a redirect in their place breaks validation of the name.
+            BlockResponse::Dname | BlockResponse::Cname if rtype.is_dnssec() => {
+                debug!("Query '{name}' is blocked; answering {rtype} with NODATA");
+                let lookup =
+                    self.negative_response(name, rtype, list, entry, BlockResponse::NoData);
+                return Ok(Some(lookup));
+            }
+            BlockResponse::Dname => return self.dname_response(name, host, rtype, list, entry),
+            BlockResponse::Cname => {
+                return self.cname_response(name, rtype, list, entry, sinkhole_ipv4, sinkhole_ipv6)
+            }
+            BlockResponse::Sinkhole | BlockResponse::Client
+                if !self.answers_with_address(rtype) =>
+            {
+                debug!("Query '{name}' is blocked; answering {rtype} with NODATA");
+                let lookup =
+                    self.negative_response(name, rtype, list, entry, BlockResponse::NoData);
+                return Ok(Some(lookup));
+            }
+            BlockResponse::Sinkhole => {}
+            BlockResponse::Client => {}
+        }
+
+        let client = match client.map(|client| client.addr) {
+            Some(IpAddr::V6(v6)) if response == BlockResponse::Client => {
+                Some(v6.to_ipv4_mapped().map_or(IpAddr::V6(v6), IpAddr::V4))
+            }
+            Some(client) if response == BlockResponse::Client => Some(client),
+            _ => None,
+        };
+
+        let rdata = match (rtype, client) {
+            (RecordType::AAAA, Some(IpAddr::V6(client))) => RData::AAAA(AAAA::from(client)),
+            (RecordType::AAAA, _) => RData::AAAA(AAAA::from(sinkhole_ipv6)),
+            (_, Some(IpAddr::V4(client))) => RData::A(A::from(client)),
+            _ => RData::A(A::from(sinkhole_ipv4)),
+        };
+
+        let ttl = self.jitter_ttl(self.response_ttl(list, entry, response));
+        let record = Record::from_rdata(name.into(), ttl, rdata);
+        Ok(Some(BlocklistLookup::new(
+            Query::query(name.into(), rtype),
+            [record],
+        )))
+    }
+
+    /// The NXDOMAIN or NODATA `response` to a query for `name` and `rtype` blocked by `list`, whose SOA has the TTL of the
+    /// response
+    fn negative_response(
+        &self,
+        name: &LowerName,
+        rtype: RecordType,
+        list: &BlockList,
+        entry: Option<EntryResponse>,
+        response: BlockResponse,
+    ) -> BlocklistLookup {
+        let response_code = match response {
+            BlockResponse::NxDomain => ResponseCode::NXDomain,
+            _ => ResponseCode::NoError,
+        };
+        let ttl = self.jitter_ttl(self.response_ttl(list, entry, response));
+        BlocklistLookup::negative(Query::query(name.into(), rtype), response_code, ttl)
+    }
+
+    /// The address A queries for names on `list` from clients in `subnet` are answered with: that of the `subnet_sinkholes`
+    /// for the subnet, or else one picked at random from the `sinkhole_pool`, unless the list sets its own sinkhole address
+    fn sinkhole_ipv4(&self, list: &BlockList, subnet: Option<IpNet>) -> Ipv4Addr {
+        if !list.pool_ipv4 {
+            return list.sinkhole_ipv4;
+        }
+
+        if let Some(sinkhole) = self.subnet_sinkhole(subnet, |sinkhole| sinkhole.sinkhole_ipv4) {
+            return sinkhole;
+        }
+
+        self.sinkhole_pool_ipv4
+            .choose(&mut rand::thread_rng())
+            .copied()
+            .unwrap_or(list.sinkhole_ipv4)
+    }
+
+    /// The address AAAA queries for names on `list` from clients in `subnet` are answered with: that of the
+    /// `subnet_sinkholes` for the subnet, or else one picked at random from the `sinkhole_pool`, unless the list sets its
+    /// own sinkhole address
+    fn sinkhole_ipv6(&self, list: &BlockList, subnet: Option<IpNet>) -> Ipv6Addr {
+        if !list.pool_ipv6 {
+            return list.sinkhole_ipv6;
+        }
+
+        if let Some(sinkhole) = self.subnet_sinkhole(subnet, |sinkhole| sinkhole.sinkhole_ipv6) {
+            return sinkhole;
+        }
+
+        self.sinkhole_pool_ipv6
+            .choose(&mut rand::thread_rng())
+            .copied()
+            .unwrap_or(list.sinkhole_ipv6)
+    }
+
+    /// The address `sinkhole` gives for the most specific of the `subnet_sinkholes` which contains the client `subnet`
+    /// and sets one
+    fn subnet_sinkhole<T>(
+        &self,
+        subnet: Option<IpNet>,
+        sinkhole: impl Fn(&SubnetSinkhole) -> Option<T>,
+    ) -> Option<T> {
+        let subnet = subnet?;
+        self.subnet_sinkholes
+            .iter()
+            .filter(|sinkholes| sinkholes.subnet.contains(&subnet))
+            .find_map(sinkhole)
+    }
+
+    /// The SOA for `origin` carried by negative responses with `ttl`, whose TTL and minimum are both `ttl`.  It is built the
+    /// first time it is needed, and shared with every later response with the same TTL.
+    pub(super) fn negative_soa(&self, origin: &LowerName, ttl: u32) -> BlocklistLookup {
+        let mut soas = self
+            .negative_soas
+            .lock()
+            .expect("blocklist negative SOA lock poisoned");
+        if let Some(soa) = soas.get(&ttl) {
+            return soa.clone();
+        }
+
+        let origin = Name::from(origin);
+        let rname = Name::from_ascii("hostmaster")
+            .and_then(|rname| rname.append_domain(&origin))
+            .unwrap_or_else(|_| origin.clone());
+        let soa = SOA::new(
+            origin.clone(),
+            rname,
+            self.serial,
+            86400,
+            7200,
+            3600000,
+            ttl,
+        );
+        let record = Record::from_rdata(origin.clone(), ttl, RData::SOA(soa));
+        let soa = BlocklistLookup::new(Query::query(origin, RecordType::SOA), [record]);
+        if soas.len() < NEGATIVE_SOA_TTLS {
+            soas.insert(ttl, soa.clone());
+        }
+        soa
+    }
+
+    /// Whether sinkhole and client responses answer `rtype` with an address: A, AAAA and ANY queries are, unless the type
+    /// is one of the `nodata_types`.  Every other type is answered with NODATA.
+    fn answers_with_address(&self, rtype: RecordType) -> bool {
+        matches!(rtype, RecordType::A | RecordType::AAAA | RecordType::ANY)
+            && !self.nodata_types.contains(&rtype)
+    }
+
+    /// Redirect a query for `name`, blocked by `list`, to the `dname_target` with a CNAME, followed by the target's address
+    /// when the query is for one, so that the client need not resolve the target itself
+    fn cname_response(
+        &self,
+        name: &LowerName,
+        rtype: RecordType,
+        list: &BlockList,
+        entry: Option<EntryResponse>,
+        sinkhole_ipv4: Ipv4Addr,
+        sinkhole_ipv6: Ipv6Addr,
+    ) -> Result<Option<BlocklistLookup>, LookupError> {
+        let Some(target) = &self.dname_target else {
+            error!(
+                "blocklist {} answers with cname, but there is no dname_target",
+                list.source
+            );
+            return Err(LookupError::from(ResponseCode::ServFail));
+        };
+
+        debug!("Query '{name}' is blocked; answering with a CNAME to {target} and its address");
+        let ttl = self.jitter_ttl(self.response_ttl(list, entry, BlockResponse::Cname));
+        let mut records = vec![Record::from_rdata(
+            name.into(),
+            ttl,
+            RData::CNAME(CNAME(target.clone())),
+        )];
+        if self.answers_with_address(rtype) {
+            let rdata = match rtype {
+                RecordType::AAAA => RData::AAAA(AAAA::from(sinkhole_ipv6)),
+                _ => RData::A(A::from(sinkhole_ipv4)),
+            };
+            records.push(Record::from_rdata(target.clone(), ttl, rdata));
+        }
+
+        Ok(Some(BlocklistLookup::new(
+            Query::query(name.into(), rtype),
+            records,
+        )))
+    }
+
+    /// Redirect a query for `name`, blocked by `list`, to the `dname_target`.  If `name` is below the wildcard entry
+    /// `host`, the answer is a DNAME redirecting the entry's subtree, followed by the CNAME it synthesizes for `name`;
+    /// otherwise it is a CNAME to the target itself.
+    fn dname_response(
+        &self,
+        name: &LowerName,
+        host: Option<&LowerName>,
+        rtype: RecordType,
+        list: &BlockList,
+        entry: Option<EntryResponse>,
+    ) -> Result<Option<BlocklistLookup>, LookupError> {
+        let Some(target) = &self.dname_target else {
+            error!(
+                "blocklist {} answers with dname, but there is no dname_target",
+                list.source
+            );
+            return Err(LookupError::from(ResponseCode::ServFail));
+        };
+
+        let ttl = self.jitter_ttl(self.response_ttl(list, entry, BlockResponse::Dname));
+        let owner = host
+            .filter(|host| host.is_wildcard())
+            .map(LowerName::base_name)
+            .filter(|owner| owner != name && owner.zone_of(name));
+        let Some(owner) = owner else {
+            debug!("Query '{name}' is blocked; answering with a CNAME to {target}");
+            let record = Record::from_rdata(name.into(), ttl, RData::CNAME(CNAME(target.clone())));
+            return Ok(Some(BlocklistLookup::new(
+                Query::query(name.into(), rtype),
+                [record],
+            )));
+        };
+
+        // The labels of the queried name below the DNAME owner are moved onto the target.  If the result is too long to
+        // be a name, the query is answered with YXDOMAIN, per section 2.2 of RFC 6672.  `Name` accepts names of up to 255
+        // characters, which take 256 bytes on the wire, one more than a name may, so the limit is checked here as well;
+        // otherwise the response could not be encoded.
+        let name = Name::from(name);
+        let prefix = Name::from_labels(
+            name.iter()
+                .take(name.num_labels() as usize - owner.num_labels() as usize),
+        );
+        let alias = prefix
+            .and_then(|prefix| prefix.append_domain(target))
+            .ok()
+            .filter(|alias| alias.len() < 255);
+        let Some(alias) = alias else {
+            debug!("Query '{name}' is blocked, but its DNAME substitution is too long");
+            return Err(LookupError::from(ResponseCode::YXDomain));
+        };
+
+        debug!("Query '{name}' is blocked; answering with a DNAME from {owner} to {target}");
+        let dname = target
+            .to_bytes()
+            .map_err(|_| LookupError::from(ResponseCode::ServFail))?;
+        let dname = RData::Unknown {
+            code: RecordType::Unknown(DNAME),
+            rdata: NULL::with(dname),
+        };
+        let records = [
+            Record::from_rdata(owner.into(), ttl, dname),
+            Record::from_rdata(name.clone(), ttl, RData::CNAME(CNAME(alias))),
+        ];
+        Ok(Some(BlocklistLookup::new(
+            Query::query(name, rtype),
+            records,
+        )))
+    }
+}
+
+/// Parse the columns following the name on a line of an extended format list: an optional action, for `sinkhole` an
+/// optional address, and then an optional `ttl=` column.
+pub(super) fn parse_entry_response<'a>(
+    columns: impl Iterator<Item = &'a str>,
+) -> Result<Option<EntryResponse>, String> {
+    let mut columns = columns.peekable();
+    let ttl = |column: &str| {
+        let ttl = column
+            .get(..4)?
+            .eq_ignore_ascii_case("ttl=")
+            .then(|| &column[4..])?;
+        Some(
+            ttl.parse::<u32>()
+                .map_err(|e| format!("invalid TTL {ttl:?}: {e}")),
+        )
+    };
+
+    let response = match columns.next_if(|column| ttl(column).is_none()) {
+        Some(action) => Some(match action.to_ascii_lowercase().as_str() {
+            "sinkhole" => BlockResponse::Sinkhole,
+            "nxdomain" => BlockResponse::NxDomain,
+            "nodata" => BlockResponse::NoData,
+            "client" => BlockResponse::Client,
+            _ => return Err(format!("unknown action {action:?}")),
+        }),
+        None => None,
+    };
+
+    let sinkhole = match columns.next_if(|column| ttl(column).is_none()) {
+        Some(addr) if response == Some(BlockResponse::Sinkhole) => Some(
+            addr.parse::<IpAddr>()
+                .map_err(|e| format!("invalid sinkhole address {addr:?}: {e}"))?,
+        ),
+        Some(column) => return Err(format!("unexpected {column:?} after the action")),
+        None => None,
+    };
+
+    let ttl = match columns.next() {
+        Some(column) => match ttl(column) {
+            Some(ttl) => Some(ttl?),
+            None => return Err(format!("unexpected {column:?} at the end of the line")),
+        },
+        None => None,
+    };
+
+    if let Some(column) = columns.next() {
+        return Err(format!("unexpected {column:?} at the end of the line"));
+    }
+
+    if response.is_none() && ttl.is_none() {
+        return Ok(None);
+    }
+
+    Ok(Some(EntryResponse {
+        response,
+        sinkhole,
+        ttl,
+    }))
+}
+
+/// Pass each line of `reader` to `line` along with its zero based line number, or `None` in place of a line longer than
+/// `max_length` bytes.  Over-long lines are never held in memory in full.
+fn read_lines(
+    mut reader: impl Read,
+    max_length: usize,
+    mut line: impl FnMut(usize, Option<&[u8]>),
+) -> io::Result<()> {
+    let mut lines = Lines::new(max_length);
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(read) => lines.push(&buf[..read], &mut line),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    lines.finish(&mut line);
+
+    Ok(())
+}
+
+/// The network a reverse lookup name under `in-addr.arpa` or `ip6.arpa` stands for, e.g. `192.0.2.0/24` for
+/// `2.0.192.in-addr.arpa`, or `None` if `name` is not one.
+fn reverse_net(name: &Name) -> Option<IpNet> {
+    let labels = name.iter().collect::<Vec<_>>();
+    match labels.as_slice() {
+        [octets @ .., suffix, arpa]
+            if suffix.eq_ignore_ascii_case(b"in-addr")
+                && arpa.eq_ignore_ascii_case(b"arpa")
+                && octets.len() <= 4 =>
+        {
+            let mut addr = [0; 4];
+            for (octet, label) in addr.iter_mut().zip(octets.iter().rev()) {
+                *octet = std::str::from_utf8(label).ok()?.parse().ok()?;
+            }
+            IpNet::new(IpAddr::from(addr), octets.len() as u8 * 8).ok()
+        }
+        [nibbles @ .., suffix, arpa]
+            if suffix.eq_ignore_ascii_case(b"ip6")
+                && arpa.eq_ignore_ascii_case(b"arpa")
+                && nibbles.len() <= 32 =>
+        {
+            let mut addr = 0u128;
+            for (i, label) in nibbles.iter().rev().enumerate() {
+                let [nibble] = label else {
+                    return None;
+                };
+                addr |= u128::from(char::from(*nibble).to_digit(16)?) << (124 - 4 * i);
+            }
+            IpNet::new(IpAddr::from(addr.to_be_bytes()), nibbles.len() as u8 * 4).ok()
+        }
+        _ => None,
+    }
+}
+
+/// The encoding set for a list, if any.  Lines are split on newline bytes before they are decoded, so only encodings
+/// which leave ASCII unchanged are accepted.
+pub(super) fn list_encoding(entry: &ListEntry) -> Result<Option<&'static Encoding>, String> {
+    let Some(label) = &entry.encoding else {
+        return Ok(None);
+    };
+
+    match Encoding::for_label(label.as_bytes()) {
+        Some(encoding) if encoding.is_ascii_compatible() => Ok(Some(encoding)),
+        Some(encoding) => Err(format!(
+            "blocklist encoding {label:?} ({}) is not ASCII compatible: {entry:?}",
+            encoding.name()
+        )),
+        None => Err(format!(
+            "blocklist encoding {label:?} is not a known encoding: {entry:?}"
+        )),
+    }
+}
+
+/// Parse a block list entry, which may omit the trailing dot of a fully qualified name, into the names it adds to the
+/// block table.
+///
+/// A leading `wildcard_token` label is stored as `*`, the label wildcards are matched with, so that the token is treated
+/// the same on insert and lookup.  An entry with a leading dot, such as `.example.com`, is shorthand for a wildcard entry
+/// for the names below it, and for the name itself if `include_apex` is set.
+fn parse_entry(
+    entry: &str,
+    wildcard_token: &str,
+    include_apex: bool,
+) -> Result<Vec<LowerName>, ProtoError> {
+    if let Some(name) = entry.strip_prefix('.') {
+        let wildcard = canonical_key(&format!("*.{name}"))?;
+        return match include_apex {
+            true => Ok(vec![canonical_key(name)?, wildcard]),
+            false => Ok(vec![wildcard]),
+        };
+    }
+
+    match entry.split_once('.') {
+        Some((label, rest)) if label == wildcard_token => {
+            Ok(vec![canonical_key(&format!("*.{rest}"))?])
+        }
+        _ => Ok(vec![canonical_key(entry)?]),
+    }
+}
+
+/// The name a block list entry is stored under, and matched against queries with: the entry as a fully qualified name,
+/// whether or not it was written with its trailing dot, and in lower case.  An empty entry is rejected, rather than
+/// being read as the root.
+fn canonical_key(entry: &str) -> Result<LowerName, ProtoError> {
+    if entry.is_empty() {
+        return Err(ProtoError::from("empty name"));
+    }
+
+    Ok(canonical_name(Name::from_str(entry)?))
+}
+
+/// The key `name` is stored under in the name-keyed tables, as [`canonical_key`] gives for list entries.  Names given
+/// through the builder and the configuration go through this as well, so that the allowlist, the monitor list, and the
+/// other tables match the same queries as the block table whether or not a name was written with its trailing dot.
+pub(super) fn canonical_name(mut name: Name) -> LowerName {
+    name.set_fqdn(true);
+    LowerName::from(name)
+}
+
+/// Checks whether a sinkhole address is one which can not accidentally route blocked traffic to a third party.
+pub(super) fn is_safe_sinkhole(addr: IpAddr, sinkhole_networks: &[IpNet]) -> bool {
+    let local = match addr {
+        IpAddr::V4(addr) => {
+            addr.is_unspecified() || addr.is_loopback() || addr.is_private() || addr.is_link_local()
+        }
+        IpAddr::V6(addr) => {
+            let segment = addr.segments()[0];
+            addr.is_unspecified()
+                || addr.is_loopback()
+                || segment & 0xfe00 == 0xfc00 // unique local, fc00::/7
+                || segment & 0xffc0 == 0xfe80 // link local, fe80::/10
+        }
+    };
+
+    local || sinkhole_networks.iter().any(|net| net.contains(&addr))
+}
+
+/// Check the file `handle` for the list at `file` against the checksum of `entry`, if it has one, returning the file
+/// rewound to its start.
+fn verify_file(file: &str, mut handle: File, entry: &ListEntry) -> Result<File, String> {
+    let Some(expected) = &entry.sha256 else {
+        return Ok(handle);
+    };
+
+    let error = |e: io::Error| format!("unable to read block list file '{file}': {e}");
+    let mut digest = digest::Context::new(&digest::SHA256);
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        match handle.read(&mut buffer).map_err(error)? {
+            0 => break,
+            read => digest.update(&buffer[..read]),
+        }
+    }
+    verify_sha256(file, expected, digest.finish())?;
+
+    handle.seek(SeekFrom::Start(0)).map_err(error)?;
+    Ok(handle)
+}
+
+/// Resident set size of the current process in kilobytes, if the platform exposes it.
+#[cfg(target_os = "linux")]
+fn resident_set_size() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn resident_set_size() -> Option<u64> {
+    None
+}
+
+fn display_rss(rss: Option<u64>) -> String {
+    match rss {
+        Some(kb) => format!("{kb}kB"),
+        None => "unknown".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::proto::rr::{LowerName, Name};
+    use crate::store::blocklist::BlocklistAuthorityBuilder;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_canonical_key() {
+        use super::{canonical_key, canonical_name};
+
+        let key = |entry: &str| canonical_key(entry).map(|name| name.to_string());
+
+        // Test: entries are fully qualified whether or not they were written with the trailing dot.
+        assert_eq!(key("example.com").unwrap(), "example.com.");
+        assert_eq!(key("example.com.").unwrap(), "example.com.");
+        assert_eq!(key("*.example.com").unwrap(), "*.example.com.");
+        assert_eq!(key("com").unwrap(), "com.");
+
+        // Test: entries are lower cased, whatever their case.
+        assert_eq!(key("Ads.Example.COM").unwrap(), "ads.example.com.");
+        assert_eq!(key("ADS.EXAMPLE.COM.").unwrap(), "ads.example.com.");
+        assert_eq!(
+            canonical_key("Ads.Example.com").unwrap(),
+            LowerName::from_str("ads.example.com.").unwrap()
+        );
+
+        // Test: the root is only read from an explicit dot; empty and malformed entries are rejected.
+        assert_eq!(key(".").unwrap(), ".");
+        assert!(key("").is_err());
+        assert!(key("example..com").is_err());
+        assert!(key(&format!("{}.com", "a".repeat(64))).is_err());
+
+        // Test: names given through the builder and the configuration get the same key as the list entry they are written
+        // like.
+        for entry in ["Ads.Example.COM", "ads.example.com."] {
+            assert_eq!(
+                canonical_name(Name::from_str(entry).unwrap()),
+                canonical_key(entry).unwrap()
+            );
+        }
+        assert!(canonical_name(Name::from_str("Ads.Example.COM").unwrap()).is_fqdn());
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_allow_overlaps() {
+        let builder = BlocklistAuthorityBuilder::new(Name::root())
+            .add_entries([
+                Name::from_str("*.ads.example.").unwrap(),
+                Name::from_str("tracker.shop.example.").unwrap(),
+                Name::from_str("unrelated.example.").unwrap(),
+            ])
+            .add_allow([Name::from_str("ok.ads.example.").unwrap()])
+            .first_party_domains(vec![Name::from_str("shop.example.").unwrap()]);
+        let policy = builder.policy().unwrap();
+
+        // Test: an allowed name below a blocked wildcard, and an entry below a first party domain, are both reported, and
+        // entries which no allowed name overrides are not.
+        let overlaps = policy.allow_overlaps();
+        assert_eq!(overlaps.len(), 2, "{overlaps:?}");
+        assert!(overlaps
+            .iter()
+            .any(|overlap| overlap
+                == "allowed name ok.ads.example. overrides *.ads.example. on builder"));
+        assert!(overlaps.iter().any(|overlap| overlap
+            == "first party domain shop.example. overrides tracker.shop.example. on builder"));
+    }
+}
@@ -0,0 +1,340 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! JSON lines log of blocked and monitored queries
+
+use std::{
+    fmt::Write as _,
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    net::IpAddr,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use time::OffsetDateTime;
+use tracing::warn;
+
+use crate::{
+    proto::rr::{LowerName, RecordType},
+    store::blocklist::ListAction,
+};
+
+/// Writes an event for each blocked or monitored query, one JSON object per line
+pub(crate) struct QueryLog {
+    /// The file events are appended to, or `None` for stdout
+    path: Option<PathBuf>,
+    file: Mutex<Option<File>>,
+}
+
+impl QueryLog {
+    /// A log written to the file at `path`, or to stdout if `path` is `None`.  The file is created if it does not exist.
+    pub(crate) fn open(path: Option<PathBuf>) -> io::Result<Self> {
+        let file = path.as_deref().map(append).transpose()?;
+        Ok(Self {
+            path,
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Open the log file again, so that events go to a new file once the old one has been moved aside for rotation.
+    pub(crate) fn reopen(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+
+        match append(path) {
+            Ok(file) => *self.file.lock().expect("query log lock poisoned") = Some(file),
+            Err(e) => warn!("unable to reopen blocklist query log {path:?}: {e}"),
+        }
+    }
+
+    pub(crate) fn write(&self, event: &QueryEvent<'_>) {
+        let mut line = event.to_json();
+        line.push('\n');
+
+        // Each event is written with a single call, so concurrent events are not interleaved within a line.
+        let mut file = self.file.lock().expect("query log lock poisoned");
+        let written = match file.as_mut() {
+            Some(file) => file.write_all(line.as_bytes()),
+            None => io::stdout().lock().write_all(line.as_bytes()),
+        };
+
+        if let Err(e) = written {
+            warn!("unable to write to blocklist query log: {e}");
+        }
+    }
+}
+
+fn append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// How a query matched the list which determined its response
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum MatchKind {
+    /// The queried name is on the list
+    Exact,
+    /// A wildcard entry on the list matched the queried name
+    Wildcard,
+    /// The answer aliased a name on the list through a CNAME
+    Cname,
+    /// The answer to a reverse lookup pointed at a name on the list
+    Ptr,
+    /// The answer contained an address on the list
+    Address,
+    /// The answer contained an address an upstream resolver uses for the names it blocks
+    UpstreamSink,
+    /// The queried name is the reverse lookup name of an address in a range on a reverse list
+    Reverse,
+    /// The queried name is a homograph of a protected domain, or below one
+    Homograph,
+}
+
+impl MatchKind {
+    /// How `name` was matched by the block table entry `host`
+    pub(crate) fn of(name: &LowerName, host: &LowerName) -> Self {
+        match name == host {
+            true => Self::Exact,
+            false => Self::Wildcard,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Exact => "exact",
+            Self::Wildcard => "wildcard",
+            Self::Cname => "cname",
+            Self::Ptr => "ptr",
+            Self::Address => "address",
+            Self::UpstreamSink => "upstream_sink",
+            Self::Reverse => "reverse",
+            Self::Homograph => "homograph",
+        }
+    }
+}
+
+/// A blocked or monitored query
+pub(crate) struct QueryEvent<'a> {
+    pub(crate) timestamp: OffsetDateTime,
+    pub(crate) client: Option<IpAddr>,
+    pub(crate) qname: &'a LowerName,
+    pub(crate) qtype: RecordType,
+    pub(crate) action: ListAction,
+    /// The source of the list which matched
+    pub(crate) list: &'a str,
+    pub(crate) match_kind: MatchKind,
+}
+
+impl QueryEvent<'_> {
+    /// The event as a single line JSON object, with the fields in a fixed order.  The timestamp is RFC 3339 in UTC, with
+    /// millisecond precision; `client` is null when the client address is unknown.
+    pub(crate) fn to_json(&self) -> String {
+        let timestamp = self.timestamp.to_offset(time::UtcOffset::UTC);
+        let action = match self.action {
+            ListAction::Block => "block",
+            ListAction::Monitor => "monitor",
+        };
+
+        let mut json = format!(
+            "{{\"timestamp\":\"{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z\",\"client\":",
+            timestamp.year(),
+            u8::from(timestamp.month()),
+            timestamp.day(),
+            timestamp.hour(),
+            timestamp.minute(),
+            timestamp.second(),
+            timestamp.millisecond(),
+        );
+        match self.client {
+            Some(client) => write_string(&mut json, &client.to_string()),
+            None => json.push_str("null"),
+        }
+        json.push_str(",\"qname\":");
+        write_string(&mut json, &self.qname.to_string());
+        json.push_str(",\"qtype\":");
+        write_string(&mut json, &self.qtype.to_string());
+        json.push_str(",\"action\":");
+        write_string(&mut json, action);
+        json.push_str(",\"list\":");
+        write_string(&mut json, self.list);
+        json.push_str(",\"match_kind\":");
+        write_string(&mut json, self.match_kind.as_str());
+        json.push('}');
+        json
+    }
+}
+
+/// Append `value` to `json` as a quoted JSON string
+fn write_string(json: &mut String, value: &str) {
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(json, "\\u{:04x}", u32::from(c));
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+#[cfg(test)]
+mod test {
+    use crate::store::blocklist::{BlocklistAuthority, BlocklistConfig, ListEntry};
+    use crate::{
+        authority::{Authority, LookupOptions, ZoneType},
+        proto::rr::domain::Name,
+        proto::rr::{LowerName, RecordType},
+    };
+    use std::path::Path;
+    use std::str::FromStr;
+
+    #[tokio::test]
+    async fn test_blocklist_query_log() {
+        let dir = std::env::temp_dir().join(format!(
+            "hickory-blocklist-query-log-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let list_dir = Path::new("../../tests/test-data/test_configs/");
+
+        let config = BlocklistConfig {
+            lists: vec![ListEntry::from_path("default/blocklist.txt")],
+            monitor_lists: vec![ListEntry::from_path("default/blocklist2.txt")],
+            query_log: Some(dir.join("queries.log").display().to_string()),
+            ..BlocklistConfig::default()
+        };
+
+        let authority = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            Some(list_dir),
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+
+        for (name, rtype) in [
+            ("foo.com.", RecordType::A),
+            ("www.foo.com.", RecordType::AAAA),
+            ("malware.com.", RecordType::A),
+            ("test.com.", RecordType::A),
+        ] {
+            let _ = authority
+                .lookup(
+                    &LowerName::from_str(name).unwrap(),
+                    rtype,
+                    LookupOptions::default(),
+                )
+                .await;
+        }
+
+        // Test: each blocked or monitored query is logged as a JSON object with a fixed set of fields, in a fixed order.
+        // Queries which pass are not logged.
+        let log = std::fs::read_to_string(dir.join("queries.log")).unwrap();
+        let lines = log.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3, "{log}");
+
+        let blocklist = list_dir.join("default/blocklist.txt").display().to_string();
+        let monitorlist = list_dir
+            .join("default/blocklist2.txt")
+            .display()
+            .to_string();
+        let expected = [
+            format!(
+                r#""client":null,"qname":"foo.com.","qtype":"A","action":"block","list":"{blocklist}","match_kind":"exact"}}"#
+            ),
+            format!(
+                r#""client":null,"qname":"www.foo.com.","qtype":"AAAA","action":"block","list":"{blocklist}","match_kind":"wildcard"}}"#
+            ),
+            format!(
+                r#""client":null,"qname":"malware.com.","qtype":"A","action":"monitor","list":"{monitorlist}","match_kind":"exact"}}"#
+            ),
+        ];
+        for (line, expected) in lines.iter().zip(expected) {
+            // The timestamp is RFC 3339 in UTC, with millisecond precision, e.g. 2024-01-01T00:00:00.000Z
+            let (timestamp, rest) = line
+                .strip_prefix(r#"{"timestamp":""#)
+                .and_then(|line| line.split_once(r#"","#))
+                .unwrap_or_else(|| panic!("no timestamp in {line}"));
+            assert_eq!(timestamp.len(), 24, "{line}");
+            assert!(timestamp.ends_with('Z'), "{line}");
+            assert_eq!(&timestamp[10..11], "T", "{line}");
+            assert_eq!(rest, expected);
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_log_exclude() {
+        let dir = std::env::temp_dir().join(format!(
+            "hickory-blocklist-log-exclude-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let list_dir = Path::new("../../tests/test-data/test_configs/");
+
+        let config = BlocklistConfig {
+            lists: vec![ListEntry::from_path("default/blocklist.txt")],
+            query_log: Some(dir.join("queries.log").display().to_string()),
+            log_exclude: vec![
+                Name::from_str("baddomain.com").unwrap(),
+                Name::from_str("*.foo.com.").unwrap(),
+            ],
+            ..BlocklistConfig::default()
+        };
+
+        let authority = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            Some(list_dir),
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+
+        for name in [
+            "baddomain.com.",
+            "www.foo.com.",
+            "a.b.foo.com.",
+            "foo.com.",
+            "example.com.",
+        ] {
+            let _ = authority
+                .lookup(
+                    &LowerName::from_str(name).unwrap(),
+                    RecordType::A,
+                    LookupOptions::default(),
+                )
+                .await;
+        }
+
+        // Test: excluded names are counted as blocked, but only the queries for other names are logged.  The wildcard
+        // excludes the names below foo.com, but not foo.com itself.
+        let stats = authority.stats();
+        assert_eq!(stats.blocked, 5);
+        assert_eq!(stats.matches.exact, 3);
+        assert_eq!(stats.matches.wildcard, 2);
+
+        let log = std::fs::read_to_string(dir.join("queries.log")).unwrap();
+        let lines = log.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2, "{log}");
+        assert!(lines[0].contains(r#""qname":"foo.com.""#), "{log}");
+        assert!(lines[1].contains(r#""qname":"example.com.""#), "{log}");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,152 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Temporary blocking of clients which query too quickly
+
+use std::{
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use lru_cache::LruCache;
+use tracing::info;
+
+/// A token bucket for each of the most recently seen clients.  Each query takes a token; a client which runs out is refused
+/// until its cooldown ends, and then starts again with a full bucket.  Once full, the least recently seen client is evicted
+/// to make room.
+pub(crate) struct ClientLimiter {
+    /// Tokens added to a bucket per second
+    rate: f64,
+    /// Tokens a bucket holds when full
+    burst: f64,
+    cooldown: Duration,
+    clients: Mutex<LruCache<IpAddr, Bucket>>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    /// When the client may query again, while it is blocked
+    blocked_until: Option<Instant>,
+}
+
+impl ClientLimiter {
+    /// A limiter allowing `rate` queries per second, in bursts of up to `burst` queries, or `rate` if `burst` is 0, which
+    /// refuses clients exceeding it for `cooldown`, and tracks up to `capacity` clients
+    pub(crate) fn new(rate: u32, burst: u32, cooldown: Duration, capacity: usize) -> Self {
+        let burst = match burst {
+            0 => rate,
+            burst => burst,
+        };
+        Self {
+            rate: f64::from(rate),
+            burst: f64::from(burst.max(1)),
+            cooldown,
+            clients: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// Take a token for a query from `client`, returning whether the query may be answered
+    pub(crate) fn allow(&self, client: IpAddr) -> bool {
+        let now = Instant::now();
+        let mut clients = self.clients.lock().expect("client limiter lock poisoned");
+        let Some(bucket) = clients.get_mut(&client) else {
+            clients.insert(
+                client,
+                Bucket {
+                    tokens: self.burst - 1.0,
+                    updated: now,
+                    blocked_until: None,
+                },
+            );
+            return true;
+        };
+
+        if let Some(blocked_until) = bucket.blocked_until {
+            if now < blocked_until {
+                return false;
+            }
+
+            bucket.blocked_until = None;
+            bucket.tokens = self.burst;
+            bucket.updated = now;
+        }
+
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst);
+        bucket.updated = now;
+        if bucket.tokens < 1.0 {
+            info!(
+                "client {client} exceeded {} queries per second; refusing its queries for {:?}",
+                self.rate, self.cooldown
+            );
+            bucket.blocked_until = Some(now + self.cooldown);
+            return false;
+        }
+
+        bucket.tokens -= 1.0;
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::server::{Protocol, RequestInfo};
+    use crate::store::blocklist::{BlocklistAuthorityBuilder, BlocklistConfig};
+    use crate::{
+        authority::{Authority, LookupOptions},
+        proto::op::{Header, Query},
+        proto::rr::domain::Name,
+        proto::rr::RecordType,
+    };
+    use std::net::SocketAddr;
+    use std::str::FromStr;
+
+    #[tokio::test]
+    async fn test_blocklist_client_rate_limit() {
+        let config = BlocklistConfig {
+            client_rate_limit: 1,
+            client_rate_burst: 3,
+            ..BlocklistConfig::default()
+        };
+        let authority = BlocklistAuthorityBuilder::with_config(Name::root(), config)
+            .add_entries([Name::from_str("foo.com.").unwrap()])
+            .build()
+            .expect("Unable to create blocklist authority");
+
+        let header = Header::new();
+        let query = Query::query(Name::from_str("example.com.").unwrap(), RecordType::A).into();
+        let search = |client: &str| {
+            let request_info = RequestInfo::new(
+                client.parse::<SocketAddr>().unwrap(),
+                Protocol::Udp,
+                &header,
+                &query,
+            );
+            authority.search(request_info, LookupOptions::default())
+        };
+
+        // Test: a client's burst of queries is answered, and then its queries are refused, even for names which are not
+        // blocked.
+        for _ in 0..3 {
+            assert!(matches!(search("192.0.2.1:5353").await, Ok(None)));
+        }
+        for _ in 0..2 {
+            match search("192.0.2.1:5353").await {
+                Err(e) if e.is_refused() => {}
+                Ok(_) => panic!("search returned Ok; expected REFUSED"),
+                Err(e) => panic!("search returned {e}; expected REFUSED"),
+            }
+        }
+
+        // Test: other clients are unaffected.
+        for _ in 0..3 {
+            assert!(matches!(search("192.0.2.2:5353").await, Ok(None)));
+        }
+    }
+}
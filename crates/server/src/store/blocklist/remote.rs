@@ -0,0 +1,755 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Fetching of remote block lists
+
+use std::{
+    fmt::Write as _,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use flate2::write::GzDecoder;
+use reqwest::{header::CONTENT_TYPE, Client, RequestBuilder};
+use ring::digest::{self, Digest};
+use tokio::sync::Semaphore;
+use tracing::{debug, warn};
+
+#[cfg(feature = "blocklist-s3")]
+use super::s3::S3Store;
+use super::{load::file_name, BlocklistConfig};
+
+/// Distinguishes the spool directories of the fetchers in this process
+static SPOOLS: AtomicUsize = AtomicUsize::new(0);
+
+/// Fetches remote block lists over a shared connection pool, limiting how many are downloaded at once so that a large
+/// number of lists does not exhaust file descriptors or trip upstream rate limits.
+///
+/// Each list is also spooled to disk as it is fetched, so that a policy can be rebuilt from it without fetching the list
+/// again or keeping its contents in memory.
+pub(super) struct Fetcher {
+    client: Client,
+    permits: Semaphore,
+    concurrency: usize,
+    max_line_length: usize,
+    /// The directory lists are spooled to, which is created on the first fetch and removed with the fetcher
+    spool: PathBuf,
+    /// Distinguishes the partial copies of lists which are being fetched at the same time
+    partials: AtomicUsize,
+    #[cfg(feature = "blocklist-s3")]
+    s3: S3Store,
+}
+
+impl Fetcher {
+    /// A fetcher which downloads at most `remote_concurrency` lists at once; a limit of zero is treated as one.
+    pub(super) fn new(config: &BlocklistConfig) -> Result<Self, String> {
+        let concurrency = config.remote_concurrency.max(1);
+        let client = Client::builder()
+            .build()
+            .map_err(|e| format!("unable to create block list HTTP client: {e}"))?;
+
+        Ok(Self {
+            client,
+            permits: Semaphore::new(concurrency),
+            concurrency,
+            max_line_length: config.max_line_length,
+            spool: std::env::temp_dir().join(format!(
+                "hickory-blocklist-{}-{}",
+                process::id(),
+                SPOOLS.fetch_add(1, Ordering::Relaxed)
+            )),
+            partials: AtomicUsize::new(0),
+            #[cfg(feature = "blocklist-s3")]
+            s3: S3Store::new(config.s3_endpoint.clone(), config.s3_region.clone()),
+        })
+    }
+
+    /// Maximum number of lists downloaded at once
+    pub(super) fn concurrency(&self) -> usize {
+        self.concurrency
+    }
+
+    /// Fetch the list at `url`, passing each line of it to `line` as it arrives, along with its zero based line number, or
+    /// `None` in place of a line longer than `max_line_length`.  This waits for a download slot if the concurrency limit has
+    /// been reached; `timeout` only starts once the download does.
+    ///
+    /// The checksum can only be verified once the whole list has arrived, so an error is returned after its lines have
+    /// been passed on if it does not match.  On success, returns the path the list was spooled to, or `None` if it could not
+    /// be spooled.
+    pub(super) async fn fetch(
+        &self,
+        url: &str,
+        sha256: Option<&str>,
+        timeout: Duration,
+        mut line: impl FnMut(usize, Option<&[u8]>),
+    ) -> Result<Option<PathBuf>, String> {
+        let _permit = self
+            .permits
+            .acquire()
+            .await
+            .map_err(|e| format!("unable to fetch block list {url}: {e}"))?;
+
+        let request = match url.starts_with("s3://") {
+            true => self.s3_request(url).await?,
+            false => self.client.get(url),
+        };
+
+        let partial = self.spool.join(format!(
+            "{}.{}.partial",
+            spool_name(url),
+            self.partials.fetch_add(1, Ordering::Relaxed)
+        ));
+        let mut spool = fs::create_dir_all(&self.spool)
+            .and_then(|()| File::create(&partial))
+            .map(BufWriter::new)
+            .map_err(|e| warn!("unable to spool block list {url} to {partial:?}: {e}"))
+            .ok();
+
+        let fetched = fetch_lines(
+            request,
+            url,
+            timeout,
+            self.max_line_length,
+            |line_number, contents| {
+                let spooled = spool.as_mut().map_or(Ok(()), |spool| {
+                    spool.write_all(contents.unwrap_or_default())?;
+                    spool.write_all(b"\n")
+                });
+                if let Err(e) = spooled {
+                    warn!("unable to spool block list {url} to {partial:?}: {e}");
+                    spool = None;
+                }
+                line(line_number, contents);
+            },
+        )
+        .await
+        .and_then(|digest| match sha256 {
+            Some(expected) => verify_sha256(url, expected, digest),
+            None => Ok(()),
+        });
+
+        let Some(spool) = spool else {
+            let _ = fs::remove_file(&partial);
+            return fetched.map(|()| None);
+        };
+        if let Err(e) = fetched {
+            drop(spool);
+            let _ = fs::remove_file(&partial);
+            return Err(e);
+        }
+
+        let target = self.spool.join(spool_name(url));
+        let saved = spool
+            .into_inner()
+            .map_err(|e| e.into_error())
+            .and_then(|file| file.sync_all())
+            .and_then(|()| fs::rename(&partial, &target));
+        match saved {
+            Ok(()) => Ok(Some(target)),
+            Err(e) => {
+                warn!("unable to spool block list {url} to {target:?}: {e}");
+                let _ = fs::remove_file(&partial);
+                Ok(None)
+            }
+        }
+    }
+
+    /// A signed request for the object at a `s3://bucket/key` url
+    #[cfg(feature = "blocklist-s3")]
+    async fn s3_request(&self, url: &str) -> Result<RequestBuilder, String> {
+        self.s3
+            .request(&self.client, url)
+            .await
+            .map_err(|e| format!("unable to fetch block list {url}: {e}"))
+    }
+
+    #[cfg(not(feature = "blocklist-s3"))]
+    async fn s3_request(&self, url: &str) -> Result<RequestBuilder, String> {
+        Err(format!(
+            "unable to fetch block list {url}: object store lists require the blocklist-s3 feature"
+        ))
+    }
+}
+
+impl Drop for Fetcher {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_dir_all(&self.spool) {
+            if e.kind() != io::ErrorKind::NotFound {
+                warn!("unable to remove block list spool {:?}: {e}", self.spool);
+            }
+        }
+    }
+}
+
+/// The name of the spooled copy of the list at `url`
+fn spool_name(url: &str) -> String {
+    format!("{}.list", file_name(url))
+}
+
+/// Send `request` for the list at `url`, passing each line of it to `line` along with its zero based line number, or
+/// `None` in place of a line longer than `max_line_length`.
+///
+/// The response body is split into lines as it arrives, rather than being read in full first.  Lists served with a `.gz`
+/// extension or a gzip content type are decompressed on the fly.  `timeout` bounds the entire transfer, including reading
+/// the body, not just establishing the connection.  Returns the SHA-256 digest of the body as it was served, before
+/// decompression.
+async fn fetch_lines(
+    request: RequestBuilder,
+    url: &str,
+    timeout: Duration,
+    max_line_length: usize,
+    mut line: impl FnMut(usize, Option<&[u8]>),
+) -> Result<Digest, String> {
+    let error = |e: &dyn std::fmt::Display| format!("unable to fetch block list {url}: {e}");
+
+    let mut response = request
+        .timeout(timeout)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| error(&e))?;
+
+    let gzip = url.ends_with(".gz")
+        || response
+            .headers()
+            .get(CONTENT_TYPE)
+            .map_or(false, |content_type| {
+                content_type == "application/gzip" || content_type == "application/x-gzip"
+            });
+    debug!("fetching block list {url}, gzip: {gzip}");
+
+    let mut decoder = gzip.then(|| GzDecoder::new(Vec::new()));
+    let mut lines = Lines::new(max_line_length);
+    let mut digest = digest::Context::new(&digest::SHA256);
+    while let Some(chunk) = response.chunk().await.map_err(|e| error(&e))? {
+        digest.update(&chunk);
+        match decoder.as_mut() {
+            Some(decoder) => {
+                decoder.write_all(&chunk).map_err(|e| error(&e))?;
+                lines.push(decoder.get_ref(), &mut line);
+                decoder.get_mut().clear();
+            }
+            None => lines.push(&chunk, &mut line),
+        }
+    }
+
+    if let Some(decoder) = decoder {
+        let rest = decoder.finish().map_err(|e| error(&e))?;
+        lines.push(&rest, &mut line);
+    }
+    lines.finish(&mut line);
+
+    Ok(digest.finish())
+}
+
+/// Check the SHA-256 `digest` of the list at `source` against the `expected` hex digest
+pub(super) fn verify_sha256(source: &str, expected: &str, digest: Digest) -> Result<(), String> {
+    let computed = digest.as_ref().iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    });
+    if !computed.eq_ignore_ascii_case(expected.trim()) {
+        return Err(format!(
+            "block list {source} does not match its checksum: expected sha256 {expected}, computed {computed}"
+        ));
+    }
+
+    debug!("block list {source} matches its sha256 checksum {computed}");
+    Ok(())
+}
+
+/// Reassembles lines which are split across chunks of a list.  Lines longer than `max_length` bytes are dropped as soon as
+/// they reach it, rather than being buffered until their end, and are passed on as `None`.
+pub(super) struct Lines {
+    partial: Vec<u8>,
+    number: usize,
+    max_length: usize,
+    overlong: bool,
+}
+
+impl Lines {
+    pub(super) fn new(max_length: usize) -> Self {
+        Self {
+            partial: Vec::new(),
+            number: 0,
+            max_length,
+            overlong: false,
+        }
+    }
+
+    pub(super) fn push(&mut self, mut data: &[u8], line: &mut impl FnMut(usize, Option<&[u8]>)) {
+        while let Some(end) = data.iter().position(|b| *b == b'\n') {
+            self.extend(&data[..end]);
+            self.pass_on(line);
+            data = &data[end + 1..];
+        }
+
+        self.extend(data);
+    }
+
+    /// Pass on the final line, if the list did not end with a newline
+    pub(super) fn finish(mut self, line: &mut impl FnMut(usize, Option<&[u8]>)) {
+        if !self.partial.is_empty() || self.overlong {
+            self.pass_on(line);
+        }
+    }
+
+    fn extend(&mut self, data: &[u8]) {
+        if self.overlong {
+            return;
+        }
+
+        if self.partial.len() + data.len() > self.max_length {
+            self.overlong = true;
+            self.partial = Vec::new();
+        } else {
+            self.partial.extend_from_slice(data);
+        }
+    }
+
+    fn pass_on(&mut self, line: &mut impl FnMut(usize, Option<&[u8]>)) {
+        line(self.number, (!self.overlong).then_some(&self.partial[..]));
+        self.partial.clear();
+        self.overlong = false;
+        self.number += 1;
+    }
+}
+
+#[cfg(test)]
+pub(super) mod test {
+    use crate::store::blocklist::{
+        BlockResponse, BlocklistAuthority, BlocklistAuthorityBuilder, BlocklistConfig, ListEntry,
+        LoadFailurePolicy,
+    };
+    use crate::{
+        authority::{Authority, LookupOptions, ZoneType},
+        proto::rr::domain::Name,
+        proto::rr::{LowerName, RecordType},
+    };
+    use std::net::SocketAddr;
+    use std::str::FromStr;
+    use std::sync::Arc;
+
+    /// Serve each connection a chunked HTTP response, splitting the body into chunks of `chunk_size` bytes.
+    pub(crate) async fn serve_chunked(body: Vec<u8>, chunk_size: usize) -> SocketAddr {
+        serve_sequence(vec![Some(body)], chunk_size).await
+    }
+
+    /// Serve each connection the next of `bodies` as a chunked HTTP response, repeating the last once they run out.  `None`
+    /// is served as a 503 error.
+    pub(crate) async fn serve_sequence(
+        bodies: Vec<Option<Vec<u8>>>,
+        chunk_size: usize,
+    ) -> SocketAddr {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut served = 0;
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = Vec::new();
+                let mut buf = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    if n == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buf[..n]);
+                }
+
+                let body = bodies[served.min(bodies.len() - 1)].clone();
+                served += 1;
+                let Some(body) = body else {
+                    stream
+                        .write_all(b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                        .await
+                        .unwrap();
+                    continue;
+                };
+
+                let mut response =
+                    b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n"
+                        .to_vec();
+                for chunk in body.chunks(chunk_size) {
+                    response.extend_from_slice(format!("{:x}\r\n", chunk.len()).as_bytes());
+                    response.extend_from_slice(chunk);
+                    response.extend_from_slice(b"\r\n");
+                }
+                response.extend_from_slice(b"0\r\n\r\n");
+                stream.write_all(&response).await.unwrap();
+            }
+        });
+
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_remote() {
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+
+        let body = b"# Remote list\nads.example.org\ntracker.example.org # Inline Comment\nlast.example.org".to_vec();
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&body).unwrap();
+        let gzip_body = encoder.finish().unwrap();
+
+        // Small chunks split names across chunk boundaries.
+        let plain = serve_chunked(body, 7).await;
+        let gzip = serve_chunked(gzip_body, 5).await;
+
+        let config = BlocklistConfig {
+            lists: vec![
+                ListEntry {
+                    path: None,
+                    url: Some(format!("http://{plain}/list.txt")),
+                    ..ListEntry::from_path("")
+                },
+                ListEntry {
+                    path: None,
+                    url: Some(format!("http://{gzip}/list.txt.gz")),
+                    response: Some(BlockResponse::NxDomain),
+                    priority: 1,
+                    ..ListEntry::from_path("")
+                },
+            ],
+            ..BlocklistConfig::default()
+        };
+
+        let authority = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            None,
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+
+        // Test: every name on the streamed lists is loaded, including the final line without a trailing newline.
+        for name in [
+            "ads.example.org.",
+            "tracker.example.org.",
+            "last.example.org.",
+        ] {
+            let res = authority
+                .lookup(
+                    &LowerName::from_str(name).unwrap(),
+                    RecordType::A,
+                    LookupOptions::default(),
+                )
+                .await;
+            match res {
+                Err(e) if e.is_nx_domain() => {}
+                Ok(_) => panic!("{name} lookup returned Ok; expected NXDOMAIN from the gzip list"),
+                Err(e) => panic!("{name} lookup error: {e}!"),
+            }
+        }
+
+        let stats = authority.stats();
+        assert_eq!(stats.lists.len(), 2);
+        assert_eq!(stats.lists[1].blocked, 3);
+
+        // Test: a list which can not be fetched is reported as an error.
+        let config = BlocklistConfig {
+            lists: vec![ListEntry {
+                path: None,
+                url: Some("http://127.0.0.1:1/list.txt".to_string()),
+                ..ListEntry::from_path("")
+            }],
+            ..BlocklistConfig::default()
+        };
+
+        let authority = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            None,
+            None,
+        )
+        .await;
+        assert!(authority.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_remote_streamed() {
+        use super::Fetcher;
+        use crate::store::blocklist::policy::Fetch;
+        use crate::store::blocklist::{BlockMatcher, HashMatcher};
+        use std::time::Duration;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::sync::mpsc;
+
+        /// A table which reports each name inserted into it
+        struct Reporting {
+            entries: HashMatcher,
+            inserted: mpsc::UnboundedSender<LowerName>,
+        }
+
+        impl BlockMatcher for Reporting {
+            fn insert(&mut self, name: LowerName, list: usize) -> Option<usize> {
+                let _ = self.inserted.send(name.clone());
+                self.entries.insert(name, list)
+            }
+
+            fn remove(&mut self, name: &LowerName) -> Option<usize> {
+                self.entries.remove(name)
+            }
+
+            fn matches(&self, name: &LowerName) -> Option<usize> {
+                self.entries.matches(name)
+            }
+
+            fn explain(&self, name: &LowerName) -> String {
+                self.entries.explain(name)
+            }
+
+            fn len(&self) -> usize {
+                self.entries.len()
+            }
+
+            fn entries(&self) -> Box<dyn Iterator<Item = (&LowerName, usize)> + '_> {
+                self.entries.entries()
+            }
+        }
+
+        // The server sends the first line, and only sends the rest of the list once that line is in the block table, so
+        // the list is only loaded in full if its lines are added as they arrive rather than once the body is complete.
+        let (inserted, mut added) = mpsc::unbounded_channel();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                let n = stream.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n12\r\nfirst.example.org\n\r\n")
+                .await
+                .unwrap();
+            let first = tokio::time::timeout(Duration::from_secs(5), added.recv()).await;
+            if first.ok().flatten() != Some(LowerName::from_str("first.example.org.").unwrap()) {
+                return;
+            }
+            stream
+                .write_all(b"11\r\nrest.example.org\n\r\n0\r\n\r\n")
+                .await
+                .unwrap();
+        });
+
+        let url = format!("http://{addr}/list.txt");
+        let config = BlocklistConfig {
+            lists: vec![ListEntry {
+                path: None,
+                url: Some(url.clone()),
+                ..ListEntry::from_path("")
+            }],
+            ..BlocklistConfig::default()
+        };
+        let source = BlocklistAuthorityBuilder::with_config(Name::root(), config.clone()).matcher(
+            move || {
+                Box::new(Reporting {
+                    entries: HashMatcher::default(),
+                    inserted: inserted.clone(),
+                })
+            },
+        );
+        let fetcher = Fetcher::new(&config).unwrap();
+
+        // Test: each line is added to the policy as it arrives, and the whole list is loaded.
+        let policy = source.load(None, Fetch::All, &fetcher).await.unwrap();
+        for name in ["first.example.org.", "rest.example.org."] {
+            let name = LowerName::from_str(name).unwrap();
+            assert_eq!(policy.blocklist.matches(&name), Some(0), "{name}");
+        }
+
+        // Test: the policy keeps the path of the spooled copy of the list, which holds its lines, rather than its body.
+        let spool = &policy.remote[&url].spool;
+        assert_eq!(
+            std::fs::read_to_string(spool).unwrap(),
+            "first.example.org\nrest.example.org\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_remote_concurrency() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::time::Duration;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Each connection is answered after a delay, so that concurrent downloads overlap; the peak number of
+        // connections being served at once is recorded.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let active = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        {
+            let (active, peak) = (active.clone(), peak.clone());
+            tokio::spawn(async move {
+                while let Ok((mut stream, _)) = listener.accept().await {
+                    let (active, peak) = (active.clone(), peak.clone());
+                    tokio::spawn(async move {
+                        let mut request = Vec::new();
+                        let mut buf = [0; 1024];
+                        while !request.ends_with(b"\r\n\r\n") {
+                            let n = stream.read(&mut buf).await.unwrap();
+                            if n == 0 {
+                                break;
+                            }
+                            request.extend_from_slice(&buf[..n]);
+                        }
+
+                        let path = String::from_utf8_lossy(&request)
+                            .split_whitespace()
+                            .nth(1)
+                            .unwrap()
+                            .trim_start_matches('/')
+                            .to_string();
+                        let body = format!("{path}.example.org\n");
+
+                        let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                        peak.fetch_max(now, Ordering::SeqCst);
+                        tokio::time::sleep(Duration::from_millis(200)).await;
+                        active.fetch_sub(1, Ordering::SeqCst);
+
+                        let response = format!(
+                            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                            body.len()
+                        );
+                        stream.write_all(response.as_bytes()).await.unwrap();
+                    });
+                }
+            });
+        }
+
+        let remote = |name: &str| ListEntry {
+            path: None,
+            url: Some(format!("http://{addr}/{name}")),
+            ..ListEntry::from_path("")
+        };
+        let config = BlocklistConfig {
+            lists: vec![remote("one"), remote("two"), remote("three")],
+            remote_concurrency: 2,
+            ..BlocklistConfig::default()
+        };
+
+        let authority = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            None,
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+
+        // Test: downloads run concurrently, but never more than the configured number at once.
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+
+        // Test: every list is loaded, in its configured order.
+        let sources = authority.list_sources();
+        assert_eq!(sources.len(), 3);
+        for (source, name) in sources.iter().zip(["one", "two", "three"]) {
+            assert!(source.source.ends_with(name), "{}", source.source);
+            assert_eq!(source.entries, 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_sha256() {
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+
+        let hex = |data: &[u8]| {
+            ring::digest::digest(&ring::digest::SHA256, data)
+                .as_ref()
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect::<String>()
+        };
+        let wrong = "0".repeat(64);
+
+        let dir =
+            std::env::temp_dir().join(format!("hickory-blocklist-sha256-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let list = b"ads.example.org\n".to_vec();
+        let path = dir.join("list.txt");
+        std::fs::write(&path, &list).unwrap();
+
+        async fn build(
+            entry: ListEntry,
+            on_load_failure: LoadFailurePolicy,
+        ) -> Result<BlocklistAuthority, String> {
+            let config = BlocklistConfig {
+                lists: vec![entry],
+                on_load_failure,
+                ..BlocklistConfig::default()
+            };
+            BlocklistAuthority::try_from_config(Name::root(), ZoneType::Hint, &config, None, None)
+                .await
+        }
+
+        async fn blocked(authority: &BlocklistAuthority) -> bool {
+            let name = LowerName::from_str("ads.example.org.").unwrap();
+            authority
+                .lookup(&name, RecordType::A, LookupOptions::default())
+                .await
+                .map_or(false, |lookup| lookup.iter().count() > 0)
+        }
+
+        // Test: a local list matching its checksum loads, whatever the case of the hex digest.
+        let local = |sha256: &str| ListEntry {
+            sha256: Some(sha256.to_string()),
+            ..ListEntry::from_path(path.display().to_string())
+        };
+        let authority = build(local(&hex(&list).to_uppercase()), LoadFailurePolicy::Fail)
+            .await
+            .expect("list matching its checksum should load");
+        assert!(blocked(&authority).await);
+
+        // Test: a local list which does not match its checksum fails to load, reporting both digests.
+        let Err(e) = build(local(&wrong), LoadFailurePolicy::Fail).await else {
+            panic!("list not matching its checksum should fail to load");
+        };
+        assert!(e.contains(&wrong) && e.contains(&hex(&list)), "{e}");
+
+        // Test: with the empty load failure policy, the blocklist starts without the list.
+        let authority = build(local(&wrong), LoadFailurePolicy::Empty)
+            .await
+            .expect("empty policy should start");
+        assert!(!blocked(&authority).await);
+
+        // Test: the checksum of a remote list covers the download as it is served, before decompression.
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&list).unwrap();
+        let gzip = encoder.finish().unwrap();
+        let server = serve_chunked(gzip.clone(), 5).await;
+        let remote = |sha256: &str| ListEntry {
+            path: None,
+            url: Some(format!("http://{server}/list.txt.gz")),
+            sha256: Some(sha256.to_string()),
+            ..ListEntry::from_path("")
+        };
+        let authority = build(remote(&hex(&gzip)), LoadFailurePolicy::Fail)
+            .await
+            .expect("remote list matching its checksum should load");
+        assert!(blocked(&authority).await);
+        assert!(build(remote(&hex(&list)), LoadFailurePolicy::Fail)
+            .await
+            .is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,180 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Cache of the responses built for blocked names
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    sync::Mutex,
+};
+
+use lru_cache::LruCache;
+
+use crate::proto::rr::{LowerName, RecordType};
+use crate::store::blocklist::{authority::BlocklistLookup, querylog::MatchKind};
+
+/// The responses most recently built for blocked queries, by name, each with the query type, the list which determined
+/// it, and how the name matched.  Once full, the responses for the least recently queried name are evicted to make room.
+///
+/// The names are spread over up to [`SHARDS`] caches by their hash, each behind its own lock and evicting on its own, so
+/// that concurrent hits on different names rarely wait on one another.  A cache belongs to a single policy, so a reload
+/// starts with an empty one.
+pub(crate) struct ResponseCache {
+    shards: Vec<Mutex<LruCache<LowerName, Vec<CachedResponse>>>>,
+    capacity: usize,
+}
+
+/// The most shards a cache is split into; a cache with fewer entries than this has one shard per entry
+const SHARDS: usize = 16;
+
+pub(crate) struct CachedResponse {
+    pub(crate) rtype: RecordType,
+    /// Index of the list which determined the response
+    pub(crate) list: usize,
+    pub(crate) match_kind: MatchKind,
+    pub(crate) lookup: BlocklistLookup,
+}
+
+impl ResponseCache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        let shards = capacity.clamp(1, SHARDS);
+        Self {
+            shards: (0..shards)
+                .map(|_| Mutex::new(LruCache::new((capacity + shards - 1) / shards)))
+                .collect(),
+            capacity,
+        }
+    }
+
+    /// The shard holding the responses for `name`
+    fn shard(&self, name: &LowerName) -> &Mutex<LruCache<LowerName, Vec<CachedResponse>>> {
+        let mut hasher = DefaultHasher::new();
+        name.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % self.shards.len()]
+    }
+
+    /// The response to a query for `name` and `rtype`, with the list which determined it and how the name matched
+    pub(crate) fn get(
+        &self,
+        name: &LowerName,
+        rtype: RecordType,
+    ) -> Option<(usize, MatchKind, BlocklistLookup)> {
+        let mut responses = self
+            .shard(name)
+            .lock()
+            .expect("response cache lock poisoned");
+        responses
+            .get_mut(name)?
+            .iter()
+            .find(|cached| cached.rtype == rtype)
+            .map(|cached| (cached.list, cached.match_kind, cached.lookup.clone()))
+    }
+
+    pub(crate) fn insert(&self, name: &LowerName, response: CachedResponse) {
+        let mut responses = self
+            .shard(name)
+            .lock()
+            .expect("response cache lock poisoned");
+        match responses.get_mut(name) {
+            Some(cached) => {
+                cached.retain(|cached| cached.rtype != response.rtype);
+                cached.push(response);
+            }
+            None => {
+                responses.insert(name.clone(), vec![response]);
+            }
+        }
+    }
+
+    /// An empty cache with the same capacity
+    pub(crate) fn empty(&self) -> Self {
+        Self::new(self.capacity)
+    }
+
+    pub(crate) fn clear(&self) {
+        for shard in &self.shards {
+            shard.lock().expect("response cache lock poisoned").clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::store::blocklist::{BlocklistAuthority, BlocklistConfig, ListEntry, ListFormat};
+    use crate::{
+        authority::{Authority, LookupOptions, ZoneType},
+        proto::rr::domain::Name,
+        proto::rr::{
+            rdata::{A, AAAA},
+            LowerName, RData, RecordType,
+        },
+    };
+    use std::net::Ipv6Addr;
+    use std::str::FromStr;
+
+    #[tokio::test]
+    async fn test_blocklist_response_cache() {
+        let dir = std::env::temp_dir().join(format!(
+            "hickory-blocklist-response-cache-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("list.txt");
+        std::fs::write(&path, "ads.example.com sinkhole 10.0.0.1\n").unwrap();
+
+        let config = BlocklistConfig {
+            lists: vec![ListEntry {
+                format: ListFormat::Extended,
+                ..ListEntry::from_path(path.display().to_string())
+            }],
+            response_cache_size: 8,
+            ..BlocklistConfig::default()
+        };
+        let authority =
+            BlocklistAuthority::try_from_config(Name::root(), ZoneType::Hint, &config, None, None)
+                .await
+                .expect("Unable to create blocklist authority");
+
+        async fn address(authority: &BlocklistAuthority, rtype: RecordType) -> RData {
+            let name = LowerName::from_str("ads.example.com.").unwrap();
+            match authority
+                .lookup(&name, rtype, LookupOptions::default())
+                .await
+            {
+                Ok(Some(lookup)) => lookup.lookup.record_iter().next().unwrap().data().clone(),
+                Ok(None) => panic!("lookup returned Ok(None); expected a sinkhole address"),
+                Err(e) => panic!("lookup returned {e}; expected a sinkhole address"),
+            }
+        }
+
+        // Test: repeated queries are answered alike, and are each counted.
+        for _ in 0..3 {
+            assert_eq!(
+                address(&authority, RecordType::A).await,
+                RData::A(A::new(10, 0, 0, 1))
+            );
+        }
+        assert_eq!(
+            address(&authority, RecordType::AAAA).await,
+            RData::AAAA(AAAA::from(Ipv6Addr::UNSPECIFIED))
+        );
+        let stats = authority.stats();
+        assert_eq!(stats.blocked, 4);
+        assert_eq!(stats.lists[0].blocked, 4);
+
+        // Test: a reload empties the cache, so the responses follow the reloaded list.
+        std::fs::write(&path, "ads.example.com sinkhole 10.0.0.2\n").unwrap();
+        authority.reload().await;
+        assert_eq!(
+            address(&authority, RecordType::A).await,
+            RData::A(A::new(10, 0, 0, 2))
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,638 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Query counters for the blocklist authority
+
+use std::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, SystemTime},
+};
+
+use crate::store::blocklist::{querylog::MatchKind, ListAction};
+
+/// A point in time copy of the blocklist query counters
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BlocklistStats {
+    /// Queries answered by the blocklist
+    pub blocked: u64,
+    /// Queries which did not match any list, and were passed on to the next store
+    pub passed: u64,
+    /// Queries which matched a monitor list, or an entry still in its grace period.  These are also counted as blocked or
+    /// passed.
+    pub monitored: u64,
+    /// Matches, broken down by how the name or answer was matched
+    pub matches: MatchStats,
+    /// Blocked queries, broken down by the list which determined the response
+    pub lists: Vec<ListStats>,
+}
+
+/// Matches counted by how they were made, showing how often the more expensive kinds of match are used.  Every match is
+/// counted, including those against monitor lists and entries in their grace period.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MatchStats {
+    /// Queried names which are on a list as they are
+    pub exact: u64,
+    /// Queried names matched by a wildcard entry, including the names below an entry with a leading dot
+    pub wildcard: u64,
+    /// Answers which aliased a listed name through a CNAME
+    pub cname: u64,
+    /// Answers to reverse lookups which pointed at a listed name
+    pub ptr: u64,
+    /// Answers containing an address on one of the `ip_blocklists`
+    pub address: u64,
+    /// Answers containing an address an upstream resolver uses for the names it blocks
+    pub upstream_sink: u64,
+    /// Reverse lookup names of addresses in a range on a reverse list
+    pub reverse: u64,
+    /// Queried names which are homographs of a protected domain, or below one
+    pub homograph: u64,
+    /// Queried names which matched a pattern on the `allow_regex_lists`, and were passed on
+    pub regex: u64,
+}
+
+/// Query counters for a single block list
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ListStats {
+    /// The file the list was loaded from
+    pub source: String,
+    /// What is done with queries for names on the list
+    pub action: ListAction,
+    /// Queries answered using this list, or for monitor lists, queries which matched it
+    pub blocked: u64,
+}
+
+/// Metadata about a single configured block list
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ListSourceInfo {
+    /// The file or URL the list was loaded from.  Members of an archive are named after the archive and the member.
+    pub source: String,
+    /// Whether the list was fetched from a URL, rather than read from a file
+    pub remote: bool,
+    /// What is done with queries for names on the list
+    pub action: ListAction,
+    /// Whether the list is currently used: it is enabled in the configuration, and it is within its schedule, if any
+    pub enabled: bool,
+    /// Number of entries read from the list, including any which are also on another list
+    pub entries: usize,
+    /// When the list was read, or for remote lists, fetched.  This is `None` for lists which are disabled.
+    pub loaded: Option<SystemTime>,
+    /// Queries answered using this list, or for monitor lists, queries which matched it
+    pub blocked: u64,
+    /// The most recent background refresh of the list, for remote lists which have been refreshed
+    pub refresh: Option<RefreshStatus>,
+}
+
+/// Outcome of the most recent background refresh of a remote list
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RefreshStatus {
+    /// When the refresh finished
+    pub at: SystemTime,
+    /// Number of consecutive failed refreshes, zero if the most recent refresh succeeded
+    pub failures: u32,
+    /// Why the most recent refresh failed, if it did
+    pub error: Option<String>,
+}
+
+/// A counter which can be incremented concurrently with being read and reset.
+#[derive(Debug, Default)]
+pub(crate) struct Counter(AtomicU64);
+
+impl Counter {
+    pub(crate) fn increment(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Read the counter, resetting it to zero if `reset` is set.
+    ///
+    /// The reset swaps the value out in one step, so an increment racing with it is either included in the value returned
+    /// here or counted towards the next interval; it is never lost or reported twice.
+    pub(crate) fn read(&self, reset: bool) -> u64 {
+        match reset {
+            true => self.0.swap(0, Ordering::Relaxed),
+            false => self.0.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Counters for each kind of match, read into [`MatchStats`]
+#[derive(Debug, Default)]
+pub(crate) struct MatchCounters {
+    exact: Counter,
+    wildcard: Counter,
+    cname: Counter,
+    ptr: Counter,
+    address: Counter,
+    upstream_sink: Counter,
+    reverse: Counter,
+    homograph: Counter,
+    regex: Counter,
+}
+
+impl MatchCounters {
+    pub(crate) fn increment(&self, match_kind: MatchKind) {
+        match match_kind {
+            MatchKind::Exact => &self.exact,
+            MatchKind::Wildcard => &self.wildcard,
+            MatchKind::Cname => &self.cname,
+            MatchKind::Ptr => &self.ptr,
+            MatchKind::Address => &self.address,
+            MatchKind::UpstreamSink => &self.upstream_sink,
+            MatchKind::Reverse => &self.reverse,
+            MatchKind::Homograph => &self.homograph,
+        }
+        .increment();
+    }
+
+    /// Count a name allowed by one of the `allow_regex_lists` patterns
+    pub(crate) fn increment_regex(&self) {
+        self.regex.increment();
+    }
+
+    pub(crate) fn read(&self, reset: bool) -> MatchStats {
+        MatchStats {
+            exact: self.exact.read(reset),
+            wildcard: self.wildcard.read(reset),
+            cname: self.cname.read(reset),
+            ptr: self.ptr.read(reset),
+            address: self.address.read(reset),
+            upstream_sink: self.upstream_sink.read(reset),
+            reverse: self.reverse.read(reset),
+            homograph: self.homograph.read(reset),
+            regex: self.regex.read(reset),
+        }
+    }
+}
+
+/// Summary of a reload of the configured lists
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReloadReport {
+    /// Number of names which are blocked after the reload, but were not before
+    pub added: usize,
+    /// Number of names which were blocked before the reload, but are not after
+    pub removed: usize,
+    /// Entries read from each list, before and after the reload.  This is empty if the reload failed.
+    pub lists: Vec<ListReload>,
+    /// Why each list which failed to load did so.  If there are any, the reload failed and the previous lists are still
+    /// in use.
+    pub errors: Vec<String>,
+    /// How long the reload took
+    pub elapsed: Duration,
+}
+
+impl ReloadReport {
+    /// Whether every list loaded, and the reloaded lists are in use
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+impl fmt::Display for ReloadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.is_ok() {
+            return write!(
+                f,
+                "failed after {:?}: {}",
+                self.elapsed,
+                self.errors.join("; ")
+            );
+        }
+
+        write!(
+            f,
+            "{} names added, {} removed in {:?}",
+            self.added, self.removed, self.elapsed
+        )?;
+        for list in &self.lists {
+            let count = |entries: Option<usize>| entries.map_or("-".to_string(), |n| n.to_string());
+            write!(
+                f,
+                "; {}: {} -> {}",
+                list.source,
+                count(list.before),
+                count(list.after)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A change in the state of a blocklist's policy, passed to the handler set with
+/// [`BlocklistAuthority::on_event`](crate::store::blocklist::BlocklistAuthority::on_event)
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PolicyEvent {
+    /// The configured lists finished loading in the background at startup, and are in use
+    Loaded {
+        /// Number of lists loaded
+        lists: usize,
+        /// Number of names blocked
+        names: usize,
+        /// How long loading took
+        elapsed: Duration,
+    },
+    /// The configured lists were reloaded, and the new lists are in use
+    Reloaded(ReloadReport),
+    /// The configured lists failed to load at startup, or to reload.  The previous lists, or the startup policy, are still
+    /// in use.
+    LoadFailed {
+        /// Why each list which failed to load did so
+        errors: Vec<String>,
+    },
+    /// A remote list could not be refreshed, and its last good copy is still in use
+    FetchFailed {
+        /// The URL of the list
+        url: String,
+        /// Why the refresh failed
+        error: String,
+        /// Number of consecutive failed refreshes
+        failures: u32,
+    },
+}
+
+/// Entries read from a single list, before and after a reload
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ListReload {
+    /// The file or URL the list was loaded from
+    pub source: String,
+    /// Entries in the list before the reload, or `None` if the list is new
+    pub before: Option<usize>,
+    /// Entries in the list after the reload, or `None` if it was removed from the configuration
+    pub after: Option<usize>,
+}
+
+/// Changes to the names blocked, and how they are answered, between two sets of lists
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PolicyDiff {
+    /// Entries which are blocked by the new lists, but were not by the old
+    pub blocked: NameChanges,
+    /// Entries which were blocked by the old lists, but are not by the new
+    pub unblocked: NameChanges,
+    /// Entries which are blocked by both, but are answered differently
+    pub changed: NameChanges,
+}
+
+impl PolicyDiff {
+    /// Whether the two sets of lists block the same names, and answer them the same way
+    pub fn is_empty(&self) -> bool {
+        self.blocked.count == 0 && self.unblocked.count == 0 && self.changed.count == 0
+    }
+}
+
+impl fmt::Display for PolicyDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (label, changes) in [
+            ("newly blocked", &self.blocked),
+            ("unblocked", &self.unblocked),
+            ("response changed", &self.changed),
+        ] {
+            writeln!(f, "{label}: {}", changes.count)?;
+            for name in &changes.names {
+                writeln!(f, "  {name}")?;
+            }
+            if changes.count > changes.names.len() {
+                writeln!(f, "  ... and {} more", changes.count - changes.names.len())?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A set of entries in a [`PolicyDiff`].  Only the first few names are kept, so that diffs of large lists stay small.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NameChanges {
+    /// Number of entries in the set
+    pub count: usize,
+    /// The first entries in the set, in sorted order
+    pub names: Vec<String>,
+}
+
+#[cfg(test)]
+mod test {
+    use crate::proto::rr::Record;
+    use crate::store::blocklist::{
+        BlockResponse, BlocklistAuthority, BlocklistConfig, BlocklistStats, ListAction, ListEntry,
+        ListFormat,
+    };
+    use crate::{
+        authority::{Authority, LookupOptions, ZoneType},
+        proto::rr::domain::Name,
+        proto::rr::{rdata::CNAME, LowerName, RData, RecordType},
+    };
+    use std::path::Path;
+    use std::str::FromStr;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_blocklist_stats() {
+        let config = BlocklistConfig {
+            lists: vec![
+                ListEntry::from_path("default/blocklist.txt"),
+                ListEntry {
+                    priority: 1,
+                    ..ListEntry::from_path("default/blocklist2.txt")
+                },
+            ],
+            ..BlocklistConfig::default()
+        };
+
+        let authority = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            Some(Path::new("../../tests/test-data/test_configs/")),
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+
+        for name in ["foo.com.", "www.foo.com.", "malware.com.", "test.com."] {
+            let _ = authority
+                .lookup(
+                    &LowerName::from_str(name).unwrap(),
+                    RecordType::A,
+                    LookupOptions::default(),
+                )
+                .await;
+        }
+
+        // Test: blocked and passed queries are counted, and attributed to the list which answered them.
+        let stats = authority.reset_stats();
+        assert_eq!(stats.blocked, 3);
+        assert_eq!(stats.passed, 1);
+        assert_eq!(stats.lists.len(), 2);
+        assert!(stats.lists[0].source.ends_with("default/blocklist.txt"));
+        assert_eq!(stats.lists[0].blocked, 2);
+        assert!(stats.lists[1].source.ends_with("default/blocklist2.txt"));
+        assert_eq!(stats.lists[1].blocked, 1);
+
+        // Test: the reset returned the counters as they were, and zeroed them for the next interval.
+        let stats = authority.stats();
+        assert_eq!(
+            BlocklistStats {
+                lists: Vec::new(),
+                ..stats.clone()
+            },
+            BlocklistStats::default()
+        );
+        assert!(stats.lists.iter().all(|list| list.blocked == 0));
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_match_stats() {
+        use crate::store::blocklist::MatchStats;
+
+        let config = BlocklistConfig {
+            lists: vec![ListEntry::from_path("default/blocklist.txt")],
+            allow_regex_lists: vec!["default/allow_regex.txt".to_string()],
+            block_cname_targets: true,
+            ..BlocklistConfig::default()
+        };
+        let authority = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            Some(Path::new("../../tests/test-data/test_configs/")),
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+
+        for name in [
+            "foo.com.",
+            "baddomain.com.",
+            "www.foo.com.",
+            "example.com.",
+            "safe1.foo.com.",
+            "test.com.",
+        ] {
+            let _ = authority
+                .lookup(
+                    &LowerName::from_str(name).unwrap(),
+                    RecordType::A,
+                    LookupOptions::default(),
+                )
+                .await;
+        }
+
+        let name = Name::from_str("www.test.com.").unwrap();
+        let answers = vec![Record::from_rdata(
+            name.clone(),
+            300,
+            RData::CNAME(CNAME(Name::from_str("baddomain.com.").unwrap())),
+        )];
+        authority
+            .filter_answers(&LowerName::from(&name), RecordType::A, answers)
+            .expect("filter_answers failed");
+
+        // Test: each match is counted by its kind; pattern matches are counted even though they pass the query on.
+        assert_eq!(
+            authority.reset_stats().matches,
+            MatchStats {
+                exact: 2,
+                wildcard: 1,
+                cname: 1,
+                regex: 2,
+                ..MatchStats::default()
+            }
+        );
+        assert_eq!(authority.stats().matches, MatchStats::default());
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_list_sources() {
+        let config = BlocklistConfig {
+            lists: vec![
+                ListEntry::from_path("default/blocklist.txt"),
+                ListEntry {
+                    enabled: false,
+                    ..ListEntry::from_path("default/blocklist2.txt")
+                },
+            ],
+            monitor_lists: vec![ListEntry {
+                format: ListFormat::Hosts,
+                ..ListEntry::from_path("default/blocklist_hosts.txt")
+            }],
+            ..BlocklistConfig::default()
+        };
+
+        let authority = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            Some(Path::new("../../tests/test-data/test_configs/")),
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+
+        let res = authority
+            .lookup(
+                &LowerName::from_str("foo.com.").unwrap(),
+                RecordType::A,
+                LookupOptions::default(),
+            )
+            .await;
+        assert!(matches!(res, Ok(Some(_))), "foo.com lookup was not blocked");
+
+        // Test: loaded lists report their entries and hits, followed by the disabled lists.
+        let sources = authority.list_sources();
+        assert_eq!(sources.len(), 3);
+        assert!(sources[0].source.ends_with("default/blocklist.txt"));
+        assert!(!sources[0].remote);
+        assert_eq!(sources[0].action, ListAction::Block);
+        assert!(sources[0].enabled);
+        assert_eq!(sources[0].entries, 4);
+        assert!(sources[0].loaded.is_some());
+        assert_eq!(sources[0].blocked, 1);
+        assert_eq!(sources[0].refresh, None);
+
+        assert!(sources[1].source.ends_with("default/blocklist_hosts.txt"));
+        assert_eq!(sources[1].action, ListAction::Monitor);
+        assert!(sources[1].entries > 0);
+
+        assert_eq!(sources[2].source, "default/blocklist2.txt");
+        assert!(!sources[2].enabled);
+        assert_eq!(sources[2].entries, 0);
+        assert_eq!(sources[2].loaded, None);
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_diff() {
+        let load = |config: BlocklistConfig| async move {
+            BlocklistAuthority::try_from_config(
+                Name::from_str(".").unwrap(),
+                ZoneType::Hint,
+                &config,
+                Some(Path::new("../../tests/test-data/test_configs/")),
+                None,
+            )
+            .await
+            .expect("Unable to create blocklist authority")
+        };
+
+        let old = load(BlocklistConfig {
+            lists: vec![
+                ListEntry::from_path("default/blocklist.txt"),
+                ListEntry::from_path("default/blocklist2.txt"),
+            ],
+            ..BlocklistConfig::default()
+        })
+        .await;
+        let new = load(BlocklistConfig {
+            lists: vec![
+                ListEntry {
+                    response: Some(BlockResponse::NxDomain),
+                    ..ListEntry::from_path("default/blocklist.txt")
+                },
+                ListEntry::from_path("default/blocklist_depth.txt"),
+            ],
+            allow_regex_lists: vec!["default/allow_regex.txt".to_string()],
+            ..BlocklistConfig::default()
+        })
+        .await;
+
+        // Test: identical lists have no differences.
+        assert!(old.diff(&old, 10).is_empty());
+
+        // Test: added lists, removed lists, allowed names and response changes are all reported.
+        let diff = old.diff(&new, 10);
+        assert_eq!(diff.blocked.count, 1);
+        assert_eq!(diff.blocked.names, vec!["*.ads.example.com."]);
+        assert_eq!(diff.unblocked.count, 3);
+        assert_eq!(
+            diff.unblocked.names,
+            vec!["example.com.", "malc0de.com.", "malware.com."]
+        );
+        assert_eq!(diff.changed.count, 3);
+        assert_eq!(
+            diff.changed.names,
+            vec!["baddomain.com.", "foo.com.", "*.foo.com."]
+        );
+
+        // Test: the names are limited, but the counts are not.
+        let diff = new.diff(&old, 2);
+        assert_eq!(diff.blocked.count, 3);
+        assert_eq!(diff.blocked.names, vec!["example.com.", "malc0de.com."]);
+        assert_eq!(diff.unblocked.count, 1);
+        assert_eq!(diff.changed.count, 3);
+        let summary = diff.to_string();
+        assert!(summary.starts_with("newly blocked: 3\n"), "{summary}");
+        assert!(summary.contains("  ... and 1 more\n"), "{summary}");
+    }
+
+    #[tokio::test]
+    async fn test_blocklist_policy_events() {
+        use crate::store::blocklist::{PolicyEvent, StartupPolicy};
+        use std::time::Duration;
+
+        let dir =
+            std::env::temp_dir().join(format!("hickory-blocklist-events-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("list.txt"), "foo.com\nbar.com\n").unwrap();
+
+        let config = BlocklistConfig {
+            lists: vec![ListEntry::from_path("list.txt")],
+            startup_policy: StartupPolicy::Open,
+            ..BlocklistConfig::default()
+        };
+        let authority = BlocklistAuthority::try_from_config(
+            Name::root(),
+            ZoneType::Hint,
+            &config,
+            Some(&dir),
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let handler = events.clone();
+        authority.on_event(move |event| handler.lock().unwrap().push(event.clone()));
+
+        // Test: the lists loaded in the background produce a loaded event.
+        let loaded = async {
+            while events.lock().unwrap().is_empty() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), loaded)
+            .await
+            .expect("lists did not load");
+        match std::mem::take(&mut *events.lock().unwrap()).as_slice() {
+            [PolicyEvent::Loaded { lists, names, .. }] => {
+                assert_eq!(*lists, 1);
+                assert_eq!(*names, 2);
+            }
+            events => panic!("unexpected events {events:?}"),
+        }
+
+        // Test: a successful reload produces a reloaded event carrying its report.
+        std::fs::write(dir.join("list.txt"), "foo.com\nbaz.com\nqux.com\n").unwrap();
+        let report = authority.reload().await;
+        assert!(report.is_ok());
+        assert_eq!(
+            std::mem::take(&mut *events.lock().unwrap()),
+            [PolicyEvent::Reloaded(report)]
+        );
+
+        // Test: a failed reload produces a load failed event carrying the errors.
+        std::fs::remove_file(dir.join("list.txt")).unwrap();
+        let report = authority.reload().await;
+        assert!(!report.is_ok());
+        match std::mem::take(&mut *events.lock().unwrap()).as_slice() {
+            [PolicyEvent::LoadFailed { errors }] => {
+                assert_eq!(errors, &report.errors);
+                assert!(errors[0].contains("list.txt"), "{errors:?}");
+            }
+            events => panic!("unexpected events {events:?}"),
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,219 @@
+// Copyright 2015-2022 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! A bounded, time expired set of recently unblocked names
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::proto::rr::LowerName;
+
+/// Names which were recently removed from the block table.  Queries for these names are answered with NODATA until the
+/// window expires, prompting clients holding a cached sinkhole answer to refresh it.
+pub(crate) struct Tombstones {
+    window: Duration,
+    capacity: usize,
+    names: Mutex<Names>,
+}
+
+#[derive(Default)]
+struct Names {
+    /// When each name was unblocked
+    unblocked: HashMap<LowerName, Instant>,
+    /// The names in the order they were unblocked, oldest first, so that expired and evicted tombstones are found without
+    /// a scan.  A name unblocked again is queued again; its earlier place is skipped when it reaches the front.
+    order: VecDeque<(LowerName, Instant)>,
+}
+
+impl Names {
+    /// Remove the oldest tombstone, returning false if there are none
+    fn pop_oldest(&mut self) -> bool {
+        while let Some((name, unblocked)) = self.order.pop_front() {
+            if self.unblocked.get(&name) == Some(&unblocked) {
+                self.unblocked.remove(&name);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Remove the tombstones unblocked longer than `window` before `now`
+    fn prune(&mut self, now: Instant, window: Duration) {
+        while let Some((_, unblocked)) = self.order.front() {
+            if now.duration_since(*unblocked) < window {
+                break;
+            }
+            self.pop_oldest();
+        }
+    }
+}
+
+impl Tombstones {
+    pub(crate) fn new(window: Duration, capacity: usize) -> Self {
+        Self {
+            window,
+            capacity,
+            names: Mutex::new(Names::default()),
+        }
+    }
+
+    /// Record that `name` was unblocked.  Expired tombstones are pruned first; if the set is still full, the oldest
+    /// tombstone is evicted to make room.
+    pub(crate) fn insert(&self, name: LowerName) {
+        self.insert_all([name]);
+    }
+
+    /// Record that each of `names` was unblocked, as with [`Self::insert`], pruning expired tombstones once for the whole
+    /// batch, e.g. for the names a reload removed
+    pub(crate) fn insert_all(&self, names: impl IntoIterator<Item = LowerName>) {
+        if self.window.is_zero() || self.capacity == 0 {
+            return;
+        }
+
+        let now = Instant::now();
+        let mut tombstones = self.names.lock().expect("tombstone lock poisoned");
+        tombstones.prune(now, self.window);
+
+        for name in names {
+            if tombstones.unblocked.len() >= self.capacity
+                && !tombstones.unblocked.contains_key(&name)
+            {
+                tombstones.pop_oldest();
+            }
+
+            tombstones.unblocked.insert(name.clone(), now);
+            tombstones.order.push_back((name, now));
+        }
+
+        // Names unblocked again leave their earlier places behind; drop them once they outnumber the tombstones.
+        if tombstones.order.len() > 2 * tombstones.unblocked.len() {
+            let Names { unblocked, order } = &mut *tombstones;
+            order.retain(|(name, at)| unblocked.get(name) == Some(at));
+        }
+    }
+
+    /// Whether `name` was unblocked within the tombstone window
+    pub(crate) fn contains(&self, name: &LowerName) -> bool {
+        if self.window.is_zero() {
+            return false;
+        }
+
+        let names = self.names.lock().expect("tombstone lock poisoned");
+        names
+            .unblocked
+            .get(name)
+            .map_or(false, |unblocked| unblocked.elapsed() < self.window)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::server::{Protocol, RequestInfo};
+    use crate::store::blocklist::{BlocklistAuthority, BlocklistConfig, ListEntry};
+    use crate::{
+        authority::{Authority, LookupOptions, ZoneType},
+        proto::op::{Header, Query, ResponseCode},
+        proto::rr::domain::Name,
+        proto::rr::{LowerName, RecordType},
+    };
+    use std::net::SocketAddr;
+    use std::path::Path;
+    use std::str::FromStr;
+
+    #[tokio::test]
+    async fn test_blocklist_tombstone() {
+        let config = BlocklistConfig {
+            lists: vec![ListEntry::from_path("default/blocklist.txt")],
+            tombstone_window: 60,
+            tombstone_ttl: 5,
+            ..BlocklistConfig::default()
+        };
+        assert_eq!(BlocklistConfig::default().tombstone_ttl, 2);
+
+        let mut authority = BlocklistAuthority::try_from_config(
+            Name::from_str(".").unwrap(),
+            ZoneType::Hint,
+            &config,
+            Some(Path::new("../../tests/test-data/test_configs/")),
+            None,
+        )
+        .await
+        .expect("Unable to create blocklist authority");
+
+        assert!(authority.remove(&LowerName::from_str("foo.com.").unwrap()));
+        assert!(authority.remove(&LowerName::from_str("*.foo.com.").unwrap()));
+        assert!(!authority.remove(&LowerName::from_str("test.com.").unwrap()));
+
+        // Test: names which were just unblocked, directly or through a wildcard, are answered with NODATA.
+        for name in ["foo.com.", "www.foo.com."] {
+            let res = authority
+                .lookup(
+                    &LowerName::from_str(name).unwrap(),
+                    RecordType::A,
+                    LookupOptions::default(),
+                )
+                .await;
+            match res {
+                Err(e) if e.is_name_exists() => {}
+                Ok(_) => panic!("{name} lookup returned Ok; expected NODATA"),
+                Err(e) => panic!("{name} lookup returned {e}; expected NODATA"),
+            }
+        }
+
+        // Test: the NODATA lasts for the `tombstone_ttl`, rather than the `nodata_ttl`, and carries no SOA.
+        let header = Header::new();
+        let query = Query::query(Name::from_str("foo.com.").unwrap(), RecordType::A).into();
+        let request_info = RequestInfo::new(
+            "127.0.0.1:5353".parse::<SocketAddr>().unwrap(),
+            Protocol::Udp,
+            &header,
+            &query,
+        );
+        let lookup = authority
+            .search(request_info, LookupOptions::default())
+            .await
+            .unwrap()
+            .expect("tombstoned name was passed on");
+        assert_eq!(lookup.response_code, ResponseCode::NoError);
+        assert_eq!(lookup.negative_ttl, Some(5));
+        assert!(lookup.soa.is_none());
+
+        // Test: names which were never blocked are still passed on.
+        let res = authority
+            .lookup(
+                &LowerName::from_str("test.com.").unwrap(),
+                RecordType::A,
+                LookupOptions::default(),
+            )
+            .await;
+        assert!(matches!(res, Ok(None)));
+    }
+
+    #[test]
+    fn test_blocklist_tombstone_capacity() {
+        use crate::store::blocklist::tombstone::Tombstones;
+        use std::time::Duration;
+
+        let name = |name: &str| LowerName::from_str(name).unwrap();
+        let tombstones = Tombstones::new(Duration::from_secs(60), 2);
+
+        // Test: once full, a batch evicts the oldest tombstones to make room for its own.
+        tombstones.insert_all([name("a.com."), name("b.com."), name("c.com.")]);
+        assert!(!tombstones.contains(&name("a.com.")));
+        assert!(tombstones.contains(&name("b.com.")));
+        assert!(tombstones.contains(&name("c.com.")));
+
+        // Test: a single insert evicts the oldest remaining tombstone.
+        tombstones.insert(name("d.com."));
+        assert!(!tombstones.contains(&name("b.com.")));
+        assert!(tombstones.contains(&name("c.com.")));
+        assert!(tombstones.contains(&name("d.com.")));
+    }
+}